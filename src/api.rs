@@ -0,0 +1,254 @@
+//! The JSON API `dlog serve` answers, over the same records, items, facts
+//! and relations the commands use. Every response body is the entities
+//! serialized as they are for `--output json`.
+use serde::{Serialize, Deserialize};
+use crate::{
+    prompt,
+    error::DError,
+    http::{Request, Response},
+    args::search::Search,
+    cmd::stats::StatsFilter,
+    models::{
+        Record, Item, Fact, Note,
+        item::RecordItem,
+        fact::Unit,
+        data::RecordData,
+        entity::EntityRef,
+        relation::EntityRelation,
+        stats::FactStats,
+    },
+};
+
+/// Records, items and fact entries matching a search
+#[derive(Debug, Default, Serialize)]
+pub struct Found {
+    pub records: Vec<Record>,
+    pub items: Vec<RecordItem>,
+    pub facts: Vec<Fact>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewRecord {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewItem {
+    pub record: String,
+    pub item: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewFact {
+    pub fact: String,
+    pub value: String,
+    /// The inbox if not given
+    pub record: Option<String>,
+    pub item: Option<String>,
+    pub units: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewLink {
+    pub from: EntityRef,
+    pub to: EntityRef,
+    /// `link` if not given
+    pub name: Option<String>,
+    pub value: Option<String>,
+}
+
+pub struct Api {
+    token: Option<String>,
+}
+
+impl Api {
+
+    pub fn new(token: Option<String>) -> Self {
+        Self { token }
+    }
+
+    pub fn handle(&mut self, req: &Request) -> Response {
+        if let Some(token) = &self.token {
+            let given = req.bearer().unwrap_or_default();
+            if ring::constant_time::verify_slices_are_equal(given.as_bytes(), token.as_bytes()).is_err() {
+                return Response::error(401, "Missing or wrong token");
+            }
+        }
+        let res = match (req.method.as_str(), req.segments().as_slice()) {
+            ("GET", ["v1", "records"]) => records().map(|r| Response::json(200, &r)),
+            ("POST", ["v1", "records"]) => req.json().and_then(new_record)
+                .map(|r| Response::json(201, &r)),
+            ("GET", ["v1", "records", name]) => Record::open(name).map(|r| Response::json(200, &r)),
+            ("GET", ["v1", "items"]) => items(req).map(|i| Response::json(200, &i)),
+            ("POST", ["v1", "items"]) => req.json().and_then(new_item)
+                .map(|i| Response::json(201, &i)),
+            ("GET", ["v1", "facts"]) => facts(req).map(|f| Response::json(200, &f)),
+            ("POST", ["v1", "facts"]) => req.json().and_then(new_fact)
+                .map(|f| Response::json(201, &f)),
+            ("GET", ["v1", "links"]) => links(req).map(|l| Response::json(200, &l)),
+            ("POST", ["v1", "links"]) => req.json().and_then(new_link)
+                .map(|l| Response::json(201, &l)),
+            ("GET", ["v1", "search"]) => search(req).map(|f| Response::json(200, &f)),
+            ("GET", ["v1", "stats"]) => stats(req).map(|s| Response::json(200, &s)),
+            (_, ["v1", "records", ..]) | (_, ["v1", "items"]) | (_, ["v1", "facts"])
+                | (_, ["v1", "links"]) | (_, ["v1", "search"]) | (_, ["v1", "stats"]) =>
+                Ok(Response::error(405, &format!("{} is not allowed on {}", req.method, req.path))),
+            _ => Ok(Response::error(404, &format!("No such endpoint {}", req.path))),
+        };
+        res.unwrap_or_else(|e| match e {
+            DError::NotFound => Response::error(404, &e.to_string()),
+            DError::AlreadyExists(_) => Response::error(409, &e.to_string()),
+            DError::Invalid(_) | DError::Json(_) | DError::ParsePath | DError::ParseDate =>
+                Response::error(400, &e.to_string()),
+            _ => Response::error(500, &e.to_string()),
+        })
+    }
+}
+
+/// A name given in a request checked as it is when given to a command, so
+/// none can lead out of the data dir
+fn valid(check: Result<(), &str>, name: &str) -> crate::DResult<()> {
+    check.map_err(|why| DError::Invalid(format!("{}: {:?}", why, name)))
+}
+
+fn query<'a>(req: &'a Request, key: &str) -> Option<&'a str> {
+    req.query.get(key).map(String::as_str).filter(|v| !v.is_empty())
+}
+
+fn records() -> crate::DResult<Vec<Record>> {
+    RecordData::read()?.records()
+        .map(|r| Record::open_path(&r.path))
+        .collect()
+}
+
+/// Lays out a record in the data dir, as `dlog record new` does
+fn new_record(new: NewRecord) -> crate::DResult<Record> {
+    valid(prompt::validate_input(new.name.clone()), &new.name)?;
+    let mut record = Record::new(Some(new.name.clone()), None);
+    record.dir = crate::config::DConfig::load()?.init_behavior().dir()?.join(&new.name);
+    record.description = new.description;
+    record.init(false)?;
+    Record::open(&new.name)
+}
+
+/// Items of `?record=`, or of every record
+fn items(req: &Request) -> crate::DResult<Vec<RecordItem>> {
+    let records = match query(req, "record") {
+        Some(name) => vec![Record::open(name)?],
+        None => records()?,
+    };
+    Ok(records.into_iter()
+        .flat_map(|r| {
+            let name = r.name;
+            r.items.into_iter().map(move |item| RecordItem { record: name.clone(), item })
+        })
+        .collect())
+}
+
+fn new_item(new: NewItem) -> crate::DResult<RecordItem> {
+    valid(prompt::validate_item_path(new.item.clone()), &new.item)?;
+    let mut record = Record::open(&new.record)?;
+    let item = Item::new(new.item);
+    record.add_item(item.clone())?;
+    Ok(RecordItem { record: record.name, item })
+}
+
+/// Entries of `?record=` (and `?item=`), or of every record, oldest first.
+/// `?fact=` keeps those of one fact and `?limit=` the latest few.
+fn facts(req: &Request) -> crate::DResult<Vec<Fact>> {
+    let mut facts = match (query(req, "record"), query(req, "item")) {
+        (Some(record), Some(item)) => {
+            let record = Record::open(record)?;
+            record.item(item).ok_or(DError::NotFound)?.facts(&record, true)?
+        },
+        (Some(record), None) => Record::open(record)?.facts()?,
+        (None, Some(_)) => return Err(DError::Invalid("Give the item's record too".into())),
+        (None, None) => {
+            let mut facts = Vec::new();
+            for record in records()? {
+                facts.extend(record.facts()?);
+            }
+            facts.sort_by_key(|f| f.created_at);
+            facts
+        },
+    };
+    if let Some(name) = query(req, "fact") {
+        facts.retain(|f| f.name.eq_ignore_ascii_case(name));
+    }
+    if let Some(limit) = query(req, "limit") {
+        let limit = limit.parse::<usize>()
+            .map_err(|_| DError::Invalid("limit must be a number".into()))?;
+        facts.drain(..facts.len().saturating_sub(limit));
+    }
+    Ok(facts)
+}
+
+/// Logs an entry as `dlog item add` and `dlog fact` do, authored by the
+/// current user, returning it as it was saved
+fn new_fact(new: NewFact) -> crate::DResult<Fact> {
+    let notes = new.notes.map(|n| vec![Note::new(&n)]).unwrap_or_default();
+    let unit = new.units.map(|u| Unit::from_stored(&u)).unwrap_or(Unit::None);
+    valid(prompt::validate_input(new.fact.clone()), &new.fact)?;
+    if let Some(item) = &new.item {
+        valid(prompt::validate_item_path(item.clone()), item)?;
+    }
+    let fact = Fact::new(new.fact, new.value, unit, Vec::new(), notes);
+    let record = match &new.record {
+        Some(record) => Some(Record::open(record)?),
+        None => None,
+    };
+    fact.write(record, new.item.map(Item::new))
+}
+
+/// Relations to or from `?entity=`, or all of them
+fn links(req: &Request) -> crate::DResult<Vec<EntityRelation>> {
+    match query(req, "entity") {
+        Some(entity) => EntityRelation::of(&entity.parse().map_err(DError::Invalid)?),
+        None => EntityRelation::all(),
+    }
+}
+
+fn new_link(new: NewLink) -> crate::DResult<EntityRelation> {
+    new.from.check()?;
+    new.to.check()?;
+    let rel = EntityRelation::new(new.name.as_deref().unwrap_or("link"), new.value,
+        new.from, new.to);
+    rel.append()?;
+    Ok(rel)
+}
+
+/// Records, items and entries whose names (or descriptions, values and
+/// notes) contain `?q=`, optionally only in `?record=`
+fn search(req: &Request) -> crate::DResult<Found> {
+    let search = Search {
+        query_str: query(req, "q").unwrap_or_default().to_string(),
+        records: query(req, "record").map(|r| vec![r.to_string()]).unwrap_or_default(),
+        ..Search::default()
+    };
+    let mut found = Found::default();
+    for record in records()?.into_iter().filter(|r| search.in_record(&r.name)) {
+        for item in record.items.iter().filter(|i| search.matches(&i.path())) {
+            found.items.push(RecordItem { record: record.name.clone(), item: item.clone() });
+        }
+        found.facts.extend(record.facts()?.into_iter()
+            .filter(|f| search.matches(&f.name) || search.matches(&f.val.raw())
+                || search.matches(&f.text())));
+        if search.matches(&record.name) || search.matches(&record.description) {
+            found.records.push(record);
+        }
+    }
+    Ok(found)
+}
+
+/// Summaries of entries, filtered by `?record=`, `?user=` or `?group=`
+fn stats(req: &Request) -> crate::DResult<Vec<FactStats>> {
+    let filter = StatsFilter {
+        record: query(req, "record").map(String::from),
+        user: query(req, "user").map(String::from),
+        group: query(req, "group").map(String::from),
+    };
+    Ok(FactStats::summarize(&filter.facts()?))
+}
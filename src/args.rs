@@ -1,3 +1,5 @@
+pub mod alias;
+pub mod complete;
 pub mod link;
 pub mod list;
 pub mod search;
@@ -0,0 +1,148 @@
+//! Expansion of the `[aliases]` of the config, e.g.
+//!
+//! ```toml
+//! [aliases]
+//! w = "item add health/body weight $1"
+//! sleep = "item add health/night sleep"
+//! ```
+//!
+//! so that `dlog w 80` runs `dlog item add health/body weight 80`, and
+//! `dlog -o json w 80` runs it with `-o json`.
+//! `$1` to `$9` are replaced by the args given after the alias and `$@` by
+//! all of them, and args no placeholder took are added to the end.
+use std::collections::{BTreeMap, HashSet};
+use clap::{Arg, ArgSettings};
+use colored::{Color, Colorize};
+use crate::error::DError;
+
+/// How deep aliases may expand to other aliases
+const MAX_DEPTH: usize = 8;
+
+/// Expands the alias the first arg after any of the `globals` flags names,
+/// if it is one and is not already a command, which `is_cmd` tells
+pub fn expand<F: Fn(&str) -> bool>(args: Vec<String>, aliases: &BTreeMap<String, String>, globals: &[Arg], is_cmd: F)
+    -> crate::DResult<Vec<String>>
+{
+    let mut args = args;
+    let mut seen = HashSet::new();
+    loop {
+        let at = command_at(&args, globals);
+        let name = match args.get(at) {
+            Some(name) => name.clone(),
+            None => break,
+        };
+        let expansion = match aliases.get(&name) {
+            Some(expansion) if !is_cmd(&name) => expansion,
+            _ => break,
+        };
+        if !seen.insert(name.clone()) || seen.len() > MAX_DEPTH {
+            return Err(DError::Invalid(format!("Alias {} expands to itself", name)));
+        }
+        let given = args.split_off(at + 1);
+        let mut used = vec![false; given.len()];
+        let mut expanded = args[..at].to_vec();
+        for word in split(expansion)? {
+            match placeholder(&word) {
+                Some(Placeholder::All) => {
+                    expanded.extend(given.iter().cloned());
+                    used.iter_mut().for_each(|u| *u = true);
+                },
+                Some(Placeholder::Arg(n)) => {
+                    let arg = given.get(n - 1).ok_or_else(|| DError::Invalid(
+                        format!("Alias {} needs at least {} args: {}", name, n, expansion)))?;
+                    expanded.push(arg.clone());
+                    used[n - 1] = true;
+                },
+                None => expanded.push(word),
+            }
+        }
+        expanded.extend(given.into_iter()
+            .zip(used)
+            .filter(|(_, used)| !used)
+            .map(|(arg, _)| arg));
+        args = expanded;
+    }
+    Ok(args)
+}
+
+/// Where the command starts in `args`: after the program name and any of
+/// the `globals` flags given before it, along with their values
+fn command_at(args: &[String], globals: &[Arg]) -> usize {
+    let mut at = 1;
+    while let Some(word) = args.get(at) {
+        let takes_value = if let Some(long) = word.strip_prefix("--") {
+            let name = long.split('=').next().unwrap_or_default();
+            match globals.iter().find(|a| a.get_long() == Some(name)) {
+                Some(arg) => arg.is_set(ArgSettings::TakesValue) && !long.contains('='),
+                None => break,
+            }
+        } else if let Some(short) = word.strip_prefix('-') {
+            let mut chars = short.chars();
+            match chars.next().and_then(|c| globals.iter().find(|a| a.get_short() == Some(c))) {
+                Some(arg) => arg.is_set(ArgSettings::TakesValue) && chars.as_str().is_empty(),
+                None => break,
+            }
+        } else {
+            break;
+        };
+        at += if takes_value { 2 } else { 1 };
+    }
+    at
+}
+
+enum Placeholder {
+    All,
+    Arg(usize),
+}
+
+fn placeholder(word: &str) -> Option<Placeholder> {
+    match word.strip_prefix('$')? {
+        "@" => Some(Placeholder::All),
+        n => n.parse().ok().filter(|n| *n > 0).map(Placeholder::Arg),
+    }
+}
+
+/// Splits an alias into args at whitespace, keeping what is in single or
+/// double quotes together
+pub fn split(s: &str) -> crate::DResult<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    for c in s.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                in_word = true;
+            },
+            (None, c) if c.is_whitespace() => if in_word {
+                words.push(std::mem::take(&mut word));
+                in_word = false;
+            },
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            },
+        }
+    }
+    if quote.is_some() {
+        return Err(DError::Invalid(format!("Unclosed quote in alias {}", s)));
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// The aliases as a section of `--help`
+pub fn help(aliases: &BTreeMap<String, String>) -> String {
+    let width = aliases.keys().map(|k| k.len()).max().unwrap_or_default();
+    let mut help = "ALIASES:".color(Color::BrightYellow).to_string();
+    for (name, expansion) in aliases {
+        let name = format!("{:width$}", name, width = width);
+        help.push_str(&format!("\n    {}    {}", name.color(Color::BrightGreen), expansion));
+    }
+    help
+}
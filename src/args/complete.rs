@@ -0,0 +1,261 @@
+//! Shell completion. The scripts `dlog --generate <shell>` prints hand the
+//! words typed so far to the hidden `dlog __complete`, which walks the
+//! commands and args of `DApp::cmd()` and fills in the names of the facts,
+//! items, records, attributes and units already in the store, e.g.
+//!
+//! ```sh
+//! $ dlog __complete -- dlog sl
+//! sleep
+//! ```
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use clap::{App, AppSettings, Arg, ArgSettings};
+use crate::models::{
+    Record, Unit, UserUnit,
+    data::{RecordData, AttribData},
+};
+
+/// The name of the hidden subcommand the scripts call
+pub const CMD: &str = "__complete";
+
+/// The shells scripts can be generated for
+pub const SHELLS: [&str; 5] = ["bash", "elvish", "fish", "powershell", "zsh"];
+
+/// What the value of an arg names, if anything in the store
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    Fact,
+    Item,
+    Record,
+    Attrib,
+    Unit,
+}
+
+impl Kind {
+
+    /// The kind of name an arg of the command at `path` takes, by the arg's
+    /// name. `NAME` is a fact, or a record, depending on the command, and
+    /// the names `attrib rename` and `attrib merge` take are attributes.
+    pub fn of(path: &[&str], arg: &str) -> Option<Self> {
+        match (path, arg) {
+            (_, "fact" | "link-fact" | "fact-name") => Some(Self::Fact),
+            (_, "item" | "ITEM" | "link-item" | "item-name" | "filteritem" | "parent") => Some(Self::Item),
+            (_, "record" | "RECORD" | "link-record" | "record-name" | "filterrecord") => Some(Self::Record),
+            (_, "attrib" | "attribs" | "attribute" | "link-attrib" | "link-attribute" | "with-attribute") =>
+                Some(Self::Attrib),
+            (_, "unit" | "UNIT" | "link-unit") => Some(Self::Unit),
+            ([] | ["fact"] | ["item", "add"], "NAME") => Some(Self::Fact),
+            (["record", "get" | "link" | "delete"], "NAME") => Some(Self::Record),
+            (["attrib", "rename"], "OLD" | "NEW") | (["attrib", "merge"], "FROM" | "INTO") =>
+                Some(Self::Attrib),
+            _ => None,
+        }
+    }
+}
+
+/// The names in the store, by kind
+#[derive(Debug, Default)]
+pub struct Names(BTreeMap<Kind, BTreeSet<String>>);
+
+impl Names {
+
+    /// Reads the names from every record. Sealed records are skipped rather
+    /// than unlocked, so completing never asks for a password.
+    pub fn load() -> crate::DResult<Self> {
+        let mut names = Self::default();
+        if !RecordData::path()?.exists() {
+            return Ok(names);
+        }
+        for def in AttribData::read()?.attribs() {
+            names.add(Kind::Attrib, &def.name);
+        }
+        for central in RecordData::read()?.records() {
+            names.add(Kind::Record, &central.name);
+            let record = match Record::open_path(&central.path) {
+                Ok(record) => record,
+                Err(_) => continue,
+            };
+            for item in &record.items {
+                names.add(Kind::Item, &item.path());
+            }
+            for fact in &record.fact_types {
+                names.add(Kind::Fact, &fact.name);
+                names.add_unit(&fact.unit);
+            }
+            for attrib in &record.attribs {
+                names.add(Kind::Attrib, &attrib.name);
+            }
+            for unit in &record.units {
+                names.add_unit(unit);
+            }
+            for fact in record.facts().unwrap_or_default() {
+                names.add(Kind::Fact, &fact.name);
+                names.add_unit(&fact.unit);
+                for attrib in &fact.attribs {
+                    names.add(Kind::Attrib, &attrib.name);
+                }
+            }
+        }
+        Ok(names)
+    }
+
+    pub fn add(&mut self, kind: Kind, name: &str) {
+        if !name.trim().is_empty() {
+            self.0.entry(kind).or_default().insert(name.to_string());
+        }
+    }
+
+    fn add_unit(&mut self, unit: &Unit) {
+        if let Unit::Other(UserUnit::Text(name)) = unit {
+            self.add(Kind::Unit, name);
+        }
+    }
+
+    pub fn get(&self, kind: Kind) -> impl Iterator<Item = &String> {
+        self.0.get(&kind).into_iter().flatten()
+    }
+}
+
+/// The completions of the last of `words`, the first being the binary.
+/// `globals` are the args every subcommand takes, and aliases are offered
+/// along with the top-level commands.
+pub fn complete<'a>(app: &App<'a>, globals: &[Arg<'a>], words: &[String], aliases: &BTreeMap<String, String>, names: &Names)
+    -> Vec<String>
+{
+    let (current, typed) = match words.split_last() {
+        Some((current, typed)) => (current.as_str(), typed.get(1..).unwrap_or_default()),
+        None => return Vec::new(),
+    };
+    let mut cmd = app;
+    let mut path = Vec::new();
+    let mut positionals = 0;
+    let mut value_of: Option<&Arg> = None;
+    for word in typed {
+        if value_of.take().is_some() {
+            continue;
+        }
+        let args = cmd.get_arguments().chain(globals.iter());
+        if let Some(long) = word.strip_prefix("--") {
+            if long.is_empty() || long.contains('=') {
+                continue;
+            }
+            match cmd.get_subcommands().find(|s| s.get_long_flag() == Some(long)) {
+                Some(sub) => {
+                    cmd = sub;
+                    path.push(sub.get_name());
+                    positionals = 0;
+                },
+                None => value_of = args
+                    .filter(|a| a.get_long() == Some(long))
+                    .find(|a| a.is_set(ArgSettings::TakesValue)),
+            }
+        } else if let Some(short) = word.strip_prefix('-').filter(|s| s.chars().count() == 1) {
+            let short = short.chars().next();
+            match cmd.get_subcommands().find(|s| s.get_short_flag() == short) {
+                Some(sub) => {
+                    cmd = sub;
+                    path.push(sub.get_name());
+                    positionals = 0;
+                },
+                None => value_of = args
+                    .filter(|a| a.get_short() == short)
+                    .find(|a| a.is_set(ArgSettings::TakesValue)),
+            }
+        } else if word.starts_with('-') {
+            continue;
+        } else if let Some(sub) = cmd.find_subcommand(word).filter(|_| positionals == 0) {
+            cmd = sub;
+            path.push(sub.get_name());
+        } else {
+            positionals += 1;
+        }
+    }
+    let mut found = Vec::new();
+    match value_of {
+        Some(arg) => found.extend(values(arg, &path, names)),
+        None if current.starts_with('-') => {
+            let longs = cmd.get_arguments()
+                .chain(globals.iter())
+                .filter(|a| !a.is_set(ArgSettings::Hidden))
+                .filter_map(|a| a.get_long())
+                .chain(cmd.get_subcommands().filter_map(|s| s.get_long_flag()));
+            found.extend(longs.map(|long| format!("--{}", long)));
+        },
+        None => {
+            if positionals == 0 {
+                found.extend(cmd.get_subcommands()
+                    .filter(|s| !s.is_set(AppSettings::Hidden))
+                    .map(|s| s.get_name().to_string())
+                    .filter(|s| !s.ends_with("_help")));
+                if path.is_empty() {
+                    found.extend(aliases.keys().cloned());
+                }
+            }
+            if let Some(arg) = cmd.get_positionals().find(|a| a.get_index() == Some(positionals + 1)) {
+                found.extend(values(arg, &path, names));
+            }
+        },
+    }
+    let mut seen = HashSet::new();
+    found.into_iter()
+        .filter(|c| c.starts_with(current))
+        .filter(|c| seen.insert(c.clone()))
+        .collect()
+}
+
+/// The values an arg may take, or the names of what it names
+fn values(arg: &Arg, path: &[&str], names: &Names) -> Vec<String> {
+    if let Some(values) = arg.get_possible_values() {
+        return values.iter().map(|v| v.to_string()).collect();
+    }
+    Kind::of(path, arg.get_name())
+        .map(|kind| names.get(kind).cloned().collect())
+        .unwrap_or_default()
+}
+
+/// The completion script for a shell, which calls `bin __complete`
+pub fn script(shell: &str, bin: &str) -> Option<String> {
+    let script = match shell {
+        "bash" => r#"_{bin}() {
+    local IFS=$'\n'
+    COMPREPLY=($({bin} {cmd} -- "${COMP_WORDS[@]:0:$((COMP_CWORD + 1))}" 2>/dev/null))
+}
+complete -o default -F _{bin} {bin}
+"#,
+        "elvish" => r#"set edit:completion:arg-completer[{bin}] = {|@words|
+    {bin} {cmd} -- $@words 2>/dev/null
+}
+"#,
+        "fish" => r#"function __{bin}_complete
+    {bin} {cmd} -- (commandline -opc) (commandline -ct) 2>/dev/null
+end
+complete -c {bin} -f -a '(__{bin}_complete)'
+"#,
+        "powershell" => r#"Register-ArgumentCompleter -Native -CommandName {bin} -ScriptBlock {
+    param($wordToComplete, $commandAst, $cursorPosition)
+    $words = @($commandAst.CommandElements |
+        Where-Object { $_.Extent.EndOffset -le $cursorPosition } |
+        ForEach-Object { $_.ToString() })
+    if ($wordToComplete -eq '') { $words += '' }
+    {bin} {cmd} -- @words 2>$null | ForEach-Object {
+        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)
+    }
+}
+"#,
+        "zsh" => r#"#compdef {bin}
+
+_{bin}() {
+    local -a candidates
+    candidates=("${(@f)$({bin} {cmd} -- "${(@)words[1,CURRENT]}" 2>/dev/null)}")
+    compadd -a candidates
+}
+
+if [ "$funcstack[1]" = "_{bin}" ]; then
+    _{bin} "$@"
+else
+    compdef _{bin} {bin}
+fi
+"#,
+        _ => return None,
+    };
+    Some(script.replace("{bin}", bin).replace("{cmd}", CMD))
+}
@@ -8,14 +8,50 @@ use crate::models::{
 };
 use clap::{ FromArgMatches, ArgMatches };
 
+/// A search query along with the filter and sort args shared by the search
+/// subcommands (`--attrib`, `--record`, `--asc`/`--desc`, `--max`)
 #[derive(Default, Debug)]
 pub struct Search {
-    query_str: String,
-    filters: Filters,
+    pub query_str: String,
+    pub filters: Filters,
+    pub attribs: Vec<String>,
+    pub records: Vec<String>,
+    pub descending: bool,
+    pub max_entries: Option<usize>,
 }
 
 impl Search {
 
+    /// Whether a name contains the query, ignoring case. An empty query
+    /// matches everything
+    pub fn matches(&self, name: &str) -> bool {
+        name.to_lowercase().contains(&self.query_str.to_lowercase())
+    }
+
+    /// Whether any of the given attributes is one searched for, or no
+    /// attributes are searched for
+    pub fn has_attribs(&self, attribs: &[Attrib]) -> bool {
+        self.attribs.is_empty() || attribs.iter()
+            .any(|a| self.attribs.iter().any(|s| s.eq_ignore_ascii_case(&a.name)))
+    }
+
+    /// Whether the record is one searched in, or no records are given
+    pub fn in_record(&self, record: &str) -> bool {
+        self.records.is_empty() || self.records.iter()
+            .any(|r| r.eq_ignore_ascii_case(record))
+    }
+
+    /// Sorts by the given key, ascending unless `--desc` was given, and
+    /// keeps at most `--max` results
+    pub fn sort_limit<T, K: Ord, F: FnMut(&T) -> K>(&self, results: &mut Vec<T>, key: F) {
+        results.sort_by_key(key);
+        if self.descending {
+            results.reverse();
+        }
+        if let Some(max) = self.max_entries {
+            results.truncate(max);
+        }
+    }
 }
 
 impl Cmd for Search {
@@ -68,7 +104,18 @@ impl IntoApp for Search {
 
 impl clap::FromArgMatches for Search {
     fn from_arg_matches(matches: &clap::ArgMatches) -> Self {
-        Search { query_str: "".into(), filters: Filters::None}
+        let values = |arg: &str| matches.values_of(arg)
+            .map(|v| v.map(String::from).collect())
+            .unwrap_or_default();
+        Search {
+            query_str: matches.value_of("QUERY").unwrap_or_default().into(),
+            filters: Filters::None,
+            attribs: values("attrib"),
+            records: values("record"),
+            descending: matches.is_present("descending"),
+            max_entries: matches.value_of("max-entries")
+                .and_then(|m| m.parse().ok()),
+        }
     }
 }
 
@@ -0,0 +1,602 @@
+//! Encryption at rest. Once locked, every file in the data dir and in each
+//! record's directory is sealed with AES-256-GCM under a key derived from a
+//! passphrase with PBKDF2, and kept as `<file>.enc`. Each command opens only
+//! what it works on: the files directly in the data dir when it starts, each
+//! area of the data dir such as the journal or history as it is looked up,
+//! and each record's files as the record is opened. Everything opened is
+//! sealed again when the command ends, or after each request for `dlog serve`.
+//! The key is cached in a private session for a while after `dlog auth unlock`,
+//! so that the passphrase is not asked for every command.
+use std::{
+    collections::HashSet,
+    fs, path::{Path, PathBuf},
+    num::NonZeroU32,
+    sync::RwLock,
+};
+use chrono::{DateTime, Duration, Local};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    error::Unspecified,
+    pbkdf2,
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Serialize, Deserialize};
+use crate::{config::DConfig, error::DError, util, models::data::RecordData};
+
+/// Extension of sealed files
+pub const SEALED: &str = "enc";
+/// Written at the start of every sealed file
+const MAGIC: &[u8] = b"DLOGENC1";
+/// Sealed with the key to check passphrases against
+const CHECK: &[u8] = b"dlog";
+const ITERATIONS: u32 = 100_000;
+
+lazy_static::lazy_static! {
+    /// The key of the store opened by this command, if it is locked
+    static ref OPEN: RwLock<Option<Key>> = RwLock::new(None);
+    /// The areas of the data dir opened since the store was last sealed
+    static ref OPENED: RwLock<HashSet<PathBuf>> = RwLock::new(HashSet::new());
+}
+
+/// How the store's key is derived, kept in `auth.toml` in the data dir,
+/// which is never sealed. The store is locked if this file exists.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthData {
+    #[serde(rename = "Salt")]
+    salt: String,
+    #[serde(rename = "Iterations")]
+    iterations: u32,
+    /// `CHECK` sealed with the key, to tell a wrong passphrase early
+    #[serde(rename = "Check")]
+    check: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Key([u8; 32]);
+
+/// A key cached after unlocking, until it expires
+#[derive(Debug, Serialize, Deserialize)]
+struct Session {
+    #[serde(rename = "Key")]
+    key: String,
+    #[serde(rename = "Expires")]
+    expires: DateTime<Local>,
+}
+
+impl AuthData {
+
+    pub fn path() -> crate::DResult<PathBuf> {
+        util::default_data_dir(Some("auth.toml"))
+    }
+
+    pub fn read() -> crate::DResult<Option<Self>> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(toml::from_str(&fs::read_to_string(path)?)?))
+    }
+
+    pub fn write(&self) -> crate::DResult<()> {
+        fs::write(Self::path()?, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// New key derivation settings for a passphrase, with a fresh salt
+    pub fn new(passphrase: &str) -> crate::DResult<(Self, Key)> {
+        let mut salt = [0u8; 16];
+        SystemRandom::new().fill(&mut salt)?;
+        let mut auth = Self {
+            salt: to_hex(&salt),
+            iterations: ITERATIONS,
+            check: String::new(),
+        };
+        let key = auth.derive(passphrase)?;
+        auth.check = to_hex(&key.seal(CHECK)?);
+        Ok((auth, key))
+    }
+
+    fn derive(&self, passphrase: &str) -> crate::DResult<Key> {
+        let iterations = NonZeroU32::new(self.iterations)
+            .ok_or_else(|| DError::Invalid("Invalid iterations in auth.toml".into()))?;
+        let mut key = [0u8; 32];
+        pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, iterations,
+            &from_hex(&self.salt)?, passphrase.as_bytes(), &mut key);
+        Ok(Key(key))
+    }
+
+    /// The key for a passphrase, if it is the right one
+    pub fn key(&self, passphrase: &str) -> crate::DResult<Key> {
+        let key = self.derive(passphrase)?;
+        self.verify(&key)?;
+        Ok(key)
+    }
+
+    fn verify(&self, key: &Key) -> crate::DResult<()> {
+        match key.open(&from_hex(&self.check)?) {
+            Ok(check) if check == CHECK => Ok(()),
+            _ => Err(DError::Invalid("Wrong passphrase".into())),
+        }
+    }
+
+    /// The key cached by `dlog auth unlock`, or else the one for the
+    /// passphrase from the environment, config or a prompt
+    pub fn unlock(&self) -> crate::DResult<Key> {
+        if let Some(key) = Session::read()?.map(|s| s.key()).transpose()? {
+            if self.verify(&key).is_ok() {
+                return Ok(key);
+            }
+        }
+        self.key(&passphrase(false)?)
+    }
+}
+
+impl Key {
+
+    pub fn seal(&self, data: &[u8]) -> crate::DResult<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new().fill(&mut nonce)?;
+        let mut sealed = data.to_vec();
+        self.aead()?.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut sealed)?;
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&nonce);
+        out.extend(sealed);
+        Ok(out)
+    }
+
+    pub fn open(&self, data: &[u8]) -> crate::DResult<Vec<u8>> {
+        if data.len() < MAGIC.len() + NONCE_LEN || !data.starts_with(MAGIC) {
+            return Err(DError::Invalid("Not a file sealed by dlog".into()));
+        }
+        let (nonce, sealed) = data[MAGIC.len()..].split_at(NONCE_LEN);
+        let mut nonce_bytes = [0u8; NONCE_LEN];
+        nonce_bytes.copy_from_slice(nonce);
+        let mut sealed = sealed.to_vec();
+        let len = self.aead()?
+            .open_in_place(Nonce::assume_unique_for_key(nonce_bytes), Aad::empty(), &mut sealed)
+            .map_err(|_| DError::Invalid("Wrong passphrase, or the file is corrupted".into()))?
+            .len();
+        sealed.truncate(len);
+        Ok(sealed)
+    }
+
+    fn aead(&self) -> crate::DResult<LessSafeKey> {
+        Ok(LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &self.0)?))
+    }
+}
+
+impl Session {
+
+    /// A directory for sessions in the runtime dir, made so only this user
+    /// can reach it. Without a runtime dir there is nowhere private to keep
+    /// the key, so it is not cached.
+    #[cfg(unix)]
+    fn dir() -> crate::DResult<Option<PathBuf>> {
+        use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+        let dir = match dirs_next::runtime_dir() {
+            Some(run) if run.is_dir() => run.join("dlog"),
+            _ => return Ok(None),
+        };
+        if let Err(e) = fs::DirBuilder::new().mode(0o700).create(&dir) {
+            if e.kind() != std::io::ErrorKind::AlreadyExists {
+                return Err(e.into());
+            }
+        }
+        let meta = fs::symlink_metadata(&dir)?;
+        // SAFETY: geteuid cannot fail and touches no memory
+        let uid = unsafe { libc::geteuid() };
+        if !meta.is_dir() || meta.uid() != uid || meta.mode() & 0o077 != 0 {
+            return Err(DError::Invalid(format!(
+                "{} is not a directory only you can reach, so the key is not kept there",
+                dir.display())));
+        }
+        Ok(Some(dir))
+    }
+
+    #[cfg(not(unix))]
+    fn dir() -> crate::DResult<Option<PathBuf>> {
+        Ok(None)
+    }
+
+    /// Sessions are kept in the runtime dir, one per data dir
+    fn path() -> crate::DResult<Option<PathBuf>> {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        util::default_data_dir(None)?.hash(&mut hasher);
+        Ok(Self::dir()?.map(|dir| dir.join(format!("session-{:x}", hasher.finish()))))
+    }
+
+    /// Options to open a session file with, never following a symlink
+    fn options() -> fs::OpenOptions {
+        #[allow(unused_mut)]
+        let mut opts = fs::OpenOptions::new();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            opts.mode(0o600).custom_flags(libc::O_NOFOLLOW);
+        }
+        opts
+    }
+
+    /// The cached session, unless there is none or it has expired
+    fn read() -> crate::DResult<Option<Self>> {
+        let path = match Self::path()? {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let mut text = String::new();
+        match Self::options().read(true).open(&path) {
+            Ok(mut file) => std::io::Read::read_to_string(&mut file, &mut text)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        match toml::from_str::<Self>(&text) {
+            Ok(session) if session.expires > Local::now() => Ok(Some(session)),
+            _ => {
+                Self::clear()?;
+                Ok(None)
+            },
+        }
+    }
+
+    fn key(&self) -> crate::DResult<Key> {
+        let bytes = from_hex(&self.key)?;
+        let mut key = [0u8; 32];
+        if bytes.len() != key.len() {
+            return Err(DError::Invalid("Invalid key in the session".into()));
+        }
+        key.copy_from_slice(&bytes);
+        Ok(Key(key))
+    }
+
+    /// Caches a key in a new session file, which only this user may read
+    fn save(key: &Key, minutes: i64) -> crate::DResult<DateTime<Local>> {
+        let path = Self::path()?.ok_or_else(|| DError::Invalid(
+            "There is no private runtime dir (XDG_RUNTIME_DIR) to keep the key in, \
+            set DLOG_PASSPHRASE or auth.password_cmd instead of unlocking".into()))?;
+        let session = Self {
+            key: to_hex(&key.0),
+            expires: Local::now() + Duration::minutes(minutes),
+        };
+        Self::clear()?;
+        let mut file = Self::options().write(true).create_new(true).open(path)?;
+        std::io::Write::write_all(&mut file, toml::to_string(&session)?.as_bytes())?;
+        Ok(session.expires)
+    }
+
+    fn clear() -> crate::DResult<()> {
+        if let Some(path) = Self::path()? {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {},
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The passphrase from `DLOG_PASSPHRASE`, the config's `auth.password_cmd`
+/// or `auth.password`, or else asked for. A new passphrase is asked for twice.
+pub fn passphrase(new: bool) -> crate::DResult<String> {
+    if let Ok(pass) = std::env::var("DLOG_PASSPHRASE") {
+        return Ok(pass);
+    }
+    let conf = DConfig::load()?;
+    if let Some(cmd) = conf.password_cmd() {
+        let mut args = cmd.split_whitespace();
+        let prog = args.next()
+            .ok_or_else(|| DError::Invalid("The password_cmd is empty".into()))?;
+        let out = std::process::Command::new(prog).args(args).output()?;
+        if !out.status.success() {
+            return Err(DError::Invalid(format!("The password_cmd {} failed", cmd)));
+        }
+        return Ok(String::from_utf8_lossy(&out.stdout).trim_end_matches(['\r', '\n']).to_string());
+    }
+    if let Some(pass) = conf.password() {
+        return Ok(pass);
+    }
+    let mut prompt = dialoguer::Password::new();
+    prompt.with_prompt("Passphrase");
+    if new {
+        prompt.with_confirmation("Repeat the passphrase", "The passphrases do not match");
+    }
+    Ok(prompt.interact()?)
+}
+
+/// Whether a file exists, sealed or not
+pub fn exists(path: &Path) -> bool {
+    path.exists() || sealed_path(path).exists()
+}
+
+fn sealed_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(SEALED);
+    path.with_file_name(name)
+}
+
+fn is_sealed(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == SEALED)
+}
+
+/// Whether a directory holds a record, sealed or not
+fn is_record(dir: &Path) -> bool {
+    exists(&dir.join("record.toml"))
+}
+
+/// Files in a directory, recursing into subdirectories but not into other
+/// records. The auth file, dotfiles such as `.gitignore` and symlinks, which
+/// may point out of the store, are left alone.
+fn files(dir: &Path, root: bool) -> crate::DResult<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !dir.is_dir() || (!root && is_record(dir)) {
+        return Ok(files);
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        if name.starts_with('.') || name == "auth.toml" || entry.file_type()?.is_symlink() {
+            continue;
+        }
+        if path.is_dir() {
+            files.extend(self::files(&path, false)?);
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Seals the plaintext files of a directory, returning how many were sealed
+fn seal_dir(dir: &Path, key: &Key) -> crate::DResult<usize> {
+    let mut sealed = 0;
+    for file in files(dir, true)?.into_iter().filter(|f| !is_sealed(f)) {
+        fs::write(sealed_path(&file), key.seal(&fs::read(&file)?)?)?;
+        fs::remove_file(&file)?;
+        sealed += 1;
+    }
+    Ok(sealed)
+}
+
+/// Opens the sealed files of a directory, returning how many were opened
+fn open_dir(dir: &Path, key: &Key) -> crate::DResult<usize> {
+    let mut opened = 0;
+    for file in files(dir, true)?.into_iter().filter(|f| is_sealed(f)) {
+        open_file(&file, key)?;
+        opened += 1;
+    }
+    Ok(opened)
+}
+
+/// Opens a sealed file in place of itself
+fn open_file(file: &Path, key: &Key) -> crate::DResult<()> {
+    fs::write(file.with_extension(""), key.open(&fs::read(file)?)?)?;
+    fs::remove_file(file)?;
+    Ok(())
+}
+
+/// Opens the sealed files directly in the data dir, leaving its areas to be
+/// opened as they are used
+fn open_top(key: &Key) -> crate::DResult<usize> {
+    let data = util::default_data_dir(None)?;
+    let mut opened = 0;
+    for file in files(&data, true)?.into_iter()
+        .filter(|f| is_sealed(f) && f.parent() == Some(data.as_path()))
+    {
+        open_file(&file, key)?;
+        opened += 1;
+    }
+    Ok(opened)
+}
+
+/// The data dir followed by the directories of all registered records.
+/// The data dir's own files must be open to read these.
+fn store_dirs() -> crate::DResult<Vec<PathBuf>> {
+    let mut dirs = vec![util::default_data_dir(None)?];
+    dirs.extend(RecordData::read()?.records().map(|r| PathBuf::from(&r.path)));
+    Ok(dirs)
+}
+
+/// Opens the files directly in the data dir for this command if the store
+/// is locked, leaving its areas and records to be opened as they are used
+pub fn open_store() -> crate::DResult<()> {
+    let auth = match AuthData::read()? {
+        Some(auth) => auth,
+        None => return Ok(()),
+    };
+    let key = auth.unlock()?;
+    // Set first, so whatever was opened is sealed again even if this fails
+    *OPEN.write().expect("auth lock poisoned") = Some(key.clone());
+    open_top(&key)?;
+    Ok(())
+}
+
+/// Opens an area of the data dir, a file or a directory such as the journal,
+/// for this command if the store is open and it has not been opened yet
+pub fn open_area(path: &Path) -> crate::DResult<()> {
+    if let Some(key) = OPEN.read().expect("auth lock poisoned").as_ref() {
+        if OPENED.write().expect("auth lock poisoned").insert(path.to_path_buf()) {
+            let sealed = sealed_path(path);
+            if sealed.is_file() {
+                open_file(&sealed, key)?;
+            } else if path.is_dir() {
+                open_dir(path, key)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Opens a record's files for this command if the store is open
+pub fn open_record(dir: &Path) -> crate::DResult<()> {
+    if let Some(key) = OPEN.read().expect("auth lock poisoned").as_ref() {
+        open_dir(dir, key)?;
+    }
+    Ok(())
+}
+
+/// Opens whatever holds a file for this command if the store is open: the
+/// record it is in, or else its area of the data dir
+pub fn open_path(path: &Path) -> crate::DResult<()> {
+    if OPEN.read().expect("auth lock poisoned").is_none() {
+        return Ok(());
+    }
+    let data = util::default_data_dir(None)?;
+    if let Some(dir) = path.ancestors().skip(1).take_while(|d| *d != data).find(|d| is_record(d)) {
+        return open_record(dir);
+    }
+    if let Some(area) = path.strip_prefix(&data).ok().and_then(|p| p.components().next()) {
+        open_area(&data.join(area))?;
+    }
+    Ok(())
+}
+
+/// Seals everything opened so far while keeping the key, so that a long
+/// running command such as `dlog serve` leaves nothing open between requests
+pub fn suspend() -> crate::DResult<()> {
+    let key = OPEN.read().expect("auth lock poisoned").clone();
+    if let Some(key) = key {
+        OPENED.write().expect("auth lock poisoned").clear();
+        seal(&key)?;
+    }
+    Ok(())
+}
+
+/// Opens the files directly in the data dir again after `suspend`
+pub fn resume() -> crate::DResult<()> {
+    let key = OPEN.read().expect("auth lock poisoned").clone();
+    if let Some(key) = key {
+        open_top(&key)?;
+    }
+    Ok(())
+}
+
+/// Seals everything opened by this command again, along with anything left
+/// open by a command which failed
+pub fn close_store() -> crate::DResult<()> {
+    let key = match OPEN.write().expect("auth lock poisoned").take() {
+        Some(key) => key,
+        None => return Ok(()),
+    };
+    OPENED.write().expect("auth lock poisoned").clear();
+    seal(&key).map(|_| ())
+}
+
+/// Seals every plaintext file in the store
+fn seal(key: &Key) -> crate::DResult<usize> {
+    let mut sealed = 0;
+    for dir in store_dirs()? {
+        sealed += seal_dir(&dir, key)?;
+    }
+    Ok(sealed)
+}
+
+/// Opens every sealed file in the store
+fn open_all(key: &Key) -> crate::DResult<usize> {
+    let mut opened = open_dir(&util::default_data_dir(None)?, key)?;
+    for dir in store_dirs()?.iter().skip(1) {
+        opened += open_dir(dir, key)?;
+    }
+    Ok(opened)
+}
+
+/// Locks the store, setting up a passphrase the first time. Returns how
+/// many files were sealed.
+pub fn lock() -> crate::DResult<usize> {
+    let key = match AuthData::read()? {
+        Some(auth) => auth.unlock()?,
+        None => {
+            let (auth, key) = AuthData::new(&passphrase(true)?)?;
+            auth.write()?;
+            key
+        },
+    };
+    open_dir(&util::default_data_dir(None)?, &key)?;
+    let sealed = seal(&key)?;
+    Session::clear()?;
+    Ok(sealed)
+}
+
+/// Checks the passphrase and caches the key for the given minutes, so that
+/// commands open the store without asking for it
+pub fn unlock(minutes: Option<i64>) -> crate::DResult<DateTime<Local>> {
+    let auth = AuthData::read()?
+        .ok_or_else(|| DError::Invalid("The store is not locked, lock it with 'dlog auth lock'".into()))?;
+    let key = auth.key(&passphrase(false)?)?;
+    let minutes = minutes.or_else(|| DConfig::load().ok().and_then(|c| c.session_minutes()))
+        .unwrap_or(15);
+    Session::save(&key, minutes)
+}
+
+/// Seals the store again under a new passphrase. Returns how many files
+/// were sealed.
+pub fn rekey() -> crate::DResult<usize> {
+    let auth = AuthData::read()?
+        .ok_or_else(|| DError::Invalid("The store is not locked, lock it with 'dlog auth lock'".into()))?;
+    let old = auth.unlock()?;
+    let new = std::env::var("DLOG_NEW_PASSPHRASE").map(Ok)
+        .unwrap_or_else(|_| {
+            eprintln!("Enter the new passphrase");
+            passphrase(true)
+        })?;
+    open_all(&old)?;
+    let (auth, key) = AuthData::new(&new)?;
+    let sealed = seal(&key)?;
+    auth.write()?;
+    Session::clear()?;
+    Ok(sealed)
+}
+
+/// Whether the store is locked, how many of its files are sealed, and
+/// until when a session is cached. Sealed records outside of the data dir
+/// are only counted while the data dir's own files are open.
+pub fn status() -> crate::DResult<(bool, usize, Option<DateTime<Local>>)> {
+    let locked = AuthData::read()?.is_some();
+    let data = util::default_data_dir(None)?;
+    let mut sealed = walk(&data)?;
+    if data.join("records.toml").exists() {
+        for dir in store_dirs()?.iter().filter(|d| !d.starts_with(&data)) {
+            sealed += walk(dir)?;
+        }
+    }
+    Ok((locked, sealed, Session::read()?.map(|s| s.expires)))
+}
+
+/// Sealed files anywhere beneath a directory, records included
+fn walk(dir: &Path) -> crate::DResult<usize> {
+    let mut sealed = 0;
+    if !dir.is_dir() {
+        return Ok(sealed);
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            sealed += walk(&path)?;
+        } else if is_sealed(&path) {
+            sealed += 1;
+        }
+    }
+    Ok(sealed)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> crate::DResult<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(DError::Invalid("Invalid hex in auth data".into()));
+    }
+    (0..hex.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16)
+            .map_err(|_| DError::Invalid("Invalid hex in auth data".into())))
+        .collect()
+}
+
+impl From<Unspecified> for DError {
+    fn from(_: Unspecified) -> Self {
+        DError::Invalid("Could not encrypt or decrypt data".into())
+    }
+}
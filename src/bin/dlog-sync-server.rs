@@ -0,0 +1,42 @@
+//! Reference server for `dlog sync`, keeping what is pushed to it in a
+//! directory, e.g. `dlog-sync-server --bind 0.0.0.0:7878 --dir ./sync`
+use std::{io::Write, net::TcpListener};
+use colored::{Color, Colorize};
+use dlog_lib::{http, sync::server::Server};
+
+fn main() {
+    let matches = clap::App::new("dlog-sync-server")
+        .about("Serve the dlog sync protocol, storing entities in a directory")
+        .arg(clap::Arg::new("bind")
+            .about("Address to listen on, port 0 picks a free port")
+            .short('b')
+            .long("bind")
+            .takes_value(true)
+            .default_value("127.0.0.1:7878"))
+        .arg(clap::Arg::new("dir")
+            .about("Directory to keep entities in")
+            .short('d')
+            .long("dir")
+            .takes_value(true)
+            .default_value("dlog-sync")
+            .value_hint(clap::ValueHint::DirPath))
+        .arg(clap::Arg::new("token")
+            .about("Bearer token clients must send, anyone may sync if not given")
+            .short('t')
+            .long("token")
+            .takes_value(true))
+        .get_matches();
+    let res = Server::open(matches.value_of("dir").unwrap_or_default(),
+            matches.value_of("token").map(String::from))
+        .and_then(|server| {
+            let listener = TcpListener::bind(matches.value_of("bind").unwrap_or_default())?;
+            println!("Listening on {}", listener.local_addr()?);
+            std::io::stdout().flush()?;
+            Ok((server, listener))
+        })
+        .and_then(|(mut server, listener)| http::serve(listener, |req| server.handle(req)));
+    if let Err(e) = res {
+        eprintln!("{}", format!("Could not serve: {}", e).color(Color::BrightRed));
+        std::process::exit(1);
+    }
+}
@@ -7,7 +7,19 @@ pub mod attribute;
 pub mod link;
 pub mod user;
 pub mod stats;
-
+pub mod attach;
+pub mod note;
+pub mod journal;
+pub mod topic;
+pub mod auth;
+pub mod sync;
+pub mod serve;
+pub mod config;
+pub mod history;
+pub mod trash;
+pub mod search;
+
+use std::sync::atomic::{AtomicBool, Ordering};
 use chrono::{DateTime, Local};
 use super::config::DConfig;
 use self::{
@@ -20,7 +32,19 @@ use self::{
     stats::StatsCmd,
     action::ActionCmd,
     relation::RelCmd,
+    attach::AttachCmd,
+    note::NoteCmd,
+    journal::JournalCmd,
+    topic::TopicCmd,
+    auth::AuthCmd,
+    sync::SyncCmd,
+    serve::ServeCmd,
+    config::ConfigCmd,
+    history::HistoryCmd,
+    trash::TrashCmd,
+    search::SearchCmd,
 };
+use crate::args::{alias, complete};
 use crate::models::{
     Entry, Fact, Record, Item, Attrib,
     note::Note, Action, Relation,  Unit, FactValue,
@@ -28,6 +52,9 @@ use crate::models::{
 use colored::{Color, Colorize};
 use clap::{Arg, ArgMatches, Clap, FromArgMatches, Subcommand};
 
+/// Whether the command run failed, see [`fail`]
+static FAILED: AtomicBool = AtomicBool::new(false);
+
 pub struct DApp {
     pub config: DConfig,
     pub subcmd: Subcmd,
@@ -45,11 +72,20 @@ pub enum Subcmd {
     User(UserCmd),
     Stats(StatsCmd),
     Relation(RelCmd),
+    Attach(AttachCmd),
+    Note(NoteCmd),
+    Journal(JournalCmd),
+    Topic(TopicCmd),
+    Auth(AuthCmd),
+    Sync(SyncCmd),
+    Serve(ServeCmd),
+    Config(ConfigCmd),
+    History(HistoryCmd),
+    Trash(TrashCmd),
     Alias(String),
     List,
     Data,
-    Search,
-    Config,
+    Search(SearchCmd),
     Export,
     Import,
     Help,
@@ -65,7 +101,7 @@ impl Default for DApp {
 }
 
 pub struct TermSettings {
-    atty: bool,
+    pub atty: bool,
     color: clap::AppSettings,
 }
 
@@ -91,22 +127,15 @@ impl Cmd for DApp {
         // let mut args: Vec<Arg> = Vec::new();
         args.extend(vec![
             Self::version(),
-            Self::output(),
-            Self::config_file(),
-            clap::Arg::new("pretty-print")
-                .about("Print output into a visually pleasing style")
-                .takes_value(false)
-                .short('p')
-                .long("pretty"),
             clap::Arg::new("generator")
                 .short('g')
                 .long("generate")
                 .about("Generate shell completion scripts")
                 .long_about("Generate completions for shell, where the shell is the value")
-                .possible_values(&[
-                    "bash", "elvish", "fish", "powershell", "zsh",
-                ]),
+                .value_name("SHELL")
+                .possible_values(&complete::SHELLS),
         ]);
+        args.extend(Self::globals());
         args
     }
 
@@ -122,17 +151,41 @@ impl Cmd for DApp {
             StatsCmd::cmd(),
             UserCmd::cmd(),
             ActionCmd::cmd(),
+            AttachCmd::cmd(),
+            AttachCmd::list_cmd(),
+            NoteCmd::cmd(),
+            JournalCmd::cmd(),
+            TopicCmd::cmd(),
+            AuthCmd::cmd(),
+            SyncCmd::cmd(),
+            ServeCmd::cmd(),
             Self::help_cmd(),
-            Self::cfg_cmd(),
+            ConfigCmd::cmd()
+                .short_flag('c')
+                .long_flag("config"),
             Self::data_cmd(),
+            HistoryCmd::cmd(),
+            HistoryCmd::undo_cmd(),
+            HistoryCmd::redo_cmd(),
+            TrashCmd::cmd(),
+            SearchCmd::cmd(),
             clap::App::new("init")
-                .about("Initialize a fact database in the current folder"),
+                .about("Initialize a fact database in the current folder")
+                .long_about("Initialize a record in the current folder (or --path), named after the folder unless a name is given. Refuses to overwrite an existing record")
+                .args(RecordCmd::init_args()),
             clap::App::new("export")
                 .about("Export all of your data to a .zip file or HTML, or save your data to a file to be imported later"),
             clap::App::new("import")
                 .about("Import dlog data or other data sources into a local Dlog database"),
             clap::App::new("inbox")
                 .about("Show operations related to unorganized facts and items"),
+            clap::App::new(complete::CMD)
+                .about("Print the completions of the last of the given words")
+                .setting(clap::AppSettings::Hidden)
+                .setting(clap::AppSettings::TrailingVarArg)
+                .arg(clap::Arg::new("WORDS")
+                    .multiple_values(true)
+                    .allow_hyphen_values(true)),
         ]
     }
 
@@ -165,29 +218,100 @@ impl Cmd for DApp {
 
     fn run(&self) {
         let term = TermSettings::new();
-        let conf = DConfig::load().expect("Could not load config");
+        let args = std::env::args().collect::<Vec<String>>();
+        let command = command_line(&args);
+        if let Some(file) = config_file_arg(&args) {
+            DConfig::set_file(file.into());
+        }
+        let conf = DConfig::load().unwrap_or_else(|e| {
+            eprintln!("{}", format!("Could not load the config: {}", e)
+                .color(Color::BrightRed));
+            std::process::exit(1);
+        });
+        let app = Self::cmd();
+        if args.get(1).map(String::as_str) == Some(complete::CMD) {
+            let words = match args.get(2).map(String::as_str) {
+                Some("--") => &args[3..],
+                _ => &args[2..],
+            };
+            let names = complete::Names::load().unwrap_or_default();
+            for word in complete::complete(&app, &Self::globals(), words, conf.aliases(), &names) {
+                println!("{}", word);
+            }
+            return;
+        }
+        let args = alias::expand(args, conf.aliases(), &Self::globals(), |name| app.find_subcommand(name).is_some())
+            .unwrap_or_else(|e| {
+                eprintln!("{}", format!("Could not expand alias: {}", e)
+                    .color(Color::BrightRed));
+                std::process::exit(1);
+            });
         let data = crate::models::data::Data::new().expect("could not load data");
-        let matches = Self::cmd()
-            .get_matches();
+        let matches = app.get_matches_from(args);
+        if let Some(shell) = matches.value_of("generator") {
+            if let Some(script) = complete::script(shell, Self::name()) {
+                print!("{}", script);
+            }
+            return;
+        }
         let app = Self::from_arg_matches(&matches);
+        if let Some(output) = global_value(&matches, "output") {
+            Self::match_output(output);
+        }
+        let pretty = match (global_flag(&matches, "pretty-print"), global_flag(&matches, "compact")) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        };
+        crate::output::set_style(crate::output::Style::new(conf.format(), pretty));
+        crate::prompt::take_as_typed(global_flag(&matches, "as-typed"));
+        if !matches!(app.subcmd, Subcmd::Auth(_) | Subcmd::Config(_)) {
+            if let Err(e) = crate::auth::open_store() {
+                eprintln!("{}", format!("Could not open the store: {}", e)
+                    .color(Color::BrightRed));
+                std::process::exit(1);
+            }
+        }
         match app.subcmd {
-            Subcmd::Config => {
-                if let Some(m) = matches.subcommand_matches("config") {
-                    if m.is_present("show") {
-                        self.config.show();
-                    }
-                }
-                if let Some(m) = matches.subcommand_matches("data") {
-                    if m.is_present("show") {
-                        self.config.show();
-                    }
-                    if m.is_present("show") {
-                        self.config.show();
-                    }
+            Subcmd::Config(cmd) => cmd.run(),
+            Subcmd::Help => {
+                let _ = Self::cmd().print_long_help();
+                if !conf.aliases().is_empty() {
+                    println!("\n{}", alias::help(conf.aliases()));
                 }
             },
+            Subcmd::Record(cmd) => cmd.run(),
+            Subcmd::Item(cmd) => cmd.run(),
+            Subcmd::Attach(cmd) => cmd.run(),
+            Subcmd::Attrib(cmd) => cmd.run(),
+            Subcmd::Note(cmd) => cmd.run(),
+            Subcmd::Journal(cmd) => cmd.run(),
+            Subcmd::Topic(cmd) => cmd.run(),
+            Subcmd::User(cmd) => cmd.run(),
+            Subcmd::Stats(cmd) => cmd.run(),
+            Subcmd::Auth(cmd) => cmd.run(),
+            Subcmd::Sync(cmd) => cmd.run(),
+            Subcmd::Serve(cmd) => cmd.run(),
+            Subcmd::History(cmd) => cmd.run(),
+            Subcmd::Trash(cmd) => cmd.run(),
+            Subcmd::Fact(cmd) => cmd.run(),
+            Subcmd::Search(cmd) => cmd.run(),
             _ => {}
         }
+        match crate::history::commit(&command) {
+            Ok(Some(op)) => reindex(&op.changes),
+            Ok(None) => {},
+            Err(e) => eprintln!("{}", format!("Could not journal the command: {}", e)
+                .color(Color::BrightRed)),
+        }
+        if let Err(e) = crate::auth::close_store() {
+            eprintln!("{}", format!("Could not lock the store again: {}", e)
+                .color(Color::BrightRed));
+            std::process::exit(1);
+        }
+        if FAILED.load(Ordering::SeqCst) {
+            std::process::exit(1);
+        }
     }
 
 }
@@ -224,49 +348,49 @@ impl DApp {
             .takes_value(false)
     }
 
+    /// The args every subcommand takes
+    pub fn globals() -> Vec<Arg<'static>> {
+        vec![
+            Self::output(),
+            Self::config_file(),
+            clap::Arg::new("pretty-print")
+                .about("Print output into a visually pleasing style")
+                .long_about("Draw tables with full, rounded borders and show times relative to now, e.g. 3 hours ago")
+                .takes_value(false)
+                .short('p')
+                .long("pretty"),
+            clap::Arg::new("compact")
+                .about("Print tables compactly, even if format.pretty is set")
+                .takes_value(false)
+                .conflicts_with("pretty-print")
+                .long("compact"),
+            clap::Arg::new("as-typed")
+                .about("Take record, item and fact names as typed, even if alike existing ones")
+                .long_about("Take record, item and fact names as typed instead of asking whether one alike was meant, which off a terminal is refused")
+                .takes_value(false)
+                .global(true)
+                .long("as-typed"),
+        ]
+    }
+
     pub fn output() -> Arg<'static> {
         clap::Arg::new("output")
             .short('o')
             .long("output")
-            .about("Print version info")
+            .about("Print listings as json, ndjson, yaml, csv or tsv instead of a table")
             .takes_value(true)
+            .global(true)
+            .value_name("FORMAT")
+            .possible_values(&crate::output::Format::NAMES)
     }
 
-    pub fn match_output(val: String) {
-        match val.as_str() {
-            "json" => { println!("JSON output") },
-            "yaml" => { println!("YAML output") }
-            _ => { println!("Invalid output type") }
+    /// Sets the format listings are printed in for the rest of the command
+    pub fn match_output(val: &str) {
+        if let Ok(format) = val.parse() {
+            crate::output::set(format);
         }
     }
 
-    pub fn cfg_cmd() -> clap::App<'static> {
-        clap::App::new("config")
-            .about("Set configuration properties from the CLI")
-            .long_about("Provide a key-value pair in the form KEY=VALUE or KEY,VALUE
-                to set a config property")
-            .short_flag('c')
-            .long_flag("config")
-            .args(&vec![
-                clap::Arg::new("show")
-                    .about("Show the config file")
-                    .short('s')
-                    .long("show")
-                    .display_order(1)
-                    .takes_value(false),
-                clap::Arg::new("data-dir")
-                    .value_hint(clap::ValueHint::DirPath),
-                clap::Arg::new("config_dir")
-                    .value_hint(clap::ValueHint::DirPath),
-                clap::Arg::new("data-path")
-                    .value_hint(clap::ValueHint::FilePath),
-                clap::Arg::new("config-path")
-                    .value_hint(clap::ValueHint::FilePath),
-                clap::Arg::new("username")
-                    .value_hint(clap::ValueHint::Username)
-            ])
-    }
-
     pub fn data_cmd() -> clap::App<'static> {
         clap::App::new("data")
             .about("Set or view central data files")
@@ -292,19 +416,107 @@ impl DApp {
         clap::Arg::new("config-file")
             .long("cf")
             .about("Manually set config file location and load")
+            .long_about("Layer a config file over dlog.toml, the current record's .dlog/config.toml and DLOG_* env vars")
+            .global(true)
             .value_hint(clap::ValueHint::FilePath)
             .takes_value(true)
     }
 
-    pub fn set_config() -> Arg<'static> {
-        clap::Arg::new("set")
-            .short('s')
-            .long("set")
-            .about("Set a config key-value pair manually")
-            .exclusive(true)
-            .takes_value(true)
+
+}
+
+/// The file given with `--cf`, read before args are parsed since it may
+/// hold aliases
+fn config_file_arg(args: &[String]) -> Option<&str> {
+    args.iter().enumerate().find_map(|(i, arg)| match arg.strip_prefix("--cf") {
+        Some("") => args.get(i + 1).map(String::as_str),
+        Some(file) => file.strip_prefix('='),
+        None => None,
+    })
+}
+
+/// The command as typed, for the history
+fn command_line(args: &[String]) -> String {
+    let words = args.iter().skip(1).map(|arg| match arg.contains(char::is_whitespace) || arg.is_empty() {
+        true => format!("{:?}", arg),
+        false => arg.clone(),
+    });
+    std::iter::once(DApp::name().to_string())
+        .chain(words)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Keeps the search index up to date with the files a command changed
+pub(crate) fn reindex(changes: &[crate::history::Change]) {
+    let paths = changes.iter().map(|c| &c.path).collect::<Vec<_>>();
+    if let Err(e) = crate::search::update(&paths) {
+        eprintln!("{}", format!("Could not update the search index: {}", e)
+            .color(Color::BrightRed));
+    }
+}
+
+/// Marks the command as failed. Commands report their own errors and return
+/// rather than exit, so the store is always locked again before dlog exits
+/// with an error.
+pub(crate) fn fail() {
+    FAILED.store(true, Ordering::SeqCst);
+}
+
+/// Opens the record named, or the one the user meant if there is none by
+/// that name but one by a name alike
+pub(crate) fn open_record(name: &str) -> crate::DResult<Record> {
+    match Record::open(name) {
+        Err(crate::error::DError::NotFound) => {
+            let data = crate::models::data::RecordData::read()?;
+            let meant = crate::prompt::meant("record", name,
+                data.records().map(|r| r.name.as_str()))?;
+            match meant == name {
+                true => Err(crate::error::DError::NotFound),
+                false => Record::open(&meant),
+            }
+        },
+        rec => rec,
+    }
+}
+
+/// The entry the user meant to log to one of `records`, asking about a
+/// fact or attribute name new to them which is alike one already there
+pub(crate) fn meant_fact(records: &[Record], fact: &Fact) -> crate::DResult<Fact> {
+    let mut entries = Vec::new();
+    for record in records {
+        entries.extend(record.facts()?);
+    }
+    let names = records.iter()
+        .flat_map(|r| r.fact_types.iter().map(|t| t.name.as_str()))
+        .chain(entries.iter().map(|f| f.name.as_str()))
+        .collect::<std::collections::BTreeSet<&str>>();
+    let mut fact = fact.clone();
+    fact.name = crate::prompt::meant("fact", &fact.name, names)?;
+    let defs = crate::models::data::AttribData::read()?;
+    let attribs = defs.attribs().iter().map(|d| d.name.as_str())
+        .chain(records.iter().flat_map(|r| r.attribs.iter().map(|a| a.name.as_str())))
+        .chain(records.iter()
+            .flat_map(|r| r.items.iter().flat_map(|i| i.attribs.iter().map(|a| a.name.as_str()))))
+        .chain(entries.iter().flat_map(|f| f.attribs.iter().map(|a| a.name.as_str())))
+        .collect::<std::collections::BTreeSet<&str>>();
+    for attrib in fact.attribs.iter_mut() {
+        attrib.name = crate::prompt::meant("attribute", &attrib.name, attribs.iter().copied())?;
     }
+    Ok(fact)
+}
+
+/// The value of a global arg, wherever in the subcommands it was given
+fn global_value<'a>(matches: &'a ArgMatches, name: &str) -> Option<&'a str> {
+    match matches.subcommand() {
+        Some((_, sub)) => global_value(sub, name).or_else(|| matches.value_of(name)),
+        None => matches.value_of(name),
+    }
+}
 
+fn global_flag(matches: &ArgMatches, name: &str) -> bool {
+    matches.is_present(name) || matches.subcommand()
+        .is_some_and(|(_, sub)| global_flag(sub, name))
 }
 
 pub trait Cmd: FromArgMatches + Default {
@@ -415,10 +627,22 @@ impl clap::Subcommand for Subcmd {
                  "relation" => Self::Relation(RelCmd::from_arg_matches(m)),
                 "stats" => Self::Stats(StatsCmd::from_arg_matches(m)),
                 "action" => Self::Action(ActionCmd::from_arg_matches(m)),
-                "config" => Self::Config,
+                "attrib" => Self::Attrib(AttribCmd::from_arg_matches(m)),
+                "attach" => Self::Attach(AttachCmd::from_arg_matches(m)),
+                "attachments" => Self::Attach(AttachCmd::list_from(m)),
+                "note" => Self::Note(NoteCmd::from_arg_matches(m)),
+                "journal" => Self::Journal(JournalCmd::from_arg_matches(m)),
+                "topic" => Self::Topic(TopicCmd::from_arg_matches(m)),
+                "auth" => Self::Auth(AuthCmd::from_arg_matches(m)),
+                "sync" => Self::Sync(SyncCmd::from_arg_matches(m)),
+                "serve" => Self::Serve(ServeCmd::from_arg_matches(m)),
+                "config" => Self::Config(ConfigCmd::from_arg_matches(m)),
+                "history" | "undo" | "redo" => Self::History(HistoryCmd::from_subcommand(subcommand)?),
+                "trash" => Self::Trash(TrashCmd::from_arg_matches(m)),
+                "init" => Self::Record(RecordCmd::init_from(m)),
                 "list" => Self::List,
-                "search" => Self::Search,
-                "help" => Self::Help,
+                "search" => Self::Search(SearchCmd::from_arg_matches(m)),
+                "help" | "base_help" => Self::Help,
                 _ => Self::Fact(FactCmd::from_arg_matches(m)),
             };
             return Some(sub)
@@ -0,0 +1,169 @@
+use std::path::PathBuf;
+use clap::{ArgMatches, FromArgMatches};
+use colored::{Color, Colorize};
+use crate::{
+    cmd::Cmd,
+    output,
+    models::{
+        entity::EntityRef,
+        attachment::{Attachment, AttachMode, Integrity},
+    },
+};
+
+#[derive(Debug, Default)]
+pub enum AttachCmd {
+    Attach(EntityRef, Vec<PathBuf>, AttachMode),
+    List(EntityRef, bool),
+    #[default]
+    Help,
+}
+
+impl Cmd for AttachCmd {
+
+    fn name() -> &'static str { "attach" }
+    fn about() -> &'static str { "Attach files to a record, item or fact entry" }
+    fn long_about() -> &'static str {
+        "Attach files such as photos, PDFs or receipts to a record, item or fact entry. Files are copied into a content-addressed blob dir in the dlog data dir by default, or symlinked with --symlink, and hashed with SHA-256 so they can be checked later with 'dlog attachments <ENTITY> --verify'"
+    }
+
+    fn args() -> Vec<clap::Arg<'static>> {
+        vec![
+            Self::entity_arg(),
+            clap::Arg::new("FILE")
+                .about("The files to attach")
+                .required(true)
+                .multiple(true)
+                .value_hint(clap::ValueHint::FilePath)
+                .index(2),
+            clap::Arg::new("symlink")
+                .about("Symlink the files instead of copying them")
+                .long_about("Keep the files where they are and store a symlink to them in the blob dir. Moving or editing the original will show up when verifying")
+                .short('s')
+                .long("symlink")
+                .alias("link")
+                .takes_value(false),
+        ]
+    }
+
+    fn subcmds() -> Vec<clap::App<'static>> {
+        Vec::new()
+    }
+
+    fn run(&self) {
+        match self {
+            Self::Attach(entity, files, mode) => {
+                if let Err(e) = entity.check() {
+                    eprintln!("{}", format!("Could not attach to {}: {}", entity, e)
+                        .color(Color::BrightRed));
+                    return super::fail();
+                }
+                for file in files {
+                    match Attachment::attach(entity.clone(), file, *mode) {
+                        Ok(att) => println!("{}", format!("Attached {} to {} ({})",
+                                att.file, entity, &att.sha256[..12])
+                            .color(Color::BrightGreen)),
+                        Err(e) => {
+                            eprintln!("{}", format!("Could not attach {}: {}",
+                                    file.display(), e)
+                                .color(Color::BrightRed));
+                            return super::fail();
+                        }
+                    }
+                }
+            },
+            Self::List(entity, verify) => match Attachment::of(entity) {
+                Ok(atts) if output::structured() => match output::render(&atts) {
+                    Ok(out) => println!("{}", out.unwrap_or_default()),
+                    Err(e) => {
+                        eprintln!("{}", format!("Could not read attachments: {}", e)
+                            .color(Color::BrightRed));
+                        super::fail();
+                    },
+                },
+                Ok(atts) if atts.is_empty() => println!("{}",
+                    format!("No attachments for {}", entity)
+                        .color(Color::BrightYellow)),
+                Ok(atts) => {
+                    println!("{}", Attachment::table(&atts, *verify));
+                    if *verify && atts.iter().any(|a| a.verify() != Integrity::Ok) {
+                        super::fail();
+                    }
+                },
+                Err(e) => {
+                    eprintln!("{}", format!("Could not read attachments: {}", e)
+                        .color(Color::BrightRed));
+                    super::fail();
+                }
+            },
+            Self::Help => Self::print_help(),
+        }
+    }
+
+    fn print_help() {
+        let help = "
+            ATTACH: Attach files to a record, item or fact \n
+                    entry, e.g. dlog attach item:dinner m.jpg\n
+        ".to_string().color(Color::BrightBlue);
+        println!("> {}", help)
+    }
+
+    fn help_cmd() -> clap::App<'static> {
+        clap::App::new("attach_help")
+            .about("Prints help command for attach")
+            .long_flag("help")
+            .short_flag('h')
+            .long_about("Prints the help information")
+    }
+}
+
+impl AttachCmd {
+
+    fn entity_arg() -> clap::Arg<'static> {
+        clap::Arg::new("ENTITY")
+            .about("The record, item or fact entry, e.g. record:health, item:dinner or fact:<id>")
+            .long_about("The entity to attach files to, given as record:<name>, item:<name> or fact:<id>. A bare name is taken as a record")
+            .required(true)
+            .validator(|e| e.parse::<EntityRef>().map(|_| ()))
+            .index(1)
+    }
+
+    /// The top-level `dlog attachments <ENTITY>` cmd
+    pub fn list_cmd() -> clap::App<'static> {
+        clap::App::new("attachments")
+            .about("List the files attached to a record, item or fact entry")
+            .args(&[
+                Self::entity_arg(),
+                clap::Arg::new("verify")
+                    .about("Check each attachment against its SHA-256")
+                    .long_about("Re-hash each attached file and report whether it is ok, modified or missing. Exits with an error if any are not ok")
+                    .short('v')
+                    .long("verify")
+                    .takes_value(false),
+            ])
+    }
+
+    pub fn list_from(matches: &ArgMatches) -> Self {
+        match matches.value_of("ENTITY").map(str::parse) {
+            Some(Ok(entity)) => Self::List(entity, matches.is_present("verify")),
+            _ => Self::Help,
+        }
+    }
+}
+
+impl FromArgMatches for AttachCmd {
+    fn from_arg_matches(matches: &ArgMatches) -> Self {
+        let entity = match matches.value_of("ENTITY").map(str::parse) {
+            Some(Ok(entity)) => entity,
+            _ => return Self::Help,
+        };
+        let files = matches.values_of("FILE")
+            .map(|f| f.map(PathBuf::from).collect())
+            .unwrap_or_default();
+        let mode = if matches.is_present("symlink") {
+            AttachMode::Symlink
+        } else {
+            AttachMode::Copy
+        };
+        Self::Attach(entity, files, mode)
+    }
+}
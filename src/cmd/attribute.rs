@@ -1,15 +1,37 @@
 use crate::{
-    models::attrib::Attrib,
+    models::{
+        Record,
+        attrib::{Attrib, AttribDef, AttribKind, AttribUsage},
+        data::{AttribData, RecordData},
+    },
+    models::tag::Tag,
+    config::DConfig,
+    error::DError,
     cmd::Cmd,
+    output, prompt,
 };
 use clap::{ArgMatches, FromArgMatches};
 use colored::{Colorize, Color};
 
 #[derive(Debug)]
 pub enum AttribCmd {
-    New(Option<Attrib>),
+    New(AttribDef),
+    List(ListFilter),
+    /// Renames an attribute which no other attribute is named as yet
+    Rename(String, String),
+    /// Folds the first attribute into the second
+    Merge(String, String),
     Help,
-    List,
+}
+
+/// Which uses of attributes to count when listing them
+#[derive(Debug, Default)]
+pub struct ListFilter {
+    /// Only attributes given to facts, items or records, or derived from tags
+    pub kind: Option<String>,
+    pub fact: Option<String>,
+    pub item: Option<String>,
+    pub record: Option<String>,
 }
 
 impl Default for AttribCmd {
@@ -22,26 +44,49 @@ impl Default for AttribCmd {
 impl Cmd for AttribCmd {
 
     fn name() -> &'static str { "attrib" }
-    fn about() -> &'static str { "The attribs cmd" }
-    fn long_about() -> &'static str { "The attribs cmd" }
+    fn about() -> &'static str { "Register, list, rename and merge attributes" }
+    fn long_about() -> &'static str {
+        "Attributes tag facts, items and records, e.g. -a meal=dinner. Attributes can be registered with a value type and allowed values, which are checked when logging facts. Renaming or merging an attribute rewrites every fact, item and record carrying it"
+    }
     fn args() -> Vec<clap::Arg<'static>> {
-        vec![
-            clap::Arg::new("help")
-        ]
+        Vec::new()
     }
 
     fn subcmds() -> Vec<clap::App<'static>> {
         vec![
-            clap::App::new("new"),
+            Self::new_cmd(),
             Self::list_cmd(),
+            Self::rename_cmd(),
+            Self::merge_cmd(),
             Self::help_cmd(),
         ]
     }
 
     fn run(&self) {
-        println!("{}", format!("Running attrib cmd...")
-            .color(Color::BrightRed))
-
+        let res = match self {
+            Self::New(def) => AttribData::read()
+                .and_then(|mut reg| reg.register(def.clone()))
+                .map(|_| println!("{}", format!("Registered attribute {}", def.name)
+                    .color(Color::BrightGreen))),
+            Self::List(filter) => list(filter)
+                .map(|table| println!("{}", table)),
+            Self::Rename(from, into) => meant(from)
+                .and_then(|from| rename(&from, into).map(|(f, i, r)| println!("{}", format!(
+                    "Renamed {} to {} on {} facts, {} items and {} records",
+                    from, into, f, i, r).color(Color::BrightGreen)))),
+            Self::Merge(from, into) => meant(from)
+                .and_then(|from| merge(&from, into).map(|(f, i, r)| println!("{}", format!(
+                    "Merged {} into {} on {} facts, {} items and {} records",
+                    from, into, f, i, r).color(Color::BrightGreen)))),
+            Self::Help => {
+                Self::print_help();
+                Ok(())
+            },
+        };
+        if let Err(e) = res {
+            eprintln!("{}", format!("{}", e).color(Color::BrightRed));
+            super::fail();
+        }
     }
 
     fn print_help() {
@@ -54,8 +99,8 @@ impl Cmd for AttribCmd {
     }
 
     fn help_cmd() -> clap::App<'static> {
-        clap::App::new("fact_help")
-            .about("Prints help command for fact")
+        clap::App::new("attrib_help")
+            .about("Prints help command for attrib")
             .long_flag("help")
             .short_flag('h')
             .long_about("Prints the help information")
@@ -64,41 +109,262 @@ impl Cmd for AttribCmd {
 }
 
 impl AttribCmd {
+
+    pub fn new_cmd() -> clap::App<'static> {
+        clap::App::new("new")
+            .about("Register an attribute")
+            .long_about("Register an attribute, optionally with the type of value it takes and the values it may take. Facts given a registered attribute with a value of the wrong type, or not among the allowed values, are refused")
+            .args(&[
+                clap::Arg::new("NAME")
+                    .about("Name of the attribute")
+                    .required(true)
+                    .validator(|a| crate::prompt::validate_input(a.into()))
+                    .index(1),
+                clap::Arg::new("type")
+                    .about("The type of value the attribute takes")
+                    .long_about("The type of value the attribute takes: flag (no value), text, number, boolean or date")
+                    .short('t')
+                    .long("type")
+                    .takes_value(true)
+                    .validator(|t| t.parse::<AttribKind>().map(|_| ())),
+                clap::Arg::new("allow")
+                    .about("A value the attribute may take")
+                    .short('a')
+                    .long("allow")
+                    .takes_value(true)
+                    .multiple(true),
+                clap::Arg::new("description")
+                    .about("A description of the attribute")
+                    .short('d')
+                    .long("description")
+                    .takes_value(true),
+            ])
+    }
+
     pub fn list_cmd() -> clap::App<'static> {
         clap::App::new("list")
             .about("List attributes in your log.")
-            .long_about("Subcommand to list all attributes in your log, or a subset based on some criteria")
+            .long_about("Subcommand to list all attributes in your log, or a subset based on some criteria, with how many facts, items and records carry each")
             .subcommands(vec![
                 clap::App::new("fact")
                     .long_about("List all attributes given to facts"),
                 clap::App::new("record")
                     .long_about("List all attributes given to records"),
                 clap::App::new("item")
-                    .long_about("List all attributes given to items")
+                    .long_about("List all attributes given to items"),
+                clap::App::new("tags")
+                    .long_about("List attributes derived from #tags in fact values and notes")
             ])
             .args(&vec![
                 clap::Arg::new("fact-name")
                     .short('f')
                     .long("fact")
+                    .takes_value(true)
                     .about("List attributes related to a specified fact in your log"),
                 clap::Arg::new("item-name")
                     .short('i')
                     .long("item")
+                    .takes_value(true)
                     .about("List attributes related to a specified item in your log"),
                 clap::Arg::new("record-name")
                     .short('r')
                     .long("record")
                     .aliases(&["rec", "log"])
-                    .about("List attributes related to a specified item in your log"),
+                    .takes_value(true)
+                    .about("List attributes related to a specified record in your log"),
             ])
 
     }
+
+    pub fn rename_cmd() -> clap::App<'static> {
+        clap::App::new("rename")
+            .about("Rename an attribute everywhere it is used")
+            .long_about("Rename an attribute in the registry and on every fact, item and record carrying it. Refuses if the new name is already used; merge the attributes instead")
+            .args(&[
+                clap::Arg::new("OLD")
+                    .about("Current name of the attribute")
+                    .required(true)
+                    .index(1),
+                clap::Arg::new("NEW")
+                    .about("New name of the attribute")
+                    .required(true)
+                    .validator(|a| crate::prompt::validate_input(a.into()))
+                    .index(2),
+            ])
+    }
+
+    pub fn merge_cmd() -> clap::App<'static> {
+        clap::App::new("merge")
+            .about("Merge one attribute into another")
+            .long_about("Replace the first attribute with the second on every fact, item and record carrying it. If both are registered, the allowed values of the first are added to the second")
+            .args(&[
+                clap::Arg::new("FROM")
+                    .about("The attribute to merge away")
+                    .required(true)
+                    .index(1),
+                clap::Arg::new("INTO")
+                    .about("The attribute to keep")
+                    .required(true)
+                    .index(2),
+            ])
+    }
+}
+
+/// Counts the uses of every attribute across all records, along with
+/// registered attributes which aren't used yet
+fn usage(registry: &AttribData, filter: &ListFilter) -> crate::DResult<Vec<AttribUsage>> {
+    let rules = DConfig::load()?.tag_rules();
+    let mut usage = registry.attribs().iter()
+        .map(|a| AttribUsage { name: a.name.clone(), ..Default::default() })
+        .collect::<Vec<AttribUsage>>();
+    for rec in RecordData::read()?.records() {
+        if filter.record.as_ref().is_some_and(|r| !r.eq_ignore_ascii_case(&rec.name)) {
+            continue;
+        }
+        let record = Record::open_path(&rec.path)?;
+        let mut paths = vec![record.csv_path()];
+        for item in &record.items {
+            if filter.item.as_ref().is_none_or(|i| item.is_under(i)) {
+                AttribUsage::count(&mut usage, &item.attribs, |u| u.items += 1);
+                paths.push(item.csv_path(&record));
+            }
+        }
+        if filter.item.is_some() {
+            paths.remove(0);
+        } else {
+            AttribUsage::count(&mut usage, &record.attribs, |u| u.records += 1);
+        }
+        for path in paths {
+            for fact in crate::models::Fact::read_csv(path)? {
+                if filter.fact.as_ref().is_none_or(|f| f.eq_ignore_ascii_case(&fact.name)) {
+                    AttribUsage::count(&mut usage, &fact.attribs, |u| u.facts += 1);
+                    let tagged = Tag::extract(&fact.text(), &rules).into_iter()
+                        .filter_map(|t| match t {
+                            Tag::Attrib(a) if fact.attribs.contains(&a) => Some(a),
+                            _ => None,
+                        })
+                        .collect::<Vec<Attrib>>();
+                    AttribUsage::count(&mut usage, &tagged, |u| u.tagged += 1);
+                }
+            }
+        }
+    }
+    if filter.fact.is_some() || filter.item.is_some() || filter.record.is_some() {
+        usage.retain(|u| u.facts + u.items + u.records > 0);
+    }
+    match filter.kind.as_deref() {
+        Some("fact") => usage.retain(|u| u.facts > 0),
+        Some("item") => usage.retain(|u| u.items > 0),
+        Some("record") => usage.retain(|u| u.records > 0),
+        Some("tags") => usage.retain(|u| u.tagged > 0),
+        _ => {},
+    }
+    usage.sort_by_key(|u| u.name.to_lowercase());
+    Ok(usage)
+}
+
+/// The attribute named, or the one the user meant if none is named so but
+/// one is named alike
+fn meant(name: &str) -> crate::DResult<String> {
+    let usage = usage(&AttribData::read()?, &ListFilter::default())?;
+    prompt::meant("attribute", name, usage.iter().map(|u| u.name.as_str()))
+}
+
+fn list(filter: &ListFilter) -> crate::DResult<String> {
+    let registry = AttribData::read()?;
+    let usage = usage(&registry, filter)?;
+    match output::render(&usage)? {
+        Some(out) => Ok(out),
+        None => Ok(AttribUsage::table(&usage, registry.attribs()).to_string()),
+    }
+}
+
+/// Renames an attribute on every fact, item and record of every record
+fn rewrite(from: &str, into: &str) -> crate::DResult<(usize, usize, usize)> {
+    let mut total = (0, 0, 0);
+    for rec in RecordData::read()?.records() {
+        let mut record = Record::open_path(&rec.path)?;
+        let (f, i, r) = record.rewrite_attribs(|a| Attrib::rename_in(a, from, into))?;
+        total = (total.0 + f, total.1 + i, total.2 + r);
+    }
+    Ok(total)
+}
+
+fn rename(from: &str, into: &str) -> crate::DResult<(usize, usize, usize)> {
+    if from.eq_ignore_ascii_case(into) {
+        return Err(DError::Invalid(format!("{} is already named {}", from, into)));
+    }
+    let mut registry = AttribData::read()?;
+    if usage(&registry, &ListFilter::default())?.iter()
+        .any(|u| u.name.eq_ignore_ascii_case(into))
+    {
+        return Err(DError::Invalid(format!(
+            "An attribute named {} already exists, use 'dlog attrib merge {} {}' to combine them",
+            into, from, into)));
+    }
+    let registered = match registry.find_mut(from) {
+        Some(def) => {
+            def.name = into.to_string();
+            true
+        },
+        None => false,
+    };
+    let total = rewrite(from, into)?;
+    if !registered && total == (0, 0, 0) {
+        return Err(DError::NotFound);
+    }
+    registry.write()?;
+    Ok(total)
+}
+
+fn merge(from: &str, into: &str) -> crate::DResult<(usize, usize, usize)> {
+    if from.eq_ignore_ascii_case(into) {
+        return Err(DError::Invalid(format!("Cannot merge {} into itself", from)));
+    }
+    let mut registry = AttribData::read()?;
+    if let Some(old) = registry.remove(from) {
+        match registry.find_mut(into) {
+            Some(def) => for val in old.allowed {
+                if !def.allowed.iter().any(|a| a.eq_ignore_ascii_case(&val)) {
+                    def.allowed.push(val);
+                }
+            },
+            None => registry.register(AttribDef { name: into.to_string(), ..old })?,
+        }
+    }
+    let total = rewrite(from, into)?;
+    registry.write()?;
+    Ok(total)
 }
 
 impl FromArgMatches for AttribCmd {
-    fn from_arg_matches(_matches: &ArgMatches) -> Self {
-        Self::print_help();
-        Self::default()
+    fn from_arg_matches(matches: &ArgMatches) -> Self {
+        match matches.subcommand() {
+            Some(("new", sub)) => {
+                let mut def = AttribDef::new(sub.value_of("NAME").unwrap_or_default());
+                def.kind = sub.value_of("type").and_then(|t| t.parse().ok());
+                def.allowed = sub.values_of("allow")
+                    .map(|v| v.map(String::from).collect())
+                    .unwrap_or_default();
+                def.description = sub.value_of("description")
+                    .unwrap_or_default().into();
+                Self::New(def)
+            },
+            Some(("list", sub)) => Self::List(ListFilter {
+                kind: sub.subcommand_name().map(String::from),
+                fact: sub.value_of("fact-name").map(String::from),
+                item: sub.value_of("item-name").map(String::from),
+                record: sub.value_of("record-name").map(String::from),
+            }),
+            Some(("rename", sub)) => Self::Rename(
+                sub.value_of("OLD").unwrap_or_default().into(),
+                sub.value_of("NEW").unwrap_or_default().into()),
+            Some(("merge", sub)) => Self::Merge(
+                sub.value_of("FROM").unwrap_or_default().into(),
+                sub.value_of("INTO").unwrap_or_default().into()),
+            Some(("attrib_help", _)) => Self::Help,
+            _ => Self::List(ListFilter::default()),
+        }
     }
 }
 
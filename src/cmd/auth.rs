@@ -0,0 +1,134 @@
+use clap::{ArgMatches, FromArgMatches};
+use colored::{Color, Colorize};
+use crate::{auth, cmd::Cmd};
+
+#[derive(Debug, Default)]
+pub enum AuthCmd {
+    #[default]
+    Status,
+    /// Seals the store, setting up a passphrase the first time
+    Lock,
+    /// Caches the key for some minutes, or as long as configured
+    Unlock(Option<i64>),
+    Rekey,
+    Help,
+}
+
+impl Cmd for AuthCmd {
+
+    fn name() -> &'static str { "auth" }
+    fn about() -> &'static str { "Encrypt your data at rest with a passphrase" }
+    fn long_about() -> &'static str {
+        "Lock the store to encrypt every record, fact entry, note and attachment with a key derived from a passphrase. Commands decrypt what they use while they run and encrypt it again when done. The passphrase is taken from $DLOG_PASSPHRASE, the config's auth.password_cmd or auth.password, or asked for, and 'dlog auth unlock' keeps it for a while so it is not asked for every command"
+    }
+
+    fn args() -> Vec<clap::Arg<'static>> {
+        Vec::new()
+    }
+
+    fn subcmds() -> Vec<clap::App<'static>> {
+        vec![
+            clap::App::new("status")
+                .about("Show whether the store is locked and unlocked for now"),
+            clap::App::new("lock")
+                .about("Encrypt the store, and forget the passphrase until unlocked again")
+                .long_about("Encrypt the store, asking for a new passphrase the first time. Ends any session started by 'dlog auth unlock'"),
+            clap::App::new("unlock")
+                .about("Keep the passphrase for a while, so commands do not ask for it")
+                .long_about("Keep the key for a while, so commands do not ask for the passphrase. It is kept in a directory of $XDG_RUNTIME_DIR only you can reach, and not at all without one")
+                .arg(clap::Arg::new("minutes")
+                    .about("How many minutes to stay unlocked for, auth.session_minutes or 15 if not given")
+                    .short('t')
+                    .long("minutes")
+                    .takes_value(true)
+                    .validator(|m| m.parse::<u32>().map(|_| ()))),
+            clap::App::new("rekey")
+                .about("Encrypt the store again under a new passphrase")
+                .long_about("Encrypt the store again under a new passphrase, taken from $DLOG_NEW_PASSPHRASE or asked for"),
+            Self::help_cmd(),
+        ]
+    }
+
+    fn run(&self) {
+        let res = match self {
+            Self::Status => auth::status()
+                .map(|(locked, sealed, session)| match (locked, session) {
+                    (false, _) => "The store is not encrypted, lock it with 'dlog auth lock'"
+                        .color(Color::BrightYellow).to_string(),
+                    (true, Some(until)) => format!("The store is encrypted ({} files), and unlocked until {}",
+                        sealed, until.format("%Y-%m-%d %H:%M")).color(Color::BrightGreen).to_string(),
+                    (true, None) => format!("The store is encrypted ({} files), and locked", sealed)
+                        .color(Color::BrightGreen).to_string(),
+                }),
+            Self::Lock => auth::lock()
+                .map(|sealed| format!("Locked the store, encrypting {} files", sealed)
+                    .color(Color::BrightGreen).to_string()),
+            Self::Unlock(minutes) => auth::unlock(*minutes)
+                .map(|until| format!("Unlocked until {}", until.format("%Y-%m-%d %H:%M"))
+                    .color(Color::BrightGreen).to_string()),
+            Self::Rekey => auth::rekey()
+                .map(|sealed| format!("Encrypted {} files under the new passphrase", sealed)
+                    .color(Color::BrightGreen).to_string()),
+            Self::Help => {
+                Self::print_help();
+                Ok(String::new())
+            },
+        };
+        match res {
+            Ok(msg) if msg.is_empty() => {},
+            Ok(msg) => println!("{}", msg),
+            Err(e) => {
+                eprintln!("{}", format!("Could not run auth cmd: {}", e)
+                    .color(Color::BrightRed));
+                super::fail();
+            }
+        }
+    }
+
+    fn print_help() {
+        let help = "
+            AUTH: Encrypt your data with a passphrase,\n
+                  e.g. dlog auth lock, dlog auth unlock\n
+        ".to_string().color(Color::BrightBlue);
+        println!("> {}", help)
+    }
+
+    fn help_cmd() -> clap::App<'static> {
+        clap::App::new("auth_help")
+            .about("Prints help command for auth")
+            .long_flag("help")
+            .short_flag('h')
+            .long_about("Prints the help information")
+    }
+}
+
+impl FromArgMatches for AuthCmd {
+    fn from_arg_matches(matches: &ArgMatches) -> Self {
+        match matches.subcommand() {
+            Some(("lock", _)) => Self::Lock,
+            Some(("unlock", sub)) => Self::Unlock(sub.value_of("minutes")
+                .and_then(|m| m.parse().ok())),
+            Some(("rekey", _)) => Self::Rekey,
+            Some(("auth_help", _)) => Self::Help,
+            _ => Self::Status,
+        }
+    }
+}
+
+impl clap::Subcommand for AuthCmd {
+    fn from_subcommand(sub: Option<(&str, &ArgMatches)>)
+        -> Option<Self>
+    {
+        let (sub, args) = sub.unwrap();
+        if sub == "auth" {
+            Some(Self::from_arg_matches(args))
+        } else {
+            None
+        }
+    }
+
+    fn augment_subcommands(app: clap::App<'_>) -> clap::App<'_>
+    {
+        app
+    }
+}
@@ -0,0 +1,228 @@
+use clap::{ArgMatches, FromArgMatches};
+use colored::{Color, Colorize};
+use comfy_table::{
+    Table, ContentArrangement, presets::UTF8_BORDERS_ONLY,
+    Cell, Attribute,
+};
+use crate::{
+    util, output,
+    cmd::Cmd,
+    config::{self, DConfig, ConfigLayer},
+};
+
+#[derive(Debug, Default)]
+pub enum ConfigCmd {
+    #[default]
+    Show,
+    Get(String),
+    Set(ConfigLayer, String, String),
+    Unset(ConfigLayer, String),
+    Edit(ConfigLayer),
+    Path,
+    Help,
+}
+
+impl Cmd for ConfigCmd {
+
+    fn name() -> &'static str { "config" }
+    fn about() -> &'static str { "Get and set configuration properties from the CLI" }
+    fn long_about() -> &'static str {
+        "Get, set and unset config keys such as format.date_format. The config is layered from its defaults, the global dlog.toml, the .dlog/config.toml of the record the current dir is in, DLOG_* env vars (e.g. DLOG_FORMAT__DATE_FORMAT) and the --cf file, each overriding the keys it sets"
+    }
+
+    fn args() -> Vec<clap::Arg<'static>> {
+        vec![
+            clap::Arg::new("show")
+                .about("Show the config file")
+                .short('s')
+                .long("show")
+                .display_order(1)
+                .takes_value(false),
+        ]
+    }
+
+    fn subcmds() -> Vec<clap::App<'static>> {
+        let local = || clap::Arg::new("local")
+            .about("The current record's .dlog/config.toml rather than dlog.toml")
+            .short('l')
+            .long("local")
+            .takes_value(false);
+        let key = || clap::Arg::new("KEY")
+            .about("A dotted key, e.g. format.date_format")
+            .required(true)
+            .index(1);
+        vec![
+            clap::App::new("show")
+                .about("Show the config, layered from all of its sources"),
+            clap::App::new("get")
+                .about("Print the value of a key")
+                .arg(key()),
+            clap::App::new("set")
+                .about("Set a key, e.g. format.pretty true or format.pretty=true")
+                .long_about("Set a key in dlog.toml, or with --local in the current record's .dlog/config.toml. The value is read as TOML if it can be, e.g. true, 3 or [\"a\", \"b\"], and as a string otherwise. Refuses keys the config does not have and values of the wrong type")
+                .args(&[
+                    key(),
+                    clap::Arg::new("VALUE")
+                        .about("The value, unless given as KEY=VALUE")
+                        .index(2),
+                    local(),
+                ]),
+            clap::App::new("unset")
+                .about("Remove a key, leaving it to the defaults and other sources")
+                .args(&[key(), local()]),
+            clap::App::new("edit")
+                .about("Edit dlog.toml in your editor, keeping it only if it is valid")
+                .arg(local()),
+            clap::App::new("path")
+                .about("List the files the config is layered from"),
+            Self::help_cmd(),
+        ]
+    }
+
+    fn run(&self) {
+        let res = match self {
+            Self::Show => show(),
+            Self::Get(key) => get(key),
+            Self::Set(layer, key, value) => set(*layer, key, value),
+            Self::Unset(layer, key) => DConfig::unset(*layer, key)
+                .map(|_| format!("Unset {}", key).color(Color::BrightGreen).to_string()),
+            Self::Edit(layer) => edit(*layer),
+            Self::Path => path(),
+            Self::Help => {
+                Self::print_help();
+                Ok(String::new())
+            },
+        };
+        match res {
+            Ok(msg) if msg.is_empty() => {},
+            Ok(msg) => println!("{}", msg),
+            Err(e) => {
+                eprintln!("{}", format!("Could not run config cmd: {}", e)
+                    .color(Color::BrightRed));
+                super::fail();
+            }
+        }
+    }
+
+    fn print_help() {
+        let help = "
+            CONFIG: Get and set config keys, e.g.\n
+                  dlog config get format.date_format\n
+                  dlog config set format.pretty true\n
+                  dlog config unset format.pretty\n
+        ".to_string().color(Color::BrightCyan);
+        println!("> {}", help)
+    }
+
+    fn help_cmd() -> clap::App<'static> {
+        clap::App::new("config_help")
+            .about("Prints help command for config")
+            .long_flag("help")
+            .short_flag('h')
+            .long_about("Prints the help information")
+    }
+}
+
+fn show() -> crate::DResult<String> {
+    let conf = DConfig::load()?;
+    match output::render_one(&conf)? {
+        Some(out) => Ok(out),
+        None => Ok(toml::to_string_pretty(&conf)?.trim_end().to_string()),
+    }
+}
+
+fn get(key: &str) -> crate::DResult<String> {
+    match DConfig::load()?.get(key)? {
+        toml::Value::String(s) => Ok(s),
+        value @ toml::Value::Table(_) => Ok(toml::to_string_pretty(&value)?.trim_end().to_string()),
+        value => Ok(value.to_string()),
+    }
+}
+
+fn set(layer: ConfigLayer, key: &str, value: &str) -> crate::DResult<String> {
+    DConfig::set(layer, key, config::parse_value(value))?;
+    Ok(format!("Set {} to {}", key, value).color(Color::BrightGreen).to_string())
+}
+
+/// Edits the file of a layer, and writes it back only if the config is
+/// valid with it
+fn edit(layer: ConfigLayer) -> crate::DResult<String> {
+    let path = DConfig::layer_file(layer)?;
+    let text = match path.exists() {
+        true => std::fs::read_to_string(&path)?,
+        false => String::new(),
+    };
+    let edited = util::edit(&DConfig::load()?.editor(), &text, "toml")?;
+    if edited == text {
+        return Ok("Config unchanged".into());
+    }
+    DConfig::layered(Some((layer, &edited)))?.format().check()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, edited)?;
+    Ok(format!("Saved {}", path.display()).color(Color::BrightGreen).to_string())
+}
+
+fn path() -> crate::DResult<String> {
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec![
+            Cell::new("Layer").add_attribute(Attribute::Bold),
+            Cell::new("Path").add_attribute(Attribute::Bold),
+            Cell::new("Exists").add_attribute(Attribute::Bold),
+        ]);
+    for (layer, path) in DConfig::sources()? {
+        table.add_row(vec![
+            layer.to_string(),
+            path.display().to_string(),
+            path.exists().to_string(),
+        ]);
+    }
+    Ok(output::table("config", table).to_string())
+}
+
+impl FromArgMatches for ConfigCmd {
+    fn from_arg_matches(matches: &ArgMatches) -> Self {
+        let layer = |m: &ArgMatches| match m.is_present("local") {
+            true => ConfigLayer::Record,
+            false => ConfigLayer::Global,
+        };
+        let key = |m: &ArgMatches| m.value_of("KEY").unwrap_or_default().to_string();
+        match matches.subcommand() {
+            Some(("get", sub)) => Self::Get(key(sub)),
+            Some(("set", sub)) => match (sub.value_of("KEY"), sub.value_of("VALUE")) {
+                (Some(k), Some(v)) => Self::Set(layer(sub), k.into(), v.into()),
+                (Some(kv), None) => match kv.split_once('=') {
+                    Some((k, v)) => Self::Set(layer(sub), k.trim().into(), v.trim().into()),
+                    None => Self::Help,
+                },
+                _ => Self::Help,
+            },
+            Some(("unset", sub)) => Self::Unset(layer(sub), key(sub)),
+            Some(("edit", sub)) => Self::Edit(layer(sub)),
+            Some(("path", _)) => Self::Path,
+            Some(("config_help", _)) => Self::Help,
+            _ => Self::Show,
+        }
+    }
+}
+
+impl clap::Subcommand for ConfigCmd {
+    fn from_subcommand(sub: Option<(&str, &ArgMatches)>)
+        -> Option<Self>
+    {
+        let (sub, args) = sub.unwrap();
+        if sub == "config" {
+            Some(Self::from_arg_matches(args))
+        } else {
+            None
+        }
+    }
+
+    fn augment_subcommands(app: clap::App<'_>) -> clap::App<'_>
+    {
+        app
+    }
+}
@@ -1,30 +1,41 @@
 use chrono::{DateTime, Local};
 use crate::{
-    args::search::{Search, Filters},
+    args::search::Search,
+    output,
+    config::DConfig,
+    error::DError,
     models::{
-        fact::{Fact, AbstractFact, Unit},
+        fact::{Fact, AbstractFact, Unit, Revision, FactEdit},
         note::Notes,
         item::Item,
         attrib::Attrib,
         record::Record,
+        data::RecordData,
     },
     cmd::Cmd,
     prompt::prompt,
 };
 use clap::{ArgMatches, FromArgMatches, Subcommand};
 use colored::{Color, Colorize};
+use serde::Serialize;
 
 #[derive(Debug)]
 pub enum FactCmd {
-    New(Fact, AbstractFact), // Option<item> Option<record> ?
+    /// An entry to log, with the record and item to log it to if given
+    New(Fact, AbstractFact, Option<String>, Option<String>),
     NewFactType(AbstractFact),
-    Delete(Fact),
-    DeleteFactType(AbstractFact),
+    Delete(String),
+    /// A fact to delete every entry of, in a record or every record
+    DeleteFactType(String, Option<String>),
     Link(Fact),
     LinkFactType(AbstractFact),
     Search(Search),
+    Edit(String, FactEdit),
+    Show(String),
     Help,
-    List,
+    /// Entries in a record, or an item of it, or in every record, with any
+    /// of the attributes given
+    List(Option<String>, Option<String>, Vec<String>),
     Invalid,
 }
 
@@ -38,8 +49,30 @@ impl Default for FactCmd {
 impl Cmd for FactCmd {
 
     fn run(&self) {
-        println!("{}", format!("Running fact cmd...")
-            .color(Color::BrightCyan))
+        let res = match self {
+            Self::New(fact, _, record, item) => new_entry(fact, record.as_deref(), item.as_deref()),
+            Self::Edit(entry, edit) => edit_entry(entry, edit),
+            Self::Show(entry) => show_entry(entry),
+            Self::Delete(entry) => delete_entry(entry),
+            Self::DeleteFactType(name, record) => delete_fact(name, record.as_deref()),
+            Self::List(record, item, attribs) => list(record.as_deref(), item.as_deref(), attribs)
+                .map(|_| String::new()),
+            Self::Search(search) => search_facts(search).map(|_| String::new()),
+            Self::Help => {
+                Self::print_help();
+                Ok(String::new())
+            },
+            _ => Ok(String::new()),
+        };
+        match res {
+            Ok(msg) if msg.is_empty() => {},
+            Ok(msg) => println!("{}", msg),
+            Err(e) => {
+                eprintln!("{}", format!("Could not run fact cmd: {}", e)
+                    .color(Color::BrightRed));
+                super::fail();
+            }
+        }
     }
 
     fn name() -> &'static str { "fact" }
@@ -71,6 +104,8 @@ impl Cmd for FactCmd {
             Self::list_cmd(),
             Self::help_cmd(),
             Self::delete_cmd(),
+            Self::edit_cmd(),
+            Self::show_cmd(),
             clap::App::new("get")
                 .about("Get info about a specific fact")
                 .long_flag("get")
@@ -112,33 +147,23 @@ impl FromArgMatches for FactCmd {
             Some(("new", sub)) => {
                 let fact = Fact::from_arg_matches(sub);
                 let af = AbstractFact::from_arg_matches(sub);
-                return FactCmd::New(fact, af)
+                return FactCmd::New(fact, af, sub.value_of("record").map(String::from),
+                    sub.value_of("item").map(String::from))
             },
             Some(("search", sub)) => {
-                if let Some(r_filts) = sub.values_of("filterrecord") {
-                    println!("{}", "Filter fact in records:");
-                    let r_filts = r_filts
-                        .inspect(|r| {
-                            println!("{}", format!("R: {}", r)
-                                .color(Color::BrightCyan));
-                        })
-                        .collect::<Vec<&str>>();
-                }
-                if let Some(i_filts) = sub.values_of("filteritem") {
-                    println!("{}", "Filter fact in items:");
-                    let i_filts = i_filts
-                        .inspect(|r| {
-                            println!("{}", format!("R: {}", r)
-                                .color(Color::BrightCyan));
-                        })
-                        .collect::<Vec<&str>>();
-                }
-                let filters = Filters::from_arg_matches(matches);
-                return FactCmd::Search(Search::default())
+                return FactCmd::Search(Search::from_arg_matches(sub))
             },
             Some(("list", sub)) => {
-                println!("List facts comand, {}", "list");
-                return FactCmd::List
+                return Self::list_from(sub)
+            },
+            Some(("edit", sub)) => {
+                return FactCmd::Edit(Self::entry_of(sub), FactEdit::from_arg_matches(sub))
+            },
+            Some(("show", sub)) => {
+                return FactCmd::Show(Self::entry_of(sub))
+            },
+            Some(("delete", sub)) => {
+                return Self::delete_from(sub)
             },
             Some(("info", sub)) => {
                 println!("Info facts comand");
@@ -151,11 +176,10 @@ impl FromArgMatches for FactCmd {
             None => {
                 let fact = Fact::from_arg_matches(&matches);
                 let fact_type = AbstractFact::from_arg_matches(&matches);
-                println!("{}", fact.table());
-                println!("{}", fact_type.table());
                 // println!("{:#?}", fact);
                 // println!("{:#?}", fact_type);
-                FactCmd::New(fact, fact_type)
+                FactCmd::New(fact, fact_type, matches.value_of("record").map(String::from),
+                    matches.value_of("item").map(String::from))
             }
         }
     }
@@ -165,62 +189,115 @@ impl FactCmd {
 
     fn search_cmd() -> clap::App<'static> {
         clap::App::new("search")
-            .about("Search for a fact")
+            .about("Search for fact entries")
+            .long_about("Search for fact entries whose fact name contains the text given, oldest first")
             .long_flag("search")
             .short_flag('s')
             .args(&[
+                clap::Arg::new("QUERY")
+                    .about("Text the fact's name should contain")
+                    .required(false)
+                    .index(1),
                 clap::Arg::new("attrib")
                     .about("Filter by attribute")
                     .short('a')
                     .long("attrib")
                     .multiple(true)
                     .required(false),
-                clap::Arg::new("sort")
-                    .about("Sort output results by specified parameter")
-                    .possible_values(&[
-                        "alphabetical", "entry-quantity", "date", "item",
-                        "record", "attrib",
-                    ])
-                    .case_insensitive(true)
-                    .value_name("SORT")
-                    .required(false)
-                    .takes_value(true),
+                clap::Arg::new("record")
+                    .about("Filter by record(s)")
+                    .long("record")
+                    .short('r')
+                    .multiple(true)
+                    .required(false),
                 clap::Arg::new("ascending")
-                    .about("Sort values ascending")
+                    .about("Sort entries oldest first")
                     .long("ascending")
                     .alias("asc")
                     .takes_value(false),
                 clap::Arg::new("descending")
-                    .about("Sort values ascending")
+                    .about("Sort entries newest first")
                     .long("descending")
                     .alias("desc")
+                    .overrides_with("ascending")
                     .takes_value(false),
-                clap::Arg::new("filteritem")
-                    .about("Filter by items")
-                    .multiple(true)
-                    .long("item")
-                    .short('i')
-                    .required(false),
-                clap::Arg::new("filterrecord")
-                    .about("Filter by record(s)")
-                    .multiple(true)
-                    .long("record")
-                    .short('s')
-                    .required(false),
-                clap::Arg::new("max-results")
+                clap::Arg::new("max-entries")
                     .about("Maximum number of entries to display")
                     .long("max")
                     .short('m')
-                    .takes_value(false)
                     .default_value("50")
-                    .value_name("rescount")
-                    .required(false),
-                clap::Arg::new("case-insensitive")
-                    .about("Search for fact case insensitive")
-                    .required(false),
+                    .takes_value(true)
+                    .required(false)
+            ])
+    }
+
+    pub fn edit_cmd() -> clap::App<'static> {
+        clap::App::new("edit")
+            .about("Edit a fact entry, keeping what it was as a revision")
+            .long_about("Change the value, units, time, attributes or notes of a fact entry given by the start of its id, 'last' for the last entry logged or e.g. 'last:sleep' for the last sleep entry. Without any flags the entry is opened in your editor. What the entry was is kept as a revision, listed by 'fact show'")
+            .arg(Self::entry_arg())
+            .args(&[
+                clap::Arg::new("value")
+                    .about("Set the value of the entry")
+                    .long("value")
+                    .short('v')
+                    .takes_value(true)
+                    .value_name("VALUE"),
+                clap::Arg::new("unit")
+                    .about("Set the units of the entry, or none with ''")
+                    .long("units")
+                    .short('u')
+                    .takes_value(true)
+                    .value_name("UNITS"),
+                clap::Arg::new("time")
+                    .about("Set the time of the entry")
+                    .long_about("Set the time of the entry, in RFC 3339 or natural language, e.g. 'yesterday 11pm'")
+                    .long("time")
+                    .short('t')
+                    .takes_value(true)
+                    .value_name("TIME"),
+                clap::Arg::new("attribs")
+                    .about("Set the attributes of the entry, replacing its own")
+                    .long("attrib")
+                    .short('a')
+                    .multiple_occurrences(true)
+                    .takes_value(true)
+                    .value_name("ATTRIBUTE"),
+                clap::Arg::new("notes")
+                    .about("Set the notes of the entry, replacing its own")
+                    .long("notes")
+                    .short('n')
+                    .multiple_occurrences(true)
+                    .takes_value(true)
+                    .value_name("NOTE"),
             ])
     }
 
+    pub fn show_cmd() -> clap::App<'static> {
+        clap::App::new("show")
+            .about("Show a fact entry and its revisions")
+            .long_about("Show a fact entry given by the start of its id, 'last' or e.g. 'last:sleep', with every version of it replaced by 'fact edit', oldest first")
+            .arg(Self::entry_arg())
+    }
+
+    fn entry_arg() -> clap::Arg<'static> {
+        clap::Arg::new("ENTRY")
+            .about("The start of the entry's id, 'last' or 'last:<fact>'")
+            .required(true)
+            .index(1)
+    }
+
+    fn delete_from(matches: &ArgMatches) -> Self {
+        match matches.value_of("fact") {
+            Some(fact) => Self::DeleteFactType(fact.into(), matches.value_of("record").map(String::from)),
+            None => Self::Delete(Self::entry_of(matches)),
+        }
+    }
+
+    fn entry_of(matches: &ArgMatches) -> String {
+        matches.value_of("ENTRY").unwrap_or("last").to_string()
+    }
+
     fn time() -> clap::Arg<'static> {
         clap::Arg::new("time")
             .about("Set the time this log should be added")
@@ -235,39 +312,69 @@ impl FactCmd {
 
     fn list_cmd() -> clap::App<'static> {
         clap::App::new("list")
-            .about("List all of the facts globaally/in record/item")
-            .long_about("Specify arguments to list different facts")
+            .about("List fact entries in every record, a record or an item")
+            .long_about("List fact entries oldest first, in every record or in the record and item given, optionally only those with any of the attributes given")
             .long_flag("ls")
             .short_flag('l')
             .args(&[
                 clap::Arg::new("record")
-                    .about("Fact in record")
-                    .short('r'),
+                    .about("List entries in this record")
+                    .long("record")
+                    .short('r')
+                    .takes_value(true)
+                    .value_name("RECORD"),
                 clap::Arg::new("item")
-                    .about("Fact in item")
-                    .short('i'),
-                clap::Arg::new("attribute")
-                    .about("Fact with attribute")
-                    .short('a'),
+                    .about("List entries in this item of the record, and its child items")
+                    .long("item")
+                    .short('i')
+                    .requires("record")
+                    .takes_value(true)
+                    .value_name("ITEM"),
+                clap::Arg::new("attrib")
+                    .about("List entries with this attribute")
+                    .long("attrib")
+                    .short('a')
+                    .multiple_occurrences(true)
+                    .takes_value(true)
+                    .value_name("ATTRIBUTE"),
             ])
     }
 
+    fn list_from(matches: &ArgMatches) -> Self {
+        Self::List(
+            matches.value_of("record").map(String::from),
+            matches.value_of("item").map(String::from),
+            matches.values_of("attrib")
+                .map(|a| a.map(String::from).collect())
+                .unwrap_or_default())
+    }
+
     pub fn delete_cmd() -> clap::App<'static> {
         clap::App::new("delete")
-            .about("Delete a fact or fact entry")
-            .long_about("Specify arguments to list different facts")
+            .about("Delete a fact entry, or a fact with all its entries")
+            .long_about("Move a fact entry, given by the start of its id, 'last' or e.g. 'last:sleep', to the trash. With --fact, move every entry of a fact in a record (or every record) to the trash along with the record's fact type. See 'dlog trash' to restore them")
             .long_flag("delete")
             .short_flag('d')
+            .alias("rm")
             .args(&[
+                clap::Arg::new("ENTRY")
+                    .about("The start of the entry's id, 'last' or 'last:<fact>'")
+                    .required_unless_present("fact")
+                    .index(1),
+                clap::Arg::new("fact")
+                    .about("Delete every entry of this fact")
+                    .long("fact")
+                    .short('f')
+                    .conflicts_with("ENTRY")
+                    .takes_value(true)
+                    .value_name("FACT"),
                 clap::Arg::new("record")
-                    .about("Fact in record")
-                    .short('r'),
-                clap::Arg::new("item")
-                    .about("Fact in item")
-                    .short('i'),
-                clap::Arg::new("attribute")
-                    .about("Fact with attribute")
-                    .short('a'),
+                    .about("Only delete the fact's entries in this record")
+                    .long("record")
+                    .short('r')
+                    .requires("fact")
+                    .takes_value(true)
+                    .value_name("RECORD"),
             ])
     }
 
@@ -283,7 +390,7 @@ impl FactCmd {
         clap::Arg::new("VALUE") //TODO if no index 3, prompt from stdin
             .requires("NAME")
             .about("Value of the fact given by NAME")
-            .validator(|a| crate::prompt::validate_input(a.into()))
+            .validator(|a| crate::prompt::validate_text(a.into()))
             .index(idx)
     }
 
@@ -313,7 +420,7 @@ impl FactCmd {
             .alias("val")
             .short('v')
             .required(false)
-            .validator(|a| crate::prompt::validate_input(a.into()))
+            .validator(|a| crate::prompt::validate_text(a.into()))
     }
 
     pub fn units() -> clap::Arg<'static> {
@@ -360,6 +467,7 @@ impl FactCmd {
             .multiple_occurrences(true)
             .value_name("NOTE")
             .takes_value(true)
+            .validator(|a| crate::prompt::validate_text(a.into()))
             .requires_all(&["VALUE", "NAME"])
     }
 
@@ -512,9 +620,13 @@ impl clap::Subcommand for FactCmd {
                 "new" => {
                     let fact = Fact::from_arg_matches(m);
                     let af = AbstractFact::from_arg_matches(m);
-                    Some(Self::New(fact, af))
+                    Some(Self::New(fact, af, m.value_of("record").map(String::from),
+                        m.value_of("item").map(String::from)))
                 },
-                "list" => Some(Self::List),
+                "list" => Some(Self::list_from(m)),
+                "edit" => Some(Self::Edit(Self::entry_of(m), FactEdit::from_arg_matches(m))),
+                "show" => Some(Self::Show(Self::entry_of(m))),
+                "delete" => Some(Self::delete_from(m)),
                 "search" => Some(Self::Search(Search::from_arg_matches(m))),
                 "help" => Some(Self::Help),
                 _ => None,
@@ -530,4 +642,167 @@ impl clap::Subcommand for FactCmd {
     }
 }
 
+impl FromArgMatches for FactEdit {
+    fn from_arg_matches(matches: &ArgMatches) -> Self {
+        let values = |name| matches.values_of(name)
+            .map(|v| v.map(String::from).collect::<Vec<String>>());
+        Self {
+            value: matches.value_of("value").map(String::from),
+            units: matches.value_of("unit").map(String::from),
+            time: matches.value_of("time").map(String::from),
+            attributes: values("attribs"),
+            notes: values("notes"),
+        }
+    }
+}
+
+/// An entry with the versions of it replaced, for structured output
+#[derive(Serialize)]
+struct Shown<'a> {
+    #[serde(flatten)]
+    entry: &'a Fact,
+    revisions: &'a [Revision],
+}
+
+/// Logs an entry given as `dlog <fact> <value>` to the record and item
+/// given, or the inbox, asking about a fact or attribute name alike one
+/// already logged to the record, or to any record if none is given
+fn new_entry(fact: &Fact, record: Option<&str>, item: Option<&str>) -> crate::DResult<String> {
+    let (record, fact) = match record {
+        Some(name) => {
+            let record = super::open_record(name)?;
+            let fact = super::meant_fact(std::slice::from_ref(&record), fact)?;
+            (record, fact)
+        },
+        None => {
+            let records = RecordData::read()?.records()
+                .map(|r| Record::open_path(&r.path))
+                .collect::<crate::DResult<Vec<Record>>>()?;
+            (Record::inbox()?, super::meant_fact(&records, fact)?)
+        },
+    };
+    let item = match item {
+        Some(path) => {
+            crate::prompt::validate_item_path(path.into()).map_err(|e| DError::Invalid(e.into()))?;
+            let items = record.items.iter().map(Item::path).collect::<Vec<String>>();
+            Some(Item::new(crate::prompt::meant("item", path, items.iter().map(String::as_str))?))
+        },
+        None => None,
+    };
+    let msg = match &item {
+        Some(item) => format!("Logged {} = {} to {} in {}",
+            fact.name, fact.val.raw(), item.path(), record.name),
+        None => format!("Logged {} = {} to {}", fact.name, fact.val.raw(), record.name),
+    };
+    fact.write(Some(record), item)?;
+    Ok(msg)
+}
+
+/// Entries in a record, or an item of it and its children, or in every
+/// record, oldest first
+fn entries(record: Option<&str>, item: Option<&str>) -> crate::DResult<Vec<Fact>> {
+    Ok(match (record, item) {
+        (Some(record), Some(item)) => {
+            let record = super::open_record(record)?;
+            record.item(item).ok_or(DError::NotFound)?.facts(&record, true)?
+        },
+        (Some(record), None) => super::open_record(record)?.facts()?,
+        (None, _) => {
+            let mut facts = Vec::new();
+            for record in RecordData::read()?.records() {
+                facts.extend(Record::open_path(&record.path)?.facts()?);
+            }
+            facts.sort_by_key(|f| f.created_at);
+            facts
+        },
+    })
+}
 
+/// Prints entries in the `--output` format, or as a table
+fn print_entries(facts: &[Fact]) -> crate::DResult<()> {
+    if let Some(out) = output::render(facts)? {
+        println!("{}", out);
+    } else if facts.is_empty() {
+        println!("{}", "No entries found".color(Color::BrightYellow));
+    } else {
+        println!("{}", Fact::list_table(facts));
+    }
+    Ok(())
+}
+
+fn list(record: Option<&str>, item: Option<&str>, attribs: &[String]) -> crate::DResult<()> {
+    let filter = Search { attribs: attribs.to_vec(), ..Search::default() };
+    let mut facts = entries(record, item)?;
+    facts.retain(|f| filter.has_attribs(&f.attribs));
+    print_entries(&facts)
+}
+
+fn search_facts(search: &Search) -> crate::DResult<()> {
+    let mut facts = Vec::new();
+    for record in RecordData::read()?.records().filter(|r| search.in_record(&r.name)) {
+        facts.extend(Record::open_path(&record.path)?.facts()?.into_iter()
+            .filter(|f| search.matches(&f.name) && search.has_attribs(&f.attribs)));
+    }
+    search.sort_limit(&mut facts, |f| f.created_at);
+    print_entries(&facts)
+}
+
+fn edit_entry(entry: &str, edit: &FactEdit) -> crate::DResult<String> {
+    let (fact, record, path) = Fact::find(entry)?;
+    let edit = match edit.is_empty() {
+        true => FactEdit::edit(&fact, &DConfig::load()?.editor())?,
+        false => edit.clone(),
+    };
+    let edited = edit.apply(&fact)?;
+    if edited == fact {
+        return Ok(format!("Entry {} is unchanged", fact.id)
+            .color(Color::BrightYellow).to_string());
+    }
+    edited.amend(&record, &path)?;
+    Ok(format!("Edited entry {} of {} in {}", edited.id, edited.name, record.name)
+        .color(Color::BrightGreen).to_string())
+}
+
+fn delete_entry(entry: &str) -> crate::DResult<String> {
+    let (fact, record, path) = Fact::find(entry)?;
+    fact.delete(&record, &path)?;
+    Ok(format!("Moved entry {} of {} to the trash", fact.id, fact.name)
+        .color(Color::BrightGreen).to_string())
+}
+
+/// Moves every entry of a fact in a record, or in each record having any,
+/// to the trash
+fn delete_fact(name: &str, record: Option<&str>) -> crate::DResult<String> {
+    let records = match record {
+        Some(record) => vec![super::open_record(record)?],
+        None => RecordData::read()?.records()
+            .map(|r| Record::open_path(&r.path))
+            .collect::<crate::DResult<Vec<Record>>>()?,
+    };
+    let mut deleted = Vec::new();
+    for mut record in records {
+        match record.remove_fact(name) {
+            Ok(trashed) => deleted.push(trashed),
+            Err(DError::NotFound) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    if deleted.is_empty() {
+        return Err(DError::Invalid(format!("No {} entries or fact type to delete", name)));
+    }
+    Ok(format!("Moved {} entries of {} in {} to the trash",
+            deleted.iter().map(|t| t.entries).sum::<usize>(), name,
+            deleted.iter().map(|t| t.record.clone()).collect::<Vec<String>>().join(", "))
+        .color(Color::BrightGreen).to_string())
+}
+
+fn show_entry(entry: &str) -> crate::DResult<String> {
+    let (fact, record, _) = Fact::find(entry)?;
+    let revisions = Revision::of(&fact.id)?;
+    if let Some(out) = output::render_one(&Shown { entry: &fact, revisions: &revisions })? {
+        return Ok(out);
+    }
+    Ok(format!("{} {} in {}\n{}",
+        "Entry".color(Color::BrightCyan), fact.id, record.name,
+        Revision::table(&fact, &revisions)))
+}
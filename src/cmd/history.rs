@@ -0,0 +1,172 @@
+use clap::{ArgMatches, FromArgMatches};
+use colored::{Color, Colorize};
+use crate::{
+    output,
+    cmd::Cmd,
+    history::{self, Op},
+};
+
+#[derive(Debug, Default)]
+pub enum HistoryCmd {
+    List(usize),
+    Undo(usize),
+    Redo(usize),
+    #[default]
+    Help,
+}
+
+impl Cmd for HistoryCmd {
+
+    fn name() -> &'static str { "history" }
+    fn about() -> &'static str { "List the last commands which changed your data" }
+    fn long_about() -> &'static str {
+        "List the last commands which changed your data, oldest first, and whether they have been undone. Every command which logs, moves, renames or deletes anything is journaled with what the files it changed held before and after, so it can be undone with 'dlog undo' and redone with 'dlog redo'"
+    }
+
+    fn args() -> Vec<clap::Arg<'static>> {
+        vec![
+            clap::Arg::new("max-entries")
+                .about("How many commands to list")
+                .long("max")
+                .short('m')
+                .takes_value(true)
+                .default_value("10")
+                .validator(count),
+        ]
+    }
+
+    fn subcmds() -> Vec<clap::App<'static>> {
+        vec![Self::help_cmd()]
+    }
+
+    fn run(&self) {
+        let res = match self {
+            Self::List(n) => list(*n),
+            Self::Undo(n) => history::undo(*n).map(|ops| report("Undid", &ops)),
+            Self::Redo(n) => history::redo(*n).map(|ops| report("Redid", &ops)),
+            Self::Help => {
+                Self::print_help();
+                Ok(String::new())
+            },
+        };
+        match res {
+            Ok(msg) if msg.is_empty() => {},
+            Ok(msg) => println!("{}", msg),
+            Err(e) => {
+                eprintln!("{}", format!("Could not run history cmd: {}", e)
+                    .color(Color::BrightRed));
+                super::fail();
+            }
+        }
+    }
+
+    fn print_help() {
+        let help = "
+            HISTORY: Undo and redo what you changed, e.g.\n
+                  dlog history\n
+                  dlog undo\n
+                  dlog undo 3\n
+                  dlog redo\n
+        ".to_string().color(Color::BrightCyan);
+        println!("> {}", help)
+    }
+
+    fn help_cmd() -> clap::App<'static> {
+        clap::App::new("history_help")
+            .about("Prints help command for history")
+            .long_flag("help")
+            .short_flag('h')
+            .long_about("Prints the help information")
+    }
+}
+
+impl HistoryCmd {
+
+    pub fn undo_cmd() -> clap::App<'static> {
+        clap::App::new("undo")
+            .about("Undo the last command which changed your data")
+            .long_about("Undo the last N commands which changed your data, newest first. Refuses to undo a command whose files were changed since by something other than dlog")
+            .arg(Self::count_arg("How many commands to undo"))
+    }
+
+    pub fn redo_cmd() -> clap::App<'static> {
+        clap::App::new("redo")
+            .about("Redo the last command undone")
+            .long_about("Redo the last N commands undone, as long as nothing else was done since")
+            .arg(Self::count_arg("How many commands to redo"))
+    }
+
+    fn count_arg(about: &'static str) -> clap::Arg<'static> {
+        clap::Arg::new("N")
+            .about(about)
+            .default_value("1")
+            .validator(count)
+            .index(1)
+    }
+
+    pub fn undo_from(matches: &ArgMatches) -> Self {
+        Self::Undo(matches.value_of("N").and_then(|n| n.parse().ok()).unwrap_or(1))
+    }
+
+    pub fn redo_from(matches: &ArgMatches) -> Self {
+        Self::Redo(matches.value_of("N").and_then(|n| n.parse().ok()).unwrap_or(1))
+    }
+}
+
+fn count(n: &str) -> Result<(), String> {
+    match n.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(()),
+        _ => Err(format!("{} is not a positive number", n)),
+    }
+}
+
+fn list(n: usize) -> crate::DResult<String> {
+    let logged = history::history(n)?;
+    if let Some(out) = output::render(&logged)? {
+        return Ok(out);
+    }
+    match logged.is_empty() {
+        true => Ok("Nothing has been changed yet".into()),
+        false => Ok(history::table(&logged).to_string()),
+    }
+}
+
+/// Says what was undone or redone, once the search index has caught up
+fn report(verb: &str, ops: &[Op]) -> String {
+    for op in ops {
+        crate::cmd::reindex(&op.changes);
+    }
+    ops.iter()
+        .map(|op| format!("{} `{}`", verb, op.cmd).color(Color::BrightGreen).to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+impl FromArgMatches for HistoryCmd {
+    fn from_arg_matches(matches: &ArgMatches) -> Self {
+        match matches.subcommand() {
+            Some(("history_help", _)) => Self::Help,
+            _ => Self::List(matches.value_of("max-entries")
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(10)),
+        }
+    }
+}
+
+impl clap::Subcommand for HistoryCmd {
+    fn from_subcommand(sub: Option<(&str, &ArgMatches)>)
+        -> Option<Self>
+    {
+        match sub {
+            Some(("history", args)) => Some(Self::from_arg_matches(args)),
+            Some(("undo", args)) => Some(Self::undo_from(args)),
+            Some(("redo", args)) => Some(Self::redo_from(args)),
+            _ => None,
+        }
+    }
+
+    fn augment_subcommands(app: clap::App<'_>) -> clap::App<'_>
+    {
+        app
+    }
+}
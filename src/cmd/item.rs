@@ -1,26 +1,55 @@
 use colored::{Color, Colorize};
+use comfy_table::{
+    Table, ContentArrangement, presets::UTF8_BORDERS_ONLY,
+    Cell, Attribute, Color as TColor,
+};
 use crate::{
-    models::{Item, Record, fact::{Fact, AbstractFact}},
+    models::{
+        Item, Record, Attrib,
+        item::RecordItem,
+        data::RecordData,
+        entity::EntityRef,
+        stats::FactStats,
+        fact::{Fact, AbstractFact},
+    },
     cmd::Cmd,
     args::search::Search,
+    error::DError,
+    output, prompt,
 };
 use clap::{Arg, ArgMatches, ArgSettings, FromArgMatches};
 
+/// Item cmds which take an item path carry the record given with --record,
+/// if any. Otherwise a path starting with a record name (`health/exercise`)
+/// resolves within that record, and anything else within the inbox.
 #[derive(Debug)]
 pub enum ItemCmd {
-    New(Item),
-    Delete(Item),
-    AddFact(Item, Fact),
+    New(Item, Option<String>),
+    Get(String, Option<String>, Option<String>),
+    Delete(String, Option<String>, Option<OnDelete>, bool),
+    AddFact(Item, Fact, Option<String>),
     AddFactType(AbstractFact),
     EditMetadata(Item),
-    List,
+    Link(String, Option<String>, Vec<Attrib>, Vec<EntityRef>),
+    Move(String, String, Option<String>),
+    Tree(Option<String>),
+    List(Option<String>, bool),
     Search(Search),
     Help,
 }
 
+/// What happens to the entries and child items of a deleted item
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnDelete {
+    /// Delete them along with the item
+    Cascade,
+    /// Keep the entries in the record and move the children up a level
+    Orphan,
+}
+
 impl Default for ItemCmd {
     fn default() -> Self {
-        ItemCmd::New(Item::default())
+        ItemCmd::Help
     }
 }
 
@@ -39,28 +68,20 @@ impl Cmd for ItemCmd {
             Self::delete_cmd(),
             clap::App::new("add")
                 .about("Add a fact entry to an item you choose")
-                .long_about("Add a fact to an item. For example, 'dlog item add health sleep 4 hr' will create the item 'health' if not already created, and addd the fact 'sleep' = '5 hrs' to it"),
-            clap::App::new("get")
-                .about("Get info about a specific item")
-                .long_flag("get")
-                .short_flag('g'),
-            clap::App::new("link")
-                .about("Link two items together, or with a record/fact")
-                .long_flag("link")
-                .short_flag('k')
+                .long_about("Add a fact to an item. For example, 'dlog item add health sleep 4 hr' will create the item 'health' if not already created, and addd the fact 'sleep' = '5 hrs' to it. Items under other items are given by path, e.g. 'dlog item add exercise/running distance 5 km'")
+                .args(Self::fact_args())
+                .arg(super::FactCmd::attributes())
+                .arg(super::FactCmd::notes()),
+            Self::move_cmd(),
+            Self::tree_cmd(),
+            Self::get_cmd(),
+            Self::link_cmd(),
         ]
     }
 
     fn args() -> Vec<clap::Arg<'static>> {
-        vec![
-            Arg::new("ITEM")
-                .about("Name of the item to log")
-                .required(false)
-                .validator(|a| crate::prompt::validate_input(a.into()))
-                .index(1),
-            super::FactCmd::key_arg(2),
-            super::FactCmd::val_arg(3),
-            super::FactCmd::val_unit(4),
+        let mut args = Self::fact_args();
+        args.extend(vec![
             Arg::new("uncategorized")
                 .aliases(&["misc", "uncat", "etc"])
                 .short('u')
@@ -74,13 +95,6 @@ impl Cmd for ItemCmd {
                 .required(false)
                 .validator(|a| crate::prompt::validate_input(a.into()))
                 .multiple(true),
-            Arg::new("record")
-                .about("Specify the record to add this fact to")
-                .long("record")
-                .short('r')
-                .required(false)
-                .validator(|a| crate::prompt::validate_input(a.into()))
-                .multiple(true),
             Arg::new("link-attribute")
                 .long("Whether to persist the attribute-item link")
                 .long_about("Link an attribute to this item (not just this fact entry)")
@@ -99,13 +113,77 @@ impl Cmd for ItemCmd {
                 .overrides_with("record") //TODO test this
                 .multiple(true)
                 .required(false),
-            ]
+            ]);
+        args
     }
 
     fn run(&self) {
-        println!("{}", format!("Running item cmd...")
-            .color(Color::BrightMagenta))
-
+        let res = match self {
+            Self::New(item, record) => locate(&item.path(), record.as_deref())
+                .and_then(|(mut rec, path)| {
+                    let item = Item { attribs: item.attribs.clone(), ..Item::new(path) };
+                    rec.add_item(item.clone())?;
+                    Ok(format!("Added item {} to {}", item.path(), rec.name))
+                }),
+            Self::AddFact(item, fact, record) => locate(&item.path(), record.as_deref())
+                .and_then(|(rec, path)| {
+                    let (path, fact) = meant(&rec, &path, fact)?;
+                    let item = Item::new(path);
+                    let msg = format!("Logged {} = {} to {} in {}",
+                        fact.name, fact.val.raw(), item.path(), rec.name);
+                    fact.write(Some(rec), Some(item))?;
+                    Ok(msg)
+                }),
+            Self::Move(from, to, record) => locate(from, record.as_deref())
+                .and_then(|(mut rec, from)| {
+                    let (_, to) = locate(to, Some(&rec.name))?;
+                    rec.move_item(&from, &to)?;
+                    Ok(format!("Moved item {} to {} in {}", from, to, rec.name))
+                }),
+            Self::Get(path, record, fact) => get(path, record.as_deref(), fact.as_deref())
+                .map(|_| String::new()),
+            Self::Link(path, record, attribs, links) => locate(path, record.as_deref())
+                .and_then(|(mut rec, path)| {
+                    let item = rec.items.iter_mut()
+                        .find(|i| i.path().eq_ignore_ascii_case(&path))
+                        .ok_or(DError::NotFound)?;
+                    for attrib in attribs {
+                        if !item.attribs.contains(attrib) {
+                            item.attribs.push(attrib.clone());
+                        }
+                    }
+                    for link in links {
+                        item.link(link.clone());
+                    }
+                    let msg = format!("Linked {} to {}", item.path(),
+                        attribs.iter().map(|a| a.name.clone())
+                            .chain(links.iter().map(|l| l.to_string()))
+                            .collect::<Vec<String>>()
+                            .join(", "));
+                    rec.write()?;
+                    Ok(msg)
+                }),
+            Self::Delete(path, record, on_delete, yes) =>
+                delete(path, record.as_deref(), *on_delete, *yes),
+            Self::List(record, uncategorized) => list(record.as_deref(), *uncategorized)
+                .map(|_| String::new()),
+            Self::Search(search) => search_items(search).map(|_| String::new()),
+            Self::Tree(record) => tree(record.as_deref()).map(|_| String::new()),
+            Self::Help => {
+                Self::print_help();
+                Ok(String::new())
+            },
+            _ => Ok("Running item cmd...".into()),
+        };
+        match res {
+            Ok(msg) if msg.is_empty() => {},
+            Ok(msg) => println!("{}", msg.color(Color::BrightMagenta)),
+            Err(e) => {
+                eprintln!("{}", format!("Could not run item cmd: {}", e)
+                    .color(Color::BrightRed));
+                super::fail();
+            }
+        }
     }
 
     fn print_help() {
@@ -128,6 +206,68 @@ impl Cmd for ItemCmd {
 }
 
 impl ItemCmd {
+
+    /// The item path and fact entry to log to it
+    fn fact_args() -> Vec<clap::Arg<'static>> {
+        vec![
+            Arg::new("ITEM")
+                .about("Name of the item to log")
+                .long_about("Name of the item to log, or its path if it is under another item, e.g. exercise/running")
+                .required(false)
+                .validator(|a| crate::prompt::validate_item_path(a.into()))
+                .index(1),
+            super::FactCmd::key_arg(2),
+            super::FactCmd::val_arg(3),
+            super::FactCmd::val_unit(4),
+            Self::record_arg(),
+        ]
+    }
+
+    fn record_arg() -> clap::Arg<'static> {
+        Arg::new("record")
+            .about("Specify the record the item is in")
+            .long_about("Specify the record the item is in. If not given, an item path starting with a record name resolves within that record, otherwise within the inbox")
+            .long("record")
+            .short('r')
+            .required(false)
+            .takes_value(true)
+            .validator(|a| crate::prompt::validate_input(a.into()))
+            .multiple(true)
+    }
+
+    fn move_cmd() -> clap::App<'static> {
+        clap::App::new("move")
+            .about("Move or rename an item, along with its entries and child items")
+            .long_about("Move an item to a new path within its record, e.g. 'dlog item move running exercise/running' nests running under exercise. Child items and all entries move with it")
+            .aliases(&["mv", "rename"])
+            .args(&[
+                Arg::new("FROM")
+                    .about("The item to move")
+                    .required(true)
+                    .validator(|a| crate::prompt::validate_item_path(a.into()))
+                    .index(1),
+                Arg::new("TO")
+                    .about("The item's new path")
+                    .required(true)
+                    .validator(|a| crate::prompt::validate_item_path(a.into()))
+                    .index(2),
+                Self::record_arg(),
+            ])
+    }
+
+    fn tree_cmd() -> clap::App<'static> {
+        clap::App::new("tree")
+            .about("Show the items of a record as a tree")
+            .long_about("Show the items of a record, or of every record if none is given, nested under their parents with the number of entries logged to each and to each including its children")
+            .args(&[
+                Arg::new("RECORD")
+                    .about("The record to show")
+                    .required(false)
+                    .validator(|a| crate::prompt::validate_input(a.into()))
+                    .index(1),
+            ])
+    }
+
     pub fn new_cmd() -> clap::App<'static> {
         clap::App::new("new")
             .about("Create a new item to associate with different facts")
@@ -151,16 +291,90 @@ impl ItemCmd {
                     .multiple(true),
                 clap::Arg::new("ITEM")
                     .about("The name of the item to be added")
+                    .long_about("The name of the item to be added, or its path to add it under another item, e.g. exercise/running. Parent items are created as needed")
+                    .validator(|a| crate::prompt::validate_item_path(a.into()))
                     .required(false)
                     .index(1),
             ])
     }
 
+    fn get_cmd() -> clap::App<'static> {
+        clap::App::new("get")
+            .about("Get info about a specific item")
+            .long_about("Show an item with the fact entries logged to it and to the items under it, and a summary of each fact")
+            .long_flag("get")
+            .short_flag('g')
+            .args(&[
+                Self::item_arg(),
+                Self::record_arg(),
+                Arg::new("fact")
+                    .about("Only show entries of this fact")
+                    .long("fact")
+                    .short('f')
+                    .takes_value(true)
+                    .required(false),
+            ])
+    }
+
+    fn link_cmd() -> clap::App<'static> {
+        clap::App::new("link")
+            .about("Link two items together, or with a record/fact")
+            .long_about("Link attributes, other items, records or facts to an item. Links are saved with the item in its record")
+            .long_flag("link")
+            .short_flag('k')
+            .args(&[
+                Self::item_arg(),
+                Self::record_arg(),
+                Arg::new("attrib")
+                    .about("Attributes to link to the item")
+                    .long("attrib")
+                    .short('a')
+                    .multiple(true)
+                    .takes_value(true),
+                Arg::new("to")
+                    .about("Items, records or facts to link to, e.g. item:diet or record:finance")
+                    .long("to")
+                    .short('t')
+                    .multiple(true)
+                    .takes_value(true)
+                    .validator(|e| e.parse::<EntityRef>().map(|_| ())),
+            ])
+    }
+
     fn delete_cmd() -> clap::App<'static> {
         clap::App::new("delete")
             .about("Delete an item from the database")
+            .long_about("Move an item from its record to the trash. If it has entries or child items, either --cascade to trash them too, or --orphan to keep its entries in the record and move its children up a level. See 'dlog trash' to restore it")
             .long_flag("delete")
             .short_flag('d')
+            .alias("rm")
+            .args(&[
+                Self::item_arg(),
+                Self::record_arg(),
+                Arg::new("cascade")
+                    .about("Also delete the item's entries and child items")
+                    .long("cascade")
+                    .short('c')
+                    .conflicts_with("orphan")
+                    .takes_value(false),
+                Arg::new("orphan")
+                    .about("Keep the item's entries in its record and move its child items up")
+                    .long("orphan")
+                    .takes_value(false),
+                Arg::new("yes")
+                    .about("Don't ask for confirmation")
+                    .long("yes")
+                    .short('y')
+                    .takes_value(false),
+            ])
+    }
+
+    fn item_arg() -> clap::Arg<'static> {
+        Arg::new("ITEM")
+            .about("The item, or its path if it is under another item")
+            .required(true)
+            .validator(|a| crate::prompt::validate_item_path(a.into()))
+            .index(1)
     }
 
     // TODO -- implement this in a trait body and implement the trait
@@ -171,6 +385,19 @@ impl ItemCmd {
             .about("List all of the items globaally or in a record")
             .long_flag("ls")
             .short_flag('l')
+            .args(&[
+                Arg::new("RECORD")
+                    .about("Only list items in this record")
+                    .required(false)
+                    .validator(|a| crate::prompt::validate_input(a.into()))
+                    .index(1),
+                Arg::new("uncategorized")
+                    .aliases(&["misc", "uncat", "etc"])
+                    .short('u')
+                    .long("uncategorized")
+                    .about("Only list items in the inbox record")
+                    .takes_value(false),
+            ])
     }
 
     fn search_cmd() -> clap::App<'static> {
@@ -179,6 +406,10 @@ impl ItemCmd {
             .long_flag("search")
             .short_flag('s')
             .args(&[
+                clap::Arg::new("QUERY")
+                    .about("Text the item's path should contain")
+                    .required(false)
+                    .index(1),
                 clap::Arg::new("attrib")
                     .about("Filter by attribute")
                     .short('a')
@@ -189,7 +420,7 @@ impl ItemCmd {
                     .about("Filter by record(s)")
                     .multiple(true)
                     .long("record")
-                    .short('s')
+                    .short('r')
                     .multiple(true)
                     .required(false),
                 clap::Arg::new("ascending")
@@ -201,12 +432,14 @@ impl ItemCmd {
                     .about("Sort values ascending")
                     .long("descending")
                     .alias("desc")
+                    .overrides_with("ascending")
                     .takes_value(false),
                 clap::Arg::new("max-entries")
                     .about("Maximum number of entries to display")
                     .long("max")
                     .short('m')
-                    .takes_value(false)
+                    .default_value("50")
+                    .takes_value(true)
                     .required(false)
             ])
     }
@@ -214,32 +447,52 @@ impl ItemCmd {
 
 impl FromArgMatches for ItemCmd {
     fn from_arg_matches(matches: &ArgMatches) -> Self {
-        println!("{}", format!("ITEM: subc: {:#?} \n matches: {:#?}",
-            matches.subcommand(),
-            matches
-        ).color(Color::Magenta));
+        let record = |m: &ArgMatches| m.value_of("record").map(String::from);
         if let Some((sub, args)) = matches.subcommand() {
-            let cmd = match sub {
-                "new" => Self::New(Item::from_arg_matches(args)),
-                "add" => Self::AddFact(Item::default(), Fact::from_arg_matches(args)), //TODO handle diff
-                "delete" => Self::Delete(Item::from_arg_matches(args)),
+            match sub {
+                "new" => Self::New(Item::from_arg_matches(args), record(args)),
+                "add" if args.is_present("ITEM") => Self::AddFact(
+                    Item::from_arg_matches(args),
+                    Fact::from_arg_matches(args),
+                    record(args)),
+                "move" => Self::Move(
+                    args.value_of("FROM").unwrap_or_default().into(),
+                    args.value_of("TO").unwrap_or_default().into(),
+                    record(args)),
+                "tree" => Self::Tree(args.value_of("RECORD").map(String::from)),
+                "get" => Self::Get(
+                    args.value_of("ITEM").unwrap_or_default().into(),
+                    record(args),
+                    args.value_of("fact").map(String::from)),
+                "link" => Self::Link(
+                    args.value_of("ITEM").unwrap_or_default().into(),
+                    record(args),
+                    Attrib::from_match(args.values_of("attrib")),
+                    args.values_of("to")
+                        .map(|v| v.filter_map(|e| e.parse().ok()).collect())
+                        .unwrap_or_default()),
+                "delete" => Self::Delete(
+                    args.value_of("ITEM").unwrap_or_default().into(),
+                    record(args),
+                    if args.is_present("cascade") { Some(OnDelete::Cascade) }
+                    else if args.is_present("orphan") { Some(OnDelete::Orphan) }
+                    else { None },
+                    args.is_present("yes")),
                 "search" => Self::Search(Search::from_arg_matches(args)),
-                "list" => Self::List,
-                _ => {
-                    let item = Item::from_arg_matches(args);
-                    println!("{}", item.table());
-                    // println!("{:#?}", item);
-                    return Self::New(item);
-                },
-            };
-            return cmd;
-        } else {
+                "list" => Self::List(
+                    args.value_of("RECORD").map(String::from),
+                    args.is_present("uncategorized")),
+                _ => Self::Help,
+            }
+        } else if matches.is_present("ITEM") {
             let item = Item::from_arg_matches(matches);
             if matches.is_present("NAME") {
                 let fact = Fact::from_arg_matches(matches);
-                return Self::AddFact(item, fact);
+                return Self::AddFact(item, fact, record(matches));
             }
-            Self::New(Item::from_arg_matches(matches))
+            Self::New(item, record(matches))
+        } else {
+            Self::Help
         }
     }
 }
@@ -247,15 +500,11 @@ impl FromArgMatches for ItemCmd {
 impl clap::Subcommand for ItemCmd {
     fn from_subcommand(sub: Option<(&str, &ArgMatches)>) -> Option<Self> {
         if let Some((sub, args)) = sub {
-            let cmd = match sub {
-                "new" => Self::New(Item::from_arg_matches(args)),
-                "add" => Self::AddFact(Item::default(), Fact::from_arg_matches(args)), //TODO handle diff
-                "delete" => Self::Delete(Item::from_arg_matches(args)),
-                "search" => Self::Search(Search::from_arg_matches(args)),
-                "list" => Self::List,
-                _ => Self::New(Item::from_arg_matches(args)),
-            };
-            Some(cmd)
+            if sub == "item" {
+                Some(Self::from_arg_matches(args))
+            } else {
+                None
+            }
         } else {
             None
         }
@@ -266,3 +515,194 @@ impl clap::Subcommand for ItemCmd {
         app
     }
 }
+
+/// Resolves an item path to the record it is in and its path within it
+fn locate(path: &str, record: Option<&str>) -> crate::DResult<(Record, String)> {
+    let mut segments = Item::split(path);
+    if let Some(record) = record {
+        let record = super::open_record(record)?;
+        if segments.len() > 1 && segments[0].eq_ignore_ascii_case(&record.name) {
+            segments.remove(0);
+        }
+        return Ok((record, segments.join("/")));
+    }
+    if segments.len() > 1 && RecordData::read()?.find(&segments[0]).is_some() {
+        let record = Record::open(&segments.remove(0))?;
+        return Ok((record, segments.join("/")));
+    }
+    Ok((Record::inbox()?, path.into()))
+}
+
+/// The item and fact the user meant to log to, asking about any item, fact
+/// or attribute name new to the record which is alike one already there
+fn meant(record: &Record, path: &str, fact: &Fact) -> crate::DResult<(String, Fact)> {
+    let items = record.items.iter().map(Item::path).collect::<Vec<String>>();
+    let path = prompt::meant("item", path, items.iter().map(String::as_str))?;
+    let fact = super::meant_fact(std::slice::from_ref(record), fact)?;
+    Ok((path, fact))
+}
+
+fn tree(record: Option<&str>) -> crate::DResult<()> {
+    let records = match record {
+        Some(record) => vec![super::open_record(record)?],
+        None => RecordData::read()?.records()
+            .map(|r| Record::open_path(&r.path))
+            .collect::<crate::DResult<Vec<Record>>>()?,
+    };
+    if output::structured() {
+        let items = records.iter()
+            .flat_map(|r| r.items.iter().map(move |i| (r, i, Vec::new())))
+            .collect::<Vec<(&Record, &Item, Vec<Fact>)>>();
+        return print_rows(&items);
+    }
+    for record in records.iter().filter(|r| record.is_some() || !r.items.is_empty()) {
+        println!("{}", record.item_tree()?);
+    }
+    Ok(())
+}
+
+fn get(path: &str, record: Option<&str>, fact: Option<&str>) -> crate::DResult<()> {
+    let (rec, path) = locate(path, record)?;
+    let item = rec.item(&path).ok_or(DError::NotFound)?;
+    let facts = match fact {
+        Some(fact) => item.get_fact(&rec, fact)?,
+        None => item.get_all_facts(&rec)?,
+    };
+    if let Some(out) = output::render(&facts)? {
+        println!("{}", out);
+        return Ok(());
+    }
+    println!("{}", item.table());
+    if facts.is_empty() {
+        println!("{}", format!("No entries logged to {} yet", item.path())
+            .color(Color::BrightYellow));
+    } else {
+        println!("{}", Fact::list_table(&facts));
+        println!("{}", FactStats::table(&FactStats::summarize(&facts)));
+    }
+    Ok(())
+}
+
+/// Deletes an item after confirmation. Items with entries or children need
+/// to be told whether to cascade or orphan them.
+fn delete(path: &str, record: Option<&str>, on_delete: Option<OnDelete>, yes: bool)
+    -> crate::DResult<String>
+{
+    let (mut rec, path) = locate(path, record)?;
+    let item = rec.item(&path).cloned().ok_or(DError::NotFound)?;
+    let facts = item.get_all_facts(&rec)?.len();
+    let children = rec.descendants(&item.path()).count();
+    let on_delete = match on_delete {
+        Some(on_delete) => on_delete,
+        None if facts == 0 && children == 0 => OnDelete::Cascade,
+        None => return Err(DError::Invalid(format!(
+            "{} has {} entries and {} child items. Use --cascade to delete them too, or --orphan to keep them",
+            item.path(), facts, children))),
+    };
+    let question = match on_delete {
+        OnDelete::Cascade => format!("Delete {} from {} with its {} entries and {} child items?",
+            item.path(), rec.name, facts, children),
+        OnDelete::Orphan => format!("Delete {} from {}, keeping its entries and child items?",
+            item.path(), rec.name),
+    };
+    let confirmed = yes || dialoguer::Confirm::new()
+        .with_prompt(question)
+        .default(false)
+        .interact()
+        .unwrap_or(false);
+    if !confirmed {
+        return Ok(format!("Kept item {}", item.path()));
+    }
+    let (facts, items) = rec.remove_item(&item.path(), on_delete == OnDelete::Cascade)?;
+    Ok(match on_delete {
+        OnDelete::Cascade => format!("Deleted {} ({} entries, {} items)",
+            item.path(), facts, items),
+        OnDelete::Orphan => format!("Deleted {}, moved {} entries to {} and {} items up",
+            item.path(), facts, rec.name, items),
+    })
+}
+
+fn records(record: Option<&str>, uncategorized: bool) -> crate::DResult<Vec<Record>> {
+    match (record, uncategorized) {
+        (Some(record), _) => Ok(vec![super::open_record(record)?]),
+        (None, true) => Ok(vec![Record::inbox()?]),
+        (None, false) => RecordData::read()?.records()
+            .map(|r| Record::open_path(&r.path))
+            .collect(),
+    }
+}
+
+fn list_table(rows: Vec<(&Record, &Item, Vec<Fact>)>) -> Table {
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec![
+            Cell::new("Item").add_attribute(Attribute::Bold)
+                .fg(TColor::Green),
+            Cell::new("Record").add_attribute(Attribute::Bold),
+            Cell::new("Entries").add_attribute(Attribute::Bold),
+            Cell::new("Attributes").add_attribute(Attribute::Bold),
+            Cell::new("Last Updated").add_attribute(Attribute::Bold),
+        ]);
+    for (rec, item, facts) in rows {
+        table.add_row(vec![
+            item.path(),
+            rec.name.clone(),
+            facts.len().to_string(),
+            Attrib::join(&item.attribs),
+            facts.last()
+                .map(|f| output::datetime(&f.created_at))
+                .unwrap_or_default(),
+        ]);
+    }
+    output::table("items", table)
+}
+
+fn list(record: Option<&str>, uncategorized: bool) -> crate::DResult<()> {
+    let records = records(record, uncategorized)?;
+    let mut rows = Vec::new();
+    for rec in &records {
+        for item in &rec.items {
+            rows.push((rec, item, item.get_all_facts(rec)?));
+        }
+    }
+    rows.sort_by_key(|(r, i, _)| (r.name.to_lowercase(), i.path().to_lowercase()));
+    if output::structured() {
+        return print_rows(&rows);
+    }
+    println!("{}", list_table(rows));
+    Ok(())
+}
+
+/// Prints listed items in the `--output` format, with their records
+fn print_rows(rows: &[(&Record, &Item, Vec<Fact>)]) -> crate::DResult<()> {
+    let items = rows.iter()
+        .map(|(r, i, _)| RecordItem { record: r.name.clone(), item: (*i).clone() })
+        .collect::<Vec<RecordItem>>();
+    if let Some(out) = output::render(&items)?.filter(|o| !o.is_empty()) {
+        println!("{}", out);
+    }
+    Ok(())
+}
+
+fn search_items(search: &Search) -> crate::DResult<()> {
+    let records = records(None, false)?;
+    let mut rows = Vec::new();
+    for rec in records.iter().filter(|r| search.in_record(&r.name)) {
+        for item in &rec.items {
+            if search.matches(&item.path()) && search.has_attribs(&item.attribs) {
+                rows.push((rec, item, item.get_all_facts(rec)?));
+            }
+        }
+    }
+    search.sort_limit(&mut rows, |(r, i, _)| (i.path().to_lowercase(), r.name.to_lowercase()));
+    if output::structured() {
+        return print_rows(&rows);
+    }
+    if rows.is_empty() {
+        println!("{}", "No items found".color(Color::BrightYellow));
+    } else {
+        println!("{}", list_table(rows));
+    }
+    Ok(())
+}
@@ -0,0 +1,184 @@
+use chrono::{Local, NaiveDate};
+use clap::{ArgMatches, FromArgMatches};
+use colored::{Color, Colorize};
+use crate::{
+    cmd::Cmd,
+    config::DConfig,
+    error::DError,
+    util,
+    output,
+    models::{journal::Journal, note::NoteDoc},
+};
+
+#[derive(Debug)]
+pub enum JournalCmd {
+    /// Writes in the journal of a day, in the editor unless a line to add
+    /// is given
+    Write(NaiveDate, Option<String>),
+    Show(NaiveDate),
+    List(Option<NaiveDate>, Option<NaiveDate>),
+    Help,
+}
+
+impl Default for JournalCmd {
+    fn default() -> Self {
+        Self::Write(Local::today().naive_local(), None)
+    }
+}
+
+impl Cmd for JournalCmd {
+
+    fn name() -> &'static str { "journal" }
+    fn about() -> &'static str { "Write in today's journal, or another day's" }
+    fn long_about() -> &'static str {
+        "Open a day's journal in your editor. Lines like 'sleep:: 7h' or 'mood:: good' are logged as facts to the journal record when saved, along with any #tags on the same line, and other #tags are kept as the journal's attributes. Saving again replaces the facts logged from the journal before"
+    }
+
+    fn args() -> Vec<clap::Arg<'static>> {
+        vec![
+            Self::date_arg(),
+            clap::Arg::new("message")
+                .about("Add a line to the journal instead of opening the editor")
+                .short('m')
+                .long("message")
+                .takes_value(true)
+                .validator(|a| crate::prompt::validate_text(a.into())),
+        ]
+    }
+
+    fn subcmds() -> Vec<clap::App<'static>> {
+        vec![
+            clap::App::new("show")
+                .about("Print a day's journal")
+                .arg(Self::date_arg()),
+            clap::App::new("list")
+                .about("List journals by date, newest first")
+                .long_flag("ls")
+                .short_flag('l')
+                .args(&[
+                    clap::Arg::new("from")
+                        .about("Only journals from this date on")
+                        .long("from")
+                        .short('f')
+                        .takes_value(true)
+                        .validator(|d| Journal::parse_date(d).map(|_| ())),
+                    clap::Arg::new("to")
+                        .about("Only journals up to this date")
+                        .long("to")
+                        .short('t')
+                        .takes_value(true)
+                        .validator(|d| Journal::parse_date(d).map(|_| ())),
+                ]),
+            Self::help_cmd(),
+        ]
+    }
+
+    fn run(&self) {
+        let rules = match DConfig::load() {
+            Ok(conf) => conf.tag_rules(),
+            Err(e) => {
+                eprintln!("{}", format!("Could not load config: {}", e)
+                    .color(Color::BrightRed));
+                return super::fail();
+            }
+        };
+        let res = match self {
+            Self::Write(date, line) => Journal::open(*date)
+                .and_then(|journal| write(journal, line.as_deref(), &rules)),
+            Self::Show(date) => Journal::open(*date)
+                .and_then(|j| match j.note.meta.version {
+                    0 => Err(DError::NotFound),
+                    _ => Ok(j.note.to_string()),
+                }),
+            Self::List(from, to) => Journal::all(*from, *to)
+                .and_then(|journals| Ok(match journals.is_empty() {
+                    _ if output::structured() => output::render(&journals)?.unwrap_or_default(),
+                    true => "No journals yet".color(Color::BrightYellow).to_string(),
+                    false => Journal::table(&journals, &rules).to_string(),
+                })),
+            Self::Help => {
+                Self::print_help();
+                Ok(String::new())
+            },
+        };
+        match res {
+            Ok(msg) if msg.is_empty() => {},
+            Ok(msg) => println!("{}", msg),
+            Err(e) => {
+                eprintln!("{}", format!("Could not run journal cmd: {}", e)
+                    .color(Color::BrightRed));
+                super::fail();
+            }
+        }
+    }
+
+    fn print_help() {
+        let help = "
+            JOURNAL: Write in today's journal, with facts\n
+                     inline like sleep:: 7h or mood:: good\n
+        ".to_string().color(Color::BrightBlue);
+        println!("> {}", help)
+    }
+
+    fn help_cmd() -> clap::App<'static> {
+        clap::App::new("journal_help")
+            .about("Prints help command for journal")
+            .long_flag("help")
+            .short_flag('h')
+            .long_about("Prints the help information")
+    }
+}
+
+impl JournalCmd {
+
+    fn date_arg() -> clap::Arg<'static> {
+        clap::Arg::new("DATE")
+            .about("The day of the journal, today if not given")
+            .long_about("The day of the journal, as a date like 2021-01-31 or in words like 'yesterday' or 'last friday'. Today if not given")
+            .required(false)
+            .validator(|d| Journal::parse_date(d).map(|_| ()))
+            .index(1)
+    }
+}
+
+/// Saves a new version of the journal with the line added, or with what is
+/// saved in the editor
+fn write(mut journal: Journal, line: Option<&str>, rules: &[crate::config::TagRule])
+    -> crate::DResult<String>
+{
+    let body = match line {
+        Some(line) if journal.note.body.is_empty() => line.trim().to_string(),
+        Some(line) => format!("{}\n{}", journal.note.body, line.trim()),
+        None => {
+            let text = util::edit(&DConfig::load()?.editor(),
+                &journal.note.to_markdown()?, "md")?;
+            NoteDoc::from_markdown(&text)?.body
+        },
+    };
+    if body.is_empty() {
+        return Err(DError::Invalid("The journal is empty, so it was not saved".into()));
+    }
+    if journal.note.meta.version > 0 && body == journal.note.body {
+        return Err(DError::Invalid("The journal is unchanged, so it was not saved".into()));
+    }
+    journal.note.body = body;
+    let facts = journal.save(rules)?;
+    Ok(format!("Saved the journal for {} with {} facts", journal.date, facts)
+        .color(Color::BrightGreen).to_string())
+}
+
+impl FromArgMatches for JournalCmd {
+    fn from_arg_matches(matches: &ArgMatches) -> Self {
+        let date = |m: &ArgMatches, arg: &str| m.value_of(arg)
+            .and_then(|d| Journal::parse_date(d).ok());
+        match matches.subcommand() {
+            Some(("show", sub)) => Self::Show(date(sub, "DATE")
+                .unwrap_or_else(|| Local::today().naive_local())),
+            Some(("list", sub)) => Self::List(date(sub, "from"), date(sub, "to")),
+            Some(("journal_help", _)) => Self::Help,
+            _ => Self::Write(date(matches, "DATE")
+                    .unwrap_or_else(|| Local::today().naive_local()),
+                matches.value_of("message").map(String::from)),
+        }
+    }
+}
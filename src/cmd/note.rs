@@ -0,0 +1,230 @@
+use clap::{ArgMatches, FromArgMatches};
+use colored::{Color, Colorize};
+use crate::{
+    cmd::Cmd,
+    config::DConfig,
+    error::DError,
+    output,
+    util,
+    models::{
+        Attrib,
+        entity::EntityRef,
+        note::NoteDoc,
+    },
+};
+
+#[derive(Debug, Default)]
+pub enum NoteCmd {
+    /// A new note for an entity, written in the editor unless a message is given
+    New(EntityRef, Vec<Attrib>, Option<String>),
+    /// A new version of an existing note
+    Edit(String, Option<String>),
+    Show(String, Option<usize>),
+    List(Option<EntityRef>),
+    Search(String),
+    #[default]
+    Help,
+}
+
+impl Cmd for NoteCmd {
+
+    fn name() -> &'static str { "note" }
+    fn about() -> &'static str { "Write Markdown notes about a record, item or fact entry" }
+    fn long_about() -> &'static str {
+        "Write a note about a record, item or fact entry in your editor (default_editor in the config, otherwise $VISUAL or $EDITOR). Notes are Markdown with +++ TOML front-matter holding the entity, timestamps and attributes, and every edit is kept as a new version"
+    }
+
+    fn args() -> Vec<clap::Arg<'static>> {
+        vec![
+            Self::entity_arg()
+                .required(false)
+                .index(1),
+            Self::message_arg(),
+            clap::Arg::new("attrib")
+                .about("Give the note an attribute")
+                .short('a')
+                .long("attrib")
+                .takes_value(true)
+                .multiple(true)
+                .requires("ENTITY")
+                .validator(|a| crate::prompt::validate_input(a.into())),
+        ]
+    }
+
+    fn subcmds() -> Vec<clap::App<'static>> {
+        vec![
+            clap::App::new("edit")
+                .about("Edit a note, saving it as a new version")
+                .args(&[
+                    Self::id_arg(),
+                    Self::message_arg(),
+                ]),
+            clap::App::new("show")
+                .about("Print a note, or one of its earlier versions")
+                .args(&[
+                    Self::id_arg(),
+                    clap::Arg::new("version")
+                        .about("The version to show, latest if not given")
+                        .short('V')
+                        .long("version")
+                        .takes_value(true)
+                        .validator(|v| v.parse::<usize>().map(|_| ())),
+                ]),
+            clap::App::new("list")
+                .about("List notes, or the notes of an entity")
+                .long_flag("ls")
+                .short_flag('l')
+                .arg(Self::entity_arg().required(false).index(1)),
+            clap::App::new("search")
+                .about("Search the text of all notes")
+                .long_about("Find notes containing every word of the query in their text or attributes, ignoring case")
+                .arg(clap::Arg::new("QUERY")
+                    .about("Words to search for")
+                    .required(true)
+                    .multiple(true)
+                    .index(1)),
+            Self::help_cmd(),
+        ]
+    }
+
+    fn run(&self) {
+        let res = match self {
+            Self::New(entity, attribs, message) => entity.check()
+                .and_then(|_| write(NoteDoc::new(entity.clone(), attribs), message.as_deref()))
+                .map(|note| format!("Saved note {} on {}",
+                    &note.meta.id.to_string()[..8], note.meta.entity)),
+            Self::Edit(id, message) => NoteDoc::find(id)
+                .and_then(|note| write(note, message.as_deref()))
+                .map(|note| format!("Saved version {} of note {}",
+                    note.meta.version, &note.meta.id.to_string()[..8])),
+            Self::Show(id, version) => NoteDoc::find(id)
+                .and_then(|note| match version {
+                    Some(v) => NoteDoc::load(&note.meta.id, Some(*v)),
+                    None => Ok(note),
+                })
+                .map(|note| note.to_string()),
+            Self::List(entity) => match entity {
+                Some(entity) => NoteDoc::of(entity),
+                None => NoteDoc::all(),
+            }.and_then(|notes| Ok(match notes.is_empty() {
+                _ if output::structured() => output::render(&notes)?.unwrap_or_default(),
+                true => "No notes yet".color(Color::BrightYellow).to_string(),
+                false => NoteDoc::table(&notes.into_iter()
+                        .map(|n| { let title = n.title().to_string(); (n, title) })
+                        .collect::<Vec<(NoteDoc, String)>>())
+                    .to_string(),
+            })),
+            Self::Search(query) => NoteDoc::search(query)
+                .and_then(|found| Ok(match found.is_empty() {
+                    _ if output::structured() => output::render(&found.into_iter()
+                        .map(|(note, _)| note)
+                        .collect::<Vec<NoteDoc>>())?.unwrap_or_default(),
+                    true => format!("No notes matching {}", query)
+                        .color(Color::BrightYellow).to_string(),
+                    false => NoteDoc::table(&found).to_string(),
+                })),
+            Self::Help => {
+                Self::print_help();
+                Ok(String::new())
+            },
+        };
+        match res {
+            Ok(msg) if msg.is_empty() => {},
+            Ok(msg) => println!("{}", msg),
+            Err(e) => {
+                eprintln!("{}", format!("Could not run note cmd: {}", e)
+                    .color(Color::BrightRed));
+                super::fail();
+            }
+        }
+    }
+
+    fn print_help() {
+        let help = "
+            NOTE: Write a Markdown note about a record, item\n
+                  or fact entry, e.g. dlog note item:dinner\n
+        ".to_string().color(Color::BrightBlue);
+        println!("> {}", help)
+    }
+
+    fn help_cmd() -> clap::App<'static> {
+        clap::App::new("note_help")
+            .about("Prints help command for note")
+            .long_flag("help")
+            .short_flag('h')
+            .long_about("Prints the help information")
+    }
+}
+
+impl NoteCmd {
+
+    fn entity_arg() -> clap::Arg<'static> {
+        clap::Arg::new("ENTITY")
+            .about("The record, item or fact entry, e.g. record:health, item:dinner or fact:<id>")
+            .long_about("The entity the note is about, given as record:<name>, item:<name> or fact:<id>. A bare name is taken as a record")
+            .validator(|e| e.parse::<EntityRef>().map(|_| ()))
+    }
+
+    fn id_arg() -> clap::Arg<'static> {
+        clap::Arg::new("ID")
+            .about("The note's id, or the start of it as shown by 'dlog note list'")
+            .required(true)
+            .index(1)
+    }
+
+    fn message_arg() -> clap::Arg<'static> {
+        clap::Arg::new("message")
+            .about("Use the given text as the note instead of opening the editor")
+            .short('m')
+            .long("message")
+            .takes_value(true)
+            .validator(|a| crate::prompt::validate_text(a.into()))
+    }
+}
+
+/// Saves the next version of a note from the message, or from what is saved
+/// in the editor. Only the attributes in the front-matter may be changed.
+fn write(mut note: NoteDoc, message: Option<&str>) -> crate::DResult<NoteDoc> {
+    let (attributes, body) = match message {
+        Some(msg) => (note.meta.attributes.clone(), msg.trim().to_string()),
+        None => {
+            let text = util::edit(&DConfig::load()?.editor(), &note.to_markdown()?, "md")?;
+            let edited = NoteDoc::from_markdown(&text)?;
+            (edited.meta.attributes, edited.body)
+        },
+    };
+    if body.is_empty() {
+        return Err(DError::Invalid("The note is empty, so it was not saved".into()));
+    }
+    if note.meta.version > 0 && body == note.body && attributes == note.meta.attributes {
+        return Err(DError::Invalid("The note is unchanged, so no new version was saved".into()));
+    }
+    note.meta.attributes = attributes;
+    note.body = body;
+    note.save()?;
+    Ok(note)
+}
+
+impl FromArgMatches for NoteCmd {
+    fn from_arg_matches(matches: &ArgMatches) -> Self {
+        let entity = |m: &ArgMatches| m.value_of("ENTITY").and_then(|e| e.parse().ok());
+        let message = |m: &ArgMatches| m.value_of("message").map(String::from);
+        match matches.subcommand() {
+            Some(("edit", sub)) => Self::Edit(
+                sub.value_of("ID").unwrap_or_default().into(), message(sub)),
+            Some(("show", sub)) => Self::Show(
+                sub.value_of("ID").unwrap_or_default().into(),
+                sub.value_of("version").and_then(|v| v.parse().ok())),
+            Some(("list", sub)) => Self::List(entity(sub)),
+            Some(("search", sub)) => Self::Search(sub.values_of("QUERY")
+                .map(|q| q.collect::<Vec<&str>>().join(" "))
+                .unwrap_or_default()),
+            Some(("note_help", _)) => Self::Help,
+            _ => match entity(matches) {
+                Some(e) => Self::New(e, Attrib::from_match(matches.values_of("attrib")),
+                    message(matches)),
+                None => Self::List(None),
+            },
+        }
+    }
+}
@@ -1,28 +1,59 @@
+use std::path::PathBuf;
 use crate::{
     models::{
-        Entry, record::Record
+        Entry, Template, Attrib, record::Record, data::RecordData,
+        entity::EntityRef,
+        attachment::{Attachment, AttachMode},
     }, cmd::Cmd,
+    config::DConfig,
+    prompt::prompt,
+    output,
+    args::search::Search,
 };
 use clap::{ArgMatches, FromArgMatches, Subcommand};
 use colored::{Color, Colorize, Style, Styles};
 
 #[derive(Debug)]
 pub enum RecordCmd {
-    New(Option<Record>),
-    List
+    New(Record, InitArgs),
+    SaveTemplate(String, String),
+    ListTemplates,
+    List,
+    /// A record's overview, collapsed to a depth and showing some number of
+    /// recent entries under each item
+    Get(String, usize, usize),
+    Search(Search),
+    Link(String, Vec<Attrib>, Vec<EntityRef>),
+    /// Moves a record to the trash, without asking with `yes`
+    Delete(String, bool),
+    Help,
+}
+
+/// How and where to lay out a new record on disk
+#[derive(Debug, Default)]
+pub struct InitArgs {
+    /// Directory given with --path, otherwise decided at init
+    pub path: Option<PathBuf>,
+    /// Initialize directly in the current folder (`dlog init`) instead of
+    /// under the configured init dir
+    pub here: bool,
+    pub template: Option<String>,
+    pub gitignore: bool,
+    /// File to attach to the record as a symlink once created
+    pub file: Option<PathBuf>,
 }
 
 impl Default for RecordCmd {
     fn default() -> Self {
-        RecordCmd::New(None)
+        RecordCmd::Help
     }
 }
 
 impl Cmd for RecordCmd {
 
-    fn name() -> &'static str { "user" }
-    fn about() -> &'static str { "The user cmd" }
-    fn long_about() -> &'static str { "The user cmd" }
+    fn name() -> &'static str { "record" }
+    fn about() -> &'static str { "The record cmd" }
+    fn long_about() -> &'static str { "The record cmd" }
 
     fn args() -> Vec<clap::Arg<'static>> {
         vec![
@@ -54,14 +85,14 @@ impl Cmd for RecordCmd {
                 .multiple(true)
                 .required(false),
             clap::Arg::new("link-fact")
-                .about("Whether to persist the item-fact link specified")
-                .long_about("Link an item to this fact (not just this fact entry)")
-                .long("link-item")
-                .aliases(&["save-item",  "item-link"])
-                .short('I')
-                .short_alias('i')
+                .about("Link (add) a fact type to this record")
+                .long_about("Add a fact type to this record, so that all of its entries are shown with the record")
+                .long("link-fact")
+                .aliases(&["save-fact",  "fact-link"])
+                .short('F')
+                .short_alias('f')
                 .requires("NAME")
-                .overrides_with("item") //TODO test this
+                .overrides_with("fact") //TODO test this
                 .multiple(true)
                 .required(false),
         ]
@@ -70,27 +101,94 @@ impl Cmd for RecordCmd {
     fn subcmds() -> Vec<clap::App<'static>> {
         vec![
             Self::new_cmd(),
+            Self::template_cmd(),
             Self::search_cmd(),
             Self::help_cmd(),
             clap::App::new("list")
                 .about("List all records")
                 .long_flag("ls")
                 .short_flag('l'),
-            clap::App::new("get")
-                .about("Get info about a specific record")
-                .long_flag("get")
-                .short_flag('g'),
-            clap::App::new("link")
-                .about("Link two records together, or with a item/fact")
-                .long_flag("link")
-                .short_flag('k')
+            Self::get_cmd(),
+            Self::link_cmd(),
+            Self::delete_cmd(),
         ]
     }
 
 
     fn run(&self) {
-        println!("{}", format!("Running record cmd...")
-            .color(Color::BrightGreen))
+        match self {
+            Self::New(record, args) => match args.init(record) {
+                Ok(rec) => println!("{}", format!("Initialized record {} in {}",
+                        rec.name, rec.dir.display())
+                    .color(Color::BrightGreen)),
+                Err(e) => {
+                    eprintln!("{}", format!("Could not initialize record: {}", e)
+                        .color(Color::BrightRed));
+                    super::fail();
+                }
+            },
+            Self::SaveTemplate(record, name) => match save_template(record, name) {
+                Ok(path) => println!("{}", format!("Saved template {} to {}",
+                        name, path.display())
+                    .color(Color::BrightGreen)),
+                Err(e) => {
+                    eprintln!("{}", format!("Could not save template: {}", e)
+                        .color(Color::BrightRed));
+                    super::fail();
+                }
+            },
+            Self::ListTemplates => match list_templates() {
+                Ok(table) => println!("{}", table),
+                Err(e) => eprintln!("{}", format!("Could not list templates: {}", e)
+                    .color(Color::BrightRed)),
+            },
+            Self::List => match list(None) {
+                Ok(table) => println!("{}", table),
+                Err(e) => {
+                    eprintln!("{}", format!("Could not list records: {}", e)
+                        .color(Color::BrightRed));
+                    super::fail();
+                }
+            },
+            Self::Search(search) => match list(Some(search)) {
+                Ok(table) => println!("{}", table),
+                Err(e) => {
+                    eprintln!("{}", format!("Could not search records: {}", e)
+                        .color(Color::BrightRed));
+                    super::fail();
+                }
+            },
+            Self::Get(name, depth, recent) => match get(name, *depth, *recent) {
+                Ok(table) => println!("{}", table),
+                Err(e) => {
+                    eprintln!("{}", format!("Could not get record {}: {}", name, e)
+                        .color(Color::BrightRed));
+                    super::fail();
+                }
+            },
+            Self::Link(name, attribs, links) => match link(name, attribs, links) {
+                Ok(()) => println!("{}", format!("Linked {} to {}", name,
+                        attribs.iter().map(|a| a.name.clone())
+                            .chain(links.iter().map(|l| l.to_string()))
+                            .collect::<Vec<String>>()
+                            .join(", "))
+                    .color(Color::BrightGreen)),
+                Err(e) => {
+                    eprintln!("{}", format!("Could not link record {}: {}", name, e)
+                        .color(Color::BrightRed));
+                    super::fail();
+                }
+            },
+            Self::Delete(name, yes) => match delete(name, *yes) {
+                Ok(msg) => println!("{}", msg),
+                Err(e) => {
+                    eprintln!("{}", format!("Could not delete record {}: {}", name, e)
+                        .color(Color::BrightRed));
+                    super::fail();
+                }
+            },
+            Self::Help => Self::print_help(),
+        }
     }
 
     fn print_help() {
@@ -113,23 +211,40 @@ impl Cmd for RecordCmd {
 
 impl FromArgMatches for RecordCmd {
     fn from_arg_matches(matches: &ArgMatches) -> Self {
-        match matches.value_of("NAME") {
-            Some(name) => {
-                println!("Got new record: {}", &name);
-                let r = Record::from_arg_matches(matches);
-                println!("{}", r.fact_entry_table());
-                println!("{}", r.fact_types_table());
-                let s = Self::default();
-                s
+        match matches.subcommand() {
+            Some(("new", sub)) => {
+                let mut record = Record::from_arg_matches(sub);
+                if !sub.is_present("NAME") {
+                    record.name = prompt("Record name?: ")
+                        .expect("Could not read record name");
+                }
+                return Self::New(record, InitArgs::from_arg_matches(sub));
             },
-            None => {
-                let r = Record::from_arg_matches(matches);
-                println!("{}", r.fact_entry_table());
-                println!("{}", r.fact_types_table());
-                let s = Self::default();
-                println!("Received no fact name, provide: to inbox");
-                Self::default()
-            }
+            Some(("template", sub)) => match sub.subcommand() {
+                Some(("save", args)) => return Self::SaveTemplate(
+                    args.value_of("RECORD").unwrap_or_default().into(),
+                    args.value_of("NAME").unwrap_or_default().into(),
+                ),
+                _ => return Self::ListTemplates,
+            },
+            Some(("list", _)) => return Self::List,
+            Some(("search", sub)) => return Self::Search(Search::from_arg_matches(sub)),
+            Some(("get", sub)) => return Self::get_from(sub),
+            Some(("link", sub)) => return Self::Link(
+                sub.value_of("NAME").unwrap_or_default().into(),
+                Attrib::from_match(sub.values_of("attrib")),
+                sub.values_of("to")
+                    .map(|v| v.filter_map(|e| e.parse().ok()).collect())
+                    .unwrap_or_default()),
+            Some(("delete", sub)) => return Self::Delete(
+                sub.value_of("NAME").unwrap_or_default().into(),
+                sub.is_present("yes")),
+            _ => {},
+        }
+        if matches.is_present("NAME") {
+            Self::get_from(matches)
+        } else {
+            Self::default()
         }
     }
 }
@@ -155,31 +270,79 @@ impl Subcommand for RecordCmd {
 }
 
 impl RecordCmd {
+
+    /// Parses the top level `dlog init`, which lays out a record in the
+    /// current folder, named after the folder unless a name is given
+    pub fn init_from(matches: &ArgMatches) -> Self {
+        let mut args = InitArgs::from_arg_matches(matches);
+        args.here = true;
+        let mut record = Record::from_arg_matches(matches);
+        if !matches.is_present("NAME") {
+            let dir = args.path.clone()
+                .or_else(|| std::env::current_dir().ok())
+                .unwrap_or_default();
+            record.name = dir.file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| "dlog".into());
+        }
+        Self::New(record, args)
+    }
+
+    /// Args shared by `dlog init` and `dlog record new`
+    pub fn init_args() -> Vec<clap::Arg<'static>> {
+        vec![
+            clap::Arg::new("path")
+                .about("The path to optionally put this record. ")
+                .long_about("The path to initiailize this record. By default, it will be initialized under the directory set by record.init_behavior in your config (<your data directory>/dlog/ unless changed)")
+                .short('p')
+                .long("path")
+                .value_hint(clap::ValueHint::DirPath)
+                .takes_value(true)
+                .required(false)
+                .value_name("PATH"),
+            clap::Arg::new("template")
+                .about("Pre-seed the record from a template")
+                .long_about("Copy the fact types, units, attributes and actions of a saved or built-in template (health, finance, reading, workouts) into the new record. An existing record, record directory or template TOML file may also be given")
+                .short('t')
+                .long("template")
+                .takes_value(true)
+                .required(false)
+                .value_name("TEMPLATE"),
+            clap::Arg::new("gitignore")
+                .about("Also write a .gitignore for the record directory")
+                .short('g')
+                .long("gitignore")
+                .takes_value(false),
+            clap::Arg::new("description")
+                .about("A short description of the record")
+                .short('d')
+                .long("description")
+                .takes_value(true)
+                .required(false)
+                .value_name("DESCRIPTION"),
+            clap::Arg::new("add-file")
+                .about("Adds a file to this record")
+                .long_about("Specify a filepath to add to this record (as a symlink). See 'dlog attach' to copy files in instead")
+                .short('f')
+                .takes_value(true)
+                .value_hint(clap::ValueHint::FilePath)
+                .required(false)
+                .long("file")
+                .value_name("FILE"),
+            clap::Arg::new("NAME")
+                .about("The name of the record to be added")
+                .validator(|a| crate::prompt::validate_input(a.into()))
+        ]
+    }
+
     fn new_cmd() -> clap::App<'static> {
         clap::App::new("new")
             .about("Create a new record")
             .long_flag("new")
             .short_flag('n')
             .aliases(&["create", "init"])
+            .args(Self::init_args())
             .args(&[
-                clap::Arg::new("path")
-                    .about("The path to optionally put this record. ")
-                    .long_about("The path to initiailize this record. By default, it will be initialized under <your data directory/dlog/")
-                    .short('p')
-                    .long("path")
-                    .value_hint(clap::ValueHint::DirPath)
-                    .takes_value(true)
-                    .required(false)
-                    .value_name("PATH"),
-                clap::Arg::new("add-file")
-                    .about("Adds a file to this record")
-                    .long_about("Specify a filepath to add to this record (as a symlink)")
-                    .short('f')
-                    .takes_value(true)
-                    .value_hint(clap::ValueHint::FilePath)
-                    .required(false)
-                    .long("file")
-                    .value_name("FILE"),
                 clap::Arg::new("record")
                     .about("Specifies the record to add this new item to; inbox if none")
                     .aliases(&["r", "rec"])
@@ -196,9 +359,122 @@ impl RecordCmd {
                     .validator(|a| crate::prompt::validate_input(a.into()))
                     .required(false)
                     .multiple(true),
+            ])
+    }
+
+    fn template_cmd() -> clap::App<'static> {
+        clap::App::new("template")
+            .about("Save or list record templates")
+            .long_about("Templates are reusable bundles of fact types, units, attributes and actions which new records can be created from with 'record new <NAME> --template <TEMPLATE>'. Health, finance, reading and workouts templates are built in")
+            .alias("templates")
+            .subcommands(vec![
+                clap::App::new("save")
+                    .about("Save the fact types, units, attributes and actions of a record as a template")
+                    .args(&[
+                        clap::Arg::new("RECORD")
+                            .about("The record to save as a template")
+                            .required(true)
+                            .index(1),
+                        clap::Arg::new("NAME")
+                            .about("The name of the new template")
+                            .required(true)
+                            .validator(|a| crate::prompt::validate_input(a.into()))
+                            .index(2),
+                    ]),
+                clap::App::new("list")
+                    .about("List built-in and saved templates")
+                    .short_flag('l')
+                    .long_flag("ls"),
+            ])
+    }
+
+    fn get_from(matches: &ArgMatches) -> Self {
+        Self::Get(
+            matches.value_of("NAME").unwrap_or_default().into(),
+            matches.value_of("depth")
+                .and_then(|d| d.parse().ok())
+                .unwrap_or(usize::MAX),
+            matches.value_of("recent")
+                .and_then(|r| r.parse().ok())
+                .unwrap_or(3),
+        )
+    }
+
+    fn get_cmd() -> clap::App<'static> {
+        clap::App::new("get")
+            .about("Get info about a specific record")
+            .long_about("Show a record with its items nested beneath it and the most recent entries beneath each item")
+            .long_flag("get")
+            .short_flag('g')
+            .args(&[
                 clap::Arg::new("NAME")
-                    .about("The name of the record to be added")
+                    .about("The record to show")
+                    .required(true)
                     .validator(|a| crate::prompt::validate_input(a.into()))
+                    .index(1),
+                clap::Arg::new("depth")
+                    .about("How many levels of items to open")
+                    .long_about("Collapse the display to a depth: 0 shows only the record, 1 adds its own entries and top level items, and each level after that opens one more level of items")
+                    .long("depth")
+                    .short('d')
+                    .takes_value(true)
+                    .validator(|d| d.parse::<usize>().map(|_| ())),
+                clap::Arg::new("recent")
+                    .about("How many recent entries to show under each item")
+                    .long("recent")
+                    .short('n')
+                    .default_value("3")
+                    .takes_value(true)
+                    .validator(|d| d.parse::<usize>().map(|_| ())),
+            ])
+    }
+
+    fn link_cmd() -> clap::App<'static> {
+        clap::App::new("link")
+            .about("Link two records together, or with a item/fact")
+            .long_about("Link attributes, items, other records or facts to a record. Links are saved in the record's record.toml")
+            .long_flag("link")
+            .short_flag('k')
+            .args(&[
+                clap::Arg::new("NAME")
+                    .about("The record to link")
+                    .required(true)
+                    .validator(|a| crate::prompt::validate_input(a.into()))
+                    .index(1),
+                clap::Arg::new("attrib")
+                    .about("Attributes to link to the record")
+                    .long("attrib")
+                    .short('a')
+                    .multiple(true)
+                    .takes_value(true),
+                clap::Arg::new("to")
+                    .about("Records, items or facts to link to, e.g. record:finance or item:diet")
+                    .long("to")
+                    .short('t')
+                    .multiple(true)
+                    .takes_value(true)
+                    .validator(|e| e.parse::<EntityRef>().map(|_| ())),
+            ])
+    }
+
+    fn delete_cmd() -> clap::App<'static> {
+        clap::App::new("delete")
+            .about("Delete a record with everything in it")
+            .long_about("Move a record, with its items, entries and files, to the trash, from where 'dlog trash restore' puts it back. The inbox can't be deleted")
+            .long_flag("delete")
+            .short_flag('d')
+            .alias("rm")
+            .args(&[
+                clap::Arg::new("NAME")
+                    .about("The record to delete")
+                    .required(true)
+                    .validator(|a| crate::prompt::validate_input(a.into()))
+                    .index(1),
+                clap::Arg::new("yes")
+                    .about("Don't ask for confirmation")
+                    .long("yes")
+                    .short('y')
+                    .takes_value(false),
             ])
     }
 
@@ -208,10 +484,15 @@ impl RecordCmd {
             .long_flag("search")
             .short_flag('s')
             .args(&[
+                clap::Arg::new("QUERY")
+                    .about("Text the record's name or description should contain")
+                    .required(false)
+                    .index(1),
                 clap::Arg::new("attrib")
                     .about("Filter by attribute")
                     .short('a')
                     .long("attrib")
+                    .multiple(true)
                     .required(false),
                 clap::Arg::new("ascending")
                     .about("Sort values ascending")
@@ -222,15 +503,131 @@ impl RecordCmd {
                     .about("Sort values ascending")
                     .long("descending")
                     .alias("desc")
+                    .overrides_with("ascending")
                     .takes_value(false),
                 clap::Arg::new("max-entries")
                     .about("Maximum number of entries to display")
                     .long("max")
                     .short('m')
                     .default_value("50")
-                    .takes_value(false)
+                    .takes_value(true)
                     .required(false)
             ])
     }
 }
 
+
+impl InitArgs {
+
+    /// Creates the record on disk, returning it as laid out
+    pub fn init(&self, record: &Record) -> crate::DResult<Record> {
+        let dir = match (&self.path, self.here) {
+            (Some(path), _) => std::env::current_dir()?.join(path),
+            (None, true) => std::env::current_dir()?,
+            (None, false) => DConfig::load()?.init_behavior().dir()?
+                .join(&record.name),
+        };
+        let mut record = Record {
+            dir,
+            name: record.name.clone(),
+            description: record.description.clone(),
+            ..Record::new(Some(record.name.clone()), None)
+        };
+        if let Some(template) = &self.template {
+            Template::load(template)?.apply(&mut record);
+        }
+        record.init(self.gitignore)?;
+        if let Some(file) = &self.file {
+            Attachment::attach(EntityRef::Record(record.name.clone()),
+                file, AttachMode::Symlink)?;
+        }
+        Ok(record)
+    }
+}
+
+/// Moves a record to the trash after confirmation
+fn delete(name: &str, yes: bool) -> crate::DResult<String> {
+    let record = super::open_record(name)?;
+    let question = format!("Delete {} with its {} items and {} entries?",
+        record.name, record.items.len(), record.facts()?.len());
+    let confirmed = yes || dialoguer::Confirm::new()
+        .with_prompt(question)
+        .default(false)
+        .interact()
+        .unwrap_or(false);
+    if !confirmed {
+        return Ok(format!("Kept record {}", record.name));
+    }
+    let trashed = record.delete()?;
+    Ok(format!("Moved {} ({} entries) to the trash", trashed.name, trashed.entries)
+        .color(Color::BrightGreen).to_string())
+}
+
+fn save_template(record: &str, name: &str) -> crate::DResult<std::path::PathBuf> {
+    Template::from_record(&super::open_record(record)?, name).save()
+}
+
+impl FromArgMatches for InitArgs {
+    fn from_arg_matches(matches: &ArgMatches) -> Self {
+        Self {
+            path: matches.value_of("path").map(PathBuf::from),
+            here: false,
+            template: matches.value_of("template").map(String::from),
+            gitignore: matches.is_present("gitignore"),
+            file: matches.value_of("add-file").map(PathBuf::from),
+        }
+    }
+}
+
+/// All registered records, or those matching a search, sorted by name
+fn list(search: Option<&Search>) -> crate::DResult<String> {
+    let mut records = RecordData::read()?.records()
+        .map(|r| Record::open_path(&r.path))
+        .collect::<crate::DResult<Vec<Record>>>()?;
+    match search {
+        Some(search) => {
+            records.retain(|r| (search.matches(&r.name) || search.matches(&r.description))
+                && search.has_attribs(&r.attribs));
+            search.sort_limit(&mut records, |r| r.name.to_lowercase());
+        },
+        None => records.sort_by_key(|r| r.name.to_lowercase()),
+    }
+    match output::render(&records)? {
+        Some(out) => Ok(out),
+        None => Ok(Record::record_table(&records)?.to_string()),
+    }
+}
+
+/// A record as itself, or its overview as a table
+fn get(name: &str, depth: usize, recent: usize) -> crate::DResult<String> {
+    let record = super::open_record(name)?;
+    match output::render_one(&record)? {
+        Some(out) => Ok(out),
+        None => Ok(record.overview(depth, recent)?.to_string()),
+    }
+}
+
+fn list_templates() -> crate::DResult<String> {
+    if output::structured() {
+        let templates = Template::list()?.into_iter()
+            .map(|(name, _)| Template::load(&name))
+            .collect::<crate::DResult<Vec<Template>>>()?;
+        return Ok(output::render(&templates)?.unwrap_or_default());
+    }
+    Ok(Template::list_table()?.to_string())
+}
+
+fn link(name: &str, attribs: &[Attrib], links: &[EntityRef]) -> crate::DResult<()> {
+    let mut record = super::open_record(name)?;
+    for attrib in attribs {
+        if !record.attribs.contains(attrib) {
+            record.attribs.push(attrib.clone());
+        }
+    }
+    for link in links {
+        if !record.links.contains(link) {
+            record.links.push(link.clone());
+        }
+    }
+    record.write()
+}
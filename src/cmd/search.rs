@@ -0,0 +1,187 @@
+use clap::{ArgMatches, FromArgMatches};
+use colored::{Color, Colorize};
+use crate::{
+    output,
+    cmd::Cmd,
+    error::DError,
+    search::{self, Hit, Index, Query, QueryFilter},
+};
+
+#[derive(Debug, Default)]
+pub struct SearchCmd {
+    pub query: Query,
+    pub max: usize,
+    pub reindex: bool,
+    pub help: bool,
+}
+
+impl Cmd for SearchCmd {
+
+    fn name() -> &'static str { "search" }
+    fn about() -> &'static str { "Search your facts, items, records and notes" }
+    fn long_about() -> &'static str {
+        "Search the names, values, units, notes and attributes of your fact entries, items and records, and the text of your notes, best matches first. The index is built the first time you search, and kept up to date by every command after"
+    }
+
+    fn args() -> Vec<clap::Arg<'static>> {
+        vec![
+            clap::Arg::new("QUERY")
+                .about("The words to search for")
+                .multiple(true)
+                .index(1),
+            clap::Arg::new("kind")
+                .about("Only search facts, items, records or notes")
+                .long("kind")
+                .short('k')
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .possible_values(&["fact", "item", "record", "note"]),
+            clap::Arg::new("record")
+                .about("Only search in the given record")
+                .long("record")
+                .short('r')
+                .takes_value(true),
+            clap::Arg::new("max-results")
+                .about("How many results to show")
+                .long("max")
+                .short('m')
+                .takes_value(true)
+                .default_value("20")
+                .validator(count),
+            clap::Arg::new("reindex")
+                .about("Build the index again from all of your data")
+                .long("reindex")
+                .takes_value(false),
+        ]
+    }
+
+    fn subcmds() -> Vec<clap::App<'static>> {
+        vec![Self::help_cmd()]
+    }
+
+    fn run(&self) {
+        let res = match self.help {
+            true => {
+                Self::print_help();
+                Ok(String::new())
+            },
+            false => self.hits(),
+        };
+        match res {
+            Ok(msg) if msg.is_empty() => {},
+            Ok(msg) => println!("{}", msg),
+            Err(e) => {
+                eprintln!("{}", format!("Could not run search cmd: {}", e)
+                    .color(Color::BrightRed));
+                super::fail();
+            }
+        }
+    }
+
+    fn print_help() {
+        let help = "
+            SEARCH: Find anything you logged, e.g.\n
+                  dlog search headache\n
+                  dlog search sleep quality -k fact -r health\n
+                  dlog search --reindex\n
+        ".to_string().color(Color::BrightCyan);
+        println!("> {}", help)
+    }
+
+    fn help_cmd() -> clap::App<'static> {
+        clap::App::new("search_help")
+            .about("Prints help command for search")
+            .long_flag("help")
+            .short_flag('h')
+            .long_about("Prints the help information")
+    }
+}
+
+impl SearchCmd {
+
+    fn hits(&self) -> crate::DResult<String> {
+        if self.query.terms.is_empty() && !self.reindex {
+            return Err(DError::Invalid("Nothing to search for".into()));
+        }
+        let index = match self.reindex {
+            true => Index::rebuild()?,
+            false => Index::open()?,
+        };
+        if self.query.terms.is_empty() {
+            return Ok("Reindexed your data".color(Color::BrightGreen).to_string());
+        }
+        let mut hits = index.search(&self.query);
+        hits.truncate(self.max);
+        if let Some(out) = output::render(&hits)? {
+            return Ok(out);
+        }
+        match hits.is_empty() {
+            true => Ok("Nothing matched".into()),
+            false => Ok(list(&hits, &self.query.terms)),
+        }
+    }
+}
+
+fn count(n: &str) -> Result<(), String> {
+    match n.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(()),
+        _ => Err(format!("{} is not a positive number", n)),
+    }
+}
+
+fn list(hits: &[Hit], terms: &[String]) -> String {
+    hits.iter()
+        .map(|hit| format!("{} {} {}\n  {}",
+            format!("{:<6}", hit.kind).color(Color::BrightCyan),
+            hit.name.bold(),
+            format!("({}, {:.2})", hit.record, hit.score).dimmed(),
+            search::highlight(&hit.snippet, terms)))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+impl FromArgMatches for SearchCmd {
+    fn from_arg_matches(matches: &ArgMatches) -> Self {
+        if let Some(("search_help", _)) = matches.subcommand() {
+            return Self { help: true, ..Self::default() };
+        }
+        let text = matches.values_of("QUERY")
+            .map(|v| v.collect::<Vec<&str>>().join(" "))
+            .unwrap_or_default();
+        let mut filters = matches.values_of("kind")
+            .map(|kinds| kinds.map(|k| match k {
+                "fact" => QueryFilter::Facts,
+                "item" => QueryFilter::Items,
+                "record" => QueryFilter::Records,
+                _ => QueryFilter::Notes,
+            }).collect::<Vec<QueryFilter>>())
+            .unwrap_or_default();
+        if let Some(record) = matches.value_of("record") {
+            filters.push(QueryFilter::InRecord(record.into()));
+        }
+        Self {
+            query: Query::new(&text, filters),
+            max: matches.value_of("max-results")
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(20),
+            reindex: matches.is_present("reindex"),
+            help: false,
+        }
+    }
+}
+
+impl clap::Subcommand for SearchCmd {
+    fn from_subcommand(sub: Option<(&str, &ArgMatches)>)
+        -> Option<Self>
+    {
+        match sub {
+            Some(("search", args)) => Some(Self::from_arg_matches(args)),
+            _ => None,
+        }
+    }
+
+    fn augment_subcommands(app: clap::App<'_>) -> clap::App<'_>
+    {
+        app
+    }
+}
@@ -0,0 +1,158 @@
+use std::{io::Write, net::TcpListener};
+use clap::{ArgMatches, FromArgMatches};
+use colored::{Color, Colorize};
+use crate::{
+    api::Api,
+    cmd::Cmd,
+    config::DConfig,
+    http,
+};
+
+#[derive(Debug)]
+pub enum ServeCmd {
+    /// Answers the JSON API on an address, requiring the given token or
+    /// the configured `auth.token`
+    Serve { bind: String, token: Option<String> },
+    Help,
+}
+
+impl Default for ServeCmd {
+    fn default() -> Self {
+        Self::Serve { bind: "127.0.0.1:7777".into(), token: None }
+    }
+}
+
+impl Cmd for ServeCmd {
+
+    fn name() -> &'static str { "serve" }
+    fn about() -> &'static str { "Serve a JSON API over your records, items and facts" }
+    fn long_about() -> &'static str {
+        "Answer HTTP requests to create, list and search records, items, facts and links, and to summarize facts, e.g. for dashboards and shortcuts on your LAN. Requests must carry 'Authorization: Bearer <token>' if auth.token is set in the config or --token is given"
+    }
+
+    fn args() -> Vec<clap::Arg<'static>> {
+        vec![
+            clap::Arg::new("bind")
+                .about("Address to listen on, port 0 picks a free port")
+                .short('b')
+                .long("bind")
+                .takes_value(true)
+                .default_value("127.0.0.1:7777"),
+            clap::Arg::new("token")
+                .about("Token requests must carry, instead of auth.token")
+                .short('t')
+                .long("token")
+                .takes_value(true),
+        ]
+    }
+
+    fn subcmds() -> Vec<clap::App<'static>> {
+        vec![
+            Self::help_cmd(),
+        ]
+    }
+
+    fn run(&self) {
+        let res = match self {
+            Self::Serve { bind, token } => serve(bind, token.clone()),
+            Self::Help => {
+                Self::print_help();
+                Ok(String::new())
+            },
+        };
+        match res {
+            Ok(msg) if msg.is_empty() => {},
+            Ok(msg) => println!("{}", msg),
+            Err(e) => {
+                eprintln!("{}", format!("Could not run serve cmd: {}", e)
+                    .color(Color::BrightRed));
+                super::fail();
+            }
+        }
+    }
+
+    fn print_help() {
+        let help = "
+            SERVE: Serve the JSON API, e.g.\n
+                   dlog serve --bind 0.0.0.0:7777 --token s3cret\n
+                   curl -H 'Authorization: Bearer s3cret' localhost:7777/v1/facts\n
+        ".to_string().color(Color::BrightCyan);
+        println!("> {}", help)
+    }
+
+    fn help_cmd() -> clap::App<'static> {
+        clap::App::new("serve_help")
+            .about("Prints help command for serve")
+            .long_flag("help")
+            .short_flag('h')
+            .long_about("Prints the help information")
+    }
+}
+
+fn serve(bind: &str, token: Option<String>) -> crate::DResult<String> {
+    let token = token.or_else(|| DConfig::load().ok().and_then(|c| c.api_token()));
+    let listener = TcpListener::bind(bind)?;
+    // The store is only opened while a request is handled
+    crate::auth::suspend()?;
+    println!("Listening on {}", listener.local_addr()?);
+    if token.is_none() {
+        println!("{}", "No auth.token set, anyone who can reach the address may read and log facts"
+            .color(Color::BrightYellow));
+    }
+    std::io::stdout().flush()?;
+    let mut api = Api::new(token);
+    http::serve(listener, |req| {
+        if let Err(e) = crate::auth::resume() {
+            return http::Response::error(500, &e.to_string());
+        }
+        let res = api.handle(req);
+        journal(req);
+        if let Err(e) = crate::auth::suspend() {
+            eprintln!("{}", format!("Could not seal the store: {}", e).color(Color::BrightRed));
+        }
+        res
+    })?;
+    Ok(String::new())
+}
+
+/// Journals what a request changed and indexes it for search, as for a
+/// command once it is done
+fn journal(req: &http::Request) {
+    let cmd = format!("dlog serve: {} {}", req.method, req.path);
+    match crate::history::commit(&cmd) {
+        Ok(Some(op)) => super::reindex(&op.changes),
+        Ok(None) => {},
+        Err(e) => eprintln!("{}", format!("Could not journal {}: {}", cmd, e)
+            .color(Color::BrightRed)),
+    }
+}
+
+impl FromArgMatches for ServeCmd {
+    fn from_arg_matches(matches: &ArgMatches) -> Self {
+        match matches.subcommand() {
+            Some(("serve_help", _)) => Self::Help,
+            _ => Self::Serve {
+                bind: matches.value_of("bind").unwrap_or("127.0.0.1:7777").into(),
+                token: matches.value_of("token").map(String::from),
+            },
+        }
+    }
+}
+
+impl clap::Subcommand for ServeCmd {
+    fn from_subcommand(sub: Option<(&str, &ArgMatches)>)
+        -> Option<Self>
+    {
+        let (sub, args) = sub.unwrap();
+        if sub == "serve" {
+            Some(Self::from_arg_matches(args))
+        } else {
+            None
+        }
+    }
+
+    fn augment_subcommands(app: clap::App<'_>) -> clap::App<'_>
+    {
+        app
+    }
+}
@@ -1,58 +1,162 @@
 use clap::{ArgMatches, FromArgMatches};
 use super::Cmd;
 use colored::{Color, Colorize};
+use crate::{
+    error::DError,
+    output,
+    models::{
+        Record, Fact,
+        stats::FactStats,
+        data::{RecordData, UserData},
+    },
+};
 
 #[derive(Debug)]
 pub enum StatsCmd {
+    /// Summarizes entries, optionally only those of a record, logged by a
+    /// user or logged by the members of a group
+    Show(StatsFilter),
     Help,
 }
 
+#[derive(Debug, Default)]
+pub struct StatsFilter {
+    pub record: Option<String>,
+    pub user: Option<String>,
+    pub group: Option<String>,
+}
+
 impl Default for StatsCmd {
     fn default() -> Self {
-        StatsCmd::Help
+        StatsCmd::Show(StatsFilter::default())
     }
 }
 
 impl Cmd for StatsCmd {
 
     fn name() -> &'static str { "stats" }
-    fn about() -> &'static str { "The stats cmd" }
-    fn long_about() -> &'static str { "The stats cmd" }
+    fn about() -> &'static str { "Summarize fact entries, by record, user or group" }
+    fn long_about() -> &'static str {
+        "Show how many entries of each fact have been logged, with totals, means and ranges for numeric values. Entries can be limited to those of one record, or those logged by a user or the members of a group"
+    }
     fn args() -> Vec<clap::Arg<'static>> {
         vec![
-            clap::Arg::new("help")
+            clap::Arg::new("record")
+                .about("Only entries of this record")
+                .short('r')
+                .long("record")
+                .takes_value(true),
+            clap::Arg::new("user")
+                .about("Only entries logged by this user")
+                .short('u')
+                .long("user")
+                .takes_value(true),
+            clap::Arg::new("group")
+                .about("Only entries logged by members of this group")
+                .short('g')
+                .long("group")
+                .takes_value(true)
+                .conflicts_with("user"),
         ]
     }
 
     fn subcmds() -> Vec<clap::App<'static>> {
         vec![
-            clap::App::new("new"),
+            Self::help_cmd(),
         ]
     }
+
     fn run(&self) {
-        println!("{}", format!("Running Stats cmd...")
-            .color(Color::BrightCyan))
+        let res = match self {
+            Self::Show(filter) => show(filter),
+            Self::Help => {
+                Self::print_help();
+                Ok(String::new())
+            },
+        };
+        match res {
+            Ok(msg) if msg.is_empty() => {},
+            Ok(msg) => println!("{}", msg),
+            Err(e) => {
+                eprintln!("{}", format!("Could not run stats cmd: {}", e)
+                    .color(Color::BrightRed));
+                super::fail();
+            }
+        }
     }
 
     fn print_help() {
-        let help = format!("Stats
-        ").color(Color::BrightCyan);
+        let help = "
+            STATS: Summarize entries of each fact, e.g.\n
+                   dlog stats --user sam --record health\n
+        ".to_string().color(Color::BrightCyan);
         println!("> {}", help)
     }
 
     fn help_cmd() -> clap::App<'static> {
-        clap::App::new("fact_help")
-            .about("Prints help command for fact")
+        clap::App::new("stats_help")
+            .about("Prints help command for stats")
             .long_flag("help")
             .short_flag('h')
             .long_about("Prints the help information")
     }
 }
 
+fn show(filter: &StatsFilter) -> crate::DResult<String> {
+    let facts = filter.facts()?;
+    if let Some(out) = output::render(&FactStats::summarize(&facts))? {
+        return Ok(out);
+    }
+    if facts.is_empty() {
+        return Ok("No entries to summarize".color(Color::BrightYellow).to_string());
+    }
+    Ok(FactStats::table(&FactStats::summarize(&facts)).to_string())
+}
+
+impl StatsFilter {
+
+    /// Entries of the record, or every record, logged by the user or the
+    /// members of the group
+    pub fn facts(&self) -> crate::DResult<Vec<Fact>> {
+        let users = UserData::read()?;
+        let authors = match (&self.user, &self.group) {
+            (Some(user), _) => {
+                users.check_user(user)?;
+                Some(vec![user.clone()])
+            },
+            (None, Some(group)) => Some(users.group(group)
+                .ok_or_else(|| DError::Invalid(format!("No group named {}", group)))?
+                .members.clone()),
+            (None, None) => None,
+        };
+        let records = match &self.record {
+            Some(record) => vec![super::open_record(record)?],
+            None => RecordData::read()?.records()
+                .map(|r| Record::open_path(&r.path))
+                .collect::<crate::DResult<Vec<Record>>>()?,
+        };
+        let mut facts = Vec::new();
+        for record in records {
+            facts.extend(record.facts()?);
+        }
+        if let Some(authors) = authors {
+            facts.retain(|f| f.author.as_ref()
+                .is_some_and(|a| authors.iter().any(|u| u.eq_ignore_ascii_case(a))));
+        }
+        Ok(facts)
+    }
+}
+
 impl FromArgMatches for StatsCmd {
-    fn from_arg_matches(_matches: &ArgMatches) -> Self {
-        Self::print_help();
-        Self::default()
+    fn from_arg_matches(matches: &ArgMatches) -> Self {
+        match matches.subcommand() {
+            Some(("stats_help", _)) => Self::Help,
+            _ => Self::Show(StatsFilter {
+                record: matches.value_of("record").map(String::from),
+                user: matches.value_of("user").map(String::from),
+                group: matches.value_of("group").map(String::from),
+            }),
+        }
     }
 }
 
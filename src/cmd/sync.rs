@@ -0,0 +1,184 @@
+use clap::{ArgMatches, FromArgMatches};
+use colored::{Color, Colorize};
+use crate::{
+    cmd::Cmd,
+    sync::{self, Client, Conflict},
+};
+
+#[derive(Debug, Default)]
+pub enum SyncCmd {
+    #[default]
+    Status,
+    Push(SyncOpts),
+    Pull(SyncOpts),
+    Help,
+}
+
+/// Overrides of the configured server, and whether to send or fetch
+/// everything rather than what changed since the last sync
+#[derive(Debug, Default)]
+pub struct SyncOpts {
+    pub url: Option<String>,
+    pub token: Option<String>,
+    pub all: bool,
+}
+
+impl Cmd for SyncCmd {
+
+    fn name() -> &'static str { "sync" }
+    fn about() -> &'static str { "Push and pull records and facts to a sync server" }
+    fn long_about() -> &'static str {
+        "Push records and fact entries changed since the last push to a sync server, and pull what others pushed since the last pull. The server is set by sync.url (and sync.token) in the config. When an entity changed on both sides the version updated last is kept, and the conflict is reported"
+    }
+
+    fn args() -> Vec<clap::Arg<'static>> {
+        Vec::new()
+    }
+
+    fn subcmds() -> Vec<clap::App<'static>> {
+        let opts = || vec![
+            clap::Arg::new("url")
+                .about("The sync server, instead of sync.url")
+                .long("url")
+                .takes_value(true)
+                .value_hint(clap::ValueHint::Url),
+            clap::Arg::new("token")
+                .about("The server's token, instead of sync.token")
+                .long("token")
+                .takes_value(true),
+            clap::Arg::new("all")
+                .about("Everything, not only what changed since the last sync")
+                .short('a')
+                .long("all")
+                .takes_value(false),
+        ];
+        vec![
+            clap::App::new("status")
+                .about("Show the sync server, when the store last synced and what is left to push"),
+            clap::App::new("push")
+                .about("Send records and facts changed since the last push")
+                .args(opts()),
+            clap::App::new("pull")
+                .about("Fetch records and facts pushed since the last pull")
+                .args(opts()),
+            Self::help_cmd(),
+        ]
+    }
+
+    fn run(&self) {
+        let res = match self {
+            Self::Status => status(),
+            Self::Push(opts) => push(opts),
+            Self::Pull(opts) => pull(opts),
+            Self::Help => {
+                Self::print_help();
+                Ok(String::new())
+            },
+        };
+        match res {
+            Ok(msg) if msg.is_empty() => {},
+            Ok(msg) => println!("{}", msg),
+            Err(e) => {
+                eprintln!("{}", format!("Could not run sync cmd: {}", e)
+                    .color(Color::BrightRed));
+                super::fail();
+            }
+        }
+    }
+
+    fn print_help() {
+        let help = "
+            SYNC: Share records with a sync server, e.g.\n
+                  dlog sync push --url http://127.0.0.1:7878\n
+                  dlog sync pull\n
+        ".to_string().color(Color::BrightCyan);
+        println!("> {}", help)
+    }
+
+    fn help_cmd() -> clap::App<'static> {
+        clap::App::new("sync_help")
+            .about("Prints help command for sync")
+            .long_flag("help")
+            .short_flag('h')
+            .long_about("Prints the help information")
+    }
+}
+
+fn status() -> crate::DResult<String> {
+    let (url, state, pending) = sync::status()?;
+    let time = |t: Option<String>| t.unwrap_or_else(|| "never".into());
+    Ok(format!("Server: {}\nLast push: {}\nLast pull: {}\n{} entities to push",
+        url.unwrap_or_else(|| "none, set sync.url in the config".into()),
+        time(state.last_push.map(|t| t.format("%Y-%m-%d %H:%M").to_string())),
+        time(state.pulled_at.map(|t| t.format("%Y-%m-%d %H:%M").to_string())),
+        pending))
+}
+
+fn push(opts: &SyncOpts) -> crate::DResult<String> {
+    let report = Client::new(opts.url.clone(), opts.token.clone())?.push(opts.all)?;
+    let mut msg = format!("Pushed {} entities, {} taken by the server", report.sent, report.updated)
+        .color(Color::BrightGreen).to_string();
+    if !report.conflicts.is_empty() {
+        msg.push_str(&format!("\n{}\n{}",
+            format!("{} conflicts, kept the newer version on the server:", report.conflicts.len())
+                .color(Color::BrightYellow),
+            Conflict::table(&report.conflicts, "Server", "Local")));
+    }
+    Ok(msg)
+}
+
+fn pull(opts: &SyncOpts) -> crate::DResult<String> {
+    let report = Client::new(opts.url.clone(), opts.token.clone())?.pull(opts.all)?;
+    let mut msg = format!("Pulled {} new and {} updated entities", report.added, report.updated)
+        .color(Color::BrightGreen).to_string();
+    if !report.conflicts.is_empty() {
+        msg.push_str(&format!("\n{}\n{}",
+            format!("{} conflicts, kept the newer version:", report.conflicts.len())
+                .color(Color::BrightYellow),
+            Conflict::table(&report.conflicts, "Local", "Server")));
+    }
+    for skipped in &report.skipped {
+        msg.push_str(&format!("\n{}", format!("Skipped {}", skipped)
+            .color(Color::BrightYellow)));
+    }
+    Ok(msg)
+}
+
+impl FromArgMatches for SyncOpts {
+    fn from_arg_matches(matches: &ArgMatches) -> Self {
+        Self {
+            url: matches.value_of("url").map(String::from),
+            token: matches.value_of("token").map(String::from),
+            all: matches.is_present("all"),
+        }
+    }
+}
+
+impl FromArgMatches for SyncCmd {
+    fn from_arg_matches(matches: &ArgMatches) -> Self {
+        match matches.subcommand() {
+            Some(("push", sub)) => Self::Push(SyncOpts::from_arg_matches(sub)),
+            Some(("pull", sub)) => Self::Pull(SyncOpts::from_arg_matches(sub)),
+            Some(("sync_help", _)) => Self::Help,
+            _ => Self::Status,
+        }
+    }
+}
+
+impl clap::Subcommand for SyncCmd {
+    fn from_subcommand(sub: Option<(&str, &ArgMatches)>)
+        -> Option<Self>
+    {
+        let (sub, args) = sub.unwrap();
+        if sub == "sync" {
+            Some(Self::from_arg_matches(args))
+        } else {
+            None
+        }
+    }
+
+    fn augment_subcommands(app: clap::App<'_>) -> clap::App<'_>
+    {
+        app
+    }
+}
@@ -0,0 +1,211 @@
+use clap::{ArgMatches, FromArgMatches};
+use colored::{Color, Colorize};
+use crate::{
+    cmd::Cmd,
+    error::DError,
+    output,
+    models::{
+        Topic,
+        data::TopicData,
+        entity::EntityRef,
+    },
+};
+
+#[derive(Debug, Default)]
+pub enum TopicCmd {
+    New(Topic),
+    #[default]
+    List,
+    /// Everything about a topic and its subtopics across records, with at
+    /// most the given number of entries per record
+    Get(String, usize),
+    /// Tags entities with a topic
+    Tag(String, Vec<EntityRef>),
+    Help,
+}
+
+impl Cmd for TopicCmd {
+
+    fn name() -> &'static str { "topic" }
+    fn about() -> &'static str { "Create topics and see everything about one across records" }
+    fn long_about() -> &'static str {
+        "Topics are subjects which facts, items and records can be tagged with, and which may be nested beneath other topics. Getting a topic shows, record by record, the items and facts tagged with it or any of its subtopics, the facts named after them, and anything related to them by a +topic tag"
+    }
+
+    fn args() -> Vec<clap::Arg<'static>> {
+        Vec::new()
+    }
+
+    fn subcmds() -> Vec<clap::App<'static>> {
+        vec![
+            clap::App::new("new")
+                .about("Create a new topic")
+                .visible_aliases(&["create", "add"])
+                .args(&[
+                    Self::name_arg(),
+                    clap::Arg::new("description")
+                        .about("What the topic is about")
+                        .short('d')
+                        .long("description")
+                        .takes_value(true),
+                    clap::Arg::new("parent")
+                        .about("Nest the topic beneath an existing one")
+                        .short('p')
+                        .long("parent")
+                        .takes_value(true),
+                ]),
+            clap::App::new("list")
+                .about("List topics, nested beneath their parents")
+                .long_flag("ls")
+                .short_flag('l'),
+            clap::App::new("get")
+                .about("Show everything about a topic across records")
+                .args(&[
+                    Self::name_arg(),
+                    clap::Arg::new("recent")
+                        .about("How many of the most recent entries to show for each record")
+                        .short('n')
+                        .long("recent")
+                        .takes_value(true)
+                        .default_value("10")
+                        .validator(|n| n.parse::<usize>().map(|_| ())),
+                ]),
+            clap::App::new("tag")
+                .about("Tag records, items or fact entries with a topic")
+                .args(&[
+                    Self::name_arg(),
+                    clap::Arg::new("ENTITY")
+                        .about("The record, item or fact entry, e.g. record:health, item:exercise/running or fact:<id>")
+                        .required(true)
+                        .multiple(true)
+                        .index(2)
+                        .validator(|e| e.parse::<EntityRef>().map(|_| ())),
+                ]),
+            Self::help_cmd(),
+        ]
+    }
+
+    fn run(&self) {
+        let res = match self {
+            Self::New(topic) => TopicData::read()
+                .and_then(|mut data| data.register(topic.clone()))
+                .map(|_| format!("Created topic {}", topic.name)
+                    .color(Color::BrightGreen).to_string()),
+            Self::List => TopicData::read()
+                .and_then(|data| match data.topics().is_empty() {
+                    _ if output::structured() => Ok(output::render(data.topics())?.unwrap_or_default()),
+                    true => Ok("No topics yet".color(Color::BrightYellow).to_string()),
+                    false => Topic::tree_table(&data).map(|t| t.to_string()),
+                }),
+            Self::Get(name, recent) => get(name, *recent),
+            Self::Tag(name, entities) => tag(name, entities),
+            Self::Help => {
+                Self::print_help();
+                Ok(String::new())
+            },
+        };
+        match res {
+            Ok(msg) if msg.is_empty() => {},
+            Ok(msg) => println!("{}", msg),
+            Err(e) => {
+                eprintln!("{}", format!("Could not run topic cmd: {}", e)
+                    .color(Color::BrightRed));
+                super::fail();
+            }
+        }
+    }
+
+    fn print_help() {
+        let help = "
+            TOPIC: Tag facts, items and records with topics\n
+                   and see each topic across all records\n
+        ".to_string().color(Color::BrightBlue);
+        println!("> {}", help)
+    }
+
+    fn help_cmd() -> clap::App<'static> {
+        clap::App::new("topic_help")
+            .about("Prints help command for topic")
+            .long_flag("help")
+            .short_flag('h')
+            .long_about("Prints the help information")
+    }
+}
+
+impl TopicCmd {
+
+    fn name_arg() -> clap::Arg<'static> {
+        clap::Arg::new("NAME")
+            .about("The topic's name")
+            .required(true)
+            .index(1)
+            .validator(|a| crate::prompt::validate_input(a.into()))
+    }
+}
+
+/// Topics mentioned with a `+topic` tag need not be registered, so they can
+/// be looked up all the same
+fn get(name: &str, recent: usize) -> crate::DResult<String> {
+    let data = TopicData::read()?;
+    let topic = data.find(name).cloned()
+        .unwrap_or_else(|| Topic::new(name, "", None));
+    let views = Topic::gather(&data.descendants(&topic.name))?;
+    if views.is_empty() && data.find(name).is_none() {
+        return Err(DError::Invalid(
+            format!("No topic named {}, create it with 'dlog topic new {}'", name, name)));
+    }
+    Ok(topic.view_table(&views, recent).to_string())
+}
+
+fn tag(name: &str, entities: &[EntityRef]) -> crate::DResult<String> {
+    let data = TopicData::read()?;
+    let topic = data.find(name)
+        .ok_or_else(|| DError::Invalid(
+            format!("No topic named {}, create it with 'dlog topic new {}'", name, name)))?;
+    let mut tagged = 0;
+    for entity in entities {
+        entity.check()?;
+        if topic.tag(entity)? {
+            tagged += 1;
+        }
+    }
+    Ok(format!("Tagged {} with topic {}", tagged, topic.name)
+        .color(Color::BrightGreen).to_string())
+}
+
+impl FromArgMatches for TopicCmd {
+    fn from_arg_matches(matches: &ArgMatches) -> Self {
+        let name = |m: &ArgMatches| m.value_of("NAME").unwrap_or_default().to_string();
+        match matches.subcommand() {
+            Some(("new", sub)) => Self::New(Topic::new(&name(sub),
+                sub.value_of("description").unwrap_or_default(),
+                sub.value_of("parent").map(String::from))),
+            Some(("get", sub)) => Self::Get(name(sub), sub.value_of("recent")
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(10)),
+            Some(("tag", sub)) => Self::Tag(name(sub), sub.values_of("ENTITY")
+                .map(|e| e.filter_map(|e| e.parse().ok()).collect())
+                .unwrap_or_default()),
+            Some(("topic_help", _)) => Self::Help,
+            _ => Self::List,
+        }
+    }
+}
+
+impl clap::Subcommand for TopicCmd {
+    fn from_subcommand(sub: Option<(&str, &ArgMatches)>)
+        -> Option<Self>
+    {
+        let (sub, args) = sub.unwrap();
+        if sub == "topic" {
+            Some(Self::from_arg_matches(args))
+        } else {
+            None
+        }
+    }
+
+    fn augment_subcommands(app: clap::App<'_>) -> clap::App<'_>
+    {
+        app
+    }
+}
@@ -0,0 +1,162 @@
+use clap::{ArgMatches, FromArgMatches};
+use colored::{Color, Colorize};
+use crate::{
+    output,
+    cmd::Cmd,
+    config::DConfig,
+    models::trash::{Trash, TrashKind},
+};
+
+#[derive(Debug, Default)]
+pub enum TrashCmd {
+    #[default]
+    List,
+    Restore(String),
+    Empty(bool),
+    Help,
+}
+
+impl Cmd for TrashCmd {
+
+    fn name() -> &'static str { "trash" }
+    fn about() -> &'static str { "List, restore or empty what you deleted" }
+    fn long_about() -> &'static str {
+        "Deleted records, items, facts and fact entries are moved to the trash, where they are kept for trash.retention_days (30 unless set, 0 keeps them until emptied) and may be restored where they were"
+    }
+
+    fn args() -> Vec<clap::Arg<'static>> {
+        Vec::new()
+    }
+
+    fn subcmds() -> Vec<clap::App<'static>> {
+        vec![
+            clap::App::new("list")
+                .about("List what is in the trash")
+                .long_flag("ls")
+                .short_flag('l'),
+            clap::App::new("restore")
+                .about("Put back something deleted where it was")
+                .long_about("Put back a deleted record, item, fact or fact entry, given by the start of its id in 'dlog trash list' or the name it was deleted with. A record or item is not restored over one of the same name")
+                .arg(clap::Arg::new("ID")
+                    .about("The start of its id, or its name")
+                    .required(true)
+                    .index(1)),
+            clap::App::new("empty")
+                .about("Delete everything in the trash for good")
+                .arg(clap::Arg::new("yes")
+                    .about("Don't ask for confirmation")
+                    .long("yes")
+                    .short('y')
+                    .takes_value(false)),
+            Self::help_cmd(),
+        ]
+    }
+
+    fn run(&self) {
+        let res = match self {
+            Self::List => list(),
+            Self::Restore(name) => restore(name),
+            Self::Empty(yes) => empty(*yes),
+            Self::Help => {
+                Self::print_help();
+                Ok(String::new())
+            },
+        };
+        match res {
+            Ok(msg) if msg.is_empty() => {},
+            Ok(msg) => println!("{}", msg),
+            Err(e) => {
+                eprintln!("{}", format!("Could not run trash cmd: {}", e)
+                    .color(Color::BrightRed));
+                super::fail();
+            }
+        }
+    }
+
+    fn print_help() {
+        let help = "
+            TRASH: Get back what you deleted, e.g.\n
+                  dlog item delete night -r health --cascade\n
+                  dlog trash list\n
+                  dlog trash restore night\n
+                  dlog trash empty\n
+        ".to_string().color(Color::BrightCyan);
+        println!("> {}", help)
+    }
+
+    fn help_cmd() -> clap::App<'static> {
+        clap::App::new("trash_help")
+            .about("Prints help command for trash")
+            .long_flag("help")
+            .short_flag('h')
+            .long_about("Prints the help information")
+    }
+}
+
+fn list() -> crate::DResult<String> {
+    let mut trash = Trash::read()?;
+    if trash.purge(DConfig::load()?.trash_retention())? > 0 {
+        trash.write()?;
+    }
+    if let Some(out) = output::render(trash.trashed())? {
+        return Ok(out);
+    }
+    match trash.trashed().is_empty() {
+        true => Ok("The trash is empty".into()),
+        false => Ok(trash.table().to_string()),
+    }
+}
+
+fn restore(name: &str) -> crate::DResult<String> {
+    let restored = Trash::read()?.restore(name)?;
+    let msg = match restored.kind {
+        TrashKind::Record => format!("Restored record {}", restored.name),
+        kind => format!("Restored {} {} to {}", kind, restored.name, restored.record),
+    };
+    Ok(msg.color(Color::BrightGreen).to_string())
+}
+
+fn empty(yes: bool) -> crate::DResult<String> {
+    let mut trash = Trash::read()?;
+    if trash.trashed().is_empty() {
+        return Ok("The trash is empty".into());
+    }
+    let confirmed = yes || dialoguer::Confirm::new()
+        .with_prompt(format!("Delete everything in the trash ({}) for good?", trash.trashed().len()))
+        .default(false)
+        .interact()
+        .unwrap_or(false);
+    if !confirmed {
+        return Ok("Kept the trash".into());
+    }
+    let n = trash.empty()?;
+    Ok(format!("Emptied the trash ({} deleted for good)", n)
+        .color(Color::BrightGreen).to_string())
+}
+
+impl FromArgMatches for TrashCmd {
+    fn from_arg_matches(matches: &ArgMatches) -> Self {
+        match matches.subcommand() {
+            Some(("restore", sub)) => Self::Restore(sub.value_of("ID").unwrap_or_default().into()),
+            Some(("empty", sub)) => Self::Empty(sub.is_present("yes")),
+            Some(("trash_help", _)) => Self::Help,
+            _ => Self::List,
+        }
+    }
+}
+
+impl clap::Subcommand for TrashCmd {
+    fn from_subcommand(sub: Option<(&str, &ArgMatches)>)
+        -> Option<Self>
+    {
+        match sub {
+            Some(("trash", args)) => Some(Self::from_arg_matches(args)),
+            _ => None,
+        }
+    }
+
+    fn augment_subcommands(app: clap::App<'_>) -> clap::App<'_>
+    {
+        app
+    }
+}
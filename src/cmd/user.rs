@@ -1,61 +1,286 @@
 use clap::{ArgMatches, FromArgMatches};
 use super::Cmd;
 use colored::{Color, Colorize};
+use crate::{
+    config::{DConfig, ConfigLayer},
+    error::DError,
+    output,
+    models::{
+        Record, User,
+        user::group::Group,
+        data::{RecordData, UserData},
+    },
+};
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub enum UserCmd {
+    New(String),
+    /// Makes the user the one facts are logged as
+    Switch(String),
+    #[default]
+    List,
+    Group(GroupCmd),
     Help
 }
 
-impl Default for UserCmd {
-    fn default() -> Self {
-        Self::Help
-    }
+#[derive(Debug)]
+pub enum GroupCmd {
+    New(Group),
+    /// Adds users to a group
+    Add(String, Vec<String>),
+    /// Gives records to a group, shared by its members
+    Share(String, Vec<String>),
+    List,
 }
 
 impl Cmd for UserCmd {
 
     fn name() -> &'static str { "user" }
-    fn about() -> &'static str { "The user cmd" }
-    fn long_about() -> &'static str { "The user cmd" }
+    fn about() -> &'static str { "Set up the users and groups sharing your data" }
+    fn long_about() -> &'static str {
+        "Several users can share one data dir, e.g. a household or a team. Facts are logged as the current user, set with 'dlog user switch', and groups own the records their members share, which only members may log to"
+    }
 
     fn args() -> Vec<clap::Arg<'static>> {
-        vec![
-            clap::Arg::new("help")
-        ]
+        Vec::new()
     }
 
     fn subcmds() -> Vec<clap::App<'static>> {
         vec![
-            clap::App::new("new"),
+            clap::App::new("new")
+                .about("Add a user, switching to them if they are the first")
+                .visible_aliases(&["create", "add"])
+                .arg(Self::user_arg()),
+            clap::App::new("switch")
+                .about("Log facts as another user from now on")
+                .arg(Self::user_arg()),
+            clap::App::new("list")
+                .about("List users, with the current one marked")
+                .long_flag("ls")
+                .short_flag('l'),
+            clap::App::new("group")
+                .about("Create groups of users which share records")
+                .subcommands(vec![
+                    clap::App::new("new")
+                        .about("Create a group")
+                        .args(&[
+                            Self::group_arg(),
+                            clap::Arg::new("description")
+                                .about("What the group is")
+                                .short('d')
+                                .long("description")
+                                .takes_value(true),
+                            clap::Arg::new("member")
+                                .about("Users to add to the group")
+                                .short('m')
+                                .long("member")
+                                .takes_value(true)
+                                .multiple(true),
+                        ]),
+                    clap::App::new("add")
+                        .about("Add users to a group")
+                        .args(&[
+                            Self::group_arg(),
+                            clap::Arg::new("USER")
+                                .about("The users to add")
+                                .required(true)
+                                .multiple(true)
+                                .index(2),
+                        ]),
+                    clap::App::new("share")
+                        .about("Share records with a group, so only its members may log to them")
+                        .args(&[
+                            Self::group_arg(),
+                            clap::Arg::new("RECORD")
+                                .about("The records to share")
+                                .required(true)
+                                .multiple(true)
+                                .index(2),
+                        ]),
+                    clap::App::new("list")
+                        .about("List groups with their members and records")
+                        .long_flag("ls")
+                        .short_flag('l'),
+                ]),
+            Self::help_cmd(),
         ]
     }
 
     fn run(&self) {
-        println!("{}", format!("Running User cmd...")
-            .color(Color::BrightCyan))
+        let res = match self {
+            Self::New(name) => new(name),
+            Self::Switch(name) => switch(name),
+            Self::List => list(),
+            Self::Group(cmd) => cmd.run(),
+            Self::Help => {
+                Self::print_help();
+                Ok(String::new())
+            },
+        };
+        match res {
+            Ok(msg) if msg.is_empty() => {},
+            Ok(msg) => println!("{}", msg),
+            Err(e) => {
+                eprintln!("{}", format!("Could not run user cmd: {}", e)
+                    .color(Color::BrightRed));
+                super::fail();
+            }
+        }
     }
 
-
     fn print_help() {
-        let help = format!("User
-        ").color(Color::BrightCyan);
+        let help = "
+            USER: Share a data dir between users, and share\n
+                  records between groups of them\n
+        ".to_string().color(Color::BrightCyan);
         println!("> {}", help)
     }
 
     fn help_cmd() -> clap::App<'static> {
-        clap::App::new("fact_help")
-            .about("Prints help command for fact")
+        clap::App::new("user_help")
+            .about("Prints help command for user")
             .long_flag("help")
             .short_flag('h')
             .long_about("Prints the help information")
     }
 }
 
+impl UserCmd {
+
+    fn user_arg() -> clap::Arg<'static> {
+        clap::Arg::new("USER")
+            .about("The user's name")
+            .required(true)
+            .index(1)
+            .validator(|a| crate::prompt::validate_input(a.into()))
+    }
+
+    fn group_arg() -> clap::Arg<'static> {
+        clap::Arg::new("GROUP")
+            .about("The group's name")
+            .required(true)
+            .index(1)
+            .validator(|a| crate::prompt::validate_input(a.into()))
+    }
+}
+
+fn new(name: &str) -> crate::DResult<String> {
+    let mut data = UserData::read()?;
+    data.register(User::new(name))?;
+    if data.current().is_none() {
+        return switch(name);
+    }
+    Ok(format!("Added user {}", name).color(Color::BrightGreen).to_string())
+}
+
+fn switch(name: &str) -> crate::DResult<String> {
+    let data = UserData::read()?;
+    data.check_user(name)?;
+    let user = data.find(name).ok_or(DError::NotFound)?;
+    DConfig::set(ConfigLayer::Global, "auth.username", toml::Value::String(user.username.clone()))?;
+    Ok(format!("Logging facts as {}", user.username)
+        .color(Color::BrightGreen).to_string())
+}
+
+fn list() -> crate::DResult<String> {
+    let data = UserData::read()?;
+    if let Some(out) = output::render(data.users())? {
+        return Ok(out);
+    }
+    if data.users().is_empty() {
+        return Ok("No users yet, add one with 'dlog user new'"
+            .color(Color::BrightYellow).to_string());
+    }
+    let mut facts = Vec::new();
+    for rec in RecordData::read()?.records() {
+        facts.extend(Record::open_path(&rec.path)?.facts()?);
+    }
+    Ok(User::table(&data, &facts).to_string())
+}
+
+impl GroupCmd {
+
+    fn run(&self) -> crate::DResult<String> {
+        let mut data = UserData::read()?;
+        match self {
+            Self::New(group) => {
+                data.register_group(group.clone())?;
+                Ok(format!("Created group {}", group.name)
+                    .color(Color::BrightGreen).to_string())
+            },
+            Self::Add(name, users) => {
+                for user in users {
+                    data.check_user(user)?;
+                }
+                let group = data.group_mut(name)?;
+                for user in users {
+                    if !group.has_member(user) {
+                        group.members.push(user.clone());
+                    }
+                }
+                let msg = format!("{} now has {} members", group.name, group.members.len());
+                data.write()?;
+                Ok(msg.color(Color::BrightGreen).to_string())
+            },
+            Self::Share(name, records) => {
+                let registry = RecordData::read()?;
+                let mut names = Vec::new();
+                for record in records {
+                    let rec = registry.find(record).ok_or_else(|| DError::Invalid(
+                        format!("No record named {}", record)))?;
+                    if let Some(owner) = data.owner(&rec.name)
+                        .filter(|g| !g.name.eq_ignore_ascii_case(name))
+                    {
+                        return Err(DError::Invalid(format!(
+                            "The record {} is already shared by the group {}", rec.name, owner.name)));
+                    }
+                    names.push(rec.name.clone());
+                }
+                let group = data.group_mut(name)?;
+                for record in names {
+                    if !group.owns(&record) {
+                        group.records.push(record);
+                    }
+                }
+                let msg = format!("{} now shares {}", group.name, group.records.join(", "));
+                data.write()?;
+                Ok(msg.color(Color::BrightGreen).to_string())
+            },
+            Self::List => match data.groups().is_empty() {
+                _ if output::structured() => Ok(output::render(data.groups())?.unwrap_or_default()),
+                true => Ok("No groups yet".color(Color::BrightYellow).to_string()),
+                false => Ok(Group::table(data.groups()).to_string()),
+            },
+        }
+    }
+}
+
 impl FromArgMatches for UserCmd {
-    fn from_arg_matches(_matches: &ArgMatches) -> Self {
-        Self::print_help();
-        Self::default()
+    fn from_arg_matches(matches: &ArgMatches) -> Self {
+        let user = |m: &ArgMatches| m.value_of("USER").unwrap_or_default().to_string();
+        let values = |m: &ArgMatches, arg: &str| m.values_of(arg)
+            .map(|v| v.map(String::from).collect::<Vec<String>>())
+            .unwrap_or_default();
+        match matches.subcommand() {
+            Some(("new", sub)) => Self::New(user(sub)),
+            Some(("switch", sub)) => Self::Switch(user(sub)),
+            Some(("group", sub)) => {
+                let group = |m: &ArgMatches| m.value_of("GROUP").unwrap_or_default().to_string();
+                Self::Group(match sub.subcommand() {
+                    Some(("new", g)) => {
+                        let mut new = Group::new(&group(g),
+                            g.value_of("description").unwrap_or_default());
+                        new.members = values(g, "member");
+                        GroupCmd::New(new)
+                    },
+                    Some(("add", g)) => GroupCmd::Add(group(g), values(g, "USER")),
+                    Some(("share", g)) => GroupCmd::Share(group(g), values(g, "RECORD")),
+                    _ => GroupCmd::List,
+                })
+            },
+            Some(("user_help", _)) => Self::Help,
+            _ => Self::List,
+        }
     }
 }
 
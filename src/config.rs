@@ -1,28 +1,64 @@
 use crate::util;
+use crate::error::DError;
 use crate::models::Record;
 use std::{
     io::{self, prelude::*, Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     convert::TryInto,
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
+    sync::RwLock,
 };
 use serde::{Serialize, Deserialize};
+use ::config::{Config, Environment, File, FileFormat};
+
+lazy_static::lazy_static! {
+    /// The file given with `--cf`, layered over every other source
+    static ref CONFIG_FILE: RwLock<Option<PathBuf>> = RwLock::new(None);
+}
+
+/// The files the config is layered from, lowest precedence first. `DLOG_*`
+/// env vars, e.g. `DLOG_FORMAT__DATE_FORMAT`, are layered between the
+/// record's file and the `--cf` file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigLayer {
+    /// `dlog.toml` in the config dir
+    Global,
+    /// `.dlog/config.toml` of the record the current dir is in
+    Record,
+    /// The file given with `--cf`
+    File,
+}
+
+impl std::fmt::Display for ConfigLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Global => f.write_str("global"),
+            Self::Record => f.write_str("record"),
+            Self::File => f.write_str("--cf"),
+        }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, )]
 pub struct DConfig {
     name: Option<String>,
-    aliases: Option<Vec<String>>,
     // fact_aliases: Option<Vec<String>>,
     // record_aliases: Option<Vec<String>>,
     data_dir: PathBuf,
-    auth: Option<AuthConfig>,
     start_of_week: chrono::Weekday,
+    default_editor: Option<String>,
     format: FormatConfig,
     records: Option<Vec<Record>>,
     record: Option<RecordConfig>,
     item: Option<ItemConfig>,
     fact: Option<FactConfig>,
-    default_editor: Option<String>,
+    tags: Option<TagConfig>,
+    auth: Option<AuthConfig>,
+    sync: Option<SyncConfig>,
+    trash: Option<TrashConfig>,
+    /// Short names for commands, e.g. `w = "fact weight $1 kg"`
+    #[serde(default)]
+    aliases: BTreeMap<String, String>,
     // prompt_for_value: bool,
     // prompt_for_record: bool,
     // prompt_for_units: bool,
@@ -33,14 +69,17 @@ impl Default for DConfig {
     fn default() -> Self {
         Self {
             name: None,
-            aliases: None,
+            aliases: BTreeMap::new(),
             data_dir: util::default_data_dir(None).expect("no valid data dir"),
             auth: None,
+            sync: None,
+            trash: Some(TrashConfig::default()),
             format: FormatConfig::default(),
             start_of_week: chrono::Weekday::Sun,
             record: Some(RecordConfig::default()),
             item: None,
             fact: None,
+            tags: Some(TagConfig::default()),
             default_editor: None,
             records: None,
             // prompt_for_units: false,
@@ -57,18 +96,126 @@ impl DConfig {
         println!("{}", p);
     }
 
+    /// The config layered from its defaults, the global `dlog.toml`, the
+    /// current record's `.dlog/config.toml`, `DLOG_*` env vars and the
+    /// `--cf` file, each overriding the keys it sets
     pub fn load() -> crate::DResult<Self> {
         let mut buf = String::new();
         let mut cf = Self::file()?;
         cf.read_to_string(&mut buf)?;
-        if buf.len() == 0 {
-            let conf = Self::default();
-            cf.write_all(toml::to_string_pretty(&conf).expect("Could not create TOML").as_bytes())?;
-            Ok(conf)
-        } else {
-            let conf: Self = toml::from_str(buf.as_str()).expect("Invalid TOML");
-            Ok(conf)
+        if buf.trim().is_empty() {
+            cf.write_all(toml::to_string_pretty(&Self::default())?.as_bytes())?;
+        }
+        Self::layered(None)
+    }
+
+    /// Layers the config from its sources, with `with` read in place of
+    /// the file of its layer, to check a change before it is written
+    pub fn layered(with: Option<(ConfigLayer, &str)>) -> crate::DResult<Self> {
+        let mut conf = Config::new();
+        conf.merge(File::from_str(&toml::to_string(&Self::default())?, FileFormat::Toml))
+            .map_err(|e| DError::Invalid(format!("Invalid default config: {}", e)))?;
+        let mut env = false;
+        for (layer, path) in Self::sources()? {
+            if layer == ConfigLayer::File {
+                conf.merge(Environment::with_prefix("DLOG").separator("__"))
+                    .map_err(|e| DError::Invalid(format!("Invalid DLOG_ env var: {}", e)))?;
+                env = true;
+            }
+            let text = match with {
+                Some((l, text)) if l == layer => text.to_string(),
+                _ if path.exists() => std::fs::read_to_string(&path)?,
+                _ if layer == ConfigLayer::File => return Err(DError::Invalid(
+                    format!("No config file at {}", path.display()))),
+                _ => continue,
+            };
+            conf.merge(File::from_str(&text, FileFormat::Toml))
+                .map_err(|e| DError::Invalid(format!("Invalid TOML in {}: {}", path.display(), e)))?;
+        }
+        if !env {
+            conf.merge(Environment::with_prefix("DLOG").separator("__"))
+                .map_err(|e| DError::Invalid(format!("Invalid DLOG_ env var: {}", e)))?;
+        }
+        conf.try_into()
+            .map_err(|e| DError::Invalid(format!("Invalid config: {}", e)))
+    }
+
+    /// Sets the file given with `--cf`
+    pub fn set_file(path: PathBuf) {
+        if let Ok(mut file) = CONFIG_FILE.write() {
+            *file = Some(path);
+        }
+    }
+
+    /// Each layer's file, lowest precedence first, whether or not it exists
+    pub fn sources() -> crate::DResult<Vec<(ConfigLayer, PathBuf)>> {
+        let mut sources = vec![(ConfigLayer::Global, Self::default_dir()?.join("dlog.toml"))];
+        if let Some(path) = Self::record_file() {
+            sources.push((ConfigLayer::Record, path));
+        }
+        if let Some(path) = CONFIG_FILE.read().ok().and_then(|f| f.clone()) {
+            sources.push((ConfigLayer::File, path));
+        }
+        Ok(sources)
+    }
+
+    /// `.dlog/config.toml` of the record the current dir is in, i.e. of the
+    /// nearest dir up from it holding a record or such a file
+    pub fn record_file() -> Option<PathBuf> {
+        let cwd = std::env::current_dir().ok()?;
+        cwd.ancestors()
+            .find(|dir| dir.join(".dlog").join("config.toml").exists()
+                || crate::auth::exists(&dir.join("record.toml")))
+            .map(|dir| dir.join(".dlog").join("config.toml"))
+    }
+
+    /// The file of a layer, which `dlog config set` writes to
+    pub fn layer_file(layer: ConfigLayer) -> crate::DResult<PathBuf> {
+        Self::sources()?.into_iter()
+            .find(|(l, _)| *l == layer)
+            .map(|(_, path)| path)
+            .ok_or_else(|| DError::Invalid(match layer {
+                ConfigLayer::Record => "The current dir is not in a record".into(),
+                _ => format!("No {} config file was given", layer),
+            }))
+    }
+
+    /// Sets a dotted key such as `format.date_format` in the file of a
+    /// layer, if the config is still valid with it
+    pub fn set(layer: ConfigLayer, key: &str, value: toml::Value) -> crate::DResult<()> {
+        let path = Self::layer_file(layer)?;
+        let mut doc = read_doc(&path)?;
+        insert(&mut doc, key, value)?;
+        let text = toml::to_string_pretty(&doc)?;
+        let conf = Self::layered(Some((layer, &text)))?;
+        conf.format.check()?;
+        if lookup(&toml::Value::try_from(&conf)?, key).is_none() {
+            return Err(DError::Invalid(unknown_key(key)));
         }
+        write_doc(&path, &text)
+    }
+
+    /// Removes a dotted key from the file of a layer, leaving the layers
+    /// beneath it to set it
+    pub fn unset(layer: ConfigLayer, key: &str) -> crate::DResult<()> {
+        let path = Self::layer_file(layer)?;
+        let mut doc = read_doc(&path)?;
+        if remove(&mut doc, key).is_none() {
+            return Err(DError::Invalid(format!("{} is not set in {}", key, path.display())));
+        }
+        let text = toml::to_string_pretty(&doc)?;
+        Self::layered(Some((layer, &text)))?;
+        write_doc(&path, &text)
+    }
+
+    /// The value of a dotted key, or the whole config for an empty key
+    pub fn get(&self, key: &str) -> crate::DResult<toml::Value> {
+        let conf = toml::Value::try_from(self)?;
+        if key.is_empty() {
+            return Ok(conf);
+        }
+        lookup(&conf, key).cloned()
+            .ok_or_else(|| DError::Invalid(format!("{} is not set", key)))
     }
 
     pub fn file() -> crate::DResult<std::fs::File> {
@@ -105,14 +252,106 @@ impl DConfig {
         self.data_dir = dir;
         self
     }
+
+    /// The editor notes are written in: `default_editor` if set, otherwise
+    /// `$VISUAL` or `$EDITOR`, falling back to vi
+    pub fn editor(&self) -> String {
+        self.default_editor.clone()
+            .or_else(|| std::env::var("VISUAL").ok())
+            .or_else(|| std::env::var("EDITOR").ok())
+            .filter(|e| !e.trim().is_empty())
+            .unwrap_or_else(|| "vi".into())
+    }
+
+    /// Rules for pulling tags and mentions out of text values and notes,
+    /// or none if tagging is turned off
+    pub fn tag_rules(&self) -> Vec<TagRule> {
+        match &self.tags {
+            Some(tags) if !tags.enabled => Vec::new(),
+            Some(tags) => tags.rules.clone(),
+            None => TagConfig::default().rules,
+        }
+    }
+
+    /// The current user, whose name is given to the facts they log
+    pub fn username(&self) -> Option<String> {
+        self.auth.as_ref().and_then(|a| a.username.clone())
+    }
+
+    /// The command printing the passphrase the store is encrypted with
+    pub fn password_cmd(&self) -> Option<String> {
+        self.auth.as_ref().and_then(|a| a.password_cmd.clone())
+    }
+
+    pub fn password(&self) -> Option<String> {
+        self.auth.as_ref().and_then(|a| a.password.clone())
+    }
+
+    pub fn session_minutes(&self) -> Option<i64> {
+        self.auth.as_ref().and_then(|a| a.session_minutes)
+    }
+
+    pub fn api_token(&self) -> Option<String> {
+        self.auth.as_ref().and_then(|a| a.token.clone())
+    }
+
+    /// The server `dlog sync` pushes to and pulls from
+    pub fn sync_url(&self) -> Option<String> {
+        self.sync.as_ref().and_then(|s| s.url.clone())
+    }
+
+    pub fn sync_token(&self) -> Option<String> {
+        self.sync.as_ref().and_then(|s| s.token.clone())
+    }
+
+    /// How long deleted things are kept in the trash, or `None` to keep
+    /// them until it is emptied
+    pub fn trash_retention(&self) -> Option<chrono::Duration> {
+        let days = self.trash.as_ref()
+            .map(|t| t.retention_days)
+            .unwrap_or_else(|| TrashConfig::default().retention_days);
+        match days {
+            0 => None,
+            days => Some(chrono::Duration::days(days as i64)),
+        }
+    }
+
+    pub fn aliases(&self) -> &BTreeMap<String, String> {
+        &self.aliases
+    }
+
+    pub fn format(&self) -> &FormatConfig {
+        &self.format
+    }
+
+    pub fn init_behavior(&self) -> InitBehavior {
+        self.record.as_ref()
+            .map(|r| r.init_behavior.clone())
+            .unwrap_or_default()
+    }
 }
 
 
-#[derive(Debug, Serialize, Deserialize)]
+/// How dates and times are written in tables, e.g. `YYYY-MM-DD` and
+/// `HH:MM` (strftime patterns such as `%d %b` work too), and which columns
+/// of each table are shown
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FormatConfig {
     time_format: String,
     hour_format: HourFormat,
     date_format: String,
+    /// Show times as e.g. "3 hours ago" rather than dates
+    #[serde(default)]
+    relative_times: bool,
+    /// Draw tables as `--pretty` does unless `--compact` is given
+    #[serde(default)]
+    pretty: bool,
+    /// The widest tables are drawn, fitting the terminal if not set
+    #[serde(default)]
+    table_width: Option<u16>,
+    /// Columns and widths of tables by name, e.g. `[format.tables.facts]`
+    #[serde(default)]
+    tables: HashMap<String, TableConfig>,
 }
 
 impl Default for FormatConfig {
@@ -120,8 +359,190 @@ impl Default for FormatConfig {
         Self {
             time_format: "HH:MM:SS".into(),
             date_format: "YYYY-MM-DD".into(),
-            hour_format: HourFormat::default()
+            hour_format: HourFormat::default(),
+            relative_times: false,
+            pretty: false,
+            table_width: None,
+            tables: HashMap::new(),
+        }
+    }
+}
+
+impl FormatConfig {
+
+    /// Refuses date and time formats which can't be written, e.g. `%Q`, or
+    /// `%H` in a date format as dates have no hours
+    pub fn check(&self) -> crate::DResult<()> {
+        if !writes_date(&self.date_pattern()) {
+            return Err(DError::Invalid(format!("{} is not a date format", self.date_format)));
         }
+        if !writes_time(&self.time_pattern()) {
+            return Err(DError::Invalid(format!("{} is not a time format", self.time_format)));
+        }
+        Ok(())
+    }
+
+    /// The date format as a strftime pattern, i.e. `%Y-%m-%d` for
+    /// `YYYY-MM-DD`
+    pub fn date_pattern(&self) -> String {
+        strftime(&self.date_format, &[
+            ("YYYY", "%Y"), ("YY", "%y"), ("MMMM", "%B"), ("MMM", "%b"),
+            ("MM", "%m"), ("DD", "%d"), ("dddd", "%A"), ("ddd", "%a"),
+        ])
+    }
+
+    /// The time format as a strftime pattern, with hours on the 12 hour
+    /// clock and AM or PM after them if `hour_format` is `TwelveHr`
+    pub fn time_pattern(&self) -> String {
+        let hours = match self.hour_format {
+            HourFormat::TwelveHr => "%I",
+            HourFormat::TwentyFourHr => "%H",
+        };
+        let mut pattern = strftime(&self.time_format, &[
+            ("HH", hours), ("MM", "%M"), ("SS", "%S"), ("AM", "%p"), ("PM", "%p"),
+        ]);
+        if matches!(self.hour_format, HourFormat::TwelveHr) && !pattern.contains("%p") {
+            pattern.push_str(" %p");
+        }
+        pattern
+    }
+
+    pub fn relative_times(&self) -> bool { self.relative_times }
+
+    pub fn pretty(&self) -> bool { self.pretty }
+
+    pub fn table_width(&self) -> Option<u16> { self.table_width }
+
+    pub fn tables(&self) -> &HashMap<String, TableConfig> { &self.tables }
+}
+
+/// Whether a date can be written with a strftime pattern
+pub fn writes_date(pattern: &str) -> bool {
+    use std::fmt::Write as _;
+    let date = chrono::NaiveDate::from_ymd(2000, 1, 1);
+    write!(String::new(), "{}", date.format(pattern)).is_ok()
+}
+
+/// Whether a time can be written with a strftime pattern
+pub fn writes_time(pattern: &str) -> bool {
+    use std::fmt::Write as _;
+    write!(String::new(), "{}", chrono::Local::now().format(pattern)).is_ok()
+}
+
+/// Replaces the tokens of a format such as `YYYY-MM-DD` with strftime
+/// specifiers, longest first. Formats which are already strftime patterns
+/// are kept as they are.
+fn strftime(format: &str, tokens: &[(&str, &str)]) -> String {
+    if format.contains('%') {
+        return format.to_string();
+    }
+    let mut out = String::new();
+    let mut rest = format;
+    while !rest.is_empty() {
+        match tokens.iter().find(|(token, _)| rest.starts_with(token)) {
+            Some((token, spec)) => {
+                out.push_str(spec);
+                rest = &rest[token.len()..];
+            },
+            None => {
+                let c = rest.chars().next().unwrap_or_default();
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+            },
+        }
+    }
+    out
+}
+
+/// The columns of a table to show, in order, and the widest each may be,
+/// by their headers, e.g.
+///
+/// ```toml
+/// [format.tables.facts]
+/// columns = ["Fact", "Value", "Created"]
+/// widths = { Notes = 40 }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TableConfig {
+    #[serde(default)]
+    pub columns: Vec<String>,
+    #[serde(default)]
+    pub widths: HashMap<String, u16>,
+}
+
+/// Parses a value given on the command line as TOML, e.g. `true`, `3` or
+/// `["a", "b"]`, or takes it as a string
+pub fn parse_value(raw: &str) -> toml::Value {
+    toml::from_str::<toml::Value>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut v| v.as_table_mut().and_then(|t| t.remove("value")))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+fn read_doc(path: &Path) -> crate::DResult<toml::Value> {
+    let text = match path.exists() {
+        true => std::fs::read_to_string(path)?,
+        false => String::new(),
+    };
+    toml::from_str(&text)
+        .map_err(|e| DError::Invalid(format!("Invalid TOML in {}: {}", path.display(), e)))
+}
+
+fn write_doc(path: &Path, text: &str) -> crate::DResult<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+fn lookup<'a>(value: &'a toml::Value, key: &str) -> Option<&'a toml::Value> {
+    key.split('.').try_fold(value, |v, k| v.get(k))
+}
+
+fn insert(doc: &mut toml::Value, key: &str, value: toml::Value) -> crate::DResult<()> {
+    let mut keys = key.split('.').collect::<Vec<&str>>();
+    let last = keys.pop().filter(|k| !k.is_empty())
+        .ok_or_else(|| DError::Invalid("Give a key such as format.date_format".into()))?;
+    let mut table = doc.as_table_mut()
+        .ok_or_else(|| DError::Invalid("The config is not a table".into()))?;
+    for (i, k) in keys.iter().enumerate() {
+        table = table.entry(k.to_string())
+            .or_insert_with(|| toml::Value::Table(Default::default()))
+            .as_table_mut()
+            .ok_or_else(|| DError::Invalid(format!("{} is not a table", keys[..=i].join("."))))?;
+    }
+    table.insert(last.to_string(), value);
+    Ok(())
+}
+
+fn remove(doc: &mut toml::Value, key: &str) -> Option<toml::Value> {
+    match key.rsplit_once('.') {
+        Some((parent, last)) => key_mut(doc, parent)?.as_table_mut()?.remove(last),
+        None => doc.as_table_mut()?.remove(key),
+    }
+}
+
+fn key_mut<'a>(value: &'a mut toml::Value, key: &str) -> Option<&'a mut toml::Value> {
+    key.split('.').try_fold(value, |v, k| v.get_mut(k))
+}
+
+/// Names the keys next to an unknown one, from those the default config
+/// has
+fn unknown_key(key: &str) -> String {
+    let defaults = toml::Value::try_from(DConfig::default()).ok();
+    let parent = key.rsplit_once('.').map(|(p, _)| p);
+    let siblings = defaults.as_ref()
+        .and_then(|d| match parent {
+            Some(parent) => lookup(d, parent),
+            None => Some(d),
+        })
+        .and_then(toml::Value::as_table)
+        .map(|t| t.keys().map(String::as_str).collect::<Vec<&str>>().join(", "));
+    match (parent, siblings) {
+        (Some(parent), Some(keys)) => format!("Unknown config key {}, {} has {}", key, parent, keys),
+        (None, Some(keys)) => format!("Unknown config key {}, expected one of {}", key, keys),
+        _ => format!("Unknown config key {}", key),
     }
 }
 
@@ -131,6 +552,11 @@ pub struct AuthConfig {
     password: Option<String>,
     email: Option<String>,
     password_cmd: Option<String>,
+    /// How long `dlog auth unlock` keeps the store open for, 15 minutes
+    /// if not set
+    session_minutes: Option<i64>,
+    /// Bearer token `dlog serve` requires of requests, if set
+    token: Option<String>,
 }
 
 impl Default for AuthConfig {
@@ -140,10 +566,33 @@ impl Default for AuthConfig {
             email: None,
             password_cmd: None,
             password: None,
+            session_minutes: None,
+            token: None,
         }
     }
 }
 
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SyncConfig {
+    /// e.g. `http://127.0.0.1:7878`
+    url: Option<String>,
+    /// Sent as a bearer token, if the server wants one
+    token: Option<String>,
+}
+
+/// How long deleted records, items and entries stay in the trash before
+/// they are purged. `0` keeps them until `dlog trash empty`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrashConfig {
+    retention_days: u32,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self { retention_days: 30 }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, )]
 pub struct RecordConfig {
     init_behavior: InitBehavior,
@@ -199,12 +648,51 @@ impl Default for DefaultFactBehavior {
     }
 }
 
+/// How text values and notes of fact entries are scanned for tags when
+/// logged, e.g. `#tag`, `@record` or `+person`
 #[derive(Debug, Serialize, Deserialize)]
-pub struct GitConfig {
+pub struct TagConfig {
+    enabled: bool,
+    #[serde(default)]
+    rules: Vec<TagRule>,
+}
 
+impl Default for TagConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            rules: vec![
+                TagRule { prefix: "#".into(), kind: TagKind::Attrib },
+                TagRule { prefix: "@".into(), kind: TagKind::Mention },
+                TagRule { prefix: "+".into(), kind: TagKind::Person },
+            ],
+        }
+    }
+}
+
+/// Words starting with `prefix` are extracted as the given kind of tag
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagRule {
+    pub prefix: String,
+    pub kind: TagKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TagKind {
+    /// Becomes an attribute of the entry, e.g. `#dinner` or `#mood=good`
+    Attrib,
+    /// Relates the entry to a record or item of that name
+    Mention,
+    /// Relates the entry to a person or topic of that name
+    Person,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+pub struct GitConfig {
+
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum InitBehavior {
     CurrentDir,
     DataDir,
@@ -221,6 +709,32 @@ impl Default for InitBehavior {
     }
 }
 
+impl InitBehavior {
+
+    /// The directory new records are created under, i.e. a record named
+    /// `health` with the default behavior lives in `<data dir>/dlog/health`
+    pub fn dir(&self) -> crate::DResult<PathBuf> {
+        let dir = match self {
+            Self::CurrentDir => std::env::current_dir()?,
+            Self::DataDir => util::default_data_dir(None)?,
+            Self::Documents => dirs_next::document_dir()
+                .ok_or(crate::error::DError::ParsePath)?
+                .join("dlog"),
+            Self::HomeDir => dirs_next::home_dir()
+                .ok_or(crate::error::DError::ParsePath)?
+                .join(".dlog"),
+            Self::Desktop => dirs_next::desktop_dir()
+                .ok_or(crate::error::DError::ParsePath)?
+                .join("dlog"),
+            Self::DataLocalDir => dirs_next::data_local_dir()
+                .ok_or(crate::error::DError::ParsePath)?
+                .join("dlog"),
+            Self::CustomDir(dir) => dir.clone(),
+        };
+        Ok(dir)
+    }
+}
+
 impl std::str::FromStr for InitBehavior {
     type Err = std::convert::Infallible;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -237,7 +751,7 @@ impl std::str::FromStr for InitBehavior {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum HourFormat {
     TwelveHr,
     TwentyFourHr,
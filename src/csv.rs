@@ -34,12 +34,28 @@ pub fn csv_writer<P, I>(path: P, item: I) -> io::Result<csv::Writer<fs::File>>
         P: Into<PathBuf>,
         I: serde::Serialize,
 {
+    let path = path.into();
+    crate::history::touch(&path)?;
     let mut wtr = csv::WriterBuilder::new()
         .has_headers(true)
         .flexible(true)
-        .from_path(&path.into())?;
+        .from_path(&path)?;
     wtr.serialize(item)?;
     wtr.flush()?;
     Ok(wtr)
 }
 
+/// Opens a CSV to append rows to, only writing headers if the file is new
+pub fn csv_appender<P: Into<PathBuf>>(path: P) -> io::Result<csv::Writer<fs::File>> {
+    let path = path.into();
+    crate::history::touch(&path)?;
+    let file = fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(&path)?;
+    let wtr = csv::WriterBuilder::new()
+        .has_headers(file.metadata()?.len() == 0)
+        .flexible(true)
+        .from_writer(file);
+    Ok(wtr)
+}
@@ -7,10 +7,15 @@ pub enum DError {
     Io(io::Error),
     Csv(csv::Error),
     Config(toml::de::Error),
+    Toml(toml::ser::Error),
     KeyRejected(ring::error::KeyRejected),
+    Json(serde_json::Error),
+    Http(reqwest::Error),
     ParsePath,
     ParseDate,
     NotFound,
+    AlreadyExists(String),
+    Invalid(String),
 }
 
 #[derive(Debug)]
@@ -25,10 +30,15 @@ impl error::Error for DError {
             DError::Io(ref err) => Some(err),
             DError::Csv(ref err) => Some(err),
             DError::Config(ref err) => Some(err),
+            DError::Toml(ref err) => Some(err),
             DError::KeyRejected(ref err) => None,
+            DError::Json(ref err) => Some(err),
+            DError::Http(ref err) => Some(err),
             DError::ParsePath => None,
             DError::NotFound => None,
             DError::ParseDate => None,
+            DError::AlreadyExists(_) => None,
+            DError::Invalid(_) => None,
         }
     }
 
@@ -39,11 +49,16 @@ impl fmt::Display for DError {
         match *self {
             DError::Io(ref err) => write!(f, "IO error: {}", err),
             DError::Config(ref err) => write!(f, "Config TOML parse err {}", err),
+            DError::Toml(ref err) => write!(f, "Could not write TOML: {}", err),
             DError::Csv(ref err) => write!(f, "Csv error: {}", err),
             DError::ParsePath => write!(f, "Invalid path"),
             DError::NotFound => write!(f, "What you were looking for is not there"),
             DError::KeyRejected(ref err) => write!(f, "Invalid key {}", err),
+            DError::Json(ref err) => write!(f, "JSON error: {}", err),
+            DError::Http(ref err) => write!(f, "HTTP error: {}", err),
             DError::ParseDate => write!(f, "Could not parse date"),
+            DError::AlreadyExists(ref what) => write!(f, "{} already exists", what),
+            DError::Invalid(ref why) => write!(f, "{}", why),
         }
     }
 }
@@ -61,6 +76,18 @@ impl From<csv::Error> for DError {
 }
 
 
+impl From<toml::de::Error> for DError {
+    fn from(err: toml::de::Error) -> Self {
+        DError::Config(err)
+    }
+}
+
+impl From<toml::ser::Error> for DError {
+    fn from(err: toml::ser::Error) -> Self {
+        DError::Toml(err)
+    }
+}
+
 impl From<std::convert::Infallible> for DError {
     fn from(_: std::convert::Infallible) -> Self {
         DError::ParsePath
@@ -72,3 +99,15 @@ impl From<ring::error::KeyRejected> for DError {
         DError::KeyRejected(key_error)
     }
 }
+
+impl From<serde_json::Error> for DError {
+    fn from(err: serde_json::Error) -> Self {
+        DError::Json(err)
+    }
+}
+
+impl From<reqwest::Error> for DError {
+    fn from(err: reqwest::Error) -> Self {
+        DError::Http(err)
+    }
+}
@@ -0,0 +1,455 @@
+//! The operation journal behind `dlog undo`, `dlog redo` and `dlog history`.
+//!
+//! Writers call [`touch`] on a file before they change it, which keeps what
+//! the file held. Once a command is done, [`commit`] appends an operation
+//! to `history/journal.ndjson` in the data dir with the bytes of each
+//! changed file which differ before and after, stored as blobs named by
+//! their SHA-256. Undoing an operation puts back what its files held
+//! before, and redoing it what they held after. The journal is appended to,
+//! undos and redos being events of their own, and only the last [`KEEP`]
+//! operations are kept.
+use std::{
+    collections::{BTreeMap, HashSet, btree_map::Entry},
+    fs, io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use chrono::{DateTime, Local};
+use comfy_table::{
+    Table, ContentArrangement, presets::UTF8_BORDERS_ONLY,
+    Cell, Attribute, Color as TColor,
+};
+use ring::digest;
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use crate::{util, output, error::DError};
+
+/// How many operations the journal keeps, the oldest being dropped first
+pub const KEEP: usize = 1000;
+
+lazy_static::lazy_static! {
+    /// What each file touched by this command held before it
+    static ref TOUCHED: Mutex<BTreeMap<PathBuf, Option<Vec<u8>>>> = Mutex::new(BTreeMap::new());
+}
+
+/// A command which changed files, with the blobs of what they held before
+/// and after
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Op {
+    pub id: Uuid,
+    pub at: DateTime<Local>,
+    pub cmd: String,
+    pub changes: Vec<Change>,
+}
+
+/// A file an operation changed, by the hashes of what it held before and
+/// after, `None` being a file which did not exist. From `at` on, the blob
+/// `removed` was replaced by the blob `added`, so logging an entry keeps
+/// the row it appended rather than the whole CSV twice.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    pub path: PathBuf,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub at: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub removed: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub added: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+enum Event {
+    Op(Op),
+    Undo { id: Uuid, at: DateTime<Local> },
+    Redo { id: Uuid, at: DateTime<Local> },
+}
+
+/// An operation in the journal, and whether it is undone
+#[derive(Debug, Serialize)]
+pub struct Logged {
+    #[serde(flatten)]
+    pub op: Op,
+    pub undone: bool,
+}
+
+/// Keeps what a file holds before it is written or removed. Only the first
+/// touch of a file in a command counts.
+pub fn touch(path: &Path) -> io::Result<()> {
+    let path = absolute(path)?;
+    let mut touched = TOUCHED.lock().expect("history lock poisoned");
+    if let Entry::Vacant(entry) = touched.entry(path) {
+        let before = read(entry.key())?;
+        entry.insert(before);
+    }
+    Ok(())
+}
+
+/// Touches every file under a directory, e.g. before removing it
+pub fn touch_dir(dir: &Path) -> io::Result<()> {
+    for file in files(dir)? {
+        touch(&file)?;
+    }
+    Ok(())
+}
+
+/// Touches every file under a directory and where it will be once the
+/// directory is moved to `to`
+pub fn touch_move(from: &Path, to: &Path) -> io::Result<()> {
+    for file in files(from)? {
+        touch(&file)?;
+        if let Ok(rel) = file.strip_prefix(from) {
+            touch(&to.join(rel))?;
+        }
+    }
+    Ok(())
+}
+
+/// Journals the files touched by the command `cmd` which it changed, if
+/// any, returning the operation
+pub fn commit(cmd: &str) -> crate::DResult<Option<Op>> {
+    let touched = std::mem::take(&mut *TOUCHED.lock().expect("history lock poisoned"));
+    let mut changes = Vec::new();
+    for (path, before) in touched {
+        let after = read(&path)?;
+        if before == after {
+            continue;
+        }
+        let (at, removed, added) = delta(before.as_deref().unwrap_or_default(),
+            after.as_deref().unwrap_or_default());
+        changes.push(Change {
+            before: before.as_deref().map(hash),
+            after: after.as_deref().map(hash),
+            at,
+            removed: store(removed)?,
+            added: store(added)?,
+            path,
+        });
+    }
+    if changes.is_empty() {
+        return Ok(None);
+    }
+    let op = Op { id: Uuid::new_v4(), at: Local::now(), cmd: cmd.into(), changes };
+    append(&Event::Op(op.clone()))?;
+    prune()?;
+    Ok(Some(op))
+}
+
+/// Undoes the last `n` operations not yet undone, newest first. Stops at an
+/// operation whose files were changed since, which is left as it is.
+pub fn undo(n: usize) -> crate::DResult<Vec<Op>> {
+    let mut undone = Vec::new();
+    for _ in 0..n {
+        let op = match replay()?.0.pop() {
+            Some(op) => op,
+            None if undone.is_empty() => return Err(DError::Invalid("Nothing to undo".into())),
+            None => break,
+        };
+        check(&op, |c| &c.after, "undone")?;
+        for change in &op.changes {
+            restore(change, true)?;
+        }
+        append(&Event::Undo { id: op.id, at: Local::now() })?;
+        undone.push(op);
+    }
+    Ok(undone)
+}
+
+/// Redoes the last `n` operations undone since anything else was done
+pub fn redo(n: usize) -> crate::DResult<Vec<Op>> {
+    let mut redone = Vec::new();
+    for _ in 0..n {
+        let op = match replay()?.1.pop() {
+            Some(op) => op,
+            None if redone.is_empty() => return Err(DError::Invalid("Nothing to redo".into())),
+            None => break,
+        };
+        check(&op, |c| &c.before, "redone")?;
+        for change in &op.changes {
+            restore(change, false)?;
+        }
+        append(&Event::Redo { id: op.id, at: Local::now() })?;
+        redone.push(op);
+    }
+    Ok(redone)
+}
+
+/// The last `n` operations, oldest first
+pub fn history(n: usize) -> crate::DResult<Vec<Logged>> {
+    let (done, _) = replay()?;
+    let ops = events()?.into_iter()
+        .filter_map(|e| match e {
+            Event::Op(op) => Some(op),
+            _ => None,
+        })
+        .collect::<Vec<Op>>();
+    Ok(ops.into_iter()
+        .rev()
+        .take(n)
+        .rev()
+        .map(|op| Logged { undone: !done.iter().any(|d| d.id == op.id), op })
+        .collect())
+}
+
+pub fn table(logged: &[Logged]) -> Table {
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec![
+            Cell::new("Id").add_attribute(Attribute::Bold),
+            Cell::new("When").add_attribute(Attribute::Bold),
+            Cell::new("Command").add_attribute(Attribute::Bold)
+                .fg(TColor::Blue),
+            Cell::new("Files").add_attribute(Attribute::Bold),
+            Cell::new("State").add_attribute(Attribute::Bold),
+        ]);
+    for entry in logged {
+        table.add_row(vec![
+            Cell::new(&entry.op.id.to_string()[..8]),
+            Cell::new(output::datetime(&entry.op.at)),
+            Cell::new(&entry.op.cmd),
+            Cell::new(entry.op.changes.len()),
+            match entry.undone {
+                true => Cell::new("undone").fg(TColor::Yellow),
+                false => Cell::new("done").fg(TColor::Green),
+            },
+        ]);
+    }
+    output::table("history", table)
+}
+
+/// The operations done, oldest first, and those which may be redone, the
+/// last undone last
+fn replay() -> crate::DResult<(Vec<Op>, Vec<Op>)> {
+    let (mut done, mut undone): (Vec<Op>, Vec<Op>) = (Vec::new(), Vec::new());
+    for event in events()? {
+        match event {
+            Event::Op(op) => {
+                done.push(op);
+                undone.clear();
+            },
+            Event::Undo { id, .. } => if let Some(i) = done.iter().rposition(|o| o.id == id) {
+                undone.push(done.remove(i));
+            },
+            Event::Redo { id, .. } => if let Some(i) = undone.iter().rposition(|o| o.id == id) {
+                done.push(undone.remove(i));
+            },
+        }
+    }
+    Ok((done, undone))
+}
+
+/// Refuses to undo or redo an operation if any of its files no longer hold
+/// what `expected` says. Opens each file's record or area first if the
+/// store is locked.
+fn check<F>(op: &Op, expected: F, verb: &str) -> crate::DResult<()>
+where
+    F: Fn(&Change) -> &Option<String>,
+{
+    for change in &op.changes {
+        crate::auth::open_path(&change.path)?;
+        let now = read(&change.path)?.as_deref().map(hash);
+        if &now != expected(change) {
+            return Err(DError::Invalid(format!("{} changed after `{}`, so it can't be {}",
+                change.path.display(), op.cmd, verb)));
+        }
+    }
+    Ok(())
+}
+
+/// Puts back what a file held before a change when undoing it, or after
+/// it otherwise, removing the file if it did not exist
+fn restore(change: &Change, undo: bool) -> crate::DResult<()> {
+    let (to, cut, put) = match undo {
+        true => (&change.before, &change.added, &change.removed),
+        false => (&change.after, &change.removed, &change.added),
+    };
+    let path = &change.path;
+    match to {
+        Some(_) => {
+            let mut bytes = read(path)?.unwrap_or_default();
+            let cut = blob(cut.as_deref())?.len();
+            bytes.splice(change.at..change.at + cut, blob(put.as_deref())?);
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(path, bytes)?;
+        },
+        None if path.exists() => {
+            fs::remove_file(path)?;
+            if let Some(dir) = path.parent() {
+                let _ = fs::remove_dir(dir);
+            }
+        },
+        None => {},
+    }
+    Ok(())
+}
+
+fn dir() -> crate::DResult<PathBuf> {
+    util::default_data_dir(Some("history"))
+}
+
+fn events() -> crate::DResult<Vec<Event>> {
+    let path = dir()?.join("journal.ndjson");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    fs::read_to_string(path)?.lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| serde_json::from_str(l).map_err(DError::from))
+        .collect()
+}
+
+fn append(event: &Event) -> crate::DResult<()> {
+    let dir = dir()?;
+    fs::create_dir_all(&dir)?;
+    let mut file = fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(dir.join("journal.ndjson"))?;
+    writeln!(file, "{}", serde_json::to_string(event)?)?;
+    Ok(())
+}
+
+/// Drops every operation which changed a file under any of `paths`, e.g.
+/// once what the files held is deleted for good, so none of it is kept
+pub fn forget(paths: &[PathBuf]) -> crate::DResult<()> {
+    let paths = paths.iter()
+        .map(|p| absolute(p))
+        .collect::<io::Result<Vec<PathBuf>>>()?;
+    rewrite(|op| !op.changes.iter().any(|c| paths.iter().any(|p| c.path.starts_with(p))))
+}
+
+/// Drops the oldest operations past the last [`KEEP`]
+fn prune() -> crate::DResult<()> {
+    let ops = events()?.iter().filter(|e| matches!(e, Event::Op(_))).count();
+    let mut drop = ops.saturating_sub(KEEP);
+    rewrite(|_| match drop {
+        0 => true,
+        _ => {
+            drop -= 1;
+            false
+        },
+    })
+}
+
+/// Keeps only the operations `keep` is true for in the journal, along with
+/// their undos and redos, and removes any blobs the others alone needed
+fn rewrite<F: FnMut(&Op) -> bool>(mut keep: F) -> crate::DResult<()> {
+    let mut dropped = HashSet::new();
+    let kept = events()?.into_iter()
+        .filter(|e| match e {
+            Event::Op(op) if keep(op) => true,
+            Event::Op(op) => {
+                dropped.insert(op.id);
+                false
+            },
+            Event::Undo { id, .. } | Event::Redo { id, .. } => !dropped.contains(id),
+        })
+        .collect::<Vec<Event>>();
+    if dropped.is_empty() {
+        return Ok(());
+    }
+    let dir = dir()?;
+    let mut journal = String::new();
+    for event in &kept {
+        journal.push_str(&serde_json::to_string(event)?);
+        journal.push('\n');
+    }
+    let tmp = dir.join("journal.ndjson.tmp");
+    fs::write(&tmp, journal)?;
+    fs::rename(tmp, dir.join("journal.ndjson"))?;
+    let used = kept.iter()
+        .filter_map(|e| match e {
+            Event::Op(op) => Some(op),
+            _ => None,
+        })
+        .flat_map(|op| op.changes.iter())
+        .flat_map(|c| vec![&c.before, &c.after, &c.removed, &c.added])
+        .flatten()
+        .collect::<HashSet<&String>>();
+    let blobs = dir.join("blobs");
+    if !blobs.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(blobs)? {
+        let entry = entry?;
+        if !used.contains(&entry.file_name().to_string_lossy().to_string()) {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Where two versions of a file differ: how many bytes they begin with
+/// alike, and what each holds in place of the other before the bytes they
+/// end with alike
+fn delta<'a>(before: &'a [u8], after: &'a [u8]) -> (usize, &'a [u8], &'a [u8]) {
+    let start = before.iter().zip(after).take_while(|(b, a)| b == a).count();
+    let end = before[start..].iter().rev()
+        .zip(after[start..].iter().rev())
+        .take_while(|(b, a)| b == a)
+        .count();
+    (start, &before[start..before.len() - end], &after[start..after.len() - end])
+}
+
+/// Keeps some bytes as a blob, returning its name, or nothing if there are
+/// none
+fn store(bytes: &[u8]) -> crate::DResult<Option<String>> {
+    if bytes.is_empty() {
+        return Ok(None);
+    }
+    let name = hash(bytes);
+    let blobs = dir()?.join("blobs");
+    fs::create_dir_all(&blobs)?;
+    let path = blobs.join(&name);
+    if !path.exists() {
+        fs::write(path, bytes)?;
+    }
+    Ok(Some(name))
+}
+
+fn blob(name: Option<&str>) -> crate::DResult<Vec<u8>> {
+    match name {
+        Some(name) => Ok(fs::read(dir()?.join("blobs").join(name))?),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn hash(bytes: &[u8]) -> String {
+    digest::digest(&digest::SHA256, bytes).as_ref().iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn read(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn absolute(path: &Path) -> io::Result<PathBuf> {
+    match path.is_absolute() {
+        true => Ok(path.to_path_buf()),
+        false => Ok(std::env::current_dir()?.join(path)),
+    }
+}
+
+fn files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !dir.is_dir() {
+        return Ok(files);
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        match path.is_dir() {
+            true => files.extend(self::files(&path)?),
+            false => files.push(path),
+        }
+    }
+    Ok(files)
+}
@@ -0,0 +1,203 @@
+//! A small blocking HTTP/1.1 server, enough for the sync reference server
+//! and `dlog serve` to answer JSON requests one at a time on a LAN.
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+use serde::{Serialize, de::DeserializeOwned};
+use crate::error::DError;
+
+/// Requests with larger bodies are refused
+const MAX_BODY: usize = 16 * 1024 * 1024;
+/// Request and header lines longer than this are refused
+const MAX_LINE: usize = 8 * 1024;
+/// Requests with more headers are refused
+const MAX_HEADERS: usize = 100;
+/// How long a client may take to send a request or read the response
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Default)]
+pub struct Request {
+    pub method: String,
+    /// The path without the query string, e.g. `/v1/entities`
+    pub path: String,
+    pub query: HashMap<String, String>,
+    /// Headers, with lowercase names
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Request {
+
+    pub fn read(stream: &mut TcpStream) -> crate::DResult<Self> {
+        let mut reader = BufReader::new(stream);
+        let line = read_line(&mut reader)?;
+        let mut parts = line.split_whitespace();
+        let (method, target) = match (parts.next(), parts.next()) {
+            (Some(method), Some(target)) => (method.to_uppercase(), target.to_string()),
+            _ => return Err(DError::Invalid("Malformed request line".into())),
+        };
+        let mut headers = HashMap::new();
+        for count in 0.. {
+            let line = read_line(&mut reader)?;
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if count == MAX_HEADERS {
+                return Err(DError::Invalid("Too many headers".into()));
+            }
+            if let Some(idx) = line.find(':') {
+                headers.insert(line[..idx].trim().to_lowercase(), line[idx + 1..].trim().to_string());
+            }
+        }
+        let len = headers.get("content-length")
+            .and_then(|l| l.parse::<usize>().ok())
+            .unwrap_or(0);
+        if len > MAX_BODY {
+            return Err(DError::Invalid("Request body too large".into()));
+        }
+        let mut body = vec![0u8; len];
+        reader.read_exact(&mut body)?;
+        let (path, query) = match target.find('?') {
+            Some(idx) => (target[..idx].to_string(), parse_query(&target[idx + 1..])),
+            None => (target, HashMap::new()),
+        };
+        Ok(Self { method, path, query, headers, body })
+    }
+
+    /// The token of an `Authorization: Bearer <token>` header
+    pub fn bearer(&self) -> Option<&str> {
+        self.headers.get("authorization")
+            .and_then(|a| a.strip_prefix("Bearer "))
+            .map(str::trim)
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> crate::DResult<T> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+
+    /// The path split into its segments, e.g. `["v1", "facts"]`
+    pub fn segments(&self) -> Vec<&str> {
+        self.path.split('/').filter(|s| !s.is_empty()).collect()
+    }
+}
+
+impl Response {
+
+    pub fn json<T: Serialize>(status: u16, body: &T) -> Self {
+        match serde_json::to_string(body) {
+            Ok(body) => Self { status, body },
+            Err(e) => Self::error(500, &e.to_string()),
+        }
+    }
+
+    /// An error as `{"error": "..."}`
+    pub fn error(status: u16, msg: &str) -> Self {
+        Self::json(status, &serde_json::json!({ "error": msg }))
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            201 => "Created",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            409 => "Conflict",
+            _ => "Internal Server Error",
+        }
+    }
+
+    pub fn write(&self, stream: &mut TcpStream) -> crate::DResult<()> {
+        write!(stream, "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status, self.reason(), self.body.len(), self.body)?;
+        stream.flush()?;
+        Ok(())
+    }
+}
+
+/// Answers requests one at a time. Requests which cannot be read are
+/// answered with a 400, and connections which cannot be accepted are skipped.
+pub fn serve<F: FnMut(&Request) -> Response>(listener: TcpListener, mut handler: F)
+    -> crate::DResult<()>
+{
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Could not accept a connection: {}", e);
+                continue;
+            },
+        };
+        if let Err(e) = stream.set_read_timeout(Some(TIMEOUT))
+            .and_then(|_| stream.set_write_timeout(Some(TIMEOUT)))
+        {
+            eprintln!("Could not set timeouts: {}", e);
+            continue;
+        }
+        let res = match Request::read(&mut stream) {
+            Ok(req) => handler(&req),
+            Err(e) => Response::error(400, &e.to_string()),
+        };
+        if let Err(e) = res.write(&mut stream) {
+            eprintln!("Could not answer request: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Reads a line of at most `MAX_LINE` bytes, empty at the end of the stream
+fn read_line<R: BufRead>(reader: &mut R) -> crate::DResult<String> {
+    let mut line = String::new();
+    reader.take(MAX_LINE as u64 + 1).read_line(&mut line)?;
+    if line.len() > MAX_LINE {
+        return Err(DError::Invalid("Request line or header too long".into()));
+    }
+    Ok(line)
+}
+
+/// Splits a query string into its decoded keys and values
+pub fn parse_query(query: &str) -> HashMap<String, String> {
+    query.split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| match p.find('=') {
+            Some(idx) => (decode(&p[..idx]), decode(&p[idx + 1..])),
+            None => (decode(p), String::new()),
+        })
+        .collect()
+}
+
+/// Decodes `+` and `%XX` escapes
+fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+                match u8::from_str_radix(hex, 16) {
+                    Ok(b) => {
+                        out.push(b);
+                        i += 2;
+                    },
+                    Err(_) => out.push(b'%'),
+                }
+            },
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
@@ -3,9 +3,15 @@ pub mod cmd;
 pub mod models;
 pub mod csv;
 pub mod config;
+pub mod auth;
+pub mod http;
+pub mod api;
+pub mod output;
+pub mod sync;
 pub mod util;
 pub mod error;
 pub mod search;
+pub mod history;
 pub mod prompt;
 pub mod args;
 
@@ -1,4 +1,5 @@
 pub mod action;
+pub mod attachment;
 pub mod data;
 pub mod fact;
 pub mod graph;
@@ -6,9 +7,15 @@ pub mod attrib;
 pub mod item;
 pub mod record;
 pub mod relation;
+pub mod stats;
 pub mod note;
+pub mod journal;
 pub mod date;
+pub mod entity;
+pub mod tag;
 pub mod topic;
+pub mod template;
+pub mod trash;
 pub mod user;
 
 pub use note::{Note, Notes};
@@ -20,6 +27,7 @@ pub use attrib::Attrib;
 pub use graph::Graph;
 pub use action::Action;
 pub use topic::Topic;
+pub use template::Template;
 pub use user::User;
 
 #[derive(Debug)]
@@ -5,7 +5,9 @@ use serde::{Serialize, Deserialize};
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Action {
     pub name: String,
+    #[serde(default)]
     pub cmd: Vec<String>,
+    #[serde(default = "Local::now")]
     pub created_at: DateTime<Local>
 }
 
@@ -0,0 +1,184 @@
+use std::{fs, io::{self, Read}, path::{Path, PathBuf}};
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Local};
+use uuid::Uuid;
+use ring::digest;
+use comfy_table::{
+    Table, ContentArrangement, presets::UTF8_BORDERS_ONLY,
+    Cell, Attribute, Color as TColor,
+};
+use crate::{
+    util, output,
+    models::entity::EntityRef,
+};
+
+/// How an attached file is kept in the data dir
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AttachMode {
+    /// Copied into the blob dir under its SHA-256
+    Copy,
+    /// Symlinked from the blob dir under the attachment's id, leaving the
+    /// file where it is
+    Symlink,
+}
+
+/// Result of re-hashing an attachment against its recorded SHA-256
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Integrity {
+    Ok,
+    Modified,
+    Missing,
+}
+
+/// A file attached to a record, item or fact entry. Files are stored
+/// content-addressed in `<data dir>/dlog/blobs`, and every attachment is
+/// listed in `<data dir>/dlog/attachments.csv`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    #[serde(rename="Id")]
+    pub id: Uuid,
+    #[serde(rename="Entity")]
+    pub entity: EntityRef,
+    #[serde(rename="File")]
+    pub file: String,
+    #[serde(rename="Sha256")]
+    pub sha256: String,
+    #[serde(rename="Mode")]
+    pub mode: AttachMode,
+    #[serde(rename="Stored")]
+    pub stored: PathBuf,
+    #[serde(rename="Datetime")]
+    pub created: DateTime<Local>,
+}
+
+impl Attachment {
+
+    pub fn index_path() -> crate::DResult<PathBuf> {
+        util::default_data_dir(Some("attachments.csv"))
+    }
+
+    pub fn blob_dir() -> crate::DResult<PathBuf> {
+        let dir = util::default_data_dir(Some("blobs"))?;
+        fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    /// Stores the file in the blob dir and adds it to the attachment index
+    pub fn attach(entity: EntityRef, file: &Path, mode: AttachMode) -> crate::DResult<Self> {
+        let file = fs::canonicalize(file)?;
+        let sha256 = sha256_file(&file)?;
+        let dir = Self::blob_dir()?.join(&sha256[..2]);
+        fs::create_dir_all(&dir)?;
+        let id = Uuid::new_v4();
+        let stored = match mode {
+            AttachMode::Copy => dir.join(&sha256),
+            AttachMode::Symlink => dir.join(format!("{}.link", id)),
+        };
+        if !stored.exists() {
+            match mode {
+                AttachMode::Copy => { fs::copy(&file, &stored)?; },
+                AttachMode::Symlink => symlink(&file, &stored)?,
+            }
+        }
+        let attachment = Self {
+            file: file.file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            created: Local::now(),
+            id, entity, sha256, mode, stored,
+        };
+        let mut wtr = crate::csv::csv_appender(Self::index_path()?)?;
+        wtr.serialize(&attachment)?;
+        wtr.flush()?;
+        Ok(attachment)
+    }
+
+    pub fn all() -> crate::DResult<Vec<Self>> {
+        let path = Self::index_path()?;
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let mut rdr = crate::csv::csv_reader(path)?;
+        let mut attachments = Vec::new();
+        for att in rdr.deserialize() {
+            attachments.push(att?);
+        }
+        Ok(attachments)
+    }
+
+    pub fn of(entity: &EntityRef) -> crate::DResult<Vec<Self>> {
+        Ok(Self::all()?.into_iter()
+            .filter(|a| a.entity.is(entity))
+            .collect())
+    }
+
+    /// Re-hashes the stored file (following symlinks) and compares it to
+    /// the hash taken when it was attached
+    pub fn verify(&self) -> Integrity {
+        match sha256_file(&self.stored) {
+            Ok(sha) if sha == self.sha256 => Integrity::Ok,
+            Ok(_) => Integrity::Modified,
+            Err(_) => Integrity::Missing,
+        }
+    }
+
+    pub fn table(attachments: &[Self], verify: bool) -> Table {
+        let mut table = Table::new();
+        let mut header = vec![
+            Cell::new("File").add_attribute(Attribute::Bold)
+                .fg(TColor::Magenta),
+            Cell::new("Mode").add_attribute(Attribute::Bold),
+            Cell::new("SHA-256").add_attribute(Attribute::Bold),
+            Cell::new("Stored").add_attribute(Attribute::Bold),
+            Cell::new("Added").add_attribute(Attribute::Bold),
+        ];
+        if verify {
+            header.push(Cell::new("Integrity").add_attribute(Attribute::Bold));
+        }
+        table.load_preset(UTF8_BORDERS_ONLY)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(header);
+        for att in attachments {
+            let mut row = vec![
+                Cell::new(&att.file),
+                Cell::new(format!("{:?}", att.mode)),
+                Cell::new(&att.sha256[..12]),
+                Cell::new(att.stored.display()),
+                Cell::new(output::datetime(&att.created)),
+            ];
+            if verify {
+                row.push(match att.verify() {
+                    Integrity::Ok => Cell::new("ok").fg(TColor::Green),
+                    Integrity::Modified => Cell::new("modified").fg(TColor::Red),
+                    Integrity::Missing => Cell::new("missing").fg(TColor::Red),
+                });
+            }
+            table.add_row(row);
+        }
+        output::table("attachments", table)
+    }
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut ctx = digest::Context::new(&digest::SHA256);
+    let mut buf = [0u8; 8192];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 { break; }
+        ctx.update(&buf[..read]);
+    }
+    Ok(ctx.finish().as_ref().iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+#[cfg(unix)]
+fn symlink(src: &Path, dst: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(src, dst)
+}
+
+#[cfg(windows)]
+fn symlink(src: &Path, dst: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(src, dst)
+}
@@ -1,6 +1,11 @@
-use crate::prompt::prompt;
+use crate::{output, prompt::prompt};
 use std::{fmt, str::FromStr, collections::HashMap};
 use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Local};
+use comfy_table::{
+    Table, ContentArrangement, presets::UTF8_BORDERS_ONLY,
+    Cell, Attribute, Color as TColor,
+};
 
 pub type Attribs = Vec<Attrib>;
 
@@ -61,22 +66,45 @@ impl Attrib {
         }
     }
 
+    /// Reads the `;` separated attributes of a fact entry CSV column
     pub fn from_col(rec: &csv::StringRecord, col: usize) -> Vec<Self> {
-            rec.iter().skip(col)
-                .map(|a| Attrib::new(a, None))
+            rec.get(col).unwrap_or_default()
+                .split(';')
+                .filter(|a| !a.is_empty())
+                .map(|a| Attrib::from(a.to_string()))
                 .collect()
     }
 
+    /// Renames every attribute called `from` to `into`, dropping any that
+    /// end up duplicated. Returns whether anything changed.
+    pub fn rename_in(attribs: &mut Vec<Self>, from: &str, into: &str) -> bool {
+        if !attribs.iter().any(|a| a.name.eq_ignore_ascii_case(from)) {
+            return false;
+        }
+        let mut renamed: Vec<Self> = Vec::with_capacity(attribs.len());
+        for mut attrib in attribs.drain(..) {
+            if attrib.name.eq_ignore_ascii_case(from) {
+                attrib.name = into.to_string();
+            }
+            if !renamed.contains(&attrib) {
+                renamed.push(attrib);
+            }
+        }
+        *attribs = renamed;
+        true
+    }
+
 }
 
 
 impl From<String> for Attrib {
     fn from(attrib: String) -> Self {
-        if attrib.contains("=") {
-            let pair = attrib.split("=").collect::<Vec<&str>>();
-            Self { name: pair[0].into(), value: Some(pair[1].into()) }
-        } else {
-            Self { name: attrib, value: None }
+        match attrib.find('=') {
+            Some(idx) => Self {
+                name: attrib[..idx].into(),
+                value: Some(attrib[idx + 1..].into()),
+            },
+            None => Self { name: attrib, value: None },
         }
     }
 }
@@ -105,7 +133,164 @@ impl Into<String> for Attrib {
 impl std::str::FromStr for Attrib {
     type Err = std::convert::Infallible;
     fn from_str(a:  &str) -> Result<Self, Self::Err> {
-        Ok(Self { name: a.to_string(), value: None })
+        Ok(Self::from(a.to_string()))
+    }
+}
+
+/// The kind of value a registered attribute takes, if any
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AttribKind {
+    /// A plain tag without a value
+    Flag,
+    Text,
+    Number,
+    Boolean,
+    Date,
+}
+
+impl FromStr for AttribKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "flag" | "tag" | "none" => Ok(Self::Flag),
+            "text" | "string" => Ok(Self::Text),
+            "number" | "num" | "int" | "real" => Ok(Self::Number),
+            "boolean" | "bool" => Ok(Self::Boolean),
+            "date" | "datetime" => Ok(Self::Date),
+            _ => Err(format!("Unknown attribute type {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for AttribKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// An attribute in the attribute registry (`attribs.toml` in the data dir).
+/// Attributes don't need to be registered to be used, but registered ones
+/// can restrict the values they are given.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttribDef {
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Description", default)]
+    pub description: String,
+    #[serde(rename = "Type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<AttribKind>,
+    #[serde(rename = "Allowed", default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed: Vec<String>,
+    #[serde(rename = "Created at", default = "Local::now")]
+    pub created: DateTime<Local>,
+}
+
+impl AttribDef {
+
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            description: String::new(),
+            kind: None,
+            allowed: Vec::new(),
+            created: Local::now(),
+        }
+    }
+
+    /// Checks that an attribute given to a fact, item or record has a value
+    /// of the registered type, and one of the allowed values if any are set
+    pub fn check(&self, attrib: &Attrib) -> Result<(), String> {
+        let val = attrib.value.as_deref();
+        let ok = match (self.kind, val) {
+            (None, _) => true,
+            (Some(AttribKind::Flag), val) => val.is_none(),
+            (Some(_), None) => false,
+            (Some(AttribKind::Text), Some(_)) => true,
+            (Some(AttribKind::Number), Some(v)) => v.parse::<f64>().is_ok(),
+            (Some(AttribKind::Boolean), Some(v)) => v.parse::<bool>().is_ok(),
+            (Some(AttribKind::Date), Some(v)) => v.parse::<chrono::NaiveDate>().is_ok()
+                || DateTime::parse_from_rfc3339(v).is_ok(),
+        };
+        if !ok {
+            return Err(match self.kind {
+                Some(AttribKind::Flag) => format!("Attribute {} takes no value", self.name),
+                Some(kind) => format!("Attribute {} takes a {} value, e.g. {}={}",
+                    self.name, kind.to_string().to_lowercase(), self.name,
+                    self.allowed.first().map(String::as_str).unwrap_or("...")),
+                None => unreachable!(),
+            });
+        }
+        match val {
+            Some(v) if !self.allowed.is_empty()
+                && !self.allowed.iter().any(|a| a.eq_ignore_ascii_case(v)) =>
+                Err(format!("{} is not an allowed value of {} (allowed: {})",
+                    v, self.name, self.allowed.join(", "))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// How many facts, items and records carry an attribute
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AttribUsage {
+    pub name: String,
+    pub facts: usize,
+    /// Facts which were given the attribute by a `#tag` in their text
+    pub tagged: usize,
+    pub items: usize,
+    pub records: usize,
+}
+
+impl AttribUsage {
+
+    /// Adds a use of each of the attributes to the counts, by name
+    pub fn count<'a, I, F>(usage: &mut Vec<Self>, attribs: I, mut add: F)
+    where
+        I: IntoIterator<Item = &'a Attrib>,
+        F: FnMut(&mut Self),
+    {
+        for attrib in attribs {
+            let idx = match usage.iter().position(|u| u.name.eq_ignore_ascii_case(&attrib.name)) {
+                Some(idx) => idx,
+                None => {
+                    usage.push(Self { name: attrib.name.clone(), ..Default::default() });
+                    usage.len() - 1
+                }
+            };
+            add(&mut usage[idx]);
+        }
+    }
+
+    /// Usage counts alongside each attribute's registered type and allowed values
+    pub fn table(usage: &[Self], registry: &[AttribDef]) -> Table {
+        let mut table = Table::new();
+        table.load_preset(UTF8_BORDERS_ONLY)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec![
+                Cell::new("Attribute").add_attribute(Attribute::Bold)
+                    .fg(TColor::Cyan),
+                Cell::new("Type").add_attribute(Attribute::Bold),
+                Cell::new("Allowed").add_attribute(Attribute::Bold),
+                Cell::new("Facts").add_attribute(Attribute::Bold),
+                Cell::new("Tagged").add_attribute(Attribute::Bold),
+                Cell::new("Items").add_attribute(Attribute::Bold),
+                Cell::new("Records").add_attribute(Attribute::Bold),
+                Cell::new("Description").add_attribute(Attribute::Bold),
+            ]);
+        for u in usage {
+            let def = registry.iter().find(|d| d.name.eq_ignore_ascii_case(&u.name));
+            table.add_row(vec![
+                u.name.clone(),
+                def.and_then(|d| d.kind).map(|k| k.to_string()).unwrap_or_default(),
+                def.map(|d| d.allowed.join(", ")).unwrap_or_default(),
+                u.facts.to_string(),
+                u.tagged.to_string(),
+                u.items.to_string(),
+                u.records.to_string(),
+                def.map(|d| d.description.clone()).unwrap_or_default(),
+            ]);
+        }
+        output::table("attribs", table)
     }
 }
 
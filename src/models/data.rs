@@ -2,8 +2,9 @@ use crate::{
     util,
     models::{
         Entry, Unit, Action, Note, Item, fact::{FactValue, Fact, AbstractFact},
-        Attrib, Relation,
-        Record,
+        Attrib, Relation, attrib::AttribDef, Topic, User,
+        user::group::Group,
+        Record, record::CentralRecord,
     },
 };
 use comfy_table::{
@@ -32,7 +33,7 @@ pub struct Data {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RecordData {
-    records: Vec<Record>
+    records: Vec<CentralRecord>
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -56,9 +57,30 @@ pub struct ActionData {
     actions: Vec<Action>,
 }
 
+/// The attribute registry, kept in `attribs.toml` in the data dir
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct AttribData {
-    attribs: Vec<Attrib>,
+    #[serde(rename = "Attribute", default, skip_serializing_if = "Vec::is_empty")]
+    attribs: Vec<AttribDef>,
+}
+
+/// The topic registry, kept in `topics.toml` in the data dir
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct TopicData {
+    #[serde(rename = "Topic", default, skip_serializing_if = "Vec::is_empty")]
+    topics: Vec<Topic>,
+}
+
+/// The users and groups sharing the data dir, kept in `users.toml`
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct UserData {
+    #[serde(rename = "User", default, skip_serializing_if = "Vec::is_empty")]
+    users: Vec<User>,
+    #[serde(rename = "Group", default, skip_serializing_if = "Vec::is_empty")]
+    groups: Vec<Group>,
+    /// The current user, read from the config
+    #[serde(skip)]
+    current: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -98,21 +120,306 @@ impl RecordData {
             .open(Self::path()?)?;
         let mut buf = String::new();
         f.read_to_string(&mut buf)?;
-        let rd: RecordData = toml::from_str(buf.as_str())
-            .expect("Could not parse recod data toml file");
+        let rd: RecordData = toml::from_str(buf.as_str())?;
         Ok(rd)
     }
 
+    /// Lays out an empty record list. This is setting up the data dir
+    /// rather than a change to it, so it is not journaled.
     pub fn create() -> crate::DResult<Self> {
-        fs::write(Self::path()?, toml::to_string(&Self::default()).expect("Could not parse toml to record data"))?;
+        fs::write(Self::path()?, toml::to_string(&Self::default())?)?;
         Ok(Self::default())
+    }
+
+    pub fn write(&self) -> crate::DResult<()> {
+        let path = Self::path()?;
+        crate::history::touch(&path)?;
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Finds a record by name, skipping entries whose directory no longer
+    /// holds a record
+    pub fn find(&self, name: &str) -> Option<&CentralRecord> {
+        self.records()
+            .find(|r| r.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Registered records whose directories still hold a record
+    pub fn records(&self) -> impl Iterator<Item = &CentralRecord> {
+        self.records.iter()
+            .filter(|r| crate::auth::exists(&PathBuf::from(&r.path).join("record.toml")))
+    }
+
+    pub fn register(&mut self, record: &Record) -> crate::DResult<()> {
+        self.records.retain(|r| !r.name.eq_ignore_ascii_case(&record.name));
+        self.records.push(CentralRecord {
+            name: record.name.clone(),
+            path: record.dir.to_string_lossy().to_string(),
+        });
+        self.write()
+    }
 
+    /// Unregisters a record, returning where it was
+    pub fn remove(&mut self, name: &str) -> Option<CentralRecord> {
+        let i = self.records.iter().position(|r| r.name.eq_ignore_ascii_case(name))?;
+        Some(self.records.remove(i))
     }
 
 }
 
 impl Default for RecordData {
     fn default() -> Self {
-        Self { records: vec![Record::default()] }
+        let inbox = Record::default();
+        Self { records: vec![CentralRecord {
+            name: inbox.name,
+            path: inbox.dir.to_string_lossy().to_string(),
+        }]}
+    }
+}
+
+impl AttribData {
+
+    pub fn path() -> crate::DResult<PathBuf> {
+        util::default_data_dir(Some("attribs.toml"))
+    }
+
+    pub fn read() -> crate::DResult<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn write(&self) -> crate::DResult<()> {
+        let path = Self::path()?;
+        crate::history::touch(&path)?;
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn attribs(&self) -> &[AttribDef] {
+        &self.attribs
+    }
+
+    pub fn find(&self, name: &str) -> Option<&AttribDef> {
+        self.attribs.iter()
+            .find(|a| a.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut AttribDef> {
+        self.attribs.iter_mut()
+            .find(|a| a.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Adds an attribute to the registry, refusing to replace one of the same name
+    pub fn register(&mut self, attrib: AttribDef) -> crate::DResult<()> {
+        if self.find(&attrib.name).is_some() {
+            return Err(crate::error::DError::AlreadyExists(
+                format!("An attribute named {}", attrib.name)));
+        }
+        self.attribs.push(attrib);
+        self.write()
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<AttribDef> {
+        let idx = self.attribs.iter()
+            .position(|a| a.name.eq_ignore_ascii_case(name))?;
+        Some(self.attribs.remove(idx))
+    }
+
+    /// Checks attributes about to be given to an entity against the registry.
+    /// Unregistered attributes are always allowed.
+    pub fn check(&self, attribs: &[Attrib]) -> crate::DResult<()> {
+        for attrib in attribs {
+            if let Some(def) = self.find(&attrib.name) {
+                def.check(attrib).map_err(crate::error::DError::Invalid)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TopicData {
+
+    pub fn path() -> crate::DResult<PathBuf> {
+        util::default_data_dir(Some("topics.toml"))
+    }
+
+    pub fn read() -> crate::DResult<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn write(&self) -> crate::DResult<()> {
+        let path = Self::path()?;
+        crate::history::touch(&path)?;
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn topics(&self) -> &[Topic] {
+        &self.topics
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Topic> {
+        self.topics.iter()
+            .find(|t| t.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Adds a topic to the registry. Its parent, if any, must already be
+    /// registered.
+    pub fn register(&mut self, topic: Topic) -> crate::DResult<()> {
+        if self.find(&topic.name).is_some() {
+            return Err(crate::error::DError::AlreadyExists(
+                format!("A topic named {}", topic.name)));
+        }
+        if let Some(parent) = &topic.parent {
+            if self.find(parent).is_none() {
+                return Err(crate::error::DError::Invalid(
+                    format!("No parent topic named {}, create it with 'dlog topic new {}'",
+                        parent, parent)));
+            }
+        }
+        self.topics.push(topic);
+        self.write()
+    }
+
+    /// The topics directly beneath a parent, or the top level topics
+    pub fn children(&self, parent: Option<&str>) -> Vec<&Topic> {
+        self.topics.iter()
+            .filter(|t| match (&t.parent, parent) {
+                (Some(p), Some(parent)) => p.eq_ignore_ascii_case(parent),
+                (None, None) => true,
+                _ => false,
+            })
+            .collect()
+    }
+
+    /// The name of a topic followed by those of every topic nested beneath it
+    pub fn descendants(&self, name: &str) -> Vec<String> {
+        let mut names = vec![name.to_string()];
+        for child in self.children(Some(name)) {
+            names.extend(self.descendants(&child.name));
+        }
+        names
+    }
+}
+
+impl UserData {
+
+    pub fn path() -> crate::DResult<PathBuf> {
+        util::default_data_dir(Some("users.toml"))
+    }
+
+    pub fn read() -> crate::DResult<Self> {
+        let path = Self::path()?;
+        let mut data: Self = match path.exists() {
+            true => toml::from_str(&fs::read_to_string(path)?)?,
+            false => Self::default(),
+        };
+        data.current = crate::config::DConfig::load()?.username();
+        Ok(data)
+    }
+
+    pub fn write(&self) -> crate::DResult<()> {
+        let path = Self::path()?;
+        crate::history::touch(&path)?;
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn users(&self) -> &[User] {
+        &self.users
+    }
+
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    pub fn find(&self, username: &str) -> Option<&User> {
+        self.users.iter()
+            .find(|u| u.username.eq_ignore_ascii_case(username))
+    }
+
+    /// The user set in the config, if they are registered
+    pub fn current(&self) -> Option<&User> {
+        self.current.as_ref().and_then(|name| self.find(name))
+    }
+
+    pub fn register(&mut self, user: User) -> crate::DResult<()> {
+        if self.find(&user.username).is_some() {
+            return Err(crate::error::DError::AlreadyExists(
+                format!("A user named {}", user.username)));
+        }
+        self.users.push(user);
+        self.write()
+    }
+
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.groups.iter()
+            .find(|g| g.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn group_mut(&mut self, name: &str) -> crate::DResult<&mut Group> {
+        self.groups.iter_mut()
+            .find(|g| g.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| crate::error::DError::Invalid(
+                format!("No group named {}, create it with 'dlog user group new {}'", name, name)))
+    }
+
+    /// Adds a group, whose members must all be registered users
+    pub fn register_group(&mut self, group: Group) -> crate::DResult<()> {
+        if self.group(&group.name).is_some() {
+            return Err(crate::error::DError::AlreadyExists(
+                format!("A group named {}", group.name)));
+        }
+        for member in &group.members {
+            self.check_user(member)?;
+        }
+        self.groups.push(group);
+        self.write()
+    }
+
+    /// Fails unless a user of that name is registered
+    pub fn check_user(&self, username: &str) -> crate::DResult<()> {
+        match self.find(username) {
+            Some(_) => Ok(()),
+            None => Err(crate::error::DError::Invalid(
+                format!("No user named {}, create them with 'dlog user new {}'", username, username))),
+        }
+    }
+
+    pub fn groups_of(&self, username: &str) -> Vec<&Group> {
+        self.groups.iter()
+            .filter(|g| g.has_member(username))
+            .collect()
+    }
+
+    /// The group a record is shared by, if any
+    pub fn owner(&self, record: &str) -> Option<&Group> {
+        self.groups.iter().find(|g| g.owns(record))
+    }
+
+    /// Records shared by a group may only be logged to by its members.
+    /// Records no group owns are open to anyone.
+    pub fn check_access(&self, record: &str, username: Option<&str>) -> crate::DResult<()> {
+        let group = match self.owner(record) {
+            Some(group) => group,
+            None => return Ok(()),
+        };
+        match username {
+            Some(name) if group.has_member(name) => Ok(()),
+            Some(name) => Err(crate::error::DError::Invalid(format!(
+                "The record {} is shared by the group {}, which {} is not a member of",
+                record, group.name, name))),
+            None => Err(crate::error::DError::Invalid(format!(
+                "The record {} is shared by the group {}, switch to one of its members with 'dlog user switch'",
+                record, group.name))),
+        }
     }
 }
@@ -0,0 +1,92 @@
+use std::{fmt, str::FromStr};
+use serde::{Serialize, Deserialize};
+use crate::{error::DError, models::data::RecordData};
+
+/// A reference to a record, item, fact entry, topic or note given on the
+/// command line, in the form `record:<name>`, `item:<name>`, `fact:<id>`,
+/// `topic:<name>` or `note:<id>`. A bare name refers to a record. Stored in the same form.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EntityRef {
+    Record(String),
+    Item(String),
+    Fact(String),
+    /// A person or subject, e.g. from a `+person` tag
+    Topic(String),
+    Note(String),
+}
+
+impl EntityRef {
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Record(_) => "record",
+            Self::Item(_) => "item",
+            Self::Fact(_) => "fact",
+            Self::Topic(_) => "topic",
+            Self::Note(_) => "note",
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Record(n) | Self::Item(n) | Self::Fact(n) | Self::Topic(n)
+                | Self::Note(n) => n.as_str(),
+        }
+    }
+
+    /// Records must be registered to have files or notes attached. Other
+    /// entities are taken as given.
+    pub fn check(&self) -> crate::DResult<()> {
+        match self {
+            Self::Record(name) => RecordData::read()?.find(name)
+                .map(|_| ())
+                .ok_or(DError::NotFound),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether this refers to the same entity, ignoring case
+    pub fn is(&self, other: &Self) -> bool {
+        self.kind() == other.kind() && self.name().eq_ignore_ascii_case(other.name())
+    }
+}
+
+impl FromStr for EntityRef {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, name) = match s.find(':') {
+            Some(idx) => (&s[..idx], &s[idx + 1..]),
+            None => ("record", s),
+        };
+        if name.is_empty() {
+            return Err(format!("No name given for {}", kind));
+        }
+        match kind.to_lowercase().as_str() {
+            "record" | "rec" | "r" => Ok(Self::Record(name.into())),
+            "item" | "i" => Ok(Self::Item(name.into())),
+            "fact" | "f" => Ok(Self::Fact(name.into())),
+            "topic" | "t" | "person" | "p" => Ok(Self::Topic(name.into())),
+            "note" | "n" => Ok(Self::Note(name.into())),
+            _ => Err(format!("Unknown entity kind {}, expected record, item, fact, topic or note", kind)),
+        }
+    }
+}
+
+impl fmt::Display for EntityRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.kind(), self.name())
+    }
+}
+
+impl Serialize for EntityRef {
+    fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        ser.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for EntityRef {
+    fn deserialize<D: serde::Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        let entity = String::deserialize(de)?;
+        entity.parse().map_err(serde::de::Error::custom)
+    }
+}
@@ -3,19 +3,25 @@ use comfy_table::{
     Cell, Attribute, Color as TColor, ToRow,
 };
 use crate::{
-    csv as Csv, prompt,
+    csv as Csv, prompt, output,
+    error::DError,
+    config::{DConfig, TagRule},
     models::{
-        Entry,
-        fact::{FactValue, AbstractFact, Unit, UserUnit},
+        Entry, Relation,
+        tag::Tag,
+        entity::EntityRef,
+        fact::{FactValue, AbstractFact, Unit, UserUnit, Revision},
         record::Record,
+        trash::{Trash, Trashed, TrashKind},
         item::Item,
+        data::{UserData, RecordData},
         note::{Note, Notes},
         attrib::{Attrib, Attribs},
         date::{Datelike, Duration, RelativeTo, Recurring},
     },
 };
 use uuid::Uuid;
-use std::{convert::TryFrom, fmt, path::PathBuf, collections::HashMap, str::FromStr};
+use std::{convert::TryFrom, fmt, path::{Path, PathBuf}, collections::HashMap, str::FromStr};
 use serde::{Serialize, Deserialize};
 use chrono::{DateTime, Local};
 use clap::{ArgMatches, FromArgMatches};
@@ -24,13 +30,14 @@ use colored::{Color, Colorize};
 /// A single key-value pair to be logged into a csv corresponding to the fact's
 /// name (key). Fact entries are automatically tagged with their time of entry
 /// and each entry may optionally be associated with a number of different attributes.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Fact {
     #[serde(rename="Id", default="Uuid::new_v4")]
     pub id: uuid::Uuid,
     #[serde(rename="Fact")]
     pub name: String,
-    #[serde(rename="Value")]
+    #[serde(rename="Value", serialize_with="FactValue::serialize_raw",
+        deserialize_with="FactValue::deserialize_raw")]
     pub val: FactValue,
     #[serde(rename="Units", default="Unit::default")]
     pub unit: Unit,
@@ -40,10 +47,21 @@ pub struct Fact {
     pub notes: Vec<Note>,
     #[serde(rename="Datetime", default="Local::now")]
     pub created_at: DateTime<chrono::Local>,
+    /// The user who logged the entry, if any users have been set up
+    #[serde(rename="Author", default, skip_serializing_if="Option::is_none")]
+    pub author: Option<String>,
+    /// When the entry was last edited, if it has been
+    #[serde(rename="Updated", default, skip_serializing_if="Option::is_none")]
+    pub updated_at: Option<DateTime<chrono::Local>>,
 }
 
 impl Fact {
 
+    /// Column headers of fact entry CSVs, in the order read by `TryFrom<StringRecord>`
+    pub const CSV_HEADERS: [&'static str; 9] = [
+        "Id", "Fact", "Value", "Datetime", "Units", "Attributes", "Notes", "Author", "Updated"
+    ];
+
     pub fn new(
         name: String,
         val: String,
@@ -57,64 +75,242 @@ impl Fact {
         Self {
             id: Uuid::new_v4(),
             name, val, created_at: Local::now(), unit, attribs, notes,
+            author: None,
+            updated_at: None,
         }
     }
 
+    /// Appends the entry to the record's own CSV, or the item's CSV if one
+    /// is given. Entries without a record go to the inbox. Text values and
+    /// notes are scanned for tags first, see [`Fact::tag`]. Entries are
+    /// authored by the current user, who must belong to the group sharing
+    /// the record if it is shared. Returns the entry as it was saved.
     pub fn write(
         &self,
         record: Option<Record>,
         item: Option<Item>
-    ) -> std::io::Result<()>
+    ) -> crate::DResult<Self>
     {
-        match (record, item) {
-            (Some(record), Some(item)) => {
-                let rec = record.get_or_create()?;
-                let rec = rec.parent().expect("Could not get record parent");
-                let item = PathBuf::from(rec).join(item.name);
-                let mut wtr = crate::csv::csv_writer(item, self)?;
-                wtr.serialize(self).expect("Could not serialize fact");
-                wtr.flush()?;
-
-            },
-            (Some(record), None) => {
-                let mut wtr = crate::csv::csv_writer(record.get_or_create()?, self)?;
-                wtr.serialize(self).expect("Could not serialize fact");
-                wtr.flush()?
+        let mut fact = self.clone();
+        let tags = fact.tag(&DConfig::load()?.tag_rules());
+        crate::models::data::AttribData::read()?.check(&fact.attribs)?;
+        let mut record = match record {
+            Some(record) => record,
+            None => Record::inbox()?,
+        };
+        let users = UserData::read()?;
+        let author = fact.author.clone().or_else(|| users.current().map(|u| u.username.clone()));
+        users.check_access(&record.name, author.as_deref())?;
+        fact.author = author;
+        match item {
+            Some(item) => {
+                let path = match record.item(&item.path()) {
+                    Some(item) => item.csv_path(&record),
+                    None => record.add_item(item)?,
+                };
+                fact.append_to(path)?;
             },
-            (None, Some(item)) => {
-                // TODO put in uncategorized, don't prompt for record
-                let rec = Record::from(prompt::prompt("What is the record name?: ")?);
-                let rec = rec.get_or_create()?;
-                let rec = rec.parent().expect("Could not get record parent");
-                let item = PathBuf::from(rec).join(item.name);
-                let mut wtr = crate::csv::csv_writer(item, self)?;
-                wtr.serialize(self).expect("Could not serialize fact");
-                wtr.flush()?;
-            }
-            (None, None) => {
-                let rec = Record::default().get_or_create()?;
-                let mut wtr = crate::csv::csv_writer(rec, self)?;
-                wtr.serialize(self).expect("Could not serialize fact");
-                wtr.flush()?;
+            None => fact.append_to(record.csv_path())?,
+        }
+        for tag in tags {
+            if let Some(entity) = tag.entity()? {
+                Relation::new(tag.relation(), None,
+                    EntityRef::Fact(fact.id.to_string()), entity)
+                    .append()?;
             }
-        };
+        }
+        Ok(fact)
+    }
+
+    /// Appends the entry as a row of a fact entry CSV, writing the headers
+    /// first if the file is new
+    pub fn append_to<P: Into<PathBuf>>(&self, path: P) -> crate::DResult<()> {
+        let path = path.into();
+        let new = !path.exists() || std::fs::metadata(&path)?.len() == 0;
+        let mut wtr = Csv::csv_appender(&path)?;
+        if new {
+            wtr.write_record(&Self::CSV_HEADERS)?;
+        }
+        wtr.write_record(&self.to_csv_row())?;
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// Rewrites a fact entry CSV with the given entries, e.g. after their
+    /// attributes have been renamed
+    pub fn write_csv<P: Into<PathBuf>>(path: P, facts: &[Self]) -> crate::DResult<()> {
+        let path = path.into();
+        crate::history::touch(&path)?;
+        let mut wtr = csv::Writer::from_path(path)?;
+        wtr.write_record(&Self::CSV_HEADERS)?;
+        for fact in facts {
+            wtr.write_record(&fact.to_csv_row())?;
+        }
+        wtr.flush()?;
         Ok(())
     }
 
-    pub fn parse_tags(self) -> Option<Vec<String>> {
-        let mut tags: Vec<String> = Vec::new();
-        match self.val {
-            FactValue::Text(txt) => {
-                tags.extend_from_slice(txt.split_whitespace()
-                    .filter(|w| w.starts_with("#"))
-                    .map(|w| w.to_string())
-                    .collect::<Vec<String>>().as_slice());
-                Some(tags)
+    /// Reads all entries of a fact entry CSV, which may not exist yet
+    pub fn read_csv<P: Into<PathBuf>>(path: P) -> crate::DResult<Vec<Self>> {
+        let path = path.into();
+        if !path.is_file() {
+            return Ok(Vec::new());
+        }
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .flexible(true)
+            .from_path(&path)?;
+        let mut facts = Vec::new();
+        for rec in rdr.records() {
+            facts.push(Self::from_row(rec?, &path)?);
+        }
+        Ok(facts)
+    }
+
+    /// An entry read from a row of a CSV, with the file named in the error
+    /// if the row cannot be read
+    pub fn from_row(rec: csv::StringRecord, path: &Path) -> crate::DResult<Self> {
+        Self::try_from(rec).map_err(|e| match e {
+            DError::Invalid(why) => DError::Invalid(format!("{}: {}", path.display(), why)),
+            e => e,
+        })
+    }
+
+    /// Finds an entry in any record by the start of its id, or the last
+    /// logged with `last`, or the last of a fact with e.g. `last:sleep`.
+    /// Returns it with the record and CSV it is in.
+    pub fn find(entry: &str) -> crate::DResult<(Self, Record, PathBuf)> {
+        let name = match entry.split_once(':') {
+            Some(("last", name)) => Some(name),
+            _ if entry == "last" => None,
+            _ => return Self::find_id(entry),
+        };
+        let mut last: Option<(Self, Record, PathBuf)> = None;
+        for rec in RecordData::read()?.records() {
+            let record = Record::open_path(&rec.path)?;
+            for path in record.csv_paths() {
+                for fact in Self::read_csv(&path)? {
+                    let later = last.as_ref().is_none_or(|(l, ..)| fact.created_at >= l.created_at);
+                    if later && name.is_none_or(|n| fact.name.eq_ignore_ascii_case(n)) {
+                        last = Some((fact, Record::open_path(&rec.path)?, path.clone()));
+                    }
+                }
+            }
+        }
+        last.ok_or_else(|| match name {
+            Some(name) => DError::Invalid(format!("No {} entry has been logged", name)),
+            None => DError::Invalid("No entry has been logged".into()),
+        })
+    }
+
+    fn find_id(prefix: &str) -> crate::DResult<(Self, Record, PathBuf)> {
+        let prefix = prefix.to_lowercase();
+        let mut found = Vec::new();
+        for rec in RecordData::read()?.records() {
+            let record = Record::open_path(&rec.path)?;
+            for path in record.csv_paths() {
+                for fact in Self::read_csv(&path)? {
+                    if fact.id.to_string().starts_with(&prefix) {
+                        found.push((fact, rec.path.clone(), path.clone()));
+                    }
+                }
+            }
+        }
+        match found.len() {
+            0 => Err(DError::NotFound),
+            1 => {
+                let (fact, dir, path) = found.remove(0);
+                Ok((fact, Record::open_path(&dir)?, path))
             },
-            _ => None,
+            _ => Err(DError::Invalid(format!("More than one entry starts with {}", prefix))),
         }
     }
 
+    /// Removes the entry from a CSV of the record, keeping it in the trash
+    pub fn delete<P: Into<PathBuf>>(&self, record: &Record, path: P) -> crate::DResult<Trashed> {
+        let path = path.into();
+        record.check_access()?;
+        let mut facts = Self::read_csv(&path)?;
+        let before = facts.len();
+        facts.retain(|f| f.id != self.id);
+        if facts.len() == before {
+            return Err(DError::NotFound);
+        }
+        Self::write_csv(&path, &facts)?;
+        Trash::put_entries(record, TrashKind::Entry, &self.name, &[(path, self.clone())], None)
+    }
+
+    /// Replaces the entry of the same id in a CSV of the record with this
+    /// one, keeping the entry it replaces as a revision. The current user
+    /// must belong to the group sharing the record if it is shared.
+    pub fn amend<P: Into<PathBuf>>(&self, record: &Record, path: P) -> crate::DResult<()> {
+        let path = path.into();
+        record.check_access()?;
+        let mut facts = Self::read_csv(&path)?;
+        let old = facts.iter_mut()
+            .find(|f| f.id == self.id)
+            .ok_or(DError::NotFound)?;
+        if old == self {
+            return Ok(());
+        }
+        Revision::save(old)?;
+        *old = Self { updated_at: Some(Local::now()), ..self.clone() };
+        Self::write_csv(&path, &facts)
+    }
+
+    /// The entry's columns, in the order of `CSV_HEADERS`
+    pub fn to_csv_row(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.name.clone(),
+            self.val.raw(),
+            self.created_at.to_rfc3339(),
+            self.unit.to_string(),
+            self.attribs.iter()
+                .map(|a| match &a.value {
+                    Some(val) => format!("{}={}", a.name, val),
+                    None => a.name.clone(),
+                })
+                .collect::<Vec<String>>()
+                .join(";"),
+            self.notes.iter()
+                .map(|n| n.notes.clone())
+                .filter(|n| !n.is_empty())
+                .collect::<Vec<String>>()
+                .join(";"),
+            self.author.clone().unwrap_or_default(),
+            self.updated_at.map(|u| u.to_rfc3339()).unwrap_or_default(),
+        ]
+    }
+
+    /// The text of the entry's value and notes, which tags are taken from
+    pub fn text(&self) -> String {
+        let mut text = match &self.val {
+            FactValue::Text(txt) => txt.clone(),
+            _ => String::new(),
+        };
+        for note in &self.notes {
+            text.push(' ');
+            text.push_str(&note.notes);
+        }
+        text
+    }
+
+    /// Scans the entry's text for tags. `#tags` are added to the entry's
+    /// attributes, and the rest are returned to be related to the entry.
+    pub fn tag(&mut self, rules: &[TagRule]) -> Vec<Tag> {
+        let mut tags = Tag::extract(&self.text(), rules);
+        for tag in &tags {
+            if let Tag::Attrib(attrib) = tag {
+                if !self.attribs.contains(attrib) {
+                    self.attribs.push(attrib.clone());
+                }
+            }
+        }
+        tags.retain(|t| !matches!(t, Tag::Attrib(_)));
+        tags
+    }
+
     pub fn parse_units_in_val(_val: String) -> Option<(String, String)> {
         None
     }
@@ -144,10 +340,39 @@ impl Fact {
                 &self.unit.to_string(),
                 &Attrib::join(&self.attribs),
                 &Note::join(&self.notes),
-                &self.created_at.to_string(),
+                &output::datetime(&self.created_at),
+            ]);
+        output::table("fact", table)
+    }
+
+    /// Many entries as rows of one table
+    pub fn list_table(facts: &[Self]) -> Table {
+        let mut table = Table::new();
+        table.load_preset(presets::UTF8_BORDERS_ONLY)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec![
+                Cell::new("Fact").add_attribute(Attribute::Bold)
+                    .fg(TColor::Blue),
+                Cell::new("Value").add_attribute(Attribute::Bold),
+                Cell::new("Units").add_attribute(Attribute::Bold),
+                Cell::new("Attributes").add_attribute(Attribute::Bold),
+                Cell::new("Notes").add_attribute(Attribute::Bold),
+                Cell::new("Author").add_attribute(Attribute::Bold),
+                Cell::new("Created").add_attribute(Attribute::Bold),
+            ]);
+        for fact in facts {
+            table.add_row(vec![
+                fact.name.clone(),
+                fact.val.raw(),
+                fact.unit.to_string(),
+                Attrib::join(&fact.attribs),
+                Note::join(&fact.notes),
+                fact.author.clone().unwrap_or_default(),
+                output::datetime(&fact.created_at),
             ]);
-        table
         }
+        output::table("facts", table)
+    }
 
 }
 impl Default for Fact {
@@ -196,11 +421,13 @@ impl FromArgMatches for Fact {
         let attribs = Attrib::get_matches(&matches);
         let notes = Note::get_matches(&matches);
         let val = FactValue::from_arg_matches(&matches);
-        let unit = Unit::from_match(matches.values_of("UNIT"));
+        let unit = Unit::from_match(matches.values_of("UNIT").or_else(|| matches.values_of("unit")));
         Self {
             id: uuid::Uuid::new_v4(),
             created_at: Local::now(),
             name, notes, val, unit, attribs,
+            author: None,
+            updated_at: None,
         }
     }
 }
@@ -213,18 +440,27 @@ impl Entry for Fact {
 
 }
 impl std::convert::TryFrom<csv::StringRecord> for Fact {
-    type Error = csv::Error;
+    type Error = DError;
     fn try_from(rec: csv::StringRecord) -> Result<Self, Self::Error> {
+        let col = |i: usize| rec.get(i).unwrap_or_default();
+        let invalid = |what: &str, val: &str| DError::Invalid(match rec.position() {
+            Some(pos) => format!("The entry on line {} has an invalid {} {:?}", pos.line(), what, val),
+            None => format!("The entry has an invalid {} {:?}", what, val),
+        });
+        let created_at = DateTime::parse_from_rfc3339(col(3))
+            .or_else(|_| DateTime::parse_from_rfc2822(col(3)))
+            .map(DateTime::from)
+            .map_err(|_| invalid("Datetime", col(3)))?;
         let fact = Fact {
-            id: Uuid::parse_str(&rec[0])
-                .expect("Could not parse UUID"),
-            name: rec[1].to_string(),
-            val: FactValue::from(rec[1].to_string()),
-            created_at: DateTime::parse_from_rfc2822(&rec[3])
-                .expect("Could not parse datetime").into(),
-            unit: Unit::Other(UserUnit::from(rec[4].to_string())), //TODO handle date parsing
+            id: Uuid::parse_str(col(0)).map_err(|_| invalid("Id", col(0)))?,
+            name: col(1).to_string(),
+            val: FactValue::from_str(col(2)).unwrap_or_default(),
+            unit: Unit::from_stored(col(4)),
             attribs: Attrib::from_col(&rec, 5),
             notes: Note::from_col(&rec, 6),
+            created_at,
+            author: Some(col(7)).filter(|a| !a.is_empty()).map(String::from),
+            updated_at: DateTime::parse_from_rfc3339(col(8)).ok().map(DateTime::from),
         };
         Ok(fact)
     }
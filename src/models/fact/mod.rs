@@ -1,11 +1,13 @@
 pub mod entry;
 pub mod value;
 pub mod units;
+pub mod revision;
 
 pub use self::{
     entry::Fact,
     value::FactValue,
-    units::{UserUnit, Unit}
+    units::{UserUnit, Unit},
+    revision::{Revision, FactEdit},
 };
 
 use comfy_table::{
@@ -13,7 +15,7 @@ use comfy_table::{
     Cell, Attribute, Color as TColor, ToRow,
 };
 use crate::{
-    csv as csv, prompt,
+    csv as csv, prompt, output,
     models::{
         Entry,
         record::Record,
@@ -72,7 +74,7 @@ impl comfy_table::ToRow for AbstractFact {
             &self.unit.to_string(),
             &Attrib::join(&self.attribs),
             &Note::join(&self.notes),
-            &self.created_at.to_string(),
+            &output::datetime(&self.created_at),
         ])
     }
 }
@@ -91,7 +93,7 @@ impl AbstractFact {
                 Cell::new("Created").add_attribute(Attribute::Bold),
             ])
             .add_row(self.clone().to_row());
-        table
+        output::table("fact_types", table)
     }
 
     pub fn insert(&self) -> Result<(), Box<dyn std::error::Error>> {
@@ -0,0 +1,201 @@
+use std::{convert::TryFrom, fs, path::PathBuf, str::FromStr};
+use chrono::{DateTime, Local};
+use chrono_english::{parse_date_string, Dialect};
+use comfy_table::{
+    Table, ContentArrangement, presets::UTF8_BORDERS_ONLY,
+    Cell, Attribute, Color as TColor,
+};
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use crate::{
+    util, output,
+    csv as Csv,
+    error::DError,
+    models::{
+        note::Note,
+        attrib::Attrib,
+        fact::{Fact, FactValue, Unit},
+    },
+};
+
+/// A version of a fact entry replaced by an edit. The versions of an entry
+/// are kept oldest first under `<data dir>/dlog/revisions/<id>.csv`, in the
+/// columns of fact entry CSVs with when each was replaced.
+#[derive(Debug, Clone, Serialize)]
+pub struct Revision {
+    #[serde(flatten)]
+    pub fact: Fact,
+    #[serde(rename = "Revised")]
+    pub revised: DateTime<Local>,
+}
+
+impl Revision {
+
+    pub fn dir() -> crate::DResult<PathBuf> {
+        let dir = util::default_data_dir(Some("revisions"))?;
+        fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    pub fn path(id: &Uuid) -> crate::DResult<PathBuf> {
+        Ok(Self::dir()?.join(format!("{}.csv", id)))
+    }
+
+    /// Keeps a version of an entry which is about to be replaced
+    pub fn save(fact: &Fact) -> crate::DResult<()> {
+        let path = Self::path(&fact.id)?;
+        let new = !path.exists() || fs::metadata(&path)?.len() == 0;
+        let mut wtr = Csv::csv_appender(&path)?;
+        if new {
+            let mut headers = Fact::CSV_HEADERS.to_vec();
+            headers.push("Revised");
+            wtr.write_record(&headers)?;
+        }
+        let mut row = fact.to_csv_row();
+        row.push(Local::now().to_rfc3339());
+        wtr.write_record(&row)?;
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// The replaced versions of an entry, oldest first
+    pub fn of(id: &Uuid) -> crate::DResult<Vec<Self>> {
+        let path = Self::path(id)?;
+        if !path.is_file() {
+            return Ok(Vec::new());
+        }
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .flexible(true)
+            .from_path(&path)?;
+        let mut revisions = Vec::new();
+        for rec in rdr.records() {
+            let rec = rec?;
+            let revised = rec.get(Fact::CSV_HEADERS.len())
+                .and_then(|r| DateTime::parse_from_rfc3339(r).ok())
+                .map(DateTime::from)
+                .unwrap_or_else(Local::now);
+            revisions.push(Self { fact: Fact::from_row(rec, &path)?, revised });
+        }
+        Ok(revisions)
+    }
+
+    /// The versions of an entry, the current one last
+    pub fn table(current: &Fact, revisions: &[Self]) -> Table {
+        let mut table = Table::new();
+        table.load_preset(UTF8_BORDERS_ONLY)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec![
+                Cell::new("Version").add_attribute(Attribute::Bold),
+                Cell::new("Fact").add_attribute(Attribute::Bold)
+                    .fg(TColor::Blue),
+                Cell::new("Value").add_attribute(Attribute::Bold),
+                Cell::new("Units").add_attribute(Attribute::Bold),
+                Cell::new("Attributes").add_attribute(Attribute::Bold),
+                Cell::new("Notes").add_attribute(Attribute::Bold),
+                Cell::new("Created").add_attribute(Attribute::Bold),
+                Cell::new("Replaced").add_attribute(Attribute::Bold),
+            ]);
+        let versions = revisions.iter()
+            .map(|r| (&r.fact, Some(&r.revised)))
+            .chain(std::iter::once((current, None)));
+        for (i, (fact, revised)) in versions.enumerate() {
+            table.add_row(vec![
+                (i + 1).to_string(),
+                fact.name.clone(),
+                fact.val.raw(),
+                fact.unit.to_string(),
+                Attrib::join(&fact.attribs),
+                Note::join(&fact.notes),
+                output::datetime(&fact.created_at),
+                revised.map(output::datetime).unwrap_or_else(|| "current".into()),
+            ]);
+        }
+        output::table("revisions", table)
+    }
+}
+
+/// Changes to a fact entry, each part left as it is if not given
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct FactEdit {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub units: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<Vec<String>>,
+}
+
+impl FactEdit {
+
+    pub fn is_empty(&self) -> bool {
+        self.value.is_none() && self.units.is_none() && self.time.is_none()
+            && self.attributes.is_none() && self.notes.is_none()
+    }
+
+    /// Every part of an entry, to be edited as TOML
+    pub fn of(fact: &Fact) -> Self {
+        Self {
+            value: Some(fact.val.raw()),
+            units: Some(match fact.unit {
+                Unit::None => String::new(),
+                ref unit => unit.to_string(),
+            }),
+            time: Some(fact.created_at.to_rfc3339()),
+            attributes: Some(fact.attribs.iter()
+                .map(|a| match &a.value {
+                    Some(val) => format!("{}={}", a.name, val),
+                    None => a.name.clone(),
+                })
+                .collect()),
+            notes: Some(fact.notes.iter()
+                .map(|n| n.notes.clone())
+                .filter(|n| !n.is_empty())
+                .collect()),
+        }
+    }
+
+    /// Opens the entry in the editor as TOML, returning what was saved
+    pub fn edit(fact: &Fact, editor: &str) -> crate::DResult<Self> {
+        let text = toml::to_string(&Self::of(fact))?;
+        let edited = util::edit(editor, &text, "toml")?;
+        Ok(toml::from_str(&edited)?)
+    }
+
+    /// The entry with the changes made, keeping its id
+    pub fn apply(&self, fact: &Fact) -> crate::DResult<Fact> {
+        let mut edited = fact.clone();
+        if let Some(value) = &self.value {
+            if value.trim().is_empty() {
+                return Err(DError::Invalid("The value can't be empty".into()));
+            }
+            edited.val = FactValue::from_str(value.trim()).unwrap_or_default();
+        }
+        if let Some(units) = &self.units {
+            edited.unit = Unit::from_stored(units.trim());
+        }
+        if let Some(time) = &self.time {
+            edited.created_at = DateTime::parse_from_rfc3339(time.trim())
+                .map(DateTime::from)
+                .or_else(|_| parse_date_string(time.trim(), Local::now(), Dialect::Us))
+                .map_err(|_| DError::Invalid(format!("{} is not a time", time)))?;
+        }
+        if let Some(attribs) = &self.attributes {
+            edited.attribs = attribs.iter()
+                .filter(|a| !a.trim().is_empty())
+                .map(|a| Attrib::from(a.trim().to_string()))
+                .collect();
+        }
+        if let Some(notes) = &self.notes {
+            edited.notes = notes.iter()
+                .filter(|n| !n.trim().is_empty())
+                .map(|n| Note::new(n.trim()))
+                .collect();
+        }
+        Ok(edited)
+    }
+}
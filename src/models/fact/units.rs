@@ -7,17 +7,14 @@ use chrono_english::{Dialect, DateError, parse_date_string};
 use clap::{ArgMatches, FromArgMatches};
 use colored::{Color, Colorize};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Stored as its display string (see `Unit::from_stored`) so that units fit
+/// in a single CSV column or TOML value
+#[derive(Debug, Clone, PartialEq)]
 pub enum Unit {
-    #[serde(rename="Date")]
     Datetime(DateTime<Local>),
-    #[serde(skip, rename="Duration")]
     Duration(Duration),
-    #[serde(rename="Boolean")]
     Boolean,
-    #[serde(rename="Other")]
     Other(UserUnit),
-    #[serde(rename="None")]
     None,
 }
 
@@ -40,6 +37,18 @@ impl Default for Unit {
 
 impl Unit {
 
+    /// Reads back a unit written with its `Display` impl
+    pub fn from_stored(s: &str) -> Self {
+        match s {
+            "" | "None" => Unit::None,
+            "Boolean" => Unit::Boolean,
+            _ => match DateTime::parse_from_rfc2822(s) {
+                Ok(date) => Unit::Datetime(date.into()),
+                Err(_) => Unit::Other(UserUnit::Text(s.to_string())),
+            }
+        }
+    }
+
     pub fn prompt(prompt_str: &str) -> Self {
         let unit = prompt(prompt_str)
             .expect("Could not prompt fact value");
@@ -98,6 +107,19 @@ impl Unit {
     }
 }
 
+impl Serialize for Unit {
+    fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        ser.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Unit {
+    fn deserialize<D: serde::Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        let unit = String::deserialize(de)?;
+        Ok(Self::from_stored(&unit))
+    }
+}
+
 impl From<Option<String>> for Unit {
     fn from(input: Option<String>) -> Self {
         if let Some(input) = input { //TODO check if datetime
@@ -48,6 +48,10 @@ impl std::cmp::PartialEq for FactValue {
             (Self::Text(t1), Self::Text(t2)) => t1 == t2,
             (Self::Boolean(b1), Self::Boolean(b2)) => b1 == b2,
             (Self::Integer(i1), Self::Integer(i2)) => i1 == i2,
+            (Self::RealNumber(r1), Self::RealNumber(r2)) => r1 == r2,
+            (Self::Integer(i), Self::RealNumber(r))
+                | (Self::RealNumber(r), Self::Integer(i)) => *i as f32 == *r,
+            (Self::Range(a1, b1), Self::Range(a2, b2)) => a1 == a2 && b1 == b2,
             (Self::Duration(d1), Self::Duration(d2)) => d1.secs.eq(&d2.secs),
             (Self::UserEnum(ue1), Self::UserEnum(ue2)) => {
                 return false;
@@ -116,6 +120,47 @@ impl std::str::FromStr for FactValue {
         }
     }
 }
+impl FactValue {
+
+    /// Numeric values as a number, durations in seconds
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Integer(i) => Some(*i as f64),
+            Self::RealNumber(r) => Some(*r as f64),
+            Self::Duration(d) => Some(d.secs as f64),
+            _ => None,
+        }
+    }
+
+    /// The value as written to a fact entry CSV, parsed back by `FromStr`
+    pub fn raw(&self) -> String {
+        match self {
+            Self::Integer(i) => i.to_string(),
+            Self::RealNumber(r) => r.to_string(),
+            Self::Boolean(b) => b.to_string(),
+            Self::Text(t) | Self::UserValue(t) => t.clone(),
+            Self::Range(n1, n2) => format!("{}-{}", n1, n2),
+            Self::Duration(d) => humantime::format_duration(
+                    std::time::Duration::from_secs(d.secs as u64))
+                .to_string()
+                .replace(' ', ""),
+            Self::None => String::new(),
+            other => other.to_string(),
+        }
+    }
+
+    /// Serializes the value as it is written to fact entry CSVs, so entries
+    /// read the same in `--output json` as in their files
+    pub fn serialize_raw<S: serde::Serializer>(val: &Self, ser: S) -> Result<S::Ok, S::Error> {
+        ser.serialize_str(&val.raw())
+    }
+
+    pub fn deserialize_raw<'de, D: serde::Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(de)?;
+        Ok(raw.parse().unwrap_or_default())
+    }
+}
+
 impl FromArgMatches for FactValue {
     fn from_arg_matches(matches: &ArgMatches) -> Self {
         if let Ok(value) = matches.value_of_t::<FactValue>("VALUE") {
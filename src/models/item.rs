@@ -8,24 +8,46 @@ use serde::{Serialize, Deserialize};
 use colored::{Color, Colorize};
 use crate::{
     prompt::prompt,
+    output,
     error::DResult,
-    models::{Entry, Fact, Record, Attrib, Note},
+    models::{Entry, Fact, Record, Attrib, Note, entity::EntityRef},
 };
 use uuid::Uuid;
 use clap::{Arg, ArgMatches, ArgSettings, FromArgMatches};
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A grouping of related fact entries within a record. Items nest: an item
+/// may have a parent item, and is addressed by its path within the record,
+/// e.g. `exercise/running`. Each item keeps its own fact entries in
+/// `<record dir>/<item path>/<name>.csv`, so child items live in directories
+/// inside their parent's.
+// NOTE attributes and notes are tables, so they come last to serialize into
+//      the record's TOML
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Item {
     #[serde(rename = "Id")]
     pub id: uuid::Uuid,
     #[serde(rename = "Item")]
     pub name: String,
-    #[serde(rename = "Attributes")]
-    pub attribs: Vec<Attrib>,
-    #[serde(rename = "Notes")]
-    pub notes: Vec<Note>,
+    #[serde(rename = "Parent", default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
     #[serde(rename = "Created at")]
     pub created: DateTime<Local>,
+    #[serde(rename = "Links", default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<EntityRef>,
+    #[serde(rename = "Attributes", default, skip_serializing_if = "Vec::is_empty")]
+    pub attribs: Vec<Attrib>,
+    #[serde(rename = "Notes", default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<Note>,
+}
+
+/// An item along with the name of the record it is in, as items are
+/// listed by `--output` and `dlog serve`
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordItem {
+    #[serde(rename = "Record")]
+    pub record: String,
+    #[serde(flatten)]
+    pub item: Item,
 }
 
 impl Default for Item {
@@ -35,55 +57,102 @@ impl Default for Item {
             .expect("Could not prompt item name");
         println!("{}", format!("Got new item: {}", &name)
             .color(Color::BrightCyan));
-        Item {
-            id: Uuid::new_v4(),
-            name,
-            created: Local::now(),
-            notes: Vec::new(),
-            attribs: Vec::new()
-        }
+        Item::new(name)
     }
 }
 
 impl Item {
 
+    /// Creates an item from its path, e.g. `exercise/running` is the item
+    /// `running` under `exercise`
     pub fn new(name: String,) -> Self {
         let id: Uuid = Uuid::new_v4();
-        Self { id, name,
+        let mut segments = Self::split(&name);
+        let name = segments.pop().unwrap_or(name);
+        let parent = if segments.is_empty() { None } else { Some(segments.join("/")) };
+        Self { id, name, parent,
                 created: Local::now(),
+                links: Vec::new(),
                 notes: Vec::new(),
                 attribs: Vec::new(),
         }
     }
 
-    pub fn insert(&self, record: Record) -> std::io::Result<PathBuf> {
-        let item = record.add_item(self)?;
-        Ok(item)
+    /// Splits an item path into its names, ignoring stray slashes
+    pub fn split(path: &str) -> Vec<String> {
+        path.split('/')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect()
     }
 
-    pub fn get_all_facts(&self) -> DResult<Vec<Fact>> {
-        let mut facts: Vec<Fact> = vec![];
-        let mut rdr = csv::Reader::from_path("~/test.csv")?;
-        while let Some(rec) = rdr.records().next() {
-            let fact: Fact = rec?.clone().deserialize(None)?;
-            facts.push(fact);
+    /// The item's full path within its record
+    pub fn path(&self) -> String {
+        match &self.parent {
+            Some(parent) => format!("{}/{}", parent, self.name),
+            None => self.name.clone(),
         }
-        Ok(facts)
     }
 
-    pub fn get_fact(&self, fact: &str) -> DResult<Vec<Fact>> {
-        let mut facts: Vec<Fact> = vec![];
-        let mut rdr = csv::Reader::from_path("~/test.csv")?;
-        while let Some(rec) = rdr.records().next() {
-            let rec = rec?.clone();
-            let name = &rec[0];
-            if name.eq_ignore_ascii_case(fact) {
-                let fact = rec.deserialize(None)?;
-                facts.push(fact);
+    /// How many parents the item has
+    pub fn depth(&self) -> usize {
+        self.parent.as_ref()
+            .map(|p| Self::split(p).len())
+            .unwrap_or(0)
+    }
+
+    /// Whether the item is `path` or one of its descendants
+    pub fn is_under(&self, path: &str) -> bool {
+        let (own, path) = (Self::split(&self.path()), Self::split(path));
+        own.len() >= path.len() && own.iter().zip(path.iter())
+            .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+
+    /// Directory holding the item's entries and child items
+    pub fn dir(&self, record: &Record) -> PathBuf {
+        Self::split(&self.path()).iter()
+            .fold(record.dir.clone(), |dir, seg| dir.join(seg))
+    }
+
+    pub fn csv_path(&self, record: &Record) -> PathBuf {
+        self.dir(record).join(format!("{}.csv", &self.name))
+    }
+
+    /// Fact entries logged to this item, and with `rollup` those logged to
+    /// any of its descendants, oldest first
+    pub fn facts(&self, record: &Record, rollup: bool) -> DResult<Vec<Fact>> {
+        let mut facts = Fact::read_csv(self.csv_path(record))?;
+        if rollup {
+            for child in record.descendants(&self.path()) {
+                facts.extend(Fact::read_csv(child.csv_path(record))?);
             }
+            facts.sort_by_key(|f| f.created_at);
         }
         Ok(facts)
+    }
+
+    pub fn insert(&self, record: &mut Record) -> DResult<PathBuf> {
+        record.add_item(self.clone())
+    }
+
+    /// All entries of the item and its descendants
+    pub fn get_all_facts(&self, record: &Record) -> DResult<Vec<Fact>> {
+        self.facts(record, true)
+    }
 
+    /// Entries of one fact logged to the item or its descendants
+    pub fn get_fact(&self, record: &Record, fact: &str) -> DResult<Vec<Fact>> {
+        Ok(self.get_all_facts(record)?.into_iter()
+            .filter(|f| f.name.eq_ignore_ascii_case(fact))
+            .collect())
+    }
+
+    /// Links the item to another entity, ignoring links it already has
+    pub fn link(&mut self, entity: EntityRef) {
+        if !self.links.contains(&entity) {
+            self.links.push(entity);
+        }
     }
 
     pub fn table(&self) -> Table {
@@ -94,33 +163,37 @@ impl Item {
                 Cell::new("Id").add_attribute(Attribute::Bold),
                 Cell::new("Item").add_attribute(Attribute::Bold)
                     .fg(TColor::Green),
+                Cell::new("Parent").add_attribute(Attribute::Bold),
                 Cell::new("Attributes").add_attribute(Attribute::Bold),
+                Cell::new("Links").add_attribute(Attribute::Bold),
                 Cell::new("Notes").add_attribute(Attribute::Bold),
                 Cell::new("Created").add_attribute(Attribute::Bold),
             ])
             .add_row(vec![
                 &self.id.to_string(),
                 &self.name,
+                self.parent.as_ref().unwrap_or(&String::new()),
                 &Attrib::join(&self.attribs),
+                &self.links.iter()
+                    .map(|l| l.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", "),
                 &Note::join(&self.notes),
-                &self.created.to_string(),
+                &output::datetime(&self.created),
             ]);
-        table
-        }
+        output::table("item", table)
+    }
 
 }
 
 impl FromArgMatches for Item {
     fn from_arg_matches(matches: &ArgMatches) -> Self {
-        match (matches.value_of("NAME"), matches.value_of("record")) {
-            (Some(item), Some(record)) => {
-                Self::new(item.into())
-            },
-            (Some(item), None)  => {
-                Self::new(item.into())
-            },
-            (_, _) => Self::default(),
-        }
+        let mut item = match matches.value_of("ITEM") {
+            Some(item) => Self::new(item.into()),
+            None => Self::default(),
+        };
+        item.attribs = Attrib::from_match(matches.values_of("attrib"));
+        item
     }
 }
 
@@ -130,10 +203,10 @@ impl comfy_table::ToRow for Item {
     fn to_row(self) -> comfy_table::Row {
         comfy_table::Row::from(vec![
             &self.id.to_string(),
-            &self.name.to_string(),
+            &self.path(),
             &Attrib::join(&self.attribs),
             &Note::join(&self.notes),
-            &self.created.to_string(),
+            &output::datetime(&self.created),
         ])
     }
 }
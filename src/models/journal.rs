@@ -0,0 +1,220 @@
+use chrono::{Local, NaiveDate, TimeZone};
+use serde::Serialize;
+use chrono_english::{parse_date_string, Dialect};
+use comfy_table::{
+    Table, ContentArrangement, presets::UTF8_BORDERS_ONLY,
+    Cell, Attribute, Color as TColor,
+};
+use crate::{
+    output,
+    config::{TagRule, TagKind},
+    models::{
+        Attrib, Fact, Record, Relation,
+        entity::EntityRef,
+        fact::Unit,
+        note::NoteDoc,
+        tag::Tag,
+    },
+};
+
+/// A day's journal: a note on the `journal` record for that date. Lines of
+/// the form `key:: value` are logged as facts to the journal record when it
+/// is saved, each related to the journal's note.
+#[derive(Debug, Clone, Serialize)]
+pub struct Journal {
+    pub date: NaiveDate,
+    pub note: NoteDoc,
+}
+
+impl Journal {
+
+    pub fn entity() -> EntityRef {
+        EntityRef::Record("journal".into())
+    }
+
+    /// The journal for a date, which is new and unsaved if nothing has been
+    /// written that day
+    pub fn open(date: NaiveDate) -> crate::DResult<Self> {
+        let note = match NoteDoc::of(&Self::entity())?.into_iter()
+            .find(|n| n.meta.date == Some(date))
+        {
+            Some(note) => note,
+            None => {
+                let mut note = NoteDoc::new(Self::entity(), &[]);
+                note.meta.date = Some(date);
+                note
+            },
+        };
+        Ok(Self { date, note })
+    }
+
+    /// Every saved journal, newest first, optionally from and to a date
+    pub fn all(from: Option<NaiveDate>, to: Option<NaiveDate>) -> crate::DResult<Vec<Self>> {
+        let mut journals = NoteDoc::of(&Self::entity())?.into_iter()
+            .filter_map(|note| Some(Self { date: note.meta.date?, note }))
+            .filter(|j| from.is_none_or(|f| j.date >= f) && to.is_none_or(|t| j.date <= t))
+            .collect::<Vec<Self>>();
+        journals.sort_by_key(|j| std::cmp::Reverse(j.date));
+        Ok(journals)
+    }
+
+    /// Parses a day given as a date like 2021-01-31, or in words like
+    /// "yesterday" or "last friday"
+    pub fn parse_date(date: &str) -> Result<NaiveDate, String> {
+        match date.to_lowercase().as_str() {
+            "today" => return Ok(Local::today().naive_local()),
+            "yesterday" => return Ok(Local::today().naive_local().pred()),
+            _ => {},
+        }
+        date.parse::<NaiveDate>()
+            .or_else(|_| parse_date_string(date, Local::now(), Dialect::Us)
+                .map(|d| d.date().naive_local()))
+            .map_err(|_| format!("Could not read {} as a date", date))
+    }
+
+    /// The facts written inline as `key:: value`, with any `#tags` on the
+    /// same line given to the fact. Values with a number followed by a unit,
+    /// e.g. `7h` or `5 km`, are split into the two.
+    pub fn inline_facts(&self, rules: &[TagRule]) -> Vec<Fact> {
+        let created = if self.date == Local::today().naive_local() {
+            Local::now()
+        } else {
+            Local.from_local_datetime(&self.date.and_hms(12, 0, 0)).unwrap()
+        };
+        let mut facts = Vec::new();
+        for line in self.note.body.lines() {
+            let line = line.trim_start()
+                .trim_start_matches(['-', '*'])
+                .trim_start();
+            let (key, rest) = match line.find("::") {
+                Some(idx) => (line[..idx].trim(), line[idx + 2..].trim()),
+                None => continue,
+            };
+            if key.is_empty() || key.contains(char::is_whitespace) || rest.is_empty() {
+                continue;
+            }
+            let mut attribs = vec![Attrib::new("journal", Some(self.date.to_string()))];
+            let mut words = Vec::new();
+            for word in rest.split_whitespace() {
+                match Tag::extract(word, rules).pop() {
+                    Some(Tag::Attrib(attrib)) => attribs.push(attrib),
+                    _ => words.push(word),
+                }
+            }
+            if words.is_empty() {
+                continue;
+            }
+            let (val, unit) = split_unit(&words.join(" "));
+            let mut fact = Fact::new(key.to_string(), val, unit, attribs, Vec::new());
+            fact.created_at = created;
+            facts.push(fact);
+        }
+        facts
+    }
+
+    /// The `#tags` written outside of inline facts, which are kept as the
+    /// note's attributes
+    pub fn tags(&self, rules: &[TagRule]) -> Vec<Attrib> {
+        let rules = rules.iter()
+            .filter(|r| r.kind == TagKind::Attrib)
+            .cloned()
+            .collect::<Vec<TagRule>>();
+        self.note.body.lines()
+            .filter(|l| !l.contains("::"))
+            .flat_map(|l| Tag::extract(l, &rules))
+            .filter_map(|t| match t {
+                Tag::Attrib(a) => Some(a),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Saves a new version of the journal's note and replaces the facts
+    /// logged from its previous version, as well as the note's relations to
+    /// what it mentions. Returns how many facts were logged.
+    pub fn save(&mut self, rules: &[TagRule]) -> crate::DResult<usize> {
+        for tag in self.tags(rules) {
+            let tag = match &tag.value {
+                Some(val) => format!("{}={}", tag.name, val),
+                None => tag.name.clone(),
+            };
+            if !self.note.meta.attributes.contains(&tag) {
+                self.note.meta.attributes.push(tag);
+            }
+        }
+        self.note.save()?;
+        let note = EntityRef::Note(self.note.meta.id.to_string());
+        let old = Relation::of(&note)?.into_iter()
+            .filter(|r| r.name == "journal")
+            .map(|r| r.r1.name().to_string())
+            .collect::<Vec<String>>();
+        let journal = Record::journal()?;
+        if !old.is_empty() {
+            let facts = Fact::read_csv(journal.csv_path())?.into_iter()
+                .filter(|f| !old.contains(&f.id.to_string()))
+                .collect::<Vec<Fact>>();
+            Fact::write_csv(journal.csv_path(), &facts)?;
+        }
+        Relation::retain(|r| !r.r1.is(&note)
+            && (r.r1.kind() != "fact" || !old.iter().any(|id| id == r.r1.name())))?;
+        let prose = self.note.body.lines()
+            .filter(|l| !l.contains("::"))
+            .collect::<Vec<&str>>()
+            .join("\n");
+        for tag in Tag::extract(&prose, rules) {
+            if let Some(entity) = tag.entity()? {
+                Relation::new(tag.relation(), None, note.clone(), entity).append()?;
+            }
+        }
+        let facts = self.inline_facts(rules);
+        for fact in &facts {
+            fact.write(Some(Record::journal()?), None)?;
+            Relation::new("journal", None,
+                EntityRef::Fact(fact.id.to_string()), note.clone())
+                .append()?;
+        }
+        Ok(facts.len())
+    }
+
+    pub fn table(journals: &[Self], rules: &[TagRule]) -> Table {
+        let mut table = Table::new();
+        table.load_preset(UTF8_BORDERS_ONLY)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec![
+                Cell::new("Date").add_attribute(Attribute::Bold)
+                    .fg(TColor::Green),
+                Cell::new("Day").add_attribute(Attribute::Bold),
+                Cell::new("Facts").add_attribute(Attribute::Bold),
+                Cell::new("Tags").add_attribute(Attribute::Bold),
+                Cell::new("Preview").add_attribute(Attribute::Bold),
+            ]);
+        for journal in journals {
+            table.add_row(vec![
+                output::date(&journal.date),
+                journal.date.format("%A").to_string(),
+                journal.inline_facts(rules).iter()
+                    .map(|f| format!("{}: {}", f.name, f.val.raw()))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                journal.note.meta.attributes.join(", "),
+                journal.note.body.lines()
+                    .map(str::trim)
+                    .find(|l| !l.is_empty() && !l.contains("::"))
+                    .unwrap_or_default()
+                    .chars().take(50).collect(),
+            ]);
+        }
+        output::table("journals", table)
+    }
+}
+
+/// Splits a value like `7h`, `7.5 km` or `good` into a value and unit
+fn split_unit(val: &str) -> (String, Unit) {
+    let end = val.find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .unwrap_or(val.len());
+    let (num, unit) = val.split_at(end);
+    if num.is_empty() || num.parse::<f32>().is_err() || unit.trim().is_empty() {
+        return (val.to_string(), Unit::None);
+    }
+    (num.to_string(), Unit::from_stored(unit.trim()))
+}
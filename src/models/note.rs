@@ -1,5 +1,16 @@
-use std::{fmt, str::FromStr};
+use std::{fmt, fs, path::PathBuf, str::FromStr};
 use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Local, NaiveDate};
+use uuid::Uuid;
+use comfy_table::{
+    Table, ContentArrangement, presets::UTF8_BORDERS_ONLY,
+    Cell, Attribute, Color as TColor,
+};
+use crate::{
+    util, output,
+    error::DError,
+    models::{Attrib, entity::EntityRef},
+};
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Notes(Vec<Note>);
@@ -50,9 +61,12 @@ impl Note {
         }
     }
 
+    /// Reads the `;` separated notes of a fact entry CSV column
     pub fn from_col(rec: &csv::StringRecord, col: usize) -> Vec<Self> {
-            rec.iter().skip(col)
-                .map(|a| Note::new(a))
+            rec.get(col).unwrap_or_default()
+                .split(';')
+                .filter(|n| !n.is_empty())
+                .map(|n| Note::new(n))
                 .collect()
     }
 }
@@ -80,3 +94,219 @@ impl std::str::FromStr for Note {
 
 }
 
+
+/// Front-matter of a note file, between the `+++` lines at its top
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NoteMeta {
+    pub id: Uuid,
+    pub entity: EntityRef,
+    pub version: usize,
+    pub created: DateTime<Local>,
+    pub updated: DateTime<Local>,
+    /// The day of a journal entry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<NaiveDate>,
+    #[serde(default)]
+    pub attributes: Vec<String>,
+}
+
+/// A Markdown note written in an editor and attached to a record, item or
+/// fact entry. Every save is kept as a new version, under
+/// `<data dir>/dlog/notes/<id>/<version>.md`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NoteDoc {
+    #[serde(flatten)]
+    pub meta: NoteMeta,
+    pub body: String,
+}
+
+impl NoteDoc {
+
+    pub fn dir() -> crate::DResult<PathBuf> {
+        let dir = util::default_data_dir(Some("notes"))?;
+        fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    /// An unsaved note, which becomes version 1 when saved
+    pub fn new(entity: EntityRef, attribs: &[Attrib]) -> Self {
+        let now = Local::now();
+        Self {
+            meta: NoteMeta {
+                id: Uuid::new_v4(),
+                version: 0,
+                created: now,
+                updated: now,
+                date: None,
+                attributes: attribs.iter()
+                    .map(|a| match &a.value {
+                        Some(val) => format!("{}={}", a.name, val),
+                        None => a.name.clone(),
+                    })
+                    .collect(),
+                entity,
+            },
+            body: String::new(),
+        }
+    }
+
+    pub fn attribs(&self) -> Vec<Attrib> {
+        self.meta.attributes.iter()
+            .map(|a| Attrib::from(a.clone()))
+            .collect()
+    }
+
+    pub fn to_markdown(&self) -> crate::DResult<String> {
+        Ok(format!("+++\n{}+++\n\n{}\n", toml::to_string(&self.meta)?, self.body))
+    }
+
+    pub fn from_markdown(text: &str) -> crate::DResult<Self> {
+        let rest = text.trim_start().strip_prefix("+++")
+            .ok_or_else(|| DError::Invalid("Note has no +++ front-matter".into()))?;
+        let end = rest.find("\n+++")
+            .ok_or_else(|| DError::Invalid("Note front-matter is not closed with +++".into()))?;
+        let meta = toml::from_str(&rest[..end])?;
+        let body = rest[end + 4..].trim().to_string();
+        Ok(Self { meta, body })
+    }
+
+    /// Saves the note as its next version
+    pub fn save(&mut self) -> crate::DResult<PathBuf> {
+        let dir = Self::dir()?.join(self.meta.id.to_string());
+        fs::create_dir_all(&dir)?;
+        self.meta.version += 1;
+        self.meta.updated = Local::now();
+        let path = dir.join(format!("{}.md", self.meta.version));
+        crate::history::touch(&path)?;
+        fs::write(&path, self.to_markdown()?)?;
+        Ok(path)
+    }
+
+    /// Saved versions of a note, oldest first
+    pub fn versions(id: &Uuid) -> crate::DResult<Vec<usize>> {
+        let mut versions = Vec::new();
+        for entry in fs::read_dir(Self::dir()?.join(id.to_string()))? {
+            let path = entry?.path();
+            if let Some(v) = path.file_stem().and_then(|v| v.to_str()?.parse().ok()) {
+                versions.push(v);
+            }
+        }
+        versions.sort_unstable();
+        Ok(versions)
+    }
+
+    /// Loads a version of a note, or its latest
+    pub fn load(id: &Uuid, version: Option<usize>) -> crate::DResult<Self> {
+        let version = match version {
+            Some(v) => v,
+            None => *Self::versions(id)?.last().ok_or(DError::NotFound)?,
+        };
+        let path = Self::dir()?.join(id.to_string()).join(format!("{}.md", version));
+        if !path.is_file() {
+            return Err(DError::NotFound);
+        }
+        Self::from_markdown(&fs::read_to_string(path)?)
+    }
+
+    /// The latest version of every note, most recently updated first
+    pub fn all() -> crate::DResult<Vec<Self>> {
+        let mut notes = Vec::new();
+        for entry in fs::read_dir(Self::dir()?)? {
+            let name = entry?.file_name();
+            if let Ok(id) = name.to_string_lossy().parse::<Uuid>() {
+                notes.push(Self::load(&id, None)?);
+            }
+        }
+        notes.sort_by_key(|n| std::cmp::Reverse(n.meta.updated));
+        Ok(notes)
+    }
+
+    pub fn of(entity: &EntityRef) -> crate::DResult<Vec<Self>> {
+        Ok(Self::all()?.into_iter()
+            .filter(|n| n.meta.entity.is(entity))
+            .collect())
+    }
+
+    /// Finds a note by its id or the start of it, as shown by `dlog note list`
+    pub fn find(id: &str) -> crate::DResult<Self> {
+        let mut matches = fs::read_dir(Self::dir()?)?
+            .filter_map(|e| e.ok()?.file_name().to_string_lossy().parse::<Uuid>().ok())
+            .filter(|n| n.to_string().starts_with(&id.to_lowercase()))
+            .collect::<Vec<Uuid>>();
+        match matches.len() {
+            0 => Err(DError::NotFound),
+            1 => Self::load(&matches.remove(0), None),
+            _ => Err(DError::Invalid(format!("More than one note starts with {}", id))),
+        }
+    }
+
+    /// Notes containing every word of the query in their text or attributes,
+    /// each with the first line which matched
+    pub fn search(query: &str) -> crate::DResult<Vec<(Self, String)>> {
+        let words = query.split_whitespace()
+            .map(str::to_lowercase)
+            .collect::<Vec<String>>();
+        let mut found = Vec::new();
+        for note in Self::all()? {
+            let text = format!("{}\n{}", note.body, note.meta.attributes.join(" "))
+                .to_lowercase();
+            if words.is_empty() || !words.iter().all(|w| text.contains(w.as_str())) {
+                continue;
+            }
+            let line = note.body.lines()
+                .find(|l| l.to_lowercase().contains(words[0].as_str()))
+                .unwrap_or_else(|| note.title())
+                .trim()
+                .to_string();
+            found.push((note, line));
+        }
+        Ok(found)
+    }
+
+    /// The first non-empty line of the note, without Markdown heading marks
+    pub fn title(&self) -> &str {
+        self.body.lines()
+            .map(|l| l.trim_start_matches('#').trim())
+            .find(|l| !l.is_empty())
+            .unwrap_or_default()
+    }
+
+    /// Notes with a preview of each: their title, or the matching line when
+    /// given from a search
+    pub fn table(notes: &[(Self, String)]) -> Table {
+        let mut table = Table::new();
+        table.load_preset(UTF8_BORDERS_ONLY)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec![
+                Cell::new("Note").add_attribute(Attribute::Bold)
+                    .fg(TColor::Green),
+                Cell::new("Entity").add_attribute(Attribute::Bold),
+                Cell::new("Version").add_attribute(Attribute::Bold),
+                Cell::new("Attributes").add_attribute(Attribute::Bold),
+                Cell::new("Updated").add_attribute(Attribute::Bold),
+                Cell::new("Preview").add_attribute(Attribute::Bold),
+            ]);
+        for (note, preview) in notes {
+            table.add_row(vec![
+                note.meta.id.to_string()[..8].to_string(),
+                note.meta.entity.to_string(),
+                note.meta.version.to_string(),
+                note.meta.attributes.join(", "),
+                output::datetime(&note.meta.updated),
+                preview.chars().take(60).collect(),
+            ]);
+        }
+        output::table("notes", table)
+    }
+}
+
+impl fmt::Display for NoteDoc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} ({}, v{}, {})", self.meta.entity, &self.meta.id.to_string()[..8],
+            self.meta.version, self.meta.updated.format("%Y-%m-%d %H:%M"))?;
+        if !self.meta.attributes.is_empty() {
+            writeln!(f, "Attributes: {}", self.meta.attributes.join(", "))?;
+        }
+        write!(f, "\n{}", self.body)
+    }
+}
@@ -5,39 +5,63 @@ use comfy_table::{
 use std::{
     path::PathBuf, fs,
     convert::TryFrom,
-    rc::Rc,
-    io::{Read, Write},
 };
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
 use chrono::{DateTime, Local};
 use crate::{
-    util,
-    models::{Entry, Unit, Item, fact::{Fact, AbstractFact}},
+    util, history,
+    output,
+    error::DError,
+    models::{
+        Entry, Unit, Item, Attrib, Action, Note,
+        data::{RecordData, UserData},
+        entity::EntityRef,
+        trash::{Trash, Trashed, TrashKind},
+        fact::{Fact, AbstractFact},
+    },
 };
 use clap::{ArgMatches, FromArgMatches};
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Entry in the central record list (`records.toml` in the data dir) pointing
+/// to a record which may live anywhere on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CentralRecord {
+    pub name: String,
     pub path: String,
 }
 
+// NOTE plain values must come before the item/fact type tables to serialize to TOML,
+//      and empty lists of tables are skipped for the same reason
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Record {
     #[serde(rename="Id")]
     pub id: uuid::Uuid,
     #[serde(rename="Record")]
     pub name: String,
-    #[serde(rename="Description")]
+    #[serde(rename="Description", default)]
     pub description: String,
-    #[serde(rename="Items")]
-    pub items: Vec<Rc<Item>>,
-    #[serde(rename="Facts")]
-    pub fact_types: Vec<AbstractFact>,
-    #[serde(rename="Directory")]
+    #[serde(rename="Directory", default)]
     pub dir: PathBuf,
     #[serde(rename="Created at")]
     pub created: DateTime<Local>,
+    /// When the record was last written, for sync to tell which side changed
+    #[serde(rename="Updated at", default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<DateTime<Local>>,
+    #[serde(rename="Units", default)]
+    pub units: Vec<Unit>,
+    #[serde(rename="Links", default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<EntityRef>,
+    #[serde(rename="Notes", default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<Note>,
+    #[serde(rename="Items", default, skip_serializing_if = "Vec::is_empty")]
+    pub items: Vec<Item>,
+    #[serde(rename="Facts", default, skip_serializing_if = "Vec::is_empty")]
+    pub fact_types: Vec<AbstractFact>,
+    #[serde(rename="Attributes", default, skip_serializing_if = "Vec::is_empty")]
+    pub attribs: Vec<Attrib>,
+    #[serde(rename="Actions", default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<Action>,
 }
 
 impl Default for Record {
@@ -50,8 +74,14 @@ impl Default for Record {
             description: String::new(),
             items: Vec::new(),
             created: Local::now(),
+            updated: None,
             dir: inbox,
             fact_types: Vec::new(),
+            units: Vec::new(),
+            links: Vec::new(),
+            notes: Vec::new(),
+            attribs: Vec::new(),
+            actions: Vec::new(),
         }
     }
 }
@@ -70,7 +100,13 @@ impl Record {
                 dir,
                 items: Vec::new(),
                 fact_types: Vec::new(),
-                created: Local::now(), }
+                units: Vec::new(),
+                links: Vec::new(),
+                notes: Vec::new(),
+                attribs: Vec::new(),
+                actions: Vec::new(),
+                created: Local::now(),
+                updated: None, }
         } else {
             Self::default()
         }
@@ -95,42 +131,348 @@ impl Record {
         }
     }
 
-    pub fn write(&self) -> crate::DResult<()> {
-        let path = dirs_next::data_dir().expect("");
-        let rec = path.join("dlog").join("dlog");
+    pub fn write(&mut self) -> crate::DResult<()> {
+        self.updated = Some(Local::now());
+        let toml = toml::to_string_pretty(self)?;
+        let path = self.dir.join("record.toml");
+        history::touch(&path)?;
+        fs::write(path, toml)?;
         Ok(())
     }
 
-    pub fn open_path(path: String) -> std::io::Result<Self> {
-        match PathBuf::try_from(path) {
-            Ok(dir) => {
-                fs::create_dir_all(&dir)?;
-                let mut rf = fs::OpenOptions::new()
-                    .read(true)
-                    .write(true)
-                    .create(true)
-                    .open(&dir.join("record.toml"))?;
-                let mut buf = String::new();
-                rf.read_to_string(&mut buf)?;
-                let tf: Record = toml::from_str(buf.as_str())
-                    .expect("Could not read record file");
-                Ok(tf)
+    /// Lays out a new record in its directory: the record's `record.toml`,
+    /// the fact entry CSV and optionally a `.gitignore`. Refuses to touch a
+    /// directory which already holds a record, or to reuse a record name.
+    pub fn init(&self, gitignore: bool) -> crate::DResult<PathBuf> {
+        if crate::auth::exists(&self.dir.join("record.toml")) {
+            return Err(DError::AlreadyExists(
+                format!("A record in {}", self.dir.display())));
+        }
+        let mut records = RecordData::read()?;
+        if records.find(&self.name).is_some() {
+            return Err(DError::AlreadyExists(
+                format!("A record named {}", self.name)));
+        }
+        let toml = toml::to_string_pretty(self)?;
+        fs::create_dir_all(&self.dir)?;
+        let csv = self.csv_path();
+        let ignore = self.dir.join(".gitignore");
+        for path in &[&csv, &ignore, &self.dir.join("record.toml")] {
+            history::touch(path)?;
+        }
+        if !csv.exists() {
+            let mut wtr = csv::Writer::from_path(&csv)?;
+            wtr.write_record(&Fact::CSV_HEADERS)?;
+            wtr.flush()?;
+        }
+        if gitignore && !ignore.exists() {
+            fs::write(&ignore, "# Generated by dlog\n/db/\n*.tmp\n")?;
+        }
+        fs::write(self.dir.join("record.toml"), toml)?;
+        records.register(self)?;
+        Ok(self.dir.clone())
+    }
+
+    /// The CSV holding fact entries logged to the record itself
+    pub fn csv_path(&self) -> PathBuf {
+        self.dir.join(format!("{}.csv", &self.name))
+    }
+
+    /// Reads the record in a directory from its `record.toml`
+    pub fn open_path<P: Into<PathBuf>>(path: P) -> crate::DResult<Self> {
+        let dir = path.into();
+        crate::auth::open_record(&dir)?;
+        let file = dir.join("record.toml");
+        if !file.is_file() {
+            return Err(DError::NotFound);
+        }
+        let mut rec: Record = toml::from_str(&fs::read_to_string(&file)?)?;
+        rec.dir = dir;
+        Ok(rec)
+    }
+
+    /// Opens a registered record by name, or the inbox
+    pub fn open(name: &str) -> crate::DResult<Self> {
+        if name.eq_ignore_ascii_case("inbox") {
+            return Self::inbox();
+        }
+        let rec = RecordData::read()?.find(name)
+            .map(|r| PathBuf::from(&r.path))
+            .ok_or(DError::NotFound)?;
+        Self::open_path(rec)
+    }
+
+    /// The record uncategorized facts and items go to, laid out the first
+    /// time it is needed
+    pub fn inbox() -> crate::DResult<Self> {
+        let inbox = Self::default();
+        match Self::open_path(&inbox.dir) {
+            Err(DError::NotFound) => {
+                inbox.init(false)?;
+                Ok(inbox)
             },
-            Err(e) => Ok(Self::default()),
+            rec => rec,
         }
     }
 
+    /// The record facts written inline in journals go to, laid out in the
+    /// data dir the first time it is needed
+    pub fn journal() -> crate::DResult<Self> {
+        match Self::open("journal") {
+            Err(DError::NotFound) => {
+                let mut journal = Self::new(Some("journal".into()), None);
+                journal.dir = util::default_data_dir(Some("journal"))?;
+                journal.description = "Facts written in daily journals".into();
+                journal.init(false)?;
+                Ok(journal)
+            },
+            rec => rec,
+        }
+    }
 
-    pub fn add_item(&self, item: &Item) -> std::io::Result<PathBuf> {
-        let rec = self.get_or_create()?;
-        let item = rec.parent().expect("Could not find parent")
-            .join(format!("{}{}", &item.name, ".csv"));
-        let mut wtr = csv::WriterBuilder::new()
-            .has_headers(true)
-            .flexible(true)
-            .from_path(&item)?;
-        wtr.flush()?;
-        Ok(item)
+    /// Looks up an item by its path, e.g. `exercise/running`
+    pub fn item(&self, path: &str) -> Option<&Item> {
+        let path = Item::split(path);
+        self.items.iter().find(|i| {
+            let own = Item::split(&i.path());
+            own.len() == path.len() && own.iter().zip(path.iter())
+                .all(|(a, b)| a.eq_ignore_ascii_case(b))
+        })
+    }
+
+    /// Items directly under the item at `path`, or the top level items
+    pub fn children<'a>(&'a self, path: Option<&'a str>) -> impl Iterator<Item = &'a Item> + 'a {
+        self.items.iter().filter(move |i| match (path, &i.parent) {
+            (None, None) => true,
+            (Some(path), Some(parent)) => Item::split(parent).iter()
+                .map(|s| s.to_lowercase())
+                .eq(Item::split(path).iter().map(|s| s.to_lowercase())),
+            _ => false,
+        })
+    }
+
+    /// All items nested anywhere under the item at `path`
+    pub fn descendants<'a>(&'a self, path: &'a str) -> impl Iterator<Item = &'a Item> + 'a {
+        let depth = Item::split(path).len();
+        self.items.iter()
+            .filter(move |i| i.is_under(path) && Item::split(&i.path()).len() > depth)
+    }
+
+    /// Adds an item to the record, creating any parent items it names which
+    /// don't exist yet along with their entry CSVs. Returns the CSV the
+    /// item's fact entries go to.
+    pub fn add_item(&mut self, item: Item) -> crate::DResult<PathBuf> {
+        if self.item(&item.path()).is_some() {
+            return Err(DError::AlreadyExists(
+                format!("An item {} in {}", item.path(), self.name)));
+        }
+        if let Some(parent) = item.parent.clone() {
+            if self.item(&parent).is_none() {
+                self.add_item(Item::new(parent))?;
+            }
+        }
+        let csv = item.csv_path(self);
+        fs::create_dir_all(item.dir(self))?;
+        history::touch(&csv)?;
+        if !csv.exists() {
+            let mut wtr = csv::Writer::from_path(&csv)?;
+            wtr.write_record(&Fact::CSV_HEADERS)?;
+            wtr.flush()?;
+        }
+        self.items.push(item);
+        self.write()?;
+        Ok(csv)
+    }
+
+    /// Moves (or renames) an item and everything under it to a new path
+    /// within the record, creating any new parent items needed
+    pub fn move_item(&mut self, from: &str, to: &str) -> crate::DResult<()> {
+        let item = self.item(from).cloned().ok_or(DError::NotFound)?;
+        let moved = Item { id: item.id, created: item.created, ..Item::new(to.into()) };
+        if self.item(to).is_some() {
+            return Err(DError::AlreadyExists(
+                format!("An item {} in {}", moved.path(), self.name)));
+        }
+        if moved.is_under(&item.path()) {
+            return Err(DError::ParsePath);
+        }
+        if let Some(parent) = moved.parent.clone() {
+            if self.item(&parent).is_none() {
+                self.add_item(Item::new(parent))?;
+            }
+        }
+        let (old_dir, new_dir) = (item.dir(self), moved.dir(self));
+        history::touch_move(&old_dir, &new_dir)?;
+        history::touch(&moved.csv_path(self))?;
+        fs::rename(&old_dir, &new_dir)?;
+        let old_csv = new_dir.join(format!("{}.csv", item.name));
+        if old_csv.exists() {
+            fs::rename(&old_csv, moved.csv_path(self))?;
+        }
+        let (old_path, new_path) = (Item::split(&item.path()), Item::split(&moved.path()));
+        for other in self.items.iter_mut().filter(|i| i.is_under(&item.path())) {
+            let mut segments = Item::split(&other.path());
+            segments.splice(..old_path.len(), new_path.iter().cloned());
+            other.name = segments.pop().unwrap_or_default();
+            other.parent = if segments.is_empty() { None } else { Some(segments.join("/")) };
+        }
+        self.write()
+    }
+
+    /// Moves an item to the trash. With `cascade` its entries and every
+    /// item under it go along with it. Otherwise they are orphaned: its
+    /// entries move to the record's own CSV and its children move up to its
+    /// parent. Returns how many entries and items were affected.
+    pub fn remove_item(&mut self, path: &str, cascade: bool) -> crate::DResult<(usize, usize)> {
+        let item = self.item(path).cloned().ok_or(DError::NotFound)?;
+        let children = self.descendants(&item.path()).cloned().collect::<Vec<Item>>();
+        if cascade {
+            let facts = item.facts(self, true)?.len();
+            Trash::put_item(self, &item)?;
+            self.items.retain(|i| !i.is_under(&item.path()));
+            self.write()?;
+            return Ok((facts, children.len() + 1));
+        }
+        let direct = self.children(Some(&item.path())).cloned().collect::<Vec<Item>>();
+        let up = |child: &Item| match &item.parent {
+            Some(parent) => format!("{}/{}", parent, child.name),
+            None => child.name.clone(),
+        };
+        for child in &direct {
+            let to = up(child);
+            if self.item(&to).is_some() {
+                return Err(DError::AlreadyExists(
+                    format!("An item {} in {}", to, self.name)));
+            }
+        }
+        let facts = Fact::read_csv(item.csv_path(self))?;
+        for fact in &facts {
+            fact.append_to(self.csv_path())?;
+        }
+        for child in &direct {
+            self.move_item(&child.path(), &up(child))?;
+        }
+        Fact::write_csv(item.csv_path(self), &[])?;
+        Trash::put_item(self, &item)?;
+        self.items.retain(|i| i.id != item.id);
+        self.write()?;
+        Ok((facts.len(), direct.len()))
+    }
+
+    /// Moves the record, with everything in it, to the trash. The inbox
+    /// can't be deleted.
+    pub fn delete(&self) -> crate::DResult<Trashed> {
+        if self.dir == Self::default().dir {
+            return Err(DError::Invalid("The inbox can't be deleted".into()));
+        }
+        self.check_access()?;
+        Trash::put_record(self)
+    }
+
+    /// Moves every entry of a fact in the record to the trash, along with
+    /// the fact type if the record has one
+    pub fn remove_fact(&mut self, name: &str) -> crate::DResult<Trashed> {
+        self.check_access()?;
+        let mut removed = Vec::new();
+        for path in self.csv_paths() {
+            let (matching, kept) = Fact::read_csv(&path)?.into_iter()
+                .partition::<Vec<Fact>, _>(|f| f.name.eq_ignore_ascii_case(name));
+            if !matching.is_empty() {
+                Fact::write_csv(&path, &kept)?;
+                removed.extend(matching.into_iter().map(|f| (path.clone(), f)));
+            }
+        }
+        let fact_type = self.fact_types.iter()
+            .position(|f| f.name.eq_ignore_ascii_case(name))
+            .map(|i| self.fact_types.remove(i));
+        if removed.is_empty() && fact_type.is_none() {
+            return Err(DError::NotFound);
+        }
+        let name = fact_type.as_ref().map(|f| f.name.clone())
+            .or_else(|| removed.first().map(|(_, f)| f.name.clone()))
+            .unwrap_or_else(|| name.to_string());
+        let trashed = Trash::put_entries(self, TrashKind::Fact, &name, &removed, fact_type)?;
+        self.write()?;
+        Ok(trashed)
+    }
+
+    /// Refuses the current user if the record is shared with a group they
+    /// are not in
+    pub fn check_access(&self) -> crate::DResult<()> {
+        let users = UserData::read()?;
+        users.check_access(&self.name, users.current().map(|u| u.username.as_str()))
+    }
+
+    /// Applies `f` to the attributes of the record, its items, its fact types
+    /// and every fact entry logged to it, writing back whatever changed.
+    /// Returns how many facts, items and records (0 or 1) were changed.
+    pub fn rewrite_attribs<F>(&mut self, f: F) -> crate::DResult<(usize, usize, usize)>
+    where
+        F: Fn(&mut Vec<Attrib>) -> bool,
+    {
+        let mut facts = 0;
+        let mut paths = vec![self.csv_path()];
+        paths.extend(self.items.iter().map(|i| i.csv_path(self)));
+        for path in paths {
+            let mut entries = Fact::read_csv(&path)?;
+            let changed = entries.iter_mut()
+                .map(|e| f(&mut e.attribs))
+                .filter(|changed| *changed)
+                .count();
+            if changed > 0 {
+                Fact::write_csv(&path, &entries)?;
+                facts += changed;
+            }
+        }
+        let items = self.items.iter_mut()
+            .map(|i| f(&mut i.attribs))
+            .filter(|changed| *changed)
+            .count();
+        let types = self.fact_types.iter_mut()
+            .map(|t| f(&mut t.attribs))
+            .filter(|changed| *changed)
+            .count();
+        let records = f(&mut self.attribs) as usize;
+        if items + types + records > 0 {
+            self.write()?;
+        }
+        Ok((facts, items, records))
+    }
+
+    /// Every item of the record as an indented tree, with the number of
+    /// entries logged to each item and to it and its descendants
+    pub fn item_tree(&self) -> crate::DResult<Table> {
+        let mut table = Table::new();
+        table.load_preset(UTF8_BORDERS_ONLY)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec![
+                Cell::new(&self.name).add_attribute(Attribute::Bold)
+                    .fg(TColor::Yellow),
+                Cell::new("Entries").add_attribute(Attribute::Bold),
+                Cell::new("Total").add_attribute(Attribute::Bold),
+                Cell::new("Last Updated").add_attribute(Attribute::Bold),
+            ]);
+        self.add_tree_rows(&mut table, None)?;
+        Ok(output::table("item_tree", table))
+    }
+
+    fn add_tree_rows(&self, table: &mut Table, parent: Option<&str>) -> crate::DResult<()> {
+        let mut children = self.children(parent).collect::<Vec<&Item>>();
+        children.sort_by_key(|i| i.name.to_lowercase());
+        for item in children {
+            let own = Fact::read_csv(item.csv_path(self))?.len();
+            let all = item.facts(self, true)?;
+            table.add_row(vec![
+                format!("{}{}", "  ".repeat(item.depth()), item.name),
+                own.to_string(),
+                all.len().to_string(),
+                all.last()
+                    .map(|f| output::datetime(&f.created_at))
+                    .unwrap_or_default(),
+            ]);
+            self.add_tree_rows(table, Some(&item.path()))?;
+        }
+        Ok(())
     }
 
     pub fn read(&self, item: Option<String>) -> std::io::Result<Vec<Fact>> {
@@ -152,7 +494,8 @@ impl Record {
         while let Some(rec) = rdr.records().next() {
             match rec {
                 Ok(rec) => {
-                    let fact = Fact::try_from(rec)?;
+                    let fact = Fact::from_row(rec, &csv)
+                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
                     println!("{:#?}", fact);
                 },
                 Err(e) => return Err(From::from(e)),
@@ -161,9 +504,33 @@ impl Record {
         Ok(vec![Fact::default()])
     }
 
-    pub fn fact_entry_table(&self) -> Table {
+    /// The CSVs of the record's own entries and each of its items' entries
+    pub fn csv_paths(&self) -> Vec<PathBuf> {
+        let mut paths = vec![self.csv_path()];
+        paths.extend(self.items.iter().map(|i| i.csv_path(self)));
+        paths
+    }
+
+    /// Every entry in the record, logged to the record itself or any of its
+    /// items, oldest first
+    pub fn facts(&self) -> crate::DResult<Vec<Fact>> {
+        let mut facts = Fact::read_csv(self.csv_path())?;
+        for item in &self.items {
+            facts.extend(Fact::read_csv(item.csv_path(self))?);
+        }
+        facts.sort_by_key(|f| f.created_at);
+        Ok(facts)
+    }
+
+    /// When an entry was last logged to the record, or when it was created
+    pub fn last_updated(&self) -> crate::DResult<DateTime<Local>> {
+        Ok(self.facts()?.last()
+            .map(|f| f.created_at)
+            .unwrap_or(self.created))
+    }
+
+    pub fn fact_entry_table(&self) -> crate::DResult<Table> {
         let mut table = Table::new();
-        // let facts = self.fact_types.iter()
         table.load_preset(UTF8_BORDERS_ONLY)
             .set_content_arrangement(ContentArrangement::Dynamic)
             .set_table_width(160)
@@ -176,10 +543,17 @@ impl Record {
                 Cell::new("Notes").add_attribute(Attribute::Bold),
                 Cell::new("Created").add_attribute(Attribute::Bold),
             ]);
-        // TODO make lookup algorithm to look through all facts in db
-        // of types corresponding to record fact types and iter through
-        // them, adding their row
-        table
+        for fact in self.facts()? {
+            table.add_row(vec![
+                fact.name.clone(),
+                fact.val.raw(),
+                fact.unit.to_string(),
+                Attrib::join(&fact.attribs),
+                Note::join(&fact.notes),
+                output::datetime(&fact.created_at),
+            ]);
+        }
+        Ok(output::table("facts", table))
     }
 
     pub fn fact_types_table(&self) -> Table {
@@ -197,10 +571,11 @@ impl Record {
                 Cell::new("Created").add_attribute(Attribute::Bold),
             ])
             .add_row(&self.fact_types);
-        table
+        output::table("fact_types", table)
     }
 
-    pub fn record_table() -> Table {
+    /// One row per record, with the last update taken from its entries
+    pub fn record_table(records: &[Record]) -> crate::DResult<Table> {
         let mut table = Table::new();
         table.load_preset(UTF8_BORDERS_ONLY)
             .set_content_arrangement(ContentArrangement::Dynamic)
@@ -208,7 +583,7 @@ impl Record {
             .set_header(vec![
                 Cell::new("Record").add_attribute(Attribute::Bold)
                     .fg(TColor::Yellow),
-                Cell::new("Name").add_attribute(Attribute::Bold),
+                Cell::new("Description").add_attribute(Attribute::Bold),
                 Cell::new("Number of Items").add_attribute(Attribute::Bold),
                 Cell::new("Attributes").add_attribute(Attribute::Bold),
                 Cell::new("Notes").add_attribute(Attribute::Bold),
@@ -216,8 +591,96 @@ impl Record {
                 Cell::new("Last Updated").add_attribute(Attribute::Bold),
                 Cell::new("Created").add_attribute(Attribute::Bold),
             ]);
-        table
+        for rec in records {
+            table.add_row(vec![
+                rec.name.clone(),
+                rec.description.clone(),
+                rec.items.len().to_string(),
+                Attrib::join(&rec.attribs),
+                Note::join(&rec.notes),
+                rec.links.iter()
+                    .map(|l| l.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                output::datetime(&rec.last_updated()?),
+                output::datetime(&rec.created),
+            ]);
+        }
+        Ok(output::table("records", table))
+    }
+
+    /// The record with its items nested beneath it and the most recent
+    /// entries beneath each. `depth` collapses the display: 0 shows only
+    /// the record, 1 adds its own entries and top level items, and each
+    /// level after that opens one more level of items and their entries.
+    pub fn overview(&self, depth: usize, recent: usize) -> crate::DResult<Table> {
+        let mut table = Table::new();
+        table.load_preset(UTF8_BORDERS_ONLY)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec![
+                Cell::new("Record").add_attribute(Attribute::Bold)
+                    .fg(TColor::Yellow),
+                Cell::new("Value").add_attribute(Attribute::Bold),
+                Cell::new("Entries").add_attribute(Attribute::Bold),
+                Cell::new("Attributes").add_attribute(Attribute::Bold),
+                Cell::new("Last Updated").add_attribute(Attribute::Bold),
+            ]);
+        let facts = self.facts()?;
+        table.add_row(vec![
+            Cell::new(&self.name).add_attribute(Attribute::Bold).fg(TColor::Yellow),
+            Cell::new(&self.description),
+            Cell::new(facts.len()),
+            Cell::new(Attrib::join(&self.attribs)),
+            Cell::new(output::datetime(&self.last_updated()?)),
+        ]);
+        if depth > 0 {
+            Self::add_fact_rows(&mut table, Fact::read_csv(self.csv_path())?, 1, recent);
+            self.add_overview_rows(&mut table, None, depth, recent)?;
+        }
+        Ok(output::table("record", table))
+    }
+
+    fn add_overview_rows(&self, table: &mut Table, parent: Option<&str>, depth: usize, recent: usize)
+        -> crate::DResult<()>
+    {
+        let mut children = self.children(parent).collect::<Vec<&Item>>();
+        children.sort_by_key(|i| i.name.to_lowercase());
+        for item in children {
+            let level = item.depth() + 1;
+            let facts = item.facts(self, true)?;
+            table.add_row(vec![
+                Cell::new(format!("{}{}", "  ".repeat(level), item.name))
+                    .fg(TColor::Green),
+                Cell::new(""),
+                Cell::new(facts.len()),
+                Cell::new(Attrib::join(&item.attribs)),
+                Cell::new(facts.last()
+                    .map(|f| output::datetime(&f.created_at))
+                    .unwrap_or_default()),
+            ]);
+            if level < depth {
+                Self::add_fact_rows(table, item.facts(self, false)?, level + 1, recent);
+                self.add_overview_rows(table, Some(&item.path()), depth, recent)?;
+            }
+        }
+        Ok(())
+    }
 
+    fn add_fact_rows(table: &mut Table, facts: Vec<Fact>, level: usize, recent: usize) {
+        for fact in facts.iter().rev().take(recent) {
+            let unit = match fact.unit {
+                Unit::None => String::new(),
+                ref unit => format!(" {}", unit),
+            };
+            table.add_row(vec![
+                Cell::new(format!("{}{}", "  ".repeat(level), fact.name))
+                    .fg(TColor::Blue),
+                Cell::new(format!("{}{}", fact.val.raw(), unit)),
+                Cell::new(""),
+                Cell::new(Attrib::join(&fact.attribs)),
+                Cell::new(output::datetime(&fact.created_at)),
+            ]);
+        }
     }
 
 }
@@ -230,8 +693,14 @@ impl From<String> for Record {
             name,
             items: Vec::new(),
             created: Local::now(),
+            updated: None,
             description: String::new(),
             fact_types: Vec::new(),
+            units: Vec::new(),
+            links: Vec::new(),
+            notes: Vec::new(),
+            attribs: Vec::new(),
+            actions: Vec::new(),
             dir: util::default_data_dir(None).expect("No valid data dir"),
         }
     }
@@ -239,13 +708,28 @@ impl From<String> for Record {
 
 impl FromArgMatches for Record {
     fn from_arg_matches(matches: &ArgMatches) -> Self {
-        Self::default()
+        let mut rec = match matches.value_of("NAME") {
+            Some(name) => Self::new(Some(name.into()), None),
+            None => Self::default(),
+        };
+        if let Some(descr) = matches.value_of("description") {
+            rec.description = descr.into();
+        }
+        rec
+    }
+}
+
+/// Displays the record's full overview, with its three most recent entries
+/// under the record and each item
+impl std::fmt::Display for Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.overview(usize::MAX, 3) {
+            Ok(table) => write!(f, "{}", table),
+            Err(e) => write!(f, "{} (could not read entries: {})", self.name, e),
+        }
     }
 }
 
-// TODO make a hierarchical table display of a table and its items/facts
-// So that a record calling fmt can just call fmt on all of its facts and produce
-// one big table for the record with all the facts and items
 impl Entry for Record {
     fn datetime(&self) -> chrono::DateTime<Local> {
         self.created
@@ -1,6 +1,7 @@
 use std::boxed::Box;
-use std::fmt;
+use std::{fmt, path::PathBuf};
 use serde::{Serialize, Deserialize};
+use crate::{util, models::entity::EntityRef};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Relation<R, S> {
@@ -30,3 +31,54 @@ impl<R, S> fmt::Display for Relation<R, S> {
         Ok(())
     }
 }
+
+/// A named relation between two entities, e.g. a fact entry which
+/// `mentions` a record. Kept in `<data dir>/dlog/relations.csv`.
+pub type EntityRelation = Relation<EntityRef, EntityRef>;
+
+impl Relation<EntityRef, EntityRef> {
+
+    pub fn index_path() -> crate::DResult<PathBuf> {
+        util::default_data_dir(Some("relations.csv"))
+    }
+
+    pub fn append(&self) -> crate::DResult<()> {
+        let mut wtr = crate::csv::csv_appender(Self::index_path()?)?;
+        wtr.serialize(self)?;
+        wtr.flush()?;
+        Ok(())
+    }
+
+    pub fn all() -> crate::DResult<Vec<Self>> {
+        let path = Self::index_path()?;
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let mut rdr = crate::csv::csv_reader(path)?;
+        let mut relations = Vec::new();
+        for rel in rdr.deserialize() {
+            relations.push(rel?);
+        }
+        Ok(relations)
+    }
+
+    /// Keeps only the relations for which `keep` is true
+    pub fn retain<F: Fn(&Self) -> bool>(keep: F) -> crate::DResult<()> {
+        let relations = Self::all()?;
+        let path = Self::index_path()?;
+        crate::history::touch(&path)?;
+        let mut wtr = csv::Writer::from_path(path)?;
+        for rel in relations.iter().filter(|r| keep(r)) {
+            wtr.serialize(rel)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// Relations to or from the entity
+    pub fn of(entity: &EntityRef) -> crate::DResult<Vec<Self>> {
+        Ok(Self::all()?.into_iter()
+            .filter(|r| r.r1.is(entity) || r.r2.is(entity))
+            .collect())
+    }
+}
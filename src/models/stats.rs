@@ -1,5 +1,11 @@
 use std::fmt;
 use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Local};
+use comfy_table::{
+    Table, ContentArrangement, presets::UTF8_BORDERS_ONLY,
+    Cell, Attribute, Color as TColor,
+};
+use crate::{output, models::Fact};
 
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 #[non_exhaustive]
@@ -27,3 +33,87 @@ impl fmt::Display for Stats {
     }
 }
 
+
+/// Summary of the entries of one fact, with numeric stats for facts whose
+/// values are numbers or durations
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FactStats {
+    #[serde(rename = "Fact")]
+    pub name: String,
+    #[serde(rename = "Entries")]
+    pub count: usize,
+    #[serde(rename = "Total")]
+    pub sum: Option<f64>,
+    #[serde(rename = "Min")]
+    pub min: Option<f64>,
+    #[serde(rename = "Max")]
+    pub max: Option<f64>,
+    #[serde(rename = "Last")]
+    pub last: Option<DateTime<Local>>,
+}
+
+impl FactStats {
+
+    /// Groups entries by fact name, in order of first appearance
+    pub fn summarize(facts: &[Fact]) -> Vec<Self> {
+        let mut stats: Vec<Self> = Vec::new();
+        for fact in facts {
+            let idx = match stats.iter().position(|s| s.name.eq_ignore_ascii_case(&fact.name)) {
+                Some(idx) => idx,
+                None => {
+                    stats.push(Self {
+                        name: fact.name.clone(), count: 0,
+                        sum: None, min: None, max: None, last: None,
+                    });
+                    stats.len() - 1
+                }
+            };
+            let stat = &mut stats[idx];
+            stat.count += 1;
+            stat.last = stat.last.max(Some(fact.created_at));
+            if let Some(n) = fact.val.as_f64() {
+                stat.sum = Some(stat.sum.unwrap_or(0.0) + n);
+                stat.min = Some(stat.min.map_or(n, |m| m.min(n)));
+                stat.max = Some(stat.max.map_or(n, |m| m.max(n)));
+            }
+        }
+        stats
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        self.sum.map(|s| s / self.count as f64)
+    }
+
+    pub fn table(stats: &[Self]) -> Table {
+        let num = |n: Option<f64>| n
+            .map(|n| format!("{:.2}", n).trim_end_matches('0').trim_end_matches('.').to_string())
+            .unwrap_or_default();
+        let mut table = Table::new();
+        table.load_preset(UTF8_BORDERS_ONLY)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec![
+                Cell::new("Fact").add_attribute(Attribute::Bold)
+                    .fg(TColor::Blue),
+                Cell::new("Entries").add_attribute(Attribute::Bold),
+                Cell::new("Total").add_attribute(Attribute::Bold),
+                Cell::new("Mean").add_attribute(Attribute::Bold),
+                Cell::new("Min").add_attribute(Attribute::Bold),
+                Cell::new("Max").add_attribute(Attribute::Bold),
+                Cell::new("Last").add_attribute(Attribute::Bold),
+            ]);
+        for stat in stats {
+            table.add_row(vec![
+                stat.name.clone(),
+                stat.count.to_string(),
+                num(stat.sum),
+                num(stat.mean()),
+                num(stat.min),
+                num(stat.max),
+                stat.last
+                    .map(|l| output::datetime(&l))
+                    .unwrap_or_default(),
+            ]);
+        }
+        output::table("stats", table)
+    }
+}
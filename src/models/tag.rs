@@ -0,0 +1,69 @@
+use crate::{
+    config::{TagRule, TagKind},
+    models::{Attrib, data::RecordData, entity::EntityRef},
+};
+
+/// A tag pulled out of a text value or note by a [`TagRule`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    Attrib(Attrib),
+    Mention(String),
+    Person(String),
+}
+
+impl Tag {
+
+    /// Every tag in the text, in order of appearance and without duplicates.
+    /// Trailing punctuation is dropped, so `#dinner.` gives `dinner`.
+    pub fn extract(text: &str, rules: &[TagRule]) -> Vec<Self> {
+        let mut tags = Vec::new();
+        for word in text.split_whitespace() {
+            let rule = match rules.iter().find(|r| !r.prefix.is_empty() && word.starts_with(&r.prefix)) {
+                Some(rule) => rule,
+                None => continue,
+            };
+            let name = word[rule.prefix.len()..]
+                .trim_end_matches(|c: char| c.is_ascii_punctuation() && c != '/');
+            // Mentions and people have to start with a letter, so that e.g.
+            // "+5" or "@3pm" aren't taken as tags
+            let valid = match rule.kind {
+                TagKind::Attrib => name.starts_with(char::is_alphanumeric),
+                TagKind::Mention | TagKind::Person => name.starts_with(char::is_alphabetic),
+            };
+            if !valid {
+                continue;
+            }
+            let tag = match rule.kind {
+                TagKind::Attrib => Self::Attrib(Attrib::from(name.to_string())),
+                TagKind::Mention => Self::Mention(name.to_string()),
+                TagKind::Person => Self::Person(name.to_string()),
+            };
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        tags
+    }
+
+    /// The entity a mention or person tag refers to. Mentions refer to the
+    /// record of that name if there is one, otherwise to an item.
+    pub fn entity(&self) -> crate::DResult<Option<EntityRef>> {
+        Ok(match self {
+            Self::Attrib(_) => None,
+            Self::Mention(name) => match RecordData::read()?.find(name) {
+                Some(rec) => Some(EntityRef::Record(rec.name.clone())),
+                None => Some(EntityRef::Item(name.clone())),
+            },
+            Self::Person(name) => Some(EntityRef::Topic(name.clone())),
+        })
+    }
+
+    /// The name of the relation a mention or person tag creates
+    pub fn relation(&self) -> &'static str {
+        match self {
+            Self::Attrib(_) => "tagged",
+            Self::Mention(_) => "mentions",
+            Self::Person(_) => "with",
+        }
+    }
+}
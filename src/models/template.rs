@@ -0,0 +1,180 @@
+use std::{fs, path::PathBuf};
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::Local;
+use comfy_table::{
+    Table, ContentArrangement, presets::UTF8_BORDERS_ONLY,
+    Cell, Attribute, Color as TColor,
+};
+use crate::{
+    output, prompt,
+    config::DConfig,
+    error::DError,
+    models::{
+        Unit, Attrib, Action, Record,
+        data::RecordData,
+        fact::AbstractFact,
+    },
+};
+
+/// Templates shipped with dlog, used when no user template of the same name exists
+const BUILTIN: [(&str, &str); 4] = [
+    ("health", include_str!("../../assets/templates/health.toml")),
+    ("finance", include_str!("../../assets/templates/finance.toml")),
+    ("reading", include_str!("../../assets/templates/reading.toml")),
+    ("workouts", include_str!("../../assets/templates/workouts.toml")),
+];
+
+/// A reusable bundle of fact types, units, attributes and default actions
+/// which new records can be created from. Uses the same keys as `record.toml`,
+/// so any record file can be read as a template.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Template {
+    #[serde(rename="Template", default)]
+    pub name: String,
+    #[serde(rename="Description", default)]
+    pub description: String,
+    #[serde(rename="Units", default)]
+    pub units: Vec<Unit>,
+    #[serde(rename="Facts", default, skip_serializing_if = "Vec::is_empty")]
+    pub fact_types: Vec<AbstractFact>,
+    #[serde(rename="Attributes", default, skip_serializing_if = "Vec::is_empty")]
+    pub attribs: Vec<Attrib>,
+    #[serde(rename="Actions", default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<Action>,
+}
+
+impl Template {
+
+    /// User templates live under `<config dir>/dlog/templates/<name>.toml`
+    pub fn dir() -> crate::DResult<PathBuf> {
+        Ok(DConfig::default_dir()?.join("templates"))
+    }
+
+    /// Looks up a template by name among the user's templates, then the
+    /// built-in ones, then registered records. Falls back to reading the
+    /// input as a path to a template file or record directory.
+    pub fn load(name: &str) -> crate::DResult<Self> {
+        let user = Self::dir()?.join(format!("{}.toml", name));
+        if user.is_file() {
+            return Self::read(user);
+        }
+        if let Some((_, toml)) = BUILTIN.iter().find(|(n, _)| *n == name) {
+            return Ok(toml::from_str(toml)?);
+        }
+        if let Some(rec) = RecordData::read()?.find(name) {
+            return Self::read(PathBuf::from(&rec.path).join("record.toml"));
+        }
+        let path = PathBuf::from(name);
+        if path.is_dir() {
+            Self::read(path.join("record.toml"))
+        } else if path.is_file() {
+            Self::read(path)
+        } else {
+            Err(DError::NotFound)
+        }
+    }
+
+    pub fn read(path: PathBuf) -> crate::DResult<Self> {
+        let mut template: Self = toml::from_str(&fs::read_to_string(&path)?)?;
+        if template.name.is_empty() {
+            template.name = path.file_stem()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+        }
+        Ok(template)
+    }
+
+    /// Saves as a user template, refusing to overwrite an existing one
+    pub fn save(&self) -> crate::DResult<PathBuf> {
+        prompt::validate_input(self.name.clone()).map_err(|why| DError::Invalid(
+            format!("A template can't be named {:?}: {}", self.name, why)))?;
+        let dir = Self::dir()?;
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.toml", self.name));
+        if path.exists() {
+            return Err(DError::AlreadyExists(format!("A template named {}", self.name)));
+        }
+        crate::history::touch(&path)?;
+        fs::write(&path, toml::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+
+    /// Names of all available templates, user templates first
+    pub fn list() -> crate::DResult<Vec<(String, bool)>> {
+        let mut names = Vec::new();
+        let dir = Self::dir()?;
+        if dir.is_dir() {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|e| e == "toml") {
+                    if let Some(name) = path.file_stem() {
+                        names.push((name.to_string_lossy().to_string(), false));
+                    }
+                }
+            }
+        }
+        names.sort();
+        for (name, _) in BUILTIN.iter() {
+            if !names.iter().any(|(n, _)| n == name) {
+                names.push((name.to_string(), true));
+            }
+        }
+        Ok(names)
+    }
+
+    pub fn from_record(record: &Record, name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: record.description.clone(),
+            units: record.units.clone(),
+            fact_types: record.fact_types.clone(),
+            attribs: record.attribs.clone(),
+            actions: record.actions.clone(),
+        }
+    }
+
+    /// Copies everything in the template into a new record, giving fact
+    /// types fresh ids
+    pub fn apply(self, record: &mut Record) {
+        if record.description.is_empty() {
+            record.description = self.description;
+        }
+        record.units = self.units;
+        record.attribs = self.attribs;
+        record.actions = self.actions;
+        record.fact_types = self.fact_types.into_iter()
+            .map(|f| AbstractFact {
+                id: Uuid::new_v4(),
+                created_at: Local::now(),
+                ..f
+            })
+            .collect();
+    }
+
+    pub fn list_table() -> crate::DResult<Table> {
+        let mut table = Table::new();
+        table.load_preset(UTF8_BORDERS_ONLY)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec![
+                Cell::new("Template").add_attribute(Attribute::Bold)
+                    .fg(TColor::Yellow),
+                Cell::new("Description").add_attribute(Attribute::Bold),
+                Cell::new("Facts").add_attribute(Attribute::Bold),
+                Cell::new("Built-in").add_attribute(Attribute::Bold),
+            ]);
+        for (name, builtin) in Self::list()? {
+            let template = Self::load(&name)?;
+            table.add_row(vec![
+                name,
+                template.description,
+                template.fact_types.iter()
+                    .map(|f| f.name.clone())
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                if builtin { "yes".into() } else { String::new() },
+            ]);
+        }
+        Ok(output::table("templates", table))
+    }
+}
@@ -1,4 +1,262 @@
+use chrono::{DateTime, Local};
+use serde::{Serialize, Deserialize};
+use comfy_table::{
+    Table, ContentArrangement, presets::UTF8_BORDERS_ONLY,
+    Cell, Attribute, Color as TColor,
+};
+use crate::{
+    output,
+    models::{
+        Attrib, Fact, Item, Record, Relation, Unit,
+        data::{RecordData, TopicData},
+        entity::EntityRef,
+    },
+};
+
+/// A subject which facts, items and records across any number of records
+/// can be tagged with, e.g. `sleep` in both the health and work records.
+/// Topics may be nested beneath a parent topic.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Topic {
-    name: String,
-    descr: String,
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Description", default)]
+    pub descr: String,
+    #[serde(rename = "Parent", default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    #[serde(rename = "Created at", default = "Local::now")]
+    pub created: DateTime<Local>,
+}
+
+/// Why an entity was found under a topic
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Match {
+    /// The fact is named after the topic
+    Name,
+    /// Tagged with `dlog topic tag`
+    Tagged,
+    /// Related by a `+topic` tag in its text
+    Mentioned,
+}
+
+/// An item or fact entry found under a topic, within its record
+#[derive(Debug, Clone)]
+pub enum TopicEntry {
+    Item(Item, usize, Match),
+    Fact(Option<String>, Fact, Match),
+}
+
+/// Everything about a topic and its subtopics in one record
+#[derive(Debug)]
+pub struct TopicView {
+    pub record: Record,
+    /// Whether the record itself is tagged with the topic
+    pub tagged: bool,
+    pub entries: Vec<TopicEntry>,
+}
+
+impl Topic {
+
+    pub fn new(name: &str, descr: &str, parent: Option<String>) -> Self {
+        Self {
+            name: name.into(),
+            descr: descr.into(),
+            parent,
+            created: Local::now(),
+        }
+    }
+
+    pub fn entity(&self) -> EntityRef {
+        EntityRef::Topic(self.name.clone())
+    }
+
+    /// Tags an entity with the topic, unless it already is
+    pub fn tag(&self, entity: &EntityRef) -> crate::DResult<bool> {
+        let tagged = Relation::of(&self.entity())?.iter()
+            .any(|r| r.name == "topic" && r.r1.is(entity) && r.r2.is(&self.entity()));
+        if tagged {
+            return Ok(false);
+        }
+        Relation::new("topic", None, entity.clone(), self.entity()).append()?;
+        Ok(true)
+    }
+
+    /// The records with anything about the topics, each with the items and
+    /// fact entries found. `topics` holds the topic and its subtopics.
+    pub fn gather(topics: &[String]) -> crate::DResult<Vec<TopicView>> {
+        let relations = Relation::all()?.into_iter()
+            .filter(|r| r.r2.kind() == "topic"
+                && topics.iter().any(|t| t.eq_ignore_ascii_case(r.r2.name())))
+            .collect::<Vec<_>>();
+        let related = |entity: &EntityRef| relations.iter()
+            .filter(|r| r.r1.is(entity))
+            .map(|r| match r.name.as_str() {
+                "topic" => Match::Tagged,
+                _ => Match::Mentioned,
+            })
+            .min_by_key(|m| *m as u8);
+        let mut views = Vec::new();
+        for rec in RecordData::read()?.records() {
+            let record = Record::open_path(&rec.path)?;
+            let mut entries = Vec::new();
+            let facts = Fact::read_csv(record.csv_path())?.into_iter()
+                .map(|f| (None, f))
+                .chain(record.items.iter()
+                    .map(|i| Ok(Fact::read_csv(i.csv_path(&record))?.into_iter()
+                        .map(|f| (Some(i.path()), f))
+                        .collect::<Vec<_>>()))
+                    .collect::<crate::DResult<Vec<_>>>()?
+                    .into_iter()
+                    .flatten())
+                .collect::<Vec<(Option<String>, Fact)>>();
+            for item in &record.items {
+                let names = [item.path(), item.name.clone(),
+                    format!("{}/{}", record.name, item.path())];
+                let found = names.iter()
+                    .find_map(|n| related(&EntityRef::Item(n.clone())));
+                if let Some(found) = found {
+                    let count = facts.iter()
+                        .filter(|(p, _)| p.as_deref().is_some_and(|p| {
+                            Item::new(p.into()).is_under(&item.path())
+                        }))
+                        .count();
+                    entries.push(TopicEntry::Item(item.clone(), count, found));
+                }
+            }
+            for (item, fact) in facts {
+                let found = related(&EntityRef::Fact(fact.id.to_string()))
+                    .or_else(|| topics.iter()
+                        .any(|t| t.eq_ignore_ascii_case(&fact.name))
+                        .then_some(Match::Name));
+                if let Some(found) = found {
+                    entries.push(TopicEntry::Fact(item, fact, found));
+                }
+            }
+            let tagged = related(&EntityRef::Record(record.name.clone())).is_some();
+            if tagged || !entries.is_empty() {
+                views.push(TopicView { record, tagged, entries });
+            }
+        }
+        Ok(views)
+    }
+
+    /// The topics nested beneath their parents, with how many facts, items
+    /// and records are found under each
+    pub fn tree_table(data: &TopicData) -> crate::DResult<Table> {
+        let mut table = Table::new();
+        table.load_preset(UTF8_BORDERS_ONLY)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec![
+                Cell::new("Topic").add_attribute(Attribute::Bold)
+                    .fg(TColor::Magenta),
+                Cell::new("Description").add_attribute(Attribute::Bold),
+                Cell::new("Facts").add_attribute(Attribute::Bold),
+                Cell::new("Items").add_attribute(Attribute::Bold),
+                Cell::new("Records").add_attribute(Attribute::Bold),
+            ]);
+        Self::add_tree_rows(data, &mut table, None, 0)?;
+        Ok(output::table("topics", table))
+    }
+
+    fn add_tree_rows(data: &TopicData, table: &mut Table, parent: Option<&str>, level: usize)
+        -> crate::DResult<()>
+    {
+        let mut children = data.children(parent);
+        children.sort_by_key(|t| t.name.to_lowercase());
+        for topic in children {
+            let views = Self::gather(&data.descendants(&topic.name))?;
+            let count = |item: bool| views.iter()
+                .flat_map(|v| v.entries.iter())
+                .filter(|e| matches!(e, TopicEntry::Item(..)) == item)
+                .count();
+            table.add_row(vec![
+                Cell::new(format!("{}{}", "  ".repeat(level), topic.name))
+                    .fg(TColor::Magenta),
+                Cell::new(&topic.descr),
+                Cell::new(count(false)),
+                Cell::new(count(true)),
+                Cell::new(views.len()),
+            ]);
+            Self::add_tree_rows(data, table, Some(&topic.name), level + 1)?;
+        }
+        Ok(())
+    }
+
+    /// The cross-record view of a topic: each record with anything about
+    /// it, and beneath each the items and most recent `recent` entries found
+    pub fn view_table(&self, views: &[TopicView], recent: usize) -> Table {
+        let mut table = Table::new();
+        table.load_preset(UTF8_BORDERS_ONLY)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec![
+                Cell::new("Topic").add_attribute(Attribute::Bold)
+                    .fg(TColor::Magenta),
+                Cell::new("Value").add_attribute(Attribute::Bold),
+                Cell::new("Found by").add_attribute(Attribute::Bold),
+                Cell::new("Attributes").add_attribute(Attribute::Bold),
+                Cell::new("Created").add_attribute(Attribute::Bold),
+            ]);
+        table.add_row(vec![
+            Cell::new(&self.name).add_attribute(Attribute::Bold).fg(TColor::Magenta),
+            Cell::new(&self.descr),
+            Cell::new(""),
+            Cell::new(""),
+            Cell::new(""),
+        ]);
+        for view in views {
+            table.add_row(vec![
+                Cell::new(format!("  {}", view.record.name)).fg(TColor::Yellow),
+                Cell::new(&view.record.description),
+                Cell::new(if view.tagged { "tagged" } else { "" }),
+                Cell::new(Attrib::join(&view.record.attribs)),
+                Cell::new(""),
+            ]);
+            for entry in &view.entries {
+                if let TopicEntry::Item(item, count, found) = entry {
+                    table.add_row(vec![
+                        Cell::new(format!("    {}", item.path())).fg(TColor::Green),
+                        Cell::new(format!("{} entries", count)),
+                        Cell::new(found.to_string()),
+                        Cell::new(Attrib::join(&item.attribs)),
+                        Cell::new(output::datetime(&item.created)),
+                    ]);
+                }
+            }
+            let mut facts = view.entries.iter()
+                .filter_map(|e| match e {
+                    TopicEntry::Fact(item, fact, found) => Some((item, fact, found)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            facts.sort_by_key(|(_, f, _)| std::cmp::Reverse(f.created_at));
+            for (item, fact, found) in facts.into_iter().take(recent) {
+                let unit = match fact.unit {
+                    Unit::None => String::new(),
+                    ref unit => format!(" {}", unit),
+                };
+                let name = match item {
+                    Some(item) => format!("{} ({})", fact.name, item),
+                    None => fact.name.clone(),
+                };
+                table.add_row(vec![
+                    Cell::new(format!("    {}", name)).fg(TColor::Blue),
+                    Cell::new(format!("{}{}", fact.val.raw(), unit)),
+                    Cell::new(found.to_string()),
+                    Cell::new(Attrib::join(&fact.attribs)),
+                    Cell::new(output::datetime(&fact.created_at)),
+                ]);
+            }
+        }
+        output::table("topic", table)
+    }
+}
+
+impl std::fmt::Display for Match {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Name => "name",
+            Self::Tagged => "tagged",
+            Self::Mentioned => "+mention",
+        })
+    }
 }
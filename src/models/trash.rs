@@ -0,0 +1,402 @@
+use std::{convert::TryFrom, fs, path::PathBuf};
+use chrono::{DateTime, Local};
+use comfy_table::{
+    Table, ContentArrangement, presets::UTF8_BORDERS_ONLY,
+    Cell, Attribute, Color as TColor,
+};
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use crate::{
+    util, history, output,
+    csv as Csv,
+    config::DConfig,
+    error::DError,
+    models::{
+        Item, Record,
+        data::RecordData,
+        fact::{Fact, AbstractFact},
+    },
+};
+
+/// What was deleted
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrashKind {
+    /// A record with everything in it
+    Record,
+    /// An item with its entries and the items under it
+    Item,
+    /// A fact type of a record with all of its entries there
+    Fact,
+    /// A single fact entry
+    Entry,
+}
+
+impl std::fmt::Display for TrashKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Record => "record",
+            Self::Item => "item",
+            Self::Fact => "fact",
+            Self::Entry => "entry",
+        })
+    }
+}
+
+/// Something deleted, kept in the trash until it is restored or purged.
+/// Deleted records and items are moved to `<data dir>/dlog/trash/<id>/files`,
+/// and deleted entries are kept in `trash/<id>/entries.csv` along with the
+/// CSV each was in.
+// NOTE items and the fact type are tables, so they come last to serialize
+//      into the trash's TOML
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trashed {
+    #[serde(rename = "Id")]
+    pub id: Uuid,
+    #[serde(rename = "Kind")]
+    pub kind: TrashKind,
+    /// The record's name, the item's path or the fact's name
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Record")]
+    pub record: String,
+    /// Where a deleted record or item was
+    #[serde(rename = "Path")]
+    pub path: PathBuf,
+    #[serde(rename = "Deleted at")]
+    pub deleted: DateTime<Local>,
+    #[serde(rename = "Entries", default)]
+    pub entries: usize,
+    /// A deleted item and the items under it, to be put back in the record
+    #[serde(rename = "Items", default, skip_serializing_if = "Vec::is_empty")]
+    pub items: Vec<Item>,
+    #[serde(rename = "Fact type", default, skip_serializing_if = "Option::is_none")]
+    pub fact_type: Option<AbstractFact>,
+}
+
+/// Everything in the trash, listed in `trash/trash.toml` in the data dir
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Trash {
+    #[serde(rename = "Trashed", default, skip_serializing_if = "Vec::is_empty")]
+    trashed: Vec<Trashed>,
+}
+
+impl Trash {
+
+    pub fn dir() -> crate::DResult<PathBuf> {
+        util::default_data_dir(Some("trash"))
+    }
+
+    pub fn path() -> crate::DResult<PathBuf> {
+        Ok(Self::dir()?.join("trash.toml"))
+    }
+
+    /// The trash, leaving out anything whose files are gone, e.g. when
+    /// emptying the trash is undone
+    pub fn read() -> crate::DResult<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let mut trash: Self = toml::from_str(&fs::read_to_string(path)?)?;
+        trash.trashed.retain(|t| t.dir().is_ok_and(|d| d.exists()));
+        Ok(trash)
+    }
+
+    pub fn write(&self) -> crate::DResult<()> {
+        let path = Self::path()?;
+        fs::create_dir_all(Self::dir()?)?;
+        history::touch(&path)?;
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Everything in the trash, the last deleted last
+    pub fn trashed(&self) -> &[Trashed] {
+        &self.trashed
+    }
+
+    /// Finds something in the trash by the start of its id, or the last
+    /// deleted by its name
+    pub fn find(&self, name: &str) -> crate::DResult<&Trashed> {
+        let prefix = name.to_lowercase();
+        let by_id = self.trashed.iter()
+            .filter(|t| t.id.to_string().starts_with(&prefix))
+            .collect::<Vec<&Trashed>>();
+        match by_id.len() {
+            1 => Ok(by_id[0]),
+            0 => self.trashed.iter()
+                .rev()
+                .find(|t| t.name.eq_ignore_ascii_case(name))
+                .ok_or(DError::NotFound),
+            _ => Err(DError::Invalid(format!("More than one deleted entry starts with {}", name))),
+        }
+    }
+
+    /// Moves a record's directory to the trash and unregisters it
+    pub fn put_record(record: &Record) -> crate::DResult<Trashed> {
+        let entries = record.facts()?.len();
+        let mut trashed = Trashed::new(TrashKind::Record, &record.name, record, entries);
+        trashed.path = record.dir.clone();
+        trashed.move_in(&record.dir)?;
+        let mut records = RecordData::read()?;
+        records.remove(&record.name);
+        records.write()?;
+        Self::put(trashed)
+    }
+
+    /// Moves an item's directory, holding its entries and the items under
+    /// it, to the trash. The item and its descendants are kept to be put
+    /// back on restore, the item first.
+    pub fn put_item(record: &Record, item: &Item) -> crate::DResult<Trashed> {
+        let entries = item.facts(record, true)?.len();
+        let mut trashed = Trashed::new(TrashKind::Item, &item.path(), record, entries);
+        trashed.path = item.dir(record);
+        trashed.items = std::iter::once(item.clone())
+            .chain(record.descendants(&item.path()).cloned())
+            .collect();
+        trashed.move_in(&item.dir(record))?;
+        Self::put(trashed)
+    }
+
+    /// Keeps entries already removed from the CSVs of a record, each with
+    /// the CSV it was in, and the fact type removed with them if any
+    pub fn put_entries(record: &Record, kind: TrashKind, name: &str, entries: &[(PathBuf, Fact)], fact_type: Option<AbstractFact>)
+        -> crate::DResult<Trashed>
+    {
+        let mut trashed = Trashed::new(kind, name, record, entries.len());
+        trashed.fact_type = fact_type;
+        let path = trashed.dir()?.join("entries.csv");
+        fs::create_dir_all(trashed.dir()?)?;
+        let mut wtr = Csv::csv_appender(&path)?;
+        let mut headers = Fact::CSV_HEADERS.to_vec();
+        headers.push("Path");
+        wtr.write_record(&headers)?;
+        for (csv, fact) in entries {
+            let mut row = fact.to_csv_row();
+            row.push(csv.to_string_lossy().to_string());
+            wtr.write_record(&row)?;
+        }
+        wtr.flush()?;
+        Self::put(trashed)
+    }
+
+    fn put(trashed: Trashed) -> crate::DResult<Trashed> {
+        let mut trash = Self::read()?;
+        trash.trashed.push(trashed.clone());
+        trash.purge(DConfig::load()?.trash_retention())?;
+        trash.write()?;
+        Ok(trashed)
+    }
+
+    /// Puts back something in the trash where it was
+    pub fn restore(&mut self, name: &str) -> crate::DResult<Trashed> {
+        let trashed = self.find(name)?.clone();
+        trashed.restore()?;
+        self.trashed.retain(|t| t.id != trashed.id);
+        trashed.remove()?;
+        self.write()?;
+        Ok(trashed)
+    }
+
+    /// Removes everything in the trash deleted longer than `retention`
+    /// ago, returning how many were purged
+    pub fn purge(&mut self, retention: Option<chrono::Duration>) -> crate::DResult<usize> {
+        let retention = match retention {
+            Some(retention) => retention,
+            None => return Ok(0),
+        };
+        let now = Local::now();
+        let (old, kept) = std::mem::take(&mut self.trashed).into_iter()
+            .partition::<Vec<Trashed>, _>(|t| now - t.deleted > retention);
+        self.trashed = kept;
+        for trashed in &old {
+            trashed.destroy()?;
+        }
+        Ok(old.len())
+    }
+
+    /// Removes everything in the trash for good, returning how many
+    pub fn empty(&mut self) -> crate::DResult<usize> {
+        let trashed = std::mem::take(&mut self.trashed);
+        for t in &trashed {
+            t.destroy()?;
+        }
+        self.write()?;
+        Ok(trashed.len())
+    }
+
+    pub fn table(&self) -> Table {
+        let retention = DConfig::load().ok().and_then(|c| c.trash_retention());
+        let mut table = Table::new();
+        table.load_preset(UTF8_BORDERS_ONLY)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec![
+                Cell::new("Id").add_attribute(Attribute::Bold),
+                Cell::new("Kind").add_attribute(Attribute::Bold),
+                Cell::new("Name").add_attribute(Attribute::Bold)
+                    .fg(TColor::Blue),
+                Cell::new("Record").add_attribute(Attribute::Bold),
+                Cell::new("Entries").add_attribute(Attribute::Bold),
+                Cell::new("Deleted").add_attribute(Attribute::Bold),
+                Cell::new("Purged").add_attribute(Attribute::Bold),
+            ]);
+        for t in &self.trashed {
+            table.add_row(vec![
+                Cell::new(&t.id.to_string()[..8]),
+                Cell::new(t.kind),
+                Cell::new(&t.name),
+                Cell::new(&t.record),
+                Cell::new(t.entries),
+                Cell::new(output::datetime(&t.deleted)),
+                Cell::new(retention
+                    .map(|r| output::datetime(&(t.deleted + r)))
+                    .unwrap_or_else(|| "never".into())),
+            ]);
+        }
+        output::table("trash", table)
+    }
+}
+
+impl Trashed {
+
+    fn new(kind: TrashKind, name: &str, record: &Record, entries: usize) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            kind,
+            name: name.to_string(),
+            record: record.name.clone(),
+            path: record.dir.clone(),
+            deleted: Local::now(),
+            entries,
+            items: Vec::new(),
+            fact_type: None,
+        }
+    }
+
+    /// Where the deleted files are kept
+    pub fn dir(&self) -> crate::DResult<PathBuf> {
+        Ok(Trash::dir()?.join(self.id.to_string()))
+    }
+
+    fn files(&self) -> crate::DResult<PathBuf> {
+        Ok(self.dir()?.join("files"))
+    }
+
+    fn move_in(&self, from: &std::path::Path) -> crate::DResult<()> {
+        let to = self.files()?;
+        history::touch_move(from, &to)?;
+        util::move_dir(from, &to)?;
+        Ok(())
+    }
+
+    fn move_out(&self, to: &std::path::Path) -> crate::DResult<()> {
+        let from = self.files()?;
+        history::touch_move(&from, to)?;
+        util::move_dir(&from, to)?;
+        Ok(())
+    }
+
+    /// The deleted entries, each with the CSV it was in
+    pub fn read_entries(&self) -> crate::DResult<Vec<(PathBuf, Fact)>> {
+        let path = self.dir()?.join("entries.csv");
+        if !path.is_file() {
+            return Ok(Vec::new());
+        }
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .flexible(true)
+            .from_path(&path)?;
+        let mut entries = Vec::new();
+        for rec in rdr.records() {
+            let rec = rec?;
+            let csv = PathBuf::from(rec.get(Fact::CSV_HEADERS.len()).unwrap_or_default());
+            entries.push((csv, Fact::from_row(rec, &path)?));
+        }
+        Ok(entries)
+    }
+
+    fn restore(&self) -> crate::DResult<()> {
+        match self.kind {
+            TrashKind::Record => {
+                let mut records = RecordData::read()?;
+                if records.find(&self.name).is_some() {
+                    return Err(DError::AlreadyExists(
+                        format!("A record named {}", self.name)));
+                }
+                if self.path.exists() {
+                    return Err(DError::AlreadyExists(
+                        format!("A directory {}", self.path.display())));
+                }
+                self.move_out(&self.path)?;
+                records.register(&Record::open_path(&self.path)?)
+            },
+            TrashKind::Item => {
+                let mut record = self.open_record()?;
+                let item = self.items.first().ok_or(DError::NotFound)?;
+                if record.item(&item.path()).is_some() {
+                    return Err(DError::AlreadyExists(
+                        format!("An item {} in {}", item.path(), record.name)));
+                }
+                if let Some(parent) = item.parent.clone() {
+                    if record.item(&parent).is_none() {
+                        record.add_item(Item::new(parent))?;
+                    }
+                }
+                self.move_out(&item.dir(&record))?;
+                record.items.extend(self.items.iter().cloned());
+                record.write()
+            },
+            TrashKind::Fact | TrashKind::Entry => {
+                let mut record = self.open_record()?;
+                let csvs = record.csv_paths();
+                for (csv, fact) in self.read_entries()? {
+                    match csvs.contains(&csv) {
+                        true => fact.append_to(csv)?,
+                        false => fact.append_to(record.csv_path())?,
+                    }
+                }
+                if let Some(fact_type) = &self.fact_type {
+                    if !record.fact_types.iter().any(|f| f.name.eq_ignore_ascii_case(&fact_type.name)) {
+                        record.fact_types.push(fact_type.clone());
+                        record.write()?;
+                    }
+                }
+                Ok(())
+            },
+        }
+    }
+
+    fn open_record(&self) -> crate::DResult<Record> {
+        Record::open(&self.record).map_err(|e| match e {
+            DError::NotFound => DError::Invalid(format!("The record {} is gone, restore it first", self.record)),
+            e => e,
+        })
+    }
+
+    /// Removes the deleted files for good, along with every operation in
+    /// the undo history which changed them or where they were deleted from
+    fn destroy(&self) -> crate::DResult<()> {
+        let mut paths = vec![self.dir()?];
+        match self.kind {
+            TrashKind::Record | TrashKind::Item => paths.push(self.path.clone()),
+            TrashKind::Fact | TrashKind::Entry =>
+                paths.extend(self.read_entries()?.into_iter().map(|(csv, _)| csv)),
+        }
+        let dir = self.dir()?;
+        if dir.exists() {
+            fs::remove_dir_all(dir)?;
+        }
+        history::forget(&paths)
+    }
+
+    /// Removes what is left of something restored from the trash
+    fn remove(&self) -> crate::DResult<()> {
+        let dir = self.dir()?;
+        if dir.exists() {
+            history::touch_dir(&dir)?;
+            fs::remove_dir_all(dir)?;
+        }
+        Ok(())
+    }
+}
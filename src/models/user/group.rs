@@ -1 +1,71 @@
-pub struct Group;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Local};
+use comfy_table::{
+    Table, ContentArrangement, presets::UTF8_BORDERS_ONLY,
+    Cell, Attribute, Color as TColor,
+};
+use crate::output;
+
+/// A group of users, e.g. a household or team, which owns the records its
+/// members share. Only members may log facts to a group's records.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Group {
+    #[serde(rename = "Id")]
+    pub id: uuid::Uuid,
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Description", default)]
+    pub description: String,
+    #[serde(rename = "Members", default)]
+    pub members: Vec<String>,
+    #[serde(rename = "Records", default)]
+    pub records: Vec<String>,
+    #[serde(rename = "Created at", default = "Local::now")]
+    pub created: DateTime<Local>,
+}
+
+impl Group {
+
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4(),
+            name: name.into(),
+            description: description.into(),
+            members: Vec::new(),
+            records: Vec::new(),
+            created: Local::now(),
+        }
+    }
+
+    pub fn has_member(&self, username: &str) -> bool {
+        self.members.iter().any(|m| m.eq_ignore_ascii_case(username))
+    }
+
+    pub fn owns(&self, record: &str) -> bool {
+        self.records.iter().any(|r| r.eq_ignore_ascii_case(record))
+    }
+
+    pub fn table(groups: &[Self]) -> Table {
+        let mut table = Table::new();
+        table.load_preset(UTF8_BORDERS_ONLY)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec![
+                Cell::new("Group").add_attribute(Attribute::Bold)
+                    .fg(TColor::Cyan),
+                Cell::new("Description").add_attribute(Attribute::Bold),
+                Cell::new("Members").add_attribute(Attribute::Bold),
+                Cell::new("Records").add_attribute(Attribute::Bold),
+                Cell::new("Created").add_attribute(Attribute::Bold),
+            ]);
+        for group in groups {
+            table.add_row(vec![
+                group.name.clone(),
+                group.description.clone(),
+                group.members.join(", "),
+                group.records.join(", "),
+                output::datetime(&group.created),
+            ]);
+        }
+        output::table("groups", table)
+    }
+}
@@ -2,11 +2,77 @@ pub mod stats;
 pub mod group;
 
 use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Local};
+use comfy_table::{
+    Table, ContentArrangement, presets::UTF8_BORDERS_ONLY,
+    Cell, Attribute, Color as TColor,
+};
+use crate::{output, models::{Fact, data::UserData}};
 
+/// Someone logging facts to a shared data dir, e.g. one member of a
+/// household. The current user is kept in the config as `auth.username`.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct User {
     #[serde(rename = "Id")]
     pub id: uuid::Uuid,
     #[serde(rename = "Username")]
     pub username: String,
+    #[serde(rename = "Created at", default = "Local::now")]
+    pub created: DateTime<Local>,
+}
+
+impl User {
+
+    pub fn new(username: &str) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4(),
+            username: username.into(),
+            created: Local::now(),
+        }
+    }
+
+    /// Whether the fact entry was logged by this user
+    pub fn authored(&self, fact: &Fact) -> bool {
+        fact.author.as_ref()
+            .is_some_and(|a| a.eq_ignore_ascii_case(&self.username))
+    }
+
+    /// One row per user, with the current user marked and how many of the
+    /// given entries each has logged
+    pub fn table(data: &UserData, facts: &[Fact]) -> Table {
+        let mut table = Table::new();
+        table.load_preset(UTF8_BORDERS_ONLY)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec![
+                Cell::new("User").add_attribute(Attribute::Bold)
+                    .fg(TColor::Cyan),
+                Cell::new("Groups").add_attribute(Attribute::Bold),
+                Cell::new("Entries").add_attribute(Attribute::Bold),
+                Cell::new("Last Entry").add_attribute(Attribute::Bold),
+                Cell::new("Created").add_attribute(Attribute::Bold),
+            ]);
+        let current = data.current().map(|u| u.id);
+        for user in data.users() {
+            let authored = facts.iter()
+                .filter(|f| user.authored(f))
+                .collect::<Vec<&Fact>>();
+            let name = match Some(user.id) == current {
+                true => format!("* {}", user.username),
+                false => format!("  {}", user.username),
+            };
+            table.add_row(vec![
+                name,
+                data.groups_of(&user.username).iter()
+                    .map(|g| g.name.clone())
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                authored.len().to_string(),
+                authored.iter().map(|f| f.created_at).max()
+                    .map(|d| output::datetime(&d))
+                    .unwrap_or_default(),
+                output::datetime(&user.created),
+            ]);
+        }
+        output::table("users", table)
+    }
 }
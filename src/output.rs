@@ -0,0 +1,260 @@
+//! Rendering of listings as JSON, NDJSON, YAML, CSV or TSV instead of
+//! tables, chosen with the global `-o/--output`. Entities are serialized
+//! with their serde names, e.g. the `Fact`, `Value` and `Datetime` of fact
+//! entries, so that they read the same as their CSV and TOML files.
+//!
+//! Tables are laid out by the `[format]` config: how their dates and times
+//! are written, which of their columns are shown, and whether they are
+//! drawn `--pretty` or compact.
+use std::{collections::HashMap, str::FromStr, sync::RwLock};
+use chrono::{DateTime, Local, NaiveDate};
+use comfy_table::{
+    presets::{UTF8_BORDERS_ONLY, UTF8_FULL},
+    modifiers::UTF8_ROUND_CORNERS,
+    Cell, ColumnConstraint, ContentArrangement, Table,
+};
+use serde::Serialize;
+use serde_json::Value;
+use crate::{
+    config::{self, FormatConfig, TableConfig},
+    error::DError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Format {
+    #[default]
+    Table,
+    Json,
+    Ndjson,
+    Yaml,
+    Csv,
+    Tsv,
+}
+
+/// How tables are drawn for the rest of the command
+#[derive(Debug, Clone)]
+pub struct Style {
+    /// strftime patterns
+    pub date: String,
+    pub time: String,
+    pub relative: bool,
+    /// Full, rounded borders and relative times rather than compact tables
+    pub pretty: bool,
+    pub width: Option<u16>,
+    pub tables: HashMap<String, TableConfig>,
+}
+
+lazy_static::lazy_static! {
+    static ref FORMAT: RwLock<Format> = RwLock::new(Format::Table);
+    static ref STYLE: RwLock<Style> = RwLock::new(Style::default());
+}
+
+impl Format {
+    pub const NAMES: [&'static str; 6] = ["table", "json", "ndjson", "yaml", "csv", "tsv"];
+}
+
+impl FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            "ndjson" | "jsonl" => Ok(Self::Ndjson),
+            "yaml" | "yml" => Ok(Self::Yaml),
+            "csv" => Ok(Self::Csv),
+            "tsv" => Ok(Self::Tsv),
+            _ => Err(format!("Unknown output format {}, expected one of {}",
+                s, Self::NAMES.join(", "))),
+        }
+    }
+}
+
+/// Sets the format for the rest of the command
+pub fn set(format: Format) {
+    if let Ok(mut f) = FORMAT.write() {
+        *f = format;
+    }
+}
+
+pub fn format() -> Format {
+    FORMAT.read().map(|f| *f).unwrap_or_default()
+}
+
+pub fn set_style(style: Style) {
+    if let Ok(mut s) = STYLE.write() {
+        *s = style;
+    }
+}
+
+pub fn style() -> Style {
+    STYLE.read().map(|s| s.clone()).unwrap_or_default()
+}
+
+/// Whether output is for scripts rather than people
+pub fn structured() -> bool {
+    format() != Format::Table
+}
+
+impl Style {
+
+    /// The style set in the config, drawn pretty if `pretty` is given and
+    /// overrides it. Date and time formats which can't be written, e.g.
+    /// edited in by hand, are left for the defaults.
+    pub fn new(format: &FormatConfig, pretty: Option<bool>) -> Self {
+        let default = FormatConfig::default();
+        Self {
+            date: Some(format.date_pattern())
+                .filter(|p| config::writes_date(p))
+                .unwrap_or_else(|| default.date_pattern()),
+            time: Some(format.time_pattern())
+                .filter(|p| config::writes_time(p))
+                .unwrap_or_else(|| default.time_pattern()),
+            relative: format.relative_times(),
+            pretty: pretty.unwrap_or_else(|| format.pretty()),
+            width: format.table_width(),
+            tables: format.tables().clone(),
+        }
+    }
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self::new(&FormatConfig::default(), None)
+    }
+}
+
+/// A time as the config writes it, or e.g. "3 hours ago" for relative
+/// times and pretty tables
+pub fn datetime(dt: &DateTime<Local>) -> String {
+    let style = style();
+    if style.relative || style.pretty {
+        chrono_humanize::HumanTime::from(*dt).to_string()
+    } else {
+        dt.format(&format!("{} {}", style.date, style.time)).to_string()
+    }
+}
+
+pub fn date(date: &NaiveDate) -> String {
+    date.format(&style().date).to_string()
+}
+
+/// Lays out a table named `name` as the config and style ask: only the
+/// columns listed for it under `[format.tables.<name>]`, in their order
+/// and no wider than their widths, with full borders if pretty.
+pub fn table(name: &str, mut table: Table) -> Table {
+    let style = style();
+    let conf = style.tables.get(name).cloned().unwrap_or_default();
+    let header = table.get_header()
+        .map(|h| h.cell_iter().cloned().collect::<Vec<Cell>>())
+        .unwrap_or_default();
+    let names = header.iter().map(Cell::get_content).collect::<Vec<String>>();
+    let keep = match conf.columns.is_empty() {
+        true => (0..names.len()).collect::<Vec<usize>>(),
+        false => conf.columns.iter()
+            .filter_map(|c| names.iter().position(|n| n.eq_ignore_ascii_case(c)))
+            .collect(),
+    };
+    let pick = |cells: Vec<Cell>| keep.iter()
+        .filter_map(|i| cells.get(*i).cloned())
+        .collect::<Vec<Cell>>();
+    let mut out = Table::new();
+    out.load_preset(if style.pretty { UTF8_FULL } else { UTF8_BORDERS_ONLY })
+        .set_content_arrangement(ContentArrangement::Dynamic);
+    if style.pretty {
+        out.apply_modifier(UTF8_ROUND_CORNERS);
+    }
+    if let Some(width) = style.width.or_else(|| table.get_table_width()) {
+        out.set_table_width(width);
+    }
+    if !header.is_empty() {
+        out.set_header(pick(header));
+    }
+    for row in table.row_iter() {
+        let cells = row.cell_iter().cloned().collect::<Vec<Cell>>();
+        out.add_row(if names.is_empty() { cells } else { pick(cells) });
+    }
+    for (col, i) in keep.iter().enumerate() {
+        let width = conf.widths.iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(&names[*i]))
+            .map(|(_, w)| *w);
+        if let (Some(width), Some(column)) = (width, out.get_column_mut(col)) {
+            column.set_constraint(ColumnConstraint::MaxWidth(width));
+        }
+    }
+    out
+}
+
+/// Renders a listing in the chosen format, or `None` if it should be
+/// printed as a table
+pub fn render<T: Serialize>(rows: &[T]) -> crate::DResult<Option<String>> {
+    let out = match format() {
+        Format::Table => return Ok(None),
+        Format::Json => serde_json::to_string_pretty(rows)?,
+        Format::Ndjson => rows.iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<String>, serde_json::Error>>()?
+            .join("\n"),
+        Format::Yaml => serde_yaml::to_string(rows)
+            .map_err(|e| DError::Invalid(format!("Could not write YAML: {}", e)))?,
+        Format::Csv => delimited(rows, b',')?,
+        Format::Tsv => delimited(rows, b'\t')?,
+    };
+    Ok(Some(out.trim_end().to_string()))
+}
+
+/// Renders a single entity, as an object rather than a list for JSON and
+/// YAML
+pub fn render_one<T: Serialize>(row: &T) -> crate::DResult<Option<String>> {
+    match format() {
+        Format::Json => Ok(Some(serde_json::to_string_pretty(row)?)),
+        Format::Yaml => serde_yaml::to_string(row)
+            .map(|y| Some(y.trim_end().to_string()))
+            .map_err(|e| DError::Invalid(format!("Could not write YAML: {}", e))),
+        _ => render(std::slice::from_ref(row)),
+    }
+}
+
+/// Rows as CSV or TSV, with a column for each field any row has, in the
+/// order they first appear. Lists and tables within a row are written as
+/// JSON.
+fn delimited<T: Serialize>(rows: &[T], delimiter: u8) -> crate::DResult<String> {
+    let rows = rows.iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<Value>, serde_json::Error>>()?;
+    let mut headers: Vec<String> = Vec::new();
+    for row in &rows {
+        match row {
+            Value::Object(fields) => for key in fields.keys() {
+                if !headers.contains(key) {
+                    headers.push(key.clone());
+                }
+            },
+            _ if headers.is_empty() => headers.push("value".into()),
+            _ => {},
+        }
+    }
+    if headers.is_empty() {
+        return Ok(String::new());
+    }
+    let cell = |v: Option<&Value>| match v {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(v) => v.to_string(),
+    };
+    let mut wtr = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(Vec::new());
+    wtr.write_record(&headers)?;
+    for row in &rows {
+        let record = match row {
+            Value::Object(fields) => headers.iter()
+                .map(|h| cell(fields.get(h)))
+                .collect::<Vec<String>>(),
+            v => vec![cell(Some(v))],
+        };
+        wtr.write_record(&record)?;
+    }
+    let bytes = wtr.into_inner()
+        .map_err(|e| DError::Invalid(e.to_string()))?;
+    Ok(String::from_utf8_lossy(&bytes).to_string())
+}
@@ -1,5 +1,8 @@
 use colored::{Colorize, Color};
-use std::{fs, io::{Read, prelude::*, self}, path::PathBuf};
+use std::{
+    fs, io::{Read, prelude::*, self}, path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 pub fn _prompt_2(prompt: &str) -> io::Result<String> {
     print!("{}", prompt);
@@ -19,12 +22,22 @@ pub fn validate_input(input: String) -> Result<(), &'static str> {
         }
     }
     if invalid.contains(&input.as_str())
+        || input.trim().is_empty()
+        || input == "." || input == ".."
         || input.len() > 40
         || input.contains("\\") {
         Err("Not a valid input")
     } else { Ok(()) }
 }
 
+/// Fact values and notes are free text, which may hold tags like `#tag`,
+/// `@record` or `+person`
+pub fn validate_text(input: String) -> Result<(), &'static str> {
+    if input.trim().is_empty() {
+        Err("Not a valid input")
+    } else { Ok(()) }
+}
+
 pub fn prompt(prompt: &str) -> io::Result<String> {
     let name = dialoguer::Input::new()
         .with_prompt(prompt)
@@ -39,3 +52,74 @@ pub fn prompt(prompt: &str) -> io::Result<String> {
     Ok(name)
 
 }
+
+/// Validates an item path such as `exercise/running`, each name of which
+/// must be valid input on its own. Empty names, as in `/tmp` or `a//b`, are
+/// refused along with `.` and `..`, so a path never leaves its record.
+pub fn validate_item_path(input: String) -> Result<(), &'static str> {
+    if input.is_empty() {
+        return Err("No item name given");
+    }
+    input.split('/').try_for_each(|n| validate_input(n.into()))
+}
+
+/// How alike two names must be, by Jaro-Winkler similarity, for one to be
+/// taken for a typo of the other
+const ALIKE: f64 = 0.85;
+
+/// The name among `names` most alike `name`, unless `name` is one of them
+/// or none is alike enough to be what was meant
+pub fn closest<'a, I>(name: &str, names: I) -> Option<&'a str>
+    where I: IntoIterator<Item = &'a str>
+{
+    let lower = name.to_lowercase();
+    let mut best: Option<(&str, f64)> = None;
+    for other in names {
+        if other.eq_ignore_ascii_case(name) {
+            return None;
+        }
+        let score = strsim::jaro_winkler(&lower, &other.to_lowercase());
+        if score >= ALIKE && best.is_none_or(|(_, b)| score > b) {
+            best = Some((other, score));
+        }
+    }
+    best.map(|(other, _)| other)
+}
+
+/// Whether names are taken as typed without asking, set by `--as-typed`
+static AS_TYPED: AtomicBool = AtomicBool::new(false);
+
+/// Takes names as typed for the rest of the command, instead of asking
+/// whether one alike was meant
+pub fn take_as_typed(yes: bool) {
+    AS_TYPED.store(yes, Ordering::Relaxed);
+}
+
+/// The name the user meant by the `kind` named `name`: the one alike it in
+/// `names` if they say so, or `name` itself. Off a terminal there is no one
+/// to ask, so a name alike another is refused rather than taken for a new
+/// one, unless names are taken as typed.
+pub fn meant<'a, I>(kind: &str, name: &str, names: I) -> crate::DResult<String>
+    where I: IntoIterator<Item = &'a str>
+{
+    if AS_TYPED.load(Ordering::Relaxed) {
+        return Ok(name.to_string());
+    }
+    let other = match closest(name, names) {
+        Some(other) => other,
+        None => return Ok(name.to_string()),
+    };
+    if !crate::cmd::TermSettings::new().atty {
+        return Err(crate::error::DError::Invalid(format!(
+            "There is no {} named {}, did you mean `{}`? Pass --as-typed to take {} as typed",
+            kind, name, other, name)));
+    }
+    let yes = dialoguer::Confirm::new()
+        .with_prompt(format!("There is no {} named {}, did you mean `{}`?", kind, name, other))
+        .default(true)
+        .interact()?;
+    Ok(match yes {
+        true => other.to_string(),
+        false => name.to_string(),
+    })
+}
@@ -1,35 +1,476 @@
-use crate::models::{Fact, Item, Record, Attrib, Relation};
+//! Full-text search across fact entries, items, records and notes.
+//!
+//! The inverted index is kept in `search/index.json` in the data dir, with
+//! each document indexed from the file it is read from: a fact entry CSV,
+//! a `record.toml` for a record and its items, or a note's directory. Once a
+//! command is done, [`update`] re-reads the files it changed, so the index
+//! follows every write without being rebuilt. Queries are ranked by BM25.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+use colored::Colorize;
 use indicatif::{ProgressBar, ProgressStyle};
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use crate::{
+    util,
+    error::DError,
+    models::{
+        Fact, Record, Attrib,
+        data::RecordData,
+        note::{Note, NoteDoc},
+    },
+};
 
+/// BM25 term frequency saturation
+const K1: f64 = 1.2;
+/// BM25 document length normalization
+const B: f64 = 0.75;
+/// How many chars of context a snippet shows around the first match
+const CONTEXT: usize = 40;
+
+/// The terms to search for, and which documents to search
+#[derive(Debug, Default)]
 pub struct Query {
     pub terms: Vec<String>,
     pub filters: Vec<QueryFilter>,
 }
 
+/// Narrows a query. Kinds are alternatives, e.g. `Facts` and `Notes` search
+/// both, while a record must match.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryFilter {
+    Facts,
+    Items,
+    Records,
+    Notes,
+    InRecord(String),
+}
+
+/// What a document is
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DocKind {
+    Fact,
+    Item,
+    Record,
+    Note,
+}
+
+impl std::fmt::Display for DocKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Fact => "fact",
+            Self::Item => "item",
+            Self::Record => "record",
+            Self::Note => "note",
+        })
+    }
+}
+
+/// A searchable document, with the text it was indexed from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Doc {
+    pub kind: DocKind,
+    pub title: String,
+    /// The record it is in, e.g. `health/night` for an item's entry, or
+    /// what a note is about
+    pub record: String,
+    pub text: String,
+    /// How many terms the text has
+    pub len: usize,
+    source: String,
+}
+
+/// A document matching a query, best first
+#[derive(Debug, Clone, Serialize)]
+pub struct Hit {
+    pub kind: DocKind,
+    pub name: String,
+    pub record: String,
+    pub score: f64,
+    pub snippet: String,
+}
+
+/// The documents, the documents each term is in and how often, and the
+/// documents read from each source
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Index {
+    docs: BTreeMap<String, Doc>,
+    postings: BTreeMap<String, BTreeMap<String, usize>>,
+    sources: BTreeMap<String, BTreeSet<String>>,
+}
+
 impl Query {
 
-    pub fn show_progress() {
-        let mut pb = ProgressBar::new(50);
-        pb.set_message("Beginning search...");
-        pb.set_style(ProgressStyle::default_bar());
-        for i in 0..100 {
-            pb.set_message(format!("At {} %", i).as_str());
+    pub fn new(text: &str, filters: Vec<QueryFilter>) -> Self {
+        Self { terms: tokenize(text), filters }
+    }
+
+    fn allows(&self, doc: &Doc) -> bool {
+        let kinds = self.filters.iter()
+            .filter_map(|f| match f {
+                QueryFilter::Facts => Some(DocKind::Fact),
+                QueryFilter::Items => Some(DocKind::Item),
+                QueryFilter::Records => Some(DocKind::Record),
+                QueryFilter::Notes => Some(DocKind::Note),
+                QueryFilter::InRecord(_) => None,
+            })
+            .collect::<Vec<DocKind>>();
+        let in_record = self.filters.iter().all(|f| match f {
+            QueryFilter::InRecord(record) => doc.record.split(&['/', ':'][..])
+                .any(|r| r.eq_ignore_ascii_case(record)),
+            _ => true,
+        });
+        in_record && (kinds.is_empty() || kinds.contains(&doc.kind))
+    }
+}
+
+impl Index {
+
+    pub fn path() -> crate::DResult<PathBuf> {
+        Ok(util::default_data_dir(Some("search"))?.join("index.json"))
+    }
+
+    /// The index as last saved, if it has been built
+    pub fn load() -> crate::DResult<Option<Self>> {
+        let path = Self::path()?;
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+    }
+
+    /// The saved index, built first if it hasn't been
+    pub fn open() -> crate::DResult<Self> {
+        match Self::load()? {
+            Some(index) => Ok(index),
+            None => Self::rebuild(),
+        }
+    }
+
+    pub fn save(&self) -> crate::DResult<()> {
+        let path = Self::path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Indexes every record and note from scratch, showing progress
+    pub fn rebuild() -> crate::DResult<Self> {
+        let mut sources = Vec::new();
+        for central in RecordData::read()?.records() {
+            let record = Record::open_path(&central.path)?;
+            sources.push(record.dir.join("record.toml"));
+            sources.extend(record.csv_paths());
+        }
+        for entry in fs::read_dir(NoteDoc::dir()?)? {
+            sources.push(entry?.path());
+        }
+        let pb = ProgressBar::new(sources.len() as u64);
+        pb.set_style(ProgressStyle::default_bar()
+            .template("{msg} {bar:40} {pos}/{len}"));
+        pb.set_message("Indexing");
+        let mut index = Self::default();
+        for source in &sources {
+            index.reindex(source)?;
             pb.inc(1);
         }
-        pb.finish_with_message("Finished!");
+        pb.finish_and_clear();
+        index.save()?;
+        Ok(index)
+    }
+
+    /// Indexes a file again, dropping what was read from it before
+    pub fn reindex(&mut self, path: &Path) -> crate::DResult<()> {
+        let source = match Source::of(path)? {
+            Some(source) => source,
+            None => return Ok(()),
+        };
+        let key = source.key();
+        for old in self.sources.remove(&key).unwrap_or_default() {
+            self.remove(&old);
+        }
+        for (id, doc) in source.docs()? {
+            self.add(id, Doc { source: key.clone(), ..doc });
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, id: String, doc: Doc) {
+        self.remove(&id);
+        let mut counts = BTreeMap::<String, usize>::new();
+        for term in tokenize(&doc.text) {
+            *counts.entry(term).or_default() += 1;
+        }
+        for (term, n) in counts {
+            self.postings.entry(term).or_default().insert(id.clone(), n);
+        }
+        self.sources.entry(doc.source.clone()).or_default().insert(id.clone());
+        self.docs.insert(id, doc);
+    }
 
+    fn remove(&mut self, id: &str) {
+        let doc = match self.docs.remove(id) {
+            Some(doc) => doc,
+            None => return,
+        };
+        for term in tokenize(&doc.text) {
+            if let Some(docs) = self.postings.get_mut(&term) {
+                docs.remove(id);
+                if docs.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+        if let Some(ids) = self.sources.get_mut(&doc.source) {
+            ids.remove(id);
+        }
+    }
+
+    /// Documents with any of the query's terms, by BM25 score, best first
+    pub fn search(&self, query: &Query) -> Vec<Hit> {
+        let n = self.docs.len() as f64;
+        let avg = self.docs.values().map(|d| d.len).sum::<usize>() as f64 / n.max(1.0);
+        let mut scores = BTreeMap::<&str, f64>::new();
+        for term in query.terms.iter().collect::<BTreeSet<&String>>() {
+            let docs = match self.postings.get(term) {
+                Some(docs) => docs,
+                None => continue,
+            };
+            let df = docs.len() as f64;
+            let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+            for (id, tf) in docs {
+                let doc = &self.docs[id];
+                if !query.allows(doc) {
+                    continue;
+                }
+                let tf = *tf as f64;
+                let norm = K1 * (1.0 - B + B * doc.len as f64 / avg.max(1.0));
+                *scores.entry(id).or_default() += idf * tf * (K1 + 1.0) / (tf + norm);
+            }
+        }
+        let mut hits = scores.into_iter()
+            .map(|(id, score)| {
+                let doc = &self.docs[id];
+                Hit {
+                    kind: doc.kind,
+                    name: doc.title.clone(),
+                    record: doc.record.clone(),
+                    score: (score * 1000.0).round() / 1000.0,
+                    snippet: snippet(&doc.text, &query.terms),
+                }
+            })
+            .collect::<Vec<Hit>>();
+        hits.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        hits
     }
 }
 
-pub enum QueryFilter {
-    Facts,
-    Items,
-    Records,
-    AllLike(String),
-    FactsLike(String),
-    ItemsLike(String),
-    RecordsLike(String),
-    ItemsWithFact(Fact),
-    RecordsWithFact(Fact),
-    // etc etc TODO implement
+/// Indexes the files a command changed again, if the index has been built
+pub fn update<P: AsRef<Path>>(paths: &[P]) -> crate::DResult<()> {
+    let mut index = match Index::load()? {
+        Some(index) => index,
+        None => return Ok(()),
+    };
+    for path in paths {
+        index.reindex(path.as_ref())?;
+    }
+    index.save()
+}
+
+/// Lowercase runs of letters and digits
+pub fn tokenize(text: &str) -> Vec<String> {
+    spans(text).into_iter()
+        .map(|(start, end)| text[start..end].to_lowercase())
+        .collect()
+}
+
+/// Where each run of letters and digits is in the text
+fn spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            },
+            _ => {},
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, text.len()));
+    }
+    spans
+}
+
+/// The text around the first of the terms in it, on one line
+fn snippet(text: &str, terms: &[String]) -> String {
+    let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    let first = spans(&text).into_iter()
+        .find(|(s, e)| terms.contains(&text[*s..*e].to_lowercase()))
+        .map(|(s, _)| s)
+        .unwrap_or(0);
+    let chars = text.char_indices().map(|(i, _)| i).collect::<Vec<usize>>();
+    let at = chars.iter().position(|i| *i == first).unwrap_or(0);
+    let start = at.saturating_sub(CONTEXT);
+    let end = (at + CONTEXT * 2).min(chars.len());
+    let byte = |c: usize| chars.get(c).copied().unwrap_or(text.len());
+    format!("{}{}{}",
+        if start > 0 { "…" } else { "" },
+        &text[byte(start)..byte(end)],
+        if end < chars.len() { "…" } else { "" })
+}
+
+/// A snippet with the terms searched for highlighted
+pub fn highlight(snippet: &str, terms: &[String]) -> String {
+    let mut out = String::new();
+    let mut last = 0;
+    for (s, e) in spans(snippet) {
+        if terms.contains(&snippet[s..e].to_lowercase()) {
+            out.push_str(&snippet[last..s]);
+            out.push_str(&snippet[s..e].bold().yellow().to_string());
+            last = e;
+        }
+    }
+    out.push_str(&snippet[last..]);
+    out
+}
+
+/// A file documents are read from
+enum Source {
+    /// A fact entry CSV of a record, and the item it belongs to, if any
+    Entries(PathBuf),
+    /// A record's `record.toml`, holding the record and its items
+    Record(PathBuf),
+    /// A note's directory, holding its versions
+    Note(PathBuf),
+}
+
+impl Source {
+
+    /// What a changed file is a source of, if anything searchable
+    fn of(path: &Path) -> crate::DResult<Option<Self>> {
+        let notes = NoteDoc::dir()?;
+        if let Ok(rel) = path.strip_prefix(&notes) {
+            return Ok(rel.components().next()
+                .map(|id| Self::Note(notes.join(id))));
+        }
+        let data = util::default_data_dir(None)?;
+        let internal = ["history", "trash", "revisions", "search"].iter()
+            .any(|d| path.starts_with(data.join(d)));
+        if internal {
+            return Ok(None);
+        }
+        Ok(match path.file_name().and_then(|n| n.to_str()) {
+            Some("record.toml") => Some(Self::Record(path.to_path_buf())),
+            Some(name) if name.ends_with(".csv") => Some(Self::Entries(path.to_path_buf())),
+            _ => None,
+        })
+    }
+
+    fn key(&self) -> String {
+        match self {
+            Self::Entries(path) | Self::Record(path) | Self::Note(path) =>
+                path.to_string_lossy().to_string(),
+        }
+    }
+
+    /// The documents in the source as it is now, none if it's gone
+    fn docs(&self) -> crate::DResult<Vec<(String, Doc)>> {
+        match self {
+            Self::Entries(path) => {
+                let record = match record_of(path)? {
+                    Some(record) => record,
+                    None => return Ok(Vec::new()),
+                };
+                let place = record.items.iter()
+                    .find(|i| i.csv_path(&record) == *path)
+                    .map(|i| format!("{}/{}", record.name, i.path()))
+                    .unwrap_or_else(|| record.name.clone());
+                Ok(Fact::read_csv(path)?.into_iter()
+                    .map(|f| (format!("fact:{}", f.id), fact_doc(&f, &place)))
+                    .collect())
+            },
+            Self::Record(path) => {
+                let dir = path.parent().unwrap_or(path);
+                if !crate::auth::exists(path) {
+                    return Ok(Vec::new());
+                }
+                let record = Record::open_path(dir)?;
+                let mut docs = vec![(format!("record:{}", record.id), doc(DocKind::Record,
+                    record.name.clone(), record.name.clone(),
+                    &[&record.name, &record.description, &Note::join(&record.notes), &attribs(&record.attribs)]))];
+                for item in &record.items {
+                    docs.push((format!("item:{}", item.id), doc(DocKind::Item,
+                        item.path(), record.name.clone(),
+                        &[&item.path().replace('/', " "), &Note::join(&item.notes), &attribs(&item.attribs)])));
+                }
+                Ok(docs)
+            },
+            Self::Note(dir) => {
+                let id = dir.file_name()
+                    .and_then(|id| id.to_str()?.parse::<Uuid>().ok());
+                let note = match id.map(|id| NoteDoc::load(&id, None)) {
+                    Some(Ok(note)) => note,
+                    Some(Err(DError::NotFound)) | None => return Ok(Vec::new()),
+                    Some(Err(e)) => return Err(e),
+                };
+                Ok(vec![(format!("note:{}", note.meta.id), doc(DocKind::Note,
+                    shorten(note.title()), note.meta.entity.to_string(),
+                    &[&note.body, &note.meta.attributes.join(" ")]))])
+            },
+        }
+    }
+}
+
+/// The record a file is in, by the nearest directory up from it holding one
+fn record_of(path: &Path) -> crate::DResult<Option<Record>> {
+    match path.ancestors().skip(1).find(|d| crate::auth::exists(&d.join("record.toml"))) {
+        Some(dir) => Ok(Some(Record::open_path(dir)?)),
+        None => Ok(None),
+    }
+}
+
+fn fact_doc(fact: &Fact, place: &str) -> Doc {
+    let units = fact.unit.to_string();
+    let title = match units.trim() {
+        "" => format!("{} = {}", fact.name, fact.val.raw()),
+        units => format!("{} = {} {}", fact.name, fact.val.raw(), units),
+    };
+    doc(DocKind::Fact, title, place.to_string(),
+        &[&fact.name, &fact.val.raw(), &units, &Note::join(&fact.notes), &attribs(&fact.attribs)])
+}
+
+/// A note's title, cut short if it is the start of a long first line
+fn shorten(title: &str) -> String {
+    match title.char_indices().nth(CONTEXT) {
+        Some((i, _)) => format!("{}…", title[..i].trim_end()),
+        None => title.to_string(),
+    }
+}
+
+fn doc(kind: DocKind, title: String, record: String, parts: &[&str]) -> Doc {
+    let text = parts.iter()
+        .filter(|p| !p.trim().is_empty())
+        .copied()
+        .collect::<Vec<&str>>()
+        .join(" · ");
+    Doc { kind, title, record, len: tokenize(&text).len(), text, source: String::new() }
+}
+
+fn attribs(attribs: &[Attrib]) -> String {
+    attribs.iter()
+        .map(|a| match &a.value {
+            Some(val) => format!("{}={}", a.name, val),
+            None => a.name.clone(),
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
 }
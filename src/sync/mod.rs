@@ -0,0 +1,516 @@
+//! Synchronizes records and fact entries with a dlog sync server, see
+//! `doc/src/usage_basic/synchronizing.md` for the protocol. Entities are
+//! exchanged by their UUIDs, and when both sides changed an entity the
+//! most recently updated version is kept and the other is reported.
+pub mod server;
+
+use std::{
+    fs,
+    convert::TryFrom,
+    path::PathBuf,
+};
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, Local};
+use comfy_table::{
+    Table, ContentArrangement, presets::UTF8_BORDERS_ONLY,
+    Cell, Attribute, Color as TColor,
+};
+use crate::{
+    util, output, prompt,
+    error::DError,
+    config::DConfig,
+    models::{Record, Item, Fact, data::RecordData},
+};
+
+/// An entity as exchanged with the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entity {
+    pub id: Uuid,
+    /// When the entity last changed on the side sending it
+    pub updated: DateTime<Local>,
+    #[serde(flatten)]
+    pub data: EntityData,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "data", rename_all = "lowercase")]
+pub enum EntityData {
+    Record(RecordDoc),
+    Fact(FactDoc),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordDoc {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Paths of the record's items, e.g. `exercise/running`
+    #[serde(default)]
+    pub items: Vec<String>,
+    pub created: DateTime<Local>,
+}
+
+/// A fact entry, with its columns as they are stored in fact entry CSVs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FactDoc {
+    pub record: String,
+    #[serde(default)]
+    pub item: Option<String>,
+    pub fact: String,
+    pub value: String,
+    #[serde(default)]
+    pub units: String,
+    #[serde(default)]
+    pub attributes: String,
+    #[serde(default)]
+    pub notes: String,
+    pub created: String,
+    #[serde(default)]
+    pub author: String,
+}
+
+/// Body of `GET /v1/entities`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Pulled {
+    pub entities: Vec<Entity>,
+    /// The server's time, to pull changes since next time
+    pub now: String,
+}
+
+/// Body of `POST /v1/entities`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Push {
+    pub entities: Vec<Entity>,
+}
+
+/// Answer to `POST /v1/entities`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Pushed {
+    pub accepted: usize,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// An entity changed on both sides. `local` is the side reporting it: the
+/// server when pushing, and this store when pulling.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conflict {
+    pub id: Uuid,
+    pub kind: String,
+    pub name: String,
+    pub local: DateTime<Local>,
+    pub remote: DateTime<Local>,
+    /// Whether the `local` or `remote` version was kept
+    pub kept: String,
+}
+
+/// When the store last pushed and pulled, kept in `sync.toml`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SyncState {
+    #[serde(rename = "Last push", default)]
+    pub last_push: Option<DateTime<Local>>,
+    /// The server's time as of the last pull
+    #[serde(rename = "Last pull", default)]
+    pub last_pull: Option<String>,
+    #[serde(rename = "Pulled at", default)]
+    pub pulled_at: Option<DateTime<Local>>,
+}
+
+impl Entity {
+
+    pub fn kind(&self) -> &'static str {
+        match self.data {
+            EntityData::Record(_) => "record",
+            EntityData::Fact(_) => "fact",
+        }
+    }
+
+    /// The record's name, or the fact's name and value
+    pub fn name(&self) -> String {
+        match &self.data {
+            EntityData::Record(rec) => rec.name.clone(),
+            EntityData::Fact(fact) => format!("{} {}", fact.fact, fact.value),
+        }
+    }
+
+    /// Checks the names of a pulled entity as they are checked when given to
+    /// a command, since they become paths in the store: a server must not
+    /// be able to write outside of it.
+    pub fn check(&self) -> crate::DResult<()> {
+        let (record, items) = match &self.data {
+            EntityData::Record(doc) => (&doc.name, doc.items.iter().collect::<Vec<&String>>()),
+            EntityData::Fact(doc) => (&doc.record, doc.item.iter().collect()),
+        };
+        let invalid = |why: &str, name: &str| DError::Invalid(
+            format!("{} {} has a bad name {:?}: {}", self.kind(), self.id, name, why));
+        prompt::validate_input(record.clone()).map_err(|why| invalid(why, record))?;
+        for item in items {
+            prompt::validate_item_path(item.clone()).map_err(|why| invalid(why, item))?;
+        }
+        Ok(())
+    }
+
+    pub fn record(record: &Record) -> Self {
+        Self {
+            id: record.id,
+            updated: record.updated.unwrap_or(record.created),
+            data: EntityData::Record(RecordDoc {
+                name: record.name.clone(),
+                description: record.description.clone(),
+                items: record.items.iter().map(Item::path).collect(),
+                created: record.created,
+            }),
+        }
+    }
+
+    pub fn fact(record: &str, item: Option<String>, fact: &Fact) -> Self {
+        let row = fact.to_csv_row();
+        Self {
+            id: fact.id,
+            updated: fact.updated_at.unwrap_or(fact.created_at),
+            data: EntityData::Fact(FactDoc {
+                record: record.into(),
+                item,
+                fact: row[1].clone(),
+                value: row[2].clone(),
+                created: row[3].clone(),
+                units: row[4].clone(),
+                attributes: row[5].clone(),
+                notes: row[6].clone(),
+                author: row[7].clone(),
+            }),
+        }
+    }
+}
+
+impl FactDoc {
+
+    pub fn to_fact(&self, id: Uuid) -> crate::DResult<Fact> {
+        let row = csv::StringRecord::from(vec![
+            id.to_string(), self.fact.clone(), self.value.clone(), self.created.clone(),
+            self.units.clone(), self.attributes.clone(), self.notes.clone(), self.author.clone(),
+        ]);
+        Ok(Fact::try_from(row)?)
+    }
+}
+
+impl Conflict {
+
+    pub fn new(local: &Entity, remote: &Entity, kept_local: bool) -> Self {
+        Self {
+            id: local.id,
+            kind: local.kind().into(),
+            name: local.name(),
+            local: local.updated,
+            remote: remote.updated,
+            kept: if kept_local { "local" } else { "remote" }.into(),
+        }
+    }
+
+    /// Conflicts as reported by the server when pushing or found when pulling
+    pub fn table(conflicts: &[Self], local: &str, remote: &str) -> Table {
+        let mut table = Table::new();
+        table.load_preset(UTF8_BORDERS_ONLY)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec![
+                Cell::new("Kind").add_attribute(Attribute::Bold).fg(TColor::Yellow),
+                Cell::new("Id").add_attribute(Attribute::Bold),
+                Cell::new("Name").add_attribute(Attribute::Bold),
+                Cell::new(local).add_attribute(Attribute::Bold),
+                Cell::new(remote).add_attribute(Attribute::Bold),
+                Cell::new("Kept").add_attribute(Attribute::Bold),
+            ]);
+        for c in conflicts {
+            let kept = if c.kept == "local" { local } else { remote };
+            table.add_row(vec![
+                c.kind.clone(),
+                c.id.to_string()[..8].to_string(),
+                c.name.clone(),
+                c.local.format("%Y-%m-%d %H:%M:%S").to_string(),
+                c.remote.format("%Y-%m-%d %H:%M:%S").to_string(),
+                kept.to_string(),
+            ]);
+        }
+        output::table("conflicts", table)
+    }
+}
+
+impl SyncState {
+
+    pub fn path() -> crate::DResult<PathBuf> {
+        util::default_data_dir(Some("sync.toml"))
+    }
+
+    pub fn read() -> crate::DResult<Self> {
+        let path = Self::path()?;
+        match path.exists() {
+            true => Ok(toml::from_str(&fs::read_to_string(path)?)?),
+            false => Ok(Self::default()),
+        }
+    }
+
+    pub fn write(&self) -> crate::DResult<()> {
+        fs::write(Self::path()?, toml::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// Every record and fact entry in the store, or only those updated since
+/// a time
+pub fn local_entities(since: Option<DateTime<Local>>) -> crate::DResult<Vec<Entity>> {
+    let mut entities = Vec::new();
+    for rec in RecordData::read()?.records() {
+        let record = Record::open_path(&rec.path)?;
+        entities.push(Entity::record(&record));
+        for (item, fact) in located_facts(&record)? {
+            entities.push(Entity::fact(&record.name, item, &fact));
+        }
+    }
+    if let Some(since) = since {
+        entities.retain(|e| e.updated > since);
+    }
+    Ok(entities)
+}
+
+/// A record's fact entries, with the path of the item each was logged to
+fn located_facts(record: &Record) -> crate::DResult<Vec<(Option<String>, Fact)>> {
+    let mut facts = Fact::read_csv(record.csv_path())?.into_iter()
+        .map(|f| (None, f))
+        .collect::<Vec<(Option<String>, Fact)>>();
+    for item in &record.items {
+        facts.extend(Fact::read_csv(item.csv_path(record))?.into_iter()
+            .map(|f| (Some(item.path()), f)));
+    }
+    Ok(facts)
+}
+
+/// The outcome of applying a pulled entity to the store
+#[derive(Debug)]
+pub enum Applied {
+    Added,
+    Updated,
+    Unchanged,
+    Conflict(Conflict),
+}
+
+/// Applies an entity pulled from the server. An entity also changed locally
+/// since the last pull is a conflict, and the newer version is kept. Items
+/// are never lost this way: a record gets every item either side has.
+pub fn apply(remote: &Entity, local: Option<&Entity>, pulled_at: Option<DateTime<Local>>)
+    -> crate::DResult<Applied>
+{
+    let local = match local {
+        Some(local) if local.data == remote.data => return Ok(Applied::Unchanged),
+        Some(local) => local,
+        None => {
+            match &remote.data {
+                EntityData::Record(doc) => add_record(remote.id, doc)?,
+                EntityData::Fact(doc) => write_fact(remote, doc, false)?,
+            }
+            return Ok(Applied::Added);
+        },
+    };
+    let changed = pulled_at.is_none_or(|at| local.updated > at);
+    let keep_local = changed && local.updated > remote.updated;
+    let conflicting = match (&local.data, &remote.data) {
+        (EntityData::Record(l), EntityData::Record(r)) => {
+            update_record(&l.name, r, !keep_local)?;
+            l.description != r.description
+        },
+        (_, EntityData::Fact(doc)) => {
+            if !keep_local {
+                write_fact(remote, doc, true)?;
+            }
+            true
+        },
+        _ => return Err(DError::Invalid(format!("{} {} is not a {}", local.kind(), local.id, remote.kind()))),
+    };
+    match changed && conflicting {
+        true => Ok(Applied::Conflict(Conflict::new(local, remote, keep_local))),
+        false => Ok(Applied::Updated),
+    }
+}
+
+fn add_record(id: Uuid, doc: &RecordDoc) -> crate::DResult<()> {
+    let mut record = Record::new(Some(doc.name.clone()), None);
+    record.id = id;
+    record.dir = util::default_data_dir(Some(&doc.name))?;
+    record.description = doc.description.clone();
+    record.created = doc.created;
+    record.init(false)?;
+    update_record(&doc.name, doc, false)
+}
+
+/// Adds any items of a pulled record the local one does not have, and
+/// optionally takes its description
+fn update_record(name: &str, doc: &RecordDoc, describe: bool) -> crate::DResult<()> {
+    let mut record = Record::open(name)?;
+    if describe && record.description != doc.description {
+        record.description = doc.description.clone();
+        record.write()?;
+    }
+    for path in &doc.items {
+        if record.item(path).is_none() {
+            record.add_item(Item::new(path.clone()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Appends a pulled fact entry to its record or item, or replaces the entry
+/// with the same id, keeping when it was last edited
+fn write_fact(remote: &Entity, doc: &FactDoc, replace: bool) -> crate::DResult<()> {
+    let id = remote.id;
+    let mut fact = doc.to_fact(id)?;
+    if remote.updated != fact.created_at {
+        fact.updated_at = Some(remote.updated);
+    }
+    let mut record = match Record::open(&doc.record) {
+        Err(DError::NotFound) => {
+            add_record(Uuid::new_v4(), &RecordDoc {
+                name: doc.record.clone(),
+                description: String::new(),
+                items: Vec::new(),
+                created: Local::now(),
+            })?;
+            Record::open(&doc.record)?
+        },
+        rec => rec?,
+    };
+    if replace {
+        let mut paths = vec![record.csv_path()];
+        paths.extend(record.items.iter().map(|i| i.csv_path(&record)));
+        for path in paths {
+            let mut facts = Fact::read_csv(&path)?;
+            if let Some(old) = facts.iter_mut().find(|f| f.id == id) {
+                *old = fact;
+                return Fact::write_csv(&path, &facts);
+            }
+        }
+    }
+    let path = match &doc.item {
+        Some(item) => match record.item(item) {
+            Some(item) => item.csv_path(&record),
+            None => record.add_item(Item::new(item.clone()))?,
+        },
+        None => record.csv_path(),
+    };
+    fact.append_to(path)
+}
+
+/// The server and token to sync with, from the config's `[sync]` table
+/// unless given
+pub struct Client {
+    url: String,
+    token: Option<String>,
+    http: reqwest::blocking::Client,
+}
+
+/// What a push or pull did
+#[derive(Debug, Default)]
+pub struct Report {
+    pub sent: usize,
+    pub added: usize,
+    pub updated: usize,
+    pub conflicts: Vec<Conflict>,
+    /// Pulled entities left out for names which would lead out of the store
+    pub skipped: Vec<String>,
+}
+
+impl Client {
+
+    pub fn new(url: Option<String>, token: Option<String>) -> crate::DResult<Self> {
+        let conf = DConfig::load()?;
+        let url = url.or_else(|| conf.sync_url())
+            .ok_or_else(|| DError::Invalid(
+                "No sync server, set sync.url in the config or pass --url".into()))?;
+        let token = token.or_else(|| conf.sync_token());
+        Ok(Self {
+            url: url.trim_end_matches('/').to_string(),
+            token,
+            http: reqwest::blocking::Client::new(),
+        })
+    }
+
+    fn endpoint(&self) -> String {
+        format!("{}/v1/entities", self.url)
+    }
+
+    fn send(&self, req: reqwest::blocking::RequestBuilder)
+        -> crate::DResult<reqwest::blocking::Response>
+    {
+        let req = match &self.token {
+            Some(token) => req.bearer_auth(token),
+            None => req,
+        };
+        let res = req.send()?;
+        if !res.status().is_success() {
+            let status = res.status();
+            let msg = res.json::<serde_json::Value>().ok()
+                .and_then(|v| v.get("error").and_then(|e| e.as_str()).map(String::from))
+                .unwrap_or_default();
+            return Err(DError::Invalid(format!("The sync server answered {}: {}", status, msg)));
+        }
+        Ok(res)
+    }
+
+    /// Sends everything changed since the last push, or everything
+    pub fn push(&self, all: bool) -> crate::DResult<Report> {
+        let mut state = SyncState::read()?;
+        let started = Local::now();
+        let entities = local_entities(if all { None } else { state.last_push })?;
+        let pushed: Pushed = self.send(self.http.post(&self.endpoint())
+            .json(&Push { entities: entities.clone() }))?
+            .json()?;
+        state.last_push = Some(started);
+        state.write()?;
+        Ok(Report {
+            sent: entities.len(),
+            updated: pushed.accepted,
+            conflicts: pushed.conflicts,
+            ..Report::default()
+        })
+    }
+
+    /// Fetches everything the server received since the last pull, or
+    /// everything, and applies it to the store
+    pub fn pull(&self, all: bool) -> crate::DResult<Report> {
+        let mut state = SyncState::read()?;
+        let mut req = self.http.get(&self.endpoint());
+        if let (false, Some(since)) = (all, &state.last_pull) {
+            req = req.query(&[("since", since)]);
+        }
+        let pulled: Pulled = self.send(req)?.json()?;
+        let mut report = Report::default();
+        let locals = local_entities(None)?;
+        for remote in &pulled.entities {
+            let local = locals.iter()
+                .find(|l| l.id == remote.id || match (&l.data, &remote.data) {
+                    (EntityData::Record(a), EntityData::Record(b)) => a.name.eq_ignore_ascii_case(&b.name),
+                    _ => false,
+                });
+            if let Err(e) = remote.check() {
+                report.skipped.push(e.to_string());
+                continue;
+            }
+            match apply(remote, local, state.pulled_at)? {
+                Applied::Added => report.added += 1,
+                Applied::Updated => report.updated += 1,
+                Applied::Unchanged => {},
+                Applied::Conflict(c) => report.conflicts.push(c),
+            }
+        }
+        state.last_pull = Some(pulled.now);
+        state.pulled_at = Some(Local::now());
+        state.write()?;
+        Ok(report)
+    }
+}
+
+/// The configured server, when the store last synced, and how many
+/// entities would be pushed
+pub fn status() -> crate::DResult<(Option<String>, SyncState, usize)> {
+    let state = SyncState::read()?;
+    let pending = local_entities(state.last_push)?.len();
+    Ok((DConfig::load()?.sync_url(), state, pending))
+}
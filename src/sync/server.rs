@@ -0,0 +1,141 @@
+//! The reference sync server run by `dlog-sync-server`, keeping every
+//! entity pushed to it in `entities.json` in its directory
+use std::{fs, path::PathBuf};
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Local};
+use crate::{
+    http::{Request, Response},
+    sync::{Entity, EntityData, Conflict, Pulled, Push, Pushed},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Stored {
+    pub entity: Entity,
+    /// When the server last took a change to the entity, which pulls are
+    /// filtered by
+    pub received: DateTime<Local>,
+}
+
+pub struct Server {
+    dir: PathBuf,
+    token: Option<String>,
+    entities: Vec<Stored>,
+}
+
+impl Server {
+
+    pub fn open<P: Into<PathBuf>>(dir: P, token: Option<String>) -> crate::DResult<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("entities.json");
+        let entities = match path.exists() {
+            true => serde_json::from_str(&fs::read_to_string(path)?)?,
+            false => Vec::new(),
+        };
+        Ok(Self { dir, token, entities })
+    }
+
+    fn save(&self) -> crate::DResult<()> {
+        fs::write(self.dir.join("entities.json"), serde_json::to_string_pretty(&self.entities)?)?;
+        Ok(())
+    }
+
+    pub fn handle(&mut self, req: &Request) -> Response {
+        if let Some(token) = &self.token {
+            let given = req.bearer().unwrap_or_default();
+            if ring::constant_time::verify_slices_are_equal(given.as_bytes(), token.as_bytes()).is_err() {
+                return Response::error(401, "Missing or wrong token");
+            }
+        }
+        if req.segments() != ["v1", "entities"] {
+            return Response::error(404, &format!("No such endpoint {}", req.path));
+        }
+        match req.method.as_str() {
+            "GET" => self.pull(req),
+            "POST" => match req.json::<Push>() {
+                Ok(push) => match self.push(push.entities) {
+                    Ok(pushed) => Response::json(200, &pushed),
+                    Err(e) => Response::error(500, &e.to_string()),
+                },
+                Err(e) => Response::error(400, &e.to_string()),
+            },
+            _ => Response::error(405, "Use GET or POST"),
+        }
+    }
+
+    fn pull(&self, req: &Request) -> Response {
+        let since = match req.query.get("since").map(|s| DateTime::parse_from_rfc3339(s)) {
+            Some(Ok(since)) => Some(since),
+            Some(Err(_)) => return Response::error(400, "since must be an RFC 3339 time"),
+            None => None,
+        };
+        let now = Local::now();
+        let entities = self.entities.iter()
+            .filter(|s| since.is_none_or(|since| s.received > since))
+            .map(|s| s.entity.clone())
+            .collect();
+        Response::json(200, &Pulled { entities, now: now.to_rfc3339() })
+    }
+
+    /// Takes pushed entities, keeping whichever version of each was updated
+    /// last. Records are matched by id or name, and keep the items of both.
+    pub fn push(&mut self, entities: Vec<Entity>) -> crate::DResult<Pushed> {
+        let mut pushed = Pushed::default();
+        let now = Local::now();
+        for theirs in entities {
+            let stored = self.entities.iter_mut().find(|s| s.entity.id == theirs.id
+                || match (&s.entity.data, &theirs.data) {
+                    (EntityData::Record(a), EntityData::Record(b)) => a.name.eq_ignore_ascii_case(&b.name),
+                    _ => false,
+                });
+            let stored = match stored {
+                Some(stored) if stored.entity.data == theirs.data => continue,
+                Some(stored) => stored,
+                None => {
+                    self.entities.push(Stored { entity: theirs, received: now });
+                    pushed.accepted += 1;
+                    continue;
+                },
+            };
+            let ours = stored.entity.clone();
+            let newer = theirs.updated >= ours.updated;
+            let mut changed = false;
+            match (&mut stored.entity.data, &theirs.data) {
+                (EntityData::Record(a), EntityData::Record(b)) => {
+                    for item in &b.items {
+                        if !a.items.iter().any(|i| i.eq_ignore_ascii_case(item)) {
+                            a.items.push(item.clone());
+                            changed = true;
+                        }
+                    }
+                    if a.description != b.description {
+                        if newer {
+                            a.description = b.description.clone();
+                            changed = true;
+                        } else {
+                            pushed.conflicts.push(Conflict::new(&ours, &theirs, true));
+                        }
+                    }
+                },
+                (data, EntityData::Fact(_)) => {
+                    if newer {
+                        *data = theirs.data.clone();
+                        changed = true;
+                    } else {
+                        pushed.conflicts.push(Conflict::new(&ours, &theirs, true));
+                    }
+                },
+                _ => pushed.conflicts.push(Conflict::new(&ours, &theirs, true)),
+            }
+            if changed {
+                if newer {
+                    stored.entity.updated = theirs.updated;
+                }
+                stored.received = now;
+                pushed.accepted += 1;
+            }
+        }
+        self.save()?;
+        Ok(pushed)
+    }
+}
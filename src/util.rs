@@ -2,7 +2,7 @@ pub mod file;
 
 // use chrono::{prelude::*, DateTime, Utc, Weekday, Month, Date};
 use colored::{Colorize, Color};
-use std::{fs, io::{Read, prelude::*, self}, path::PathBuf};
+use std::{fs, io::{Read, prelude::*, self}, path::{Path, PathBuf}};
 
 pub fn default_data_dir(child: Option<&str>) -> crate::DResult<PathBuf> {
     let path = dirs_next::data_dir()
@@ -16,7 +16,9 @@ pub fn default_data_dir(child: Option<&str>) -> crate::DResult<PathBuf> {
     dir.recursive(true);
     dir.create(&path)?;
     if let Some(child) = child {
-        Ok(path.join(child))
+        let path = path.join(child);
+        crate::auth::open_area(&path)?;
+        Ok(path)
     } else {
         Ok(path)
     }
@@ -55,6 +57,53 @@ pub fn write_file(input: String, path: PathBuf) -> io::Result<fs::File> {
     Ok(file)
 }
 
+/// Opens `text` in the editor in a temporary file with the given extension,
+/// returning what was saved. The editor may be given with arguments, e.g.
+/// `code --wait`.
+pub fn edit(editor: &str, text: &str, ext: &str) -> crate::DResult<String> {
+    let path = std::env::temp_dir()
+        .join(format!("dlog-{}.{}", uuid::Uuid::new_v4(), ext));
+    fs::write(&path, text)?;
+    let mut args = editor.split_whitespace();
+    let program = args.next().unwrap_or("vi");
+    let status = std::process::Command::new(program)
+        .args(args)
+        .arg(&path)
+        .status();
+    let edited = fs::read_to_string(&path);
+    fs::remove_file(&path)?;
+    if !status?.success() {
+        return Err(crate::error::DError::Invalid(
+            format!("{} exited with an error, nothing was saved", program)));
+    }
+    Ok(edited?)
+}
+
+/// Moves a directory and everything in it, copying it over if it can't be
+/// renamed, e.g. to another filesystem
+pub fn move_dir(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    copy_dir(from, to)?;
+    fs::remove_dir_all(from)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        match entry.file_type()?.is_dir() {
+            true => copy_dir(&entry.path(), &to.join(entry.file_name()))?,
+            false => { fs::copy(entry.path(), to.join(entry.file_name()))?; },
+        }
+    }
+    Ok(())
+}
+
 pub fn get_or_create_conf_dir() -> io::Result<PathBuf> {
     let def_conf_dir = dirs_next::config_dir()
         .expect("Couldn't find default config dir")
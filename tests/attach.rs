@@ -0,0 +1,84 @@
+use predicates::prelude::*;
+
+mod common;
+use common::dlog;
+
+#[test]
+fn attach_copies_into_blob_dir() {
+    let home = tempfile::tempdir().unwrap();
+    let file = home.path().join("receipt.txt");
+    std::fs::write(&file, "coffee 3.50").unwrap();
+    dlog(&home).args(&["record", "new", "finance"]).assert().success();
+    dlog(&home).args(&["attach", "record:finance"]).arg(&file)
+        .assert()
+        .success();
+    let blobs = home.path().join("data").join("dlog").join("blobs");
+    let prefix = std::fs::read_dir(&blobs).unwrap().next().unwrap().unwrap();
+    let blob = std::fs::read_dir(prefix.path()).unwrap().next().unwrap().unwrap();
+    assert_eq!(blob.file_name().len(), 64);
+    assert_eq!(std::fs::read_to_string(blob.path()).unwrap(), "coffee 3.50");
+    dlog(&home).args(&["attachments", "finance", "--verify"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("receipt.txt"))
+        .stdout(predicate::str::contains("ok"));
+}
+
+#[test]
+fn verify_flags_modified_symlinked_file() {
+    let home = tempfile::tempdir().unwrap();
+    let file = home.path().join("lab.pdf");
+    std::fs::write(&file, "cholesterol 180").unwrap();
+    dlog(&home).args(&["attach", "item:bloodwork", "--symlink"]).arg(&file)
+        .assert()
+        .success();
+    std::fs::write(&file, "cholesterol 240").unwrap();
+    dlog(&home).args(&["attachments", "item:bloodwork", "--verify"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("modified"));
+}
+
+#[test]
+fn symlinks_to_identical_files_are_kept_apart() {
+    let home = tempfile::tempdir().unwrap();
+    let (first, second) = (home.path().join("a.txt"), home.path().join("b.txt"));
+    std::fs::write(&first, "same").unwrap();
+    std::fs::write(&second, "same").unwrap();
+    dlog(&home).args(&["attach", "item:first", "--symlink"]).arg(&first)
+        .assert()
+        .success();
+    dlog(&home).args(&["attach", "item:second", "--symlink"]).arg(&second)
+        .assert()
+        .success();
+    std::fs::remove_file(&first).unwrap();
+    dlog(&home).args(&["attachments", "item:second", "--verify"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ok"));
+}
+
+#[test]
+fn record_new_file_attaches_symlink() {
+    let home = tempfile::tempdir().unwrap();
+    let file = home.path().join("meal.jpg");
+    std::fs::write(&file, "not really a jpeg").unwrap();
+    dlog(&home).args(&["record", "new", "meals", "--file"]).arg(&file)
+        .assert()
+        .success();
+    dlog(&home).args(&["attachments", "record:meals"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("meal.jpg"))
+        .stdout(predicate::str::contains("Symlink"));
+}
+
+#[test]
+fn attach_to_unknown_record_fails() {
+    let home = tempfile::tempdir().unwrap();
+    let file = home.path().join("x.txt");
+    std::fs::write(&file, "x").unwrap();
+    dlog(&home).args(&["attach", "nowhere"]).arg(&file)
+        .assert()
+        .failure();
+}
@@ -3,6 +3,10 @@ use dlog_lib::models::{
     fact::{Fact, FactValue},
     attrib::Attrib,
 };
+use predicates::prelude::*;
+
+mod common;
+use common::dlog;
 
 #[test]
 pub fn entry_linked_attrib_from_lc_a_flag() -> Result<(), String> {
@@ -10,8 +14,8 @@ pub fn entry_linked_attrib_from_lc_a_flag() -> Result<(), String> {
     let long = DApp::run_cmd("dlog sleep 5 hr --attrib dreamt");
     if let (Ok(s), Ok(l)) = (short, long) {
         match (s.subcmd, l.subcmd) {
-            (Subcmd::Fact(FactCmd::New(fs, afs)),
-             Subcmd::Fact(FactCmd::New(fl, afl))) => {
+            (Subcmd::Fact(FactCmd::New(fs, afs, ..)),
+             Subcmd::Fact(FactCmd::New(fl, afl, ..))) => {
                 let sa = Attrib::from("dreamt".to_string());
                 debug_assert_eq!(fs.attribs, vec![sa.clone()]);
                 debug_assert_eq!(fl.attribs, vec![sa.clone()]);
@@ -32,8 +36,8 @@ pub fn fact_type_linked_attrib_from_up_a_flag() -> Result<(), String> {
     let long = DApp::run_cmd("dlog sleep 5 hr --link-attrib health");
     if let (Ok(s), Ok(l)) = (short, long) {
         match (s.subcmd, l.subcmd) {
-            (Subcmd::Fact(FactCmd::New(fs, afs)),
-             Subcmd::Fact(FactCmd::New(fl, afl))) => {
+            (Subcmd::Fact(FactCmd::New(fs, afs, ..)),
+             Subcmd::Fact(FactCmd::New(fl, afl, ..))) => {
                 let sa = Attrib::from("health".to_string());
                 debug_assert_eq!(afs.attribs, vec![sa.clone()]);
                 debug_assert_eq!(afl.attribs, vec![sa.clone()]);
@@ -54,8 +58,8 @@ pub fn attrib_with_equals_gives_val() -> Result<(), String> {
     let long = DApp::run_cmd("dlog nap 5 hrs -A mood=cranky");
     if let (Ok(s), Ok(l)) = (short, long) {
         match (s.subcmd, l.subcmd) {
-            (Subcmd::Fact(FactCmd::New(fs, afs)),
-             Subcmd::Fact(FactCmd::New(fl, afl))) => {
+            (Subcmd::Fact(FactCmd::New(fs, afs, ..)),
+             Subcmd::Fact(FactCmd::New(fl, afl, ..))) => {
                 let sa = Attrib::from("health".to_string());
                 debug_assert_eq!(afs.attribs, vec![sa.clone()]);
                 debug_assert_eq!(afl.attribs, vec![sa.clone()]);
@@ -70,3 +74,101 @@ pub fn attrib_with_equals_gives_val() -> Result<(), String> {
         Err("NO".to_string())
     }
 }
+
+#[test]
+fn registered_attrib_rejects_disallowed_value() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["attrib", "new", "mood", "--type", "text", "--allow", "good", "--allow", "bad"])
+        .assert()
+        .success();
+    dlog(&home).args(&["item", "add", "health/sleep", "hours", "7", "-a", "mood=meh"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not an allowed value"));
+    dlog(&home).args(&["item", "add", "health/sleep", "hours", "7", "-a", "mood=good"])
+        .assert()
+        .success();
+    dlog(&home).args(&["attrib", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"mood\s+Text\s+good, bad\s+1\s+0\s+0").unwrap());
+}
+
+#[test]
+fn rename_rewrites_facts_and_items() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "health/sleep", "hours", "7", "-a", "where=home"])
+        .assert()
+        .success();
+    dlog(&home).args(&["item", "link", "health/sleep", "-a", "where=bed"])
+        .assert()
+        .success();
+    dlog(&home).args(&["attrib", "rename", "where", "place"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 facts, 1 items"));
+    dlog(&home).args(&["attrib", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("place"))
+        .stdout(predicate::str::contains("where").not());
+}
+
+#[test]
+fn rename_onto_existing_attrib_suggests_merge() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["attrib", "new", "mood"]).assert().success();
+    dlog(&home).args(&["attrib", "new", "feeling"]).assert().success();
+    dlog(&home).args(&["attrib", "rename", "mood", "feeling"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("attrib merge"));
+    dlog(&home).args(&["attrib", "merge", "mood", "feeling"])
+        .assert()
+        .success();
+    dlog(&home).args(&["attrib", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("feeling"))
+        .stdout(predicate::str::contains("mood").not());
+}
+
+#[test]
+fn tags_in_values_and_notes_become_attribs_and_relations() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["record", "new", "food"]).assert().success();
+    dlog(&home).args(&["item", "add", "health/meals", "lunch", "salad #veggie",
+            "-n", "with +alice, about @food."])
+        .assert()
+        .success();
+    dlog(&home).args(&["attrib", "list", "tags"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"veggie\s+1\s+1").unwrap());
+    let relations = std::fs::read_to_string(
+        home.path().join("data").join("dlog").join("relations.csv")).unwrap();
+    assert!(relations.contains("with,fact:"));
+    assert!(relations.contains("topic:alice"));
+    assert!(relations.contains("mentions,fact:"));
+    assert!(relations.contains("record:food"));
+}
+
+#[test]
+fn tagging_can_be_turned_off_in_config() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    let conf = home.path().join("config").join("dlog").join("dlog.toml");
+    let toml = std::fs::read_to_string(&conf).unwrap()
+        .replace("enabled = true", "enabled = false");
+    std::fs::write(&conf, toml).unwrap();
+    dlog(&home).args(&["item", "add", "health/meals", "lunch", "salad #veggie"])
+        .assert()
+        .success();
+    dlog(&home).args(&["attrib", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("veggie").not());
+}
@@ -0,0 +1,137 @@
+use assert_cmd::cmd::Command;
+use predicates::prelude::*;
+
+mod common;
+
+fn dlog(home: &tempfile::TempDir) -> Command {
+    let run = home.path().join("run");
+    std::fs::create_dir_all(&run).unwrap();
+    let mut cmd = common::dlog(home);
+    cmd.env("XDG_RUNTIME_DIR", run)
+        .env_remove("DLOG_PASSPHRASE");
+    cmd
+}
+
+fn record_files(home: &tempfile::TempDir) -> Vec<String> {
+    let dir = home.path().join("data").join("dlog").join("health");
+    std::fs::read_dir(dir).unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+        .collect()
+}
+
+#[test]
+fn locked_store_is_sealed_between_commands() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "health/night", "sleep", "7"]).assert().success();
+    dlog(&home).env("DLOG_PASSPHRASE", "hunter2")
+        .args(&["auth", "lock"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Locked the store"));
+    let files = record_files(&home);
+    assert!(files.contains(&"record.toml.enc".to_string()));
+    assert!(!files.contains(&"record.toml".to_string()));
+    let sealed = std::fs::read(home.path().join("data").join("dlog")
+        .join("health").join("night").join("night.csv.enc")).unwrap();
+    assert!(!String::from_utf8_lossy(&sealed).contains("sleep"));
+    dlog(&home).env("DLOG_PASSPHRASE", "wrong")
+        .args(&["item", "get", "health/night"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Wrong passphrase"));
+    dlog(&home).env("DLOG_PASSPHRASE", "hunter2")
+        .args(&["item", "add", "health/night", "sleep", "6"])
+        .assert()
+        .success();
+    dlog(&home).env("DLOG_PASSPHRASE", "hunter2")
+        .args(&["item", "get", "health/night"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("7"))
+        .stdout(predicate::str::contains("6"));
+    assert!(!record_files(&home).contains(&"record.toml".to_string()));
+    assert!(!home.path().join("data").join("dlog").join("records.toml").exists());
+}
+
+#[test]
+fn unlock_keeps_the_key_until_locked() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).env("DLOG_PASSPHRASE", "hunter2").args(&["auth", "lock"]).assert().success();
+    dlog(&home).env("DLOG_PASSPHRASE", "hunter2")
+        .args(&["auth", "unlock", "-t", "5"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Unlocked until"));
+    dlog(&home).args(&["item", "add", "health/night", "sleep", "7"]).assert().success();
+    dlog(&home).args(&["auth", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("unlocked until"));
+    dlog(&home).args(&["auth", "lock"]).assert().success();
+    dlog(&home).args(&["auth", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("and locked"));
+    assert!(record_files(&home).contains(&"health.csv.enc".to_string()));
+}
+
+#[cfg(unix)]
+#[test]
+fn unlock_keeps_the_key_only_where_no_one_else_can_reach_it() {
+    use std::os::unix::fs::PermissionsExt;
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).env("DLOG_PASSPHRASE", "hunter2").args(&["auth", "lock"]).assert().success();
+    dlog(&home).env("DLOG_PASSPHRASE", "hunter2")
+        .env("XDG_RUNTIME_DIR", home.path().join("nosuchdir"))
+        .args(&["auth", "unlock"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no private runtime dir"));
+    dlog(&home).env("DLOG_PASSPHRASE", "hunter2").args(&["auth", "unlock"]).assert().success();
+    let dir = home.path().join("run").join("dlog");
+    assert_eq!(std::fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+    for session in std::fs::read_dir(&dir).unwrap() {
+        let mode = session.unwrap().metadata().unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+#[test]
+fn rekey_replaces_the_passphrase() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "health/night", "sleep", "7"]).assert().success();
+    dlog(&home).env("DLOG_PASSPHRASE", "hunter2").args(&["auth", "lock"]).assert().success();
+    dlog(&home).env("DLOG_PASSPHRASE", "hunter2")
+        .env("DLOG_NEW_PASSPHRASE", "correct horse")
+        .args(&["auth", "rekey"])
+        .assert()
+        .success();
+    dlog(&home).env("DLOG_PASSPHRASE", "hunter2")
+        .args(&["item", "get", "health/night"])
+        .assert()
+        .failure();
+    dlog(&home).env("DLOG_PASSPHRASE", "correct horse")
+        .args(&["item", "get", "health/night"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("sleep"));
+}
+
+#[test]
+fn failed_command_leaves_the_store_sealed() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).env("DLOG_PASSPHRASE", "hunter2").args(&["auth", "lock"]).assert().success();
+    dlog(&home).env("DLOG_PASSPHRASE", "hunter2")
+        .args(&["record", "get", "nosuchrecordzzz"])
+        .assert()
+        .failure();
+    let data = home.path().join("data").join("dlog");
+    assert!(!data.join("records.toml").exists());
+    assert!(data.join("records.toml.enc").exists());
+    assert!(!record_files(&home).contains(&"record.toml".to_string()));
+}
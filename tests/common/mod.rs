@@ -0,0 +1,30 @@
+#![allow(dead_code)]
+
+use assert_cmd::cmd::Command;
+use std::{
+    io::{BufRead, BufReader},
+    process::{self, Child, Stdio},
+};
+
+/// `dlog` keeping its data and config under `home`
+pub fn dlog(home: &tempfile::TempDir) -> Command {
+    let mut cmd = Command::cargo_bin("dlog").unwrap();
+    cmd.env("XDG_DATA_HOME", home.path().join("data"))
+        .env("XDG_CONFIG_HOME", home.path().join("config"));
+    cmd
+}
+
+/// Runs the command, which should succeed, and returns what it printed
+pub fn stdout(cmd: &mut Command) -> String {
+    String::from_utf8(cmd.assert().success().get_output().stdout.clone()).unwrap()
+}
+
+/// Spawns a server and waits for its `Listening on <addr>` line, returning
+/// the child and the url it can be reached at
+pub fn listen(cmd: &mut process::Command) -> (Child, String) {
+    let mut child = cmd.stdout(Stdio::piped()).spawn().unwrap();
+    let mut line = String::new();
+    BufReader::new(child.stdout.as_mut().unwrap()).read_line(&mut line).unwrap();
+    let addr = line.trim().trim_start_matches("Listening on ").to_string();
+    (child, format!("http://{}", addr))
+}
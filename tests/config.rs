@@ -0,0 +1,76 @@
+use predicates::prelude::*;
+
+mod common;
+use common::dlog;
+
+#[test]
+fn set_get_and_unset_dotted_keys() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["config", "set", "format.date_format", "DD/MM/YYYY"])
+        .assert()
+        .success();
+    dlog(&home).args(&["config", "get", "format.date_format"])
+        .assert()
+        .success()
+        .stdout("DD/MM/YYYY\n");
+    dlog(&home).args(&["config", "set", "format.table_width=100"]).assert().success();
+    dlog(&home).args(&["config", "get", "format.table_width"])
+        .assert()
+        .success()
+        .stdout("100\n");
+    dlog(&home).args(&["config", "unset", "format.date_format"]).assert().success();
+    dlog(&home).args(&["config", "get", "format.date_format"])
+        .assert()
+        .success()
+        .stdout("YYYY-MM-DD\n");
+}
+
+#[test]
+fn rejects_unknown_keys_and_wrong_types() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["config", "set", "format.dat_format", "YYYY"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown config key format.dat_format")
+            .and(predicate::str::contains("date_format")));
+    dlog(&home).args(&["config", "set", "format.pretty", "sometimes"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("format.pretty"));
+    let conf = home.path().join("config").join("dlog").join("dlog.toml");
+    let conf = std::fs::read_to_string(conf).unwrap();
+    assert!(!conf.contains("dat_format"));
+    assert!(!conf.contains("sometimes"));
+}
+
+#[test]
+fn record_env_and_cf_layers_override_the_global_file() {
+    let home = tempfile::tempdir().unwrap();
+    let project = home.path().join("project");
+    std::fs::create_dir_all(&project).unwrap();
+    dlog(&home).current_dir(&project).arg("init").assert().success();
+    dlog(&home).args(&["config", "set", "format.time_format", "HH:MM:SS"]).assert().success();
+    dlog(&home).current_dir(&project)
+        .args(&["config", "set", "--local", "format.time_format", "HH"])
+        .assert()
+        .success();
+    dlog(&home).args(&["config", "get", "format.time_format"])
+        .assert()
+        .stdout("HH:MM:SS\n");
+    dlog(&home).current_dir(&project).args(&["config", "get", "format.time_format"])
+        .assert()
+        .stdout("HH\n");
+    dlog(&home).current_dir(&project)
+        .env("DLOG_FORMAT__TIME_FORMAT", "HH:MM")
+        .args(&["config", "get", "format.time_format"])
+        .assert()
+        .stdout("HH:MM\n");
+    let cf = home.path().join("cf.toml");
+    std::fs::write(&cf, "[format]\ntime_format = \"SS\"\n").unwrap();
+    dlog(&home).current_dir(&project)
+        .env("DLOG_FORMAT__TIME_FORMAT", "HH:MM")
+        .args(&["config", "get", "format.time_format", "--cf"])
+        .arg(&cf)
+        .assert()
+        .stdout("SS\n");
+}
@@ -1,14 +1,19 @@
 use clap::FromArgMatches;
+use predicates::prelude::*;
 use dlog_lib::{
     cmd::{DApp, Cmd, Subcmd,
         fact::FactCmd,
     },
     models::{
         fact::{Fact, FactValue, AbstractFact},
-        units::Units,
+        fact::Unit,
     }
 };
 
+#[path = "common/mod.rs"]
+mod common;
+use common::dlog;
+
 pub fn fact_cmd_no_key_prompts_user() {
 
 }
@@ -18,7 +23,7 @@ pub fn fact_cmd_first_pos_gives_fact_name() -> Result<(), String> {
     let cmd = DApp::run_cmd("dlog sleep");
     if let Ok(cmd) = cmd {
         match cmd.subcmd {
-            Subcmd::Fact(FactCmd::New(f, af)) => {
+            Subcmd::Fact(FactCmd::New(f, af, ..)) => {
                 debug_assert_eq!(f.name.as_str(), "sleep");
                 Ok(())
             },
@@ -36,7 +41,7 @@ pub fn fact_cmd_second_pos_gives_fact_val() -> Result<(), String> {
     let cmd = DApp::run_cmd("dlog sleep 5");
     if let Ok(cmd) = cmd {
         match cmd.subcmd {
-            Subcmd::Fact(FactCmd::New(f, af)) => {
+            Subcmd::Fact(FactCmd::New(f, af, ..)) => {
                 debug_assert_eq!(f.name, "sleep".to_string());
                 debug_assert_eq!(f.val, FactValue::RealNumber(5.0));
                 Ok(())
@@ -48,3 +53,188 @@ pub fn fact_cmd_second_pos_gives_fact_val() -> Result<(), String> {
         Err("NO".to_string())
     }
 }
+
+fn night(home: &tempfile::TempDir) -> String {
+    let path = home.path().join("data").join("dlog").join("health").join("night").join("night.csv");
+    std::fs::read_to_string(path).unwrap()
+}
+
+#[test]
+fn edit_last_entry_with_flags() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "night", "sleep", "7", "-r", "health"]).assert().success();
+    dlog(&home).args(&["fact", "edit", "last", "-v", "8", "-u", "hours", "-a", "quality=good", "-n", "woke once"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Edited entry"));
+    let csv = night(&home);
+    assert!(csv.contains(",sleep,8,") && csv.contains(",hours,quality=good,woke once,"));
+    assert!(!csv.contains(",sleep,7,"));
+    dlog(&home).args(&["fact", "edit", "last", "-v", "8", "-u", "hours", "-a", "quality=good", "-n", "woke once"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("unchanged"));
+}
+
+#[test]
+fn show_lists_the_revisions_of_the_last_of_a_fact() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "night", "sleep", "7", "-r", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "night", "mood", "5", "-r", "health"]).assert().success();
+    dlog(&home).args(&["fact", "edit", "last:sleep", "-v", "6"]).assert().success();
+    dlog(&home).args(&["fact", "edit", "last:sleep", "-v", "9"]).assert().success();
+    assert!(night(&home).contains(",mood,5,"));
+    let out = dlog(&home).args(&["--output", "json", "fact", "show", "last:sleep"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let shown: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(shown["Value"], "9");
+    let revisions = shown["revisions"].as_array().unwrap();
+    assert_eq!(revisions.iter().map(|r| r["Value"].as_str().unwrap()).collect::<Vec<&str>>(), vec!["7", "6"]);
+    dlog(&home).args(&["fact", "show", "last:sleep"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("current"));
+}
+
+#[test]
+fn edit_refuses_unknown_entries() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["fact", "edit", "last", "-v", "1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No entry has been logged"));
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "night", "sleep", "7", "-r", "health"]).assert().success();
+    dlog(&home).args(&["fact", "edit", "last:nap", "-v", "1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No nap entry has been logged"));
+    dlog(&home).args(&["fact", "edit", "last", "-v", "   "])
+        .assert()
+        .failure();
+    dlog(&home).args(&["fact", "show", "zzzz"]).assert().failure();
+}
+
+fn health(home: &tempfile::TempDir) {
+    dlog(home).args(&["record", "new", "health"]).assert().success();
+    dlog(home).args(&["item", "add", "sleep", "hours", "7", "-r", "health", "-a", "quality=good"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn record_typo_is_refused_with_a_suggestion() {
+    let home = tempfile::tempdir().unwrap();
+    health(&home);
+    dlog(&home).args(&["item", "add", "sleep", "hours", "8", "-r", "helth"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("did you mean `health`?"));
+    dlog(&home).args(&["record", "get", "helht"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("did you mean `health`?"));
+    dlog(&home).args(&["record", "get", "garden"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("did you mean").not());
+}
+
+#[test]
+fn item_fact_and_attribute_typos_are_not_logged() {
+    let home = tempfile::tempdir().unwrap();
+    health(&home);
+    dlog(&home).args(&["item", "add", "slep", "hours", "8", "-r", "health"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("There is no item named slep, did you mean `sleep`?"));
+    dlog(&home).args(&["item", "add", "sleep", "huors", "8", "-r", "health"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("There is no fact named huors, did you mean `hours`?"));
+    dlog(&home).args(&["item", "add", "sleep", "hours", "8", "-r", "health", "-a", "qualty=ok"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("did you mean `quality`?"));
+    let dir = home.path().join("data").join("dlog").join("health");
+    assert!(!dir.join("slep").exists());
+    let csv = std::fs::read_to_string(dir.join("sleep").join("sleep.csv")).unwrap();
+    assert_eq!(csv.lines().count(), 2);
+}
+
+#[test]
+fn known_and_unrelated_names_are_logged() {
+    let home = tempfile::tempdir().unwrap();
+    health(&home);
+    dlog(&home).args(&["item", "add", "Sleep", "HOURS", "8", "-r", "Health"])
+        .assert()
+        .success();
+    dlog(&home).args(&["item", "add", "run", "distance", "5", "-r", "health"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Logged distance = 5 to run in health"));
+}
+
+#[test]
+fn names_alike_others_are_taken_as_typed_when_asked() {
+    let home = tempfile::tempdir().unwrap();
+    health(&home);
+    dlog(&home).args(&["item", "add", "sleep", "hour", "8", "-r", "health"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Pass --as-typed to take hour as typed"));
+    dlog(&home).args(&["item", "add", "sleep", "hour", "8", "-r", "health", "--as-typed"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Logged hour = 8 to sleep in health"));
+    dlog(&home).args(&["--as-typed", "hour", "9", "-r", "health", "sleep"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn logged_fact_typos_are_refused_with_a_suggestion() {
+    let home = tempfile::tempdir().unwrap();
+    health(&home);
+    dlog(&home).args(&["huors", "8"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("There is no fact named huors, did you mean `hours`?"));
+    dlog(&home).args(&["hours", "8", "-a", "qualty=ok"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("did you mean `quality`?"));
+    dlog(&home).args(&["hours", "8", "-r", "helth", "log"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("did you mean `health`?"));
+    dlog(&home).args(&["hours", "8"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Logged hours = 8 to Inbox"));
+    let inbox = home.path().join("data").join("dlog").join("inbox").join("Inbox.csv");
+    assert_eq!(std::fs::read_to_string(inbox).unwrap().lines().count(), 2);
+}
+
+#[test]
+fn attribute_typos_are_refused_with_a_suggestion() {
+    let home = tempfile::tempdir().unwrap();
+    health(&home);
+    dlog(&home).args(&["attrib", "rename", "qualty", "rest"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("There is no attribute named qualty, did you mean `quality`?"));
+    dlog(&home).args(&["attrib", "merge", "qualty", "rest"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("did you mean `quality`?"));
+    dlog(&home).args(&["attrib", "rename", "quality", "rest"])
+        .assert()
+        .success();
+}
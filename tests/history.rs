@@ -0,0 +1,120 @@
+use predicates::prelude::*;
+
+mod common;
+use common::dlog;
+
+fn night(home: &tempfile::TempDir) -> std::path::PathBuf {
+    home.path().join("data").join("dlog").join("health").join("night").join("night.csv")
+}
+
+#[test]
+fn undo_and_redo_a_logged_entry() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "night", "sleep", "7", "-r", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "night", "sleep", "70", "-r", "health"]).assert().success();
+    dlog(&home).arg("undo")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Undid `dlog item add night sleep 70 -r health`"));
+    let csv = std::fs::read_to_string(night(&home)).unwrap();
+    assert!(csv.contains("sleep,7,") && !csv.contains("sleep,70,"));
+    dlog(&home).arg("redo").assert().success();
+    assert!(std::fs::read_to_string(night(&home)).unwrap().contains("sleep,70,"));
+    dlog(&home).args(&["undo", "2"]).assert().success();
+    assert!(!night(&home).exists());
+}
+
+#[test]
+fn undo_and_redo_in_a_locked_store() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).env("DLOG_PASSPHRASE", "hunter2").args(&["auth", "lock"]).assert().success();
+    for value in &["7", "6"] {
+        dlog(&home).env("DLOG_PASSPHRASE", "hunter2")
+            .args(&["item", "add", "night", "sleep", value, "-r", "health"])
+            .assert()
+            .success();
+    }
+    dlog(&home).env("DLOG_PASSPHRASE", "hunter2")
+        .arg("undo")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Undid `dlog item add night sleep 6 -r health`"));
+    let out = dlog(&home).env("DLOG_PASSPHRASE", "hunter2")
+        .args(&["item", "get", "health/night", "-o", "json"])
+        .output()
+        .unwrap();
+    let out = String::from_utf8_lossy(&out.stdout).to_string();
+    assert!(out.contains("\"7\"") && !out.contains("\"6\""));
+    dlog(&home).env("DLOG_PASSPHRASE", "hunter2").arg("redo").assert().success();
+    assert!(!night(&home).exists());
+    dlog(&home).env("DLOG_PASSPHRASE", "hunter2")
+        .args(&["item", "get", "health/night"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("6"));
+}
+
+#[test]
+fn undo_refuses_files_changed_since() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).arg("undo")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Nothing to undo"));
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "night", "sleep", "7", "-r", "health"]).assert().success();
+    let mut csv = std::fs::read_to_string(night(&home)).unwrap();
+    csv.push_str("edited by hand\n");
+    std::fs::write(night(&home), &csv).unwrap();
+    dlog(&home).arg("undo")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("changed after `dlog item add night sleep 7 -r health`"));
+    assert_eq!(std::fs::read_to_string(night(&home)).unwrap(), csv);
+}
+
+#[test]
+fn history_lists_commands_and_new_ones_drop_redos() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "night", "sleep", "7", "-r", "health"]).assert().success();
+    dlog(&home).arg("undo").assert().success();
+    dlog(&home).args(&["history", "-o", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"cmd\": \"dlog record new health\""))
+        .stdout(predicate::str::contains("\"undone\": true"));
+    dlog(&home).args(&["item", "add", "body", "weight", "80", "-r", "health"]).assert().success();
+    dlog(&home).arg("redo")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Nothing to redo"));
+    dlog(&home).args(&["history", "--max", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("weight 80"))
+        .stdout(predicate::str::contains("record new").not());
+}
+
+#[test]
+fn only_what_changed_is_kept() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["search", "sleep"]).assert().success();
+    dlog(&home).arg("undo")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Nothing to undo"));
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "night", "sleep", "7", "-r", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "night", "sleep", "70", "-r", "health"]).assert().success();
+    let blobs = home.path().join("data").join("dlog").join("history").join("blobs");
+    for blob in std::fs::read_dir(blobs).unwrap() {
+        let blob = std::fs::read_to_string(blob.unwrap().path()).unwrap_or_default();
+        assert!(!(blob.contains("sleep,7,") && blob.contains("sleep,70,")));
+    }
+    dlog(&home).arg("undo").assert().success();
+    let csv = std::fs::read_to_string(night(&home)).unwrap();
+    assert!(csv.contains("sleep,7,") && !csv.contains("sleep,70,"));
+}
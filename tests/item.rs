@@ -1,16 +1,21 @@
+use predicates::prelude::*;
 use dlog_lib::cmd::{DApp, Cmd, fact::FactCmd, attribute::AttribCmd, Subcmd};
 use dlog_lib::models::{
     fact::{Fact, FactValue},
     attrib::Attrib,
 };
 
+#[path = "common/mod.rs"]
+mod common;
+use common::dlog;
+
 pub fn entry_linked_attrib_from_lc_a_flag() -> Result<(), String> {
     let short = DApp::run_cmd("dlog sleep 5 hr -a dreamt");
     let long = DApp::run_cmd("dlog sleep 5 hr --attrib dreamt");
     if let (Ok(s), Ok(l)) = (short, long) {
         match (s.subcmd, l.subcmd) {
-            (Subcmd::Fact(FactCmd::New(fs, afs)),
-             Subcmd::Fact(FactCmd::New(fl, afl))) => {
+            (Subcmd::Fact(FactCmd::New(fs, afs, ..)),
+             Subcmd::Fact(FactCmd::New(fl, afl, ..))) => {
                 println!("{}", fs.table());
                 println!("{}", fl.table());
                 let sa = Attrib::from("dreamt".to_string());
@@ -26,3 +31,124 @@ pub fn entry_linked_attrib_from_lc_a_flag() -> Result<(), String> {
         Err("NO".to_string())
     }
 }
+
+#[test]
+fn child_item_facts_roll_up_into_parent() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "health/exercise/running", "distance", "5"])
+        .assert()
+        .success();
+    dlog(&home).args(&["item", "add", "exercise", "pushups", "30", "-r", "health"])
+        .assert()
+        .success();
+    let rec = home.path().join("data").join("dlog").join("health");
+    let running = rec.join("exercise").join("running").join("running.csv");
+    let running = std::fs::read_to_string(running).unwrap();
+    assert!(running.contains("distance,5,"));
+    let toml = std::fs::read_to_string(rec.join("record.toml")).unwrap();
+    assert!(toml.contains("Parent = 'exercise'"));
+    let out = dlog(&home).args(&["item", "tree", "health"]).output().unwrap();
+    let out = String::from_utf8_lossy(&out.stdout).to_string();
+    let exercise = out.lines().find(|l| l.contains("exercise")).unwrap();
+    let counts = exercise.split_whitespace().collect::<Vec<&str>>();
+    assert_eq!(&counts[2..4], &["1", "2"]);
+}
+
+#[test]
+fn item_move_carries_children_and_entries() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "health/cardio/running", "distance", "5"])
+        .assert()
+        .success();
+    dlog(&home).args(&["item", "move", "health/cardio", "exercise/cardio"])
+        .assert()
+        .success();
+    let rec = home.path().join("data").join("dlog").join("health");
+    assert!(rec.join("exercise").join("cardio").join("running")
+        .join("running.csv").is_file());
+    assert!(!rec.join("cardio").exists());
+    dlog(&home).args(&["item", "move", "health/exercise", "exercise/cardio/inner"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn item_get_shows_rolled_up_entries_and_stats() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    for km in &["5", "7"] {
+        dlog(&home).args(&["item", "add", "health/exercise/running", "distance", km])
+            .assert()
+            .success();
+    }
+    let out = dlog(&home).args(&["item", "get", "health/exercise"]).output().unwrap();
+    let out = String::from_utf8_lossy(&out.stdout).to_string();
+    let stats = out.lines()
+        .filter(|l| l.contains("distance"))
+        .last()
+        .unwrap()
+        .split_whitespace()
+        .collect::<Vec<&str>>();
+    assert_eq!(&stats[1..7], &["distance", "2", "12", "6", "5", "7"]);
+}
+
+#[test]
+fn item_delete_needs_cascade_or_orphan() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "health/diet", "calories", "2100"])
+        .assert()
+        .success();
+    dlog(&home).args(&["item", "delete", "health/diet", "-y"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Use --cascade"));
+    dlog(&home).args(&["item", "delete", "health/diet", "--orphan", "-y"])
+        .assert()
+        .success();
+    let rec = home.path().join("data").join("dlog").join("health");
+    assert!(!rec.join("diet").exists());
+    let csv = std::fs::read_to_string(rec.join("health.csv")).unwrap();
+    assert!(csv.contains("calories,2100,"));
+    dlog(&home).args(&["item", "list", "health"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("diet").not());
+}
+
+#[test]
+fn item_path_cannot_leave_the_record() {
+    let home = tempfile::tempdir().unwrap();
+    for path in &["../../../../pwned", "a/../../b", "/tmp/pwned", "a//b", "."] {
+        dlog(&home).args(&["item", "add", path]).assert().failure();
+        dlog(&home).args(&["item", "add", path, "hours", "7"]).assert().failure();
+    }
+    assert!(!home.path().join("pwned").exists());
+    assert!(!home.path().join("data").join("pwned").exists());
+    dlog(&home).args(&["item", "add", "exercise/running", "distance", "5"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn unreadable_entries_are_reported_with_their_file_and_line() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "health/night", "sleep", "7"]).assert().success();
+    let csv = home.path().join("data").join("dlog").join("health").join("night").join("night.csv");
+    let rows = std::fs::read_to_string(&csv).unwrap();
+    let (header, row) = rows.split_once('\n').unwrap();
+    let id = row.split(',').next().unwrap();
+    std::fs::write(&csv, format!("{}\n{}", header, row.replacen(id, "not-an-id", 1))).unwrap();
+    dlog(&home).args(&["item", "get", "health/night"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("night.csv: The entry on line 2 has an invalid Id \"not-an-id\""));
+    std::fs::write(&csv, format!("{}\n{},sleep,7,yesterday,,,,,\n", header, id)).unwrap();
+    dlog(&home).args(&["item", "get", "health/night"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("has an invalid Datetime \"yesterday\""));
+}
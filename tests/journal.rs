@@ -0,0 +1,73 @@
+use predicates::prelude::*;
+
+mod common;
+use common::dlog;
+
+fn journal_csv(home: &tempfile::TempDir) -> String {
+    std::fs::read_to_string(home.path()
+        .join("data").join("dlog").join("journal").join("journal.csv"))
+        .unwrap()
+}
+
+#[cfg(unix)]
+#[test]
+fn inline_facts_are_logged_from_the_editor() {
+    use std::os::unix::fs::PermissionsExt;
+    let home = tempfile::tempdir().unwrap();
+    let editor = home.path().join("editor.sh");
+    std::fs::write(&editor, "#!/bin/sh\nprintf 'Long day #work\\n\\n- sleep:: 6.5h #restless\\n- mood:: good\\n' >> \"$1\"\n")
+        .unwrap();
+    std::fs::set_permissions(&editor, std::fs::Permissions::from_mode(0o755)).unwrap();
+    dlog(&home).arg("journal")
+        .env("EDITOR", &editor)
+        .env_remove("VISUAL")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("with 2 facts"));
+    let csv = journal_csv(&home);
+    assert!(csv.contains(",sleep,6.5,"));
+    assert!(csv.contains(",h,journal="));
+    assert!(csv.contains(";restless,"));
+    assert!(csv.contains(",mood,good,"));
+    dlog(&home).args(&["journal", "show"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Attributes: work"));
+}
+
+#[test]
+fn saving_again_replaces_logged_facts() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["journal", "-m", "sleep:: 7h"]).assert().success();
+    dlog(&home).args(&["journal", "-m", "water:: 2 l"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("with 2 facts"));
+    let csv = journal_csv(&home);
+    assert_eq!(csv.matches(",sleep,").count(), 1);
+    assert_eq!(csv.matches(",water,2,").count(), 1);
+}
+
+#[test]
+fn journals_are_listed_by_date() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["journal", "2021-01-30", "-m", "Quiet day. mood:: calm"])
+        .assert()
+        .success();
+    dlog(&home).args(&["journal", "2021-01-31", "-m", "weight:: 70 kg"])
+        .assert()
+        .success();
+    dlog(&home).args(&["journal", "list", "--from", "2021-01-31"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2021-01-31"))
+        .stdout(predicate::str::contains("weight: 70"))
+        .stdout(predicate::str::contains("2021-01-30").not());
+    dlog(&home).args(&["journal", "show", "2021-01-30"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Quiet day"));
+    dlog(&home).args(&["journal", "show", "2021-01-29"])
+        .assert()
+        .failure();
+}
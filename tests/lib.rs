@@ -11,6 +11,9 @@ use dlog_lib::models::{
     attrib::Attrib,
 };
 
+mod common;
+use common::dlog;
+
 type Err = Box<dyn std::error::Error>;
 
 #[test]
@@ -52,7 +55,7 @@ pub fn etc() -> Result<(), String> {
     let cmd = DApp::run_cmd("dlog sleep 4 hr -a slept=well -n tenmp -A att=a -A b -N perm");
     if let Ok(cmd) = cmd {
         match cmd.subcmd {
-             Subcmd::Fact(FactCmd::New(f, af)) => {
+             Subcmd::Fact(FactCmd::New(f, af, ..)) => {
                 let as1 = Attrib::new("slept".into(), Some("well".into()));
                 let al1 = Attrib::new("att".into(), Some("a".into()));
                 let al2 = Attrib::new("b".into(), None);
@@ -66,3 +69,123 @@ pub fn etc() -> Result<(), String> {
         Err("NO".to_string())
     }
 }
+
+fn alias(home: &tempfile::TempDir, name: &str, expansion: &str) {
+    dlog(home).args(&["config", "set", &format!("aliases.{}", name), expansion])
+        .assert()
+        .success();
+}
+
+#[test]
+fn aliases_expand_positional_args() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    alias(&home, "w", "item add health/body $2 $1");
+    alias(&home, "sleep", "item add health/night sleep");
+    dlog(&home).args(&["w", "80", "weight"]).assert().success();
+    dlog(&home).args(&["sleep", "7"]).assert().success();
+    let rec = home.path().join("data").join("dlog").join("health");
+    let body = std::fs::read_to_string(rec.join("body").join("body.csv")).unwrap();
+    assert!(body.contains("weight,80,"));
+    let night = std::fs::read_to_string(rec.join("night").join("night.csv")).unwrap();
+    assert!(night.contains("sleep,7,"));
+}
+
+#[test]
+fn aliases_expand_after_global_flags() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    alias(&home, "w", "item add health/body weight $1");
+    alias(&home, "body", "item get health/body");
+    dlog(&home).args(&["-o", "json", "w", "80"]).assert().success();
+    dlog(&home).args(&["--output=json", "-p", "w", "81"]).assert().success();
+    dlog(&home).args(&["-o", "json", "body"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"Value\": \"80\""))
+        .stdout(predicate::str::contains("\"Value\": \"81\""));
+}
+
+#[test]
+fn aliases_need_their_args_and_may_not_loop() {
+    let home = tempfile::tempdir().unwrap();
+    alias(&home, "w", "item add health/body weight $1");
+    dlog(&home).arg("w")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Alias w needs at least 1 args"));
+    alias(&home, "a", "b");
+    alias(&home, "b", "a");
+    dlog(&home).arg("a")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expands to itself"));
+}
+
+#[test]
+fn help_lists_aliases_and_commands_win() {
+    let home = tempfile::tempdir().unwrap();
+    alias(&home, "coffee", "fact caffeine 95 mg -a drink=coffee");
+    alias(&home, "record", "fact x 1");
+    dlog(&home).arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ALIASES:")
+            .and(predicate::str::contains("fact caffeine 95 mg -a drink=coffee")));
+    dlog(&home).args(&["record", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Record"));
+}
+
+fn complete(home: &tempfile::TempDir, words: &[&str]) -> Vec<String> {
+    let out = dlog(home).arg("__complete").arg("--").args(words)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    String::from_utf8(out.stdout).unwrap().lines().map(String::from).collect()
+}
+
+#[test]
+fn generate_prints_a_script_for_each_shell() {
+    let home = tempfile::tempdir().unwrap();
+    for shell in &["bash", "elvish", "fish", "powershell", "zsh"] {
+        dlog(&home).args(&["--generate", shell])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("dlog __complete --"));
+    }
+    dlog(&home).args(&["--generate", "tcsh"]).assert().failure();
+}
+
+#[test]
+fn fact_names_complete_from_the_store() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "night", "sleep", "7", "-r", "health"]).assert().success();
+    assert_eq!(complete(&home, &["dlog", "sl"]), vec!["sleep"]);
+    assert_eq!(complete(&home, &["dlog", "item", "add", "ni"]), vec!["night"]);
+    assert_eq!(complete(&home, &["dlog", "item", "add", "night", "sleep", "8", "-r", "he"]), vec!["health"]);
+}
+
+#[test]
+fn attribute_names_complete_for_rename_and_merge() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "night", "sleep", "7", "-r", "health", "-a", "quality=good"])
+        .assert()
+        .success();
+    assert_eq!(complete(&home, &["dlog", "attrib", "rename", "qu"]), vec!["quality"]);
+    assert_eq!(complete(&home, &["dlog", "attrib", "merge", "rest", "qu"]), vec!["quality"]);
+}
+
+#[test]
+fn commands_and_flags_complete_without_hidden_ones() {
+    let home = tempfile::tempdir().unwrap();
+    let cmds = complete(&home, &["dlog", ""]);
+    assert!(cmds.contains(&"record".to_string()));
+    assert!(!cmds.iter().any(|c| c.starts_with("__") || c.ends_with("_help")));
+    assert_eq!(complete(&home, &["dlog", "config", "--l"]), Vec::<String>::new());
+    assert_eq!(complete(&home, &["dlog", "config", "set", "--l"]), vec!["--local"]);
+    assert_eq!(complete(&home, &["dlog", "stats", "--output", "js"]), vec!["json"]);
+}
@@ -0,0 +1,87 @@
+use predicates::prelude::*;
+
+mod common;
+use common::dlog;
+
+fn notes(home: &tempfile::TempDir) -> Vec<std::path::PathBuf> {
+    std::fs::read_dir(home.path().join("data").join("dlog").join("notes"))
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect()
+}
+
+#[cfg(unix)]
+#[test]
+fn note_is_written_in_editor_with_front_matter() {
+    use std::os::unix::fs::PermissionsExt;
+    let home = tempfile::tempdir().unwrap();
+    let editor = home.path().join("editor.sh");
+    std::fs::write(&editor, "#!/bin/sh\nprintf '# Checkup\\n\\nBlood pressure is fine\\n' >> \"$1\"\n")
+        .unwrap();
+    std::fs::set_permissions(&editor, std::fs::Permissions::from_mode(0o755)).unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["note", "health", "-a", "mood=good"])
+        .env("EDITOR", &editor)
+        .env_remove("VISUAL")
+        .assert()
+        .success();
+    let dir = notes(&home).remove(0);
+    let text = std::fs::read_to_string(dir.join("1.md")).unwrap();
+    assert!(text.starts_with("+++\n"));
+    assert!(text.contains("entity = \"record:health\""));
+    assert!(text.contains("attributes = [\"mood=good\"]"));
+    assert!(text.contains("Blood pressure is fine"));
+}
+
+#[test]
+fn edits_are_kept_as_versions() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["note", "item:dinner", "-m", "Pasta was too salty"])
+        .assert()
+        .success();
+    let id = notes(&home).remove(0).file_name().unwrap().to_string_lossy().to_string();
+    dlog(&home).args(&["note", "edit", &id[..8], "-m", "Pasta was fine after all"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("version 2"));
+    dlog(&home).args(&["note", "show", &id[..8]])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("fine after all"));
+    dlog(&home).args(&["note", "show", &id[..8], "--version", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("too salty"));
+    dlog(&home).args(&["note", "edit", &id[..8], "-m", "Pasta was fine after all"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn notes_are_listed_and_searched() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["note", "item:dinner", "-m", "Pasta was too salty"])
+        .assert()
+        .success();
+    dlog(&home).args(&["note", "item:lunch", "-m", "Soup and bread"])
+        .assert()
+        .success();
+    dlog(&home).args(&["note", "list", "item:lunch"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Soup and bread"))
+        .stdout(predicate::str::contains("Pasta").not());
+    dlog(&home).args(&["note", "search", "SALTY", "pasta"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("item:dinner"))
+        .stdout(predicate::str::contains("item:lunch").not());
+}
+
+#[test]
+fn note_on_unknown_record_fails() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["note", "record:missing", "-m", "x"])
+        .assert()
+        .failure();
+}
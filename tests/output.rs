@@ -0,0 +1,123 @@
+use serde_json::Value;
+
+mod common;
+use common::{dlog, stdout};
+
+#[test]
+fn record_list_as_json() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    let out = stdout(dlog(&home).args(&["record", "list", "-o", "json"]));
+    let records: Value = serde_json::from_str(&out).unwrap();
+    let records = records.as_array().unwrap();
+    assert!(records.iter().any(|r| r["Record"] == "health"));
+}
+
+#[test]
+fn item_entries_as_csv() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "health/night", "sleep", "7"]).assert().success();
+    let out = stdout(dlog(&home).args(&["item", "get", "night", "-r", "health", "--output", "csv"]));
+    let mut lines = out.lines();
+    assert!(lines.next().unwrap().starts_with("Id,Fact,Value"));
+    let row = lines.next().unwrap();
+    assert!(row.contains(",sleep,7,"));
+}
+
+#[test]
+fn fact_list_and_search_as_json() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "health/night", "sleep", "7", "-a", "quality=good"]).assert().success();
+    dlog(&home).args(&["item", "add", "health/run", "distance", "5"]).assert().success();
+    let out = stdout(dlog(&home).args(&["fact", "list", "-o", "json"]));
+    let facts: Value = serde_json::from_str(&out).unwrap();
+    assert_eq!(facts.as_array().unwrap().len(), 2);
+    assert_eq!(facts[0]["Fact"], "sleep");
+    let out = stdout(dlog(&home).args(&["fact", "list", "-r", "health", "-a", "quality", "-o", "json"]));
+    let facts: Value = serde_json::from_str(&out).unwrap();
+    assert_eq!(facts.as_array().unwrap().len(), 1);
+    let out = stdout(dlog(&home).args(&["fact", "search", "dist", "-o", "json"]));
+    let facts: Value = serde_json::from_str(&out).unwrap();
+    assert_eq!(facts.as_array().unwrap().len(), 1);
+    assert_eq!(facts[0]["Value"], "5");
+}
+
+#[test]
+fn stats_as_ndjson() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "health/night", "sleep", "7"]).assert().success();
+    dlog(&home).args(&["item", "add", "health/night", "sleep", "8"]).assert().success();
+    let out = stdout(dlog(&home).args(&["stats", "-o", "ndjson"]));
+    let sleep: Value = out.lines()
+        .map(|l| serde_json::from_str::<Value>(l).unwrap())
+        .find(|s| s["Fact"] == "sleep")
+        .unwrap();
+    assert_eq!(sleep["Entries"], 2);
+    assert_eq!(sleep["Total"], 15.0);
+}
+
+/// Runs `edit` over the config written by the first command
+fn configure(home: &tempfile::TempDir, edit: impl Fn(String) -> String) {
+    let path = home.path().join("config").join("dlog").join("dlog.toml");
+    let conf = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(&path, edit(conf)).unwrap();
+}
+
+fn health(home: &tempfile::TempDir) {
+    dlog(home).args(&["record", "new", "health"]).assert().success();
+    dlog(home).args(&["item", "add", "health/night", "sleep", "7"]).assert().success();
+}
+
+#[test]
+fn tables_show_configured_columns_in_order() {
+    let home = tempfile::tempdir().unwrap();
+    health(&home);
+    configure(&home, |c| c.replace("[format.tables]\n",
+        "[format.tables.items]\ncolumns = [\"entries\", \"Item\"]\n"));
+    let out = stdout(dlog(&home).args(&["item", "list"]));
+    let header = out.lines().nth(1).unwrap();
+    assert!(header.find("Entries").unwrap() < header.find("Item").unwrap());
+    assert!(!header.contains("Record"));
+    assert!(!out.contains("health"));
+}
+
+#[test]
+fn dates_follow_the_format_config() {
+    let home = tempfile::tempdir().unwrap();
+    health(&home);
+    configure(&home, |c| c
+        .replace("date_format = 'YYYY-MM-DD'", "date_format = 'DD/MM/YYYY'")
+        .replace("hour_format = 'TwelveHr'", "hour_format = 'TwentyFourHr'"));
+    let out = stdout(dlog(&home).args(&["record", "list"]));
+    let today = chrono::Local::now().format("%d/%m/%Y").to_string();
+    assert!(out.contains(&today));
+    assert!(!out.contains(" AM") && !out.contains(" PM"));
+}
+
+#[test]
+fn formats_which_cannot_be_written_are_refused() {
+    let home = tempfile::tempdir().unwrap();
+    health(&home);
+    dlog(&home).args(&["config", "set", "format.date_format", "%Q"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("%Q is not a date format"));
+    configure(&home, |c| c.replace("date_format = 'YYYY-MM-DD'", "date_format = '%Q'"));
+    let out = stdout(dlog(&home).args(&["record", "list"]));
+    assert!(out.contains(&chrono::Local::now().format("%Y-%m-%d").to_string()));
+}
+
+#[test]
+fn pretty_tables_show_relative_times() {
+    let home = tempfile::tempdir().unwrap();
+    health(&home);
+    let out = stdout(dlog(&home).args(&["-p", "item", "list"]));
+    assert!(out.contains('╭'));
+    assert!(out.contains("now"));
+    let out = stdout(dlog(&home).args(&["item", "list"]));
+    assert!(!out.contains('╭'));
+    assert!(out.contains(&chrono::Local::now().format("%Y-%m-%d").to_string()));
+}
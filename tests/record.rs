@@ -1,6 +1,163 @@
 use assert_cmd::{cmd::Command, cargo::cargo_bin, output::OutputOkExt};
 use predicates::{prelude::*, str::{diff, similar},};
+
+#[path = "common/mod.rs"]
+mod common;
+use common::dlog;
 #[test]
 fn record_test() {
 
 }
+
+#[test]
+fn init_lays_out_record_in_current_dir() {
+    let home = tempfile::tempdir().unwrap();
+    let dir = home.path().join("health");
+    std::fs::create_dir(&dir).unwrap();
+    dlog(&home).current_dir(&dir)
+        .args(&["init", "--gitignore", "-d", "sleep and such"])
+        .assert()
+        .success();
+    let rec = std::fs::read_to_string(dir.join("record.toml")).unwrap();
+    assert!(rec.contains("Record = 'health'"));
+    assert!(rec.contains("Description = 'sleep and such'"));
+    assert!(dir.join("health.csv").is_file());
+    assert!(dir.join(".gitignore").is_file());
+}
+
+#[test]
+fn init_refuses_to_clobber_record() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).current_dir(home.path())
+        .args(&["init", "work"])
+        .assert()
+        .success();
+    dlog(&home).current_dir(home.path())
+        .args(&["init", "other"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already exists"));
+}
+
+#[test]
+fn record_new_copies_template_fact_types() {
+    let home = tempfile::tempdir().unwrap();
+    let template = home.path().join("template.toml");
+    std::fs::write(&template, "[[Facts]]\nFact = 'sleep'\nUnit = 'hr'\n").unwrap();
+    dlog(&home)
+        .args(&["record", "new", "health", "--template"])
+        .arg(&template)
+        .assert()
+        .success();
+    let rec = home.path().join("data").join("dlog").join("health");
+    let rec = std::fs::read_to_string(rec.join("record.toml")).unwrap();
+    assert!(rec.contains("Fact = 'sleep'"));
+    assert!(rec.contains("Unit = 'hr'"));
+}
+
+#[test]
+fn record_new_from_builtin_template() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home)
+        .args(&["record", "new", "body", "--template", "health"])
+        .assert()
+        .success();
+    let rec = home.path().join("data").join("dlog").join("body");
+    let rec = std::fs::read_to_string(rec.join("record.toml")).unwrap();
+    assert!(rec.contains("Fact = 'sleep'"));
+    assert!(rec.contains("name = 'weigh-in'"));
+    assert!(!home.path().join("config").join("dlog").join("templates").exists());
+}
+
+#[test]
+fn builtin_template_actions_log_their_fact() {
+    let home = tempfile::tempdir().unwrap();
+    for template in &["health", "workouts", "finance"] {
+        dlog(&home)
+            .args(&["record", "new", template, "--template", template])
+            .assert()
+            .success();
+        let dir = home.path().join("data").join("dlog").join(template);
+        let rec: toml::Value = std::fs::read_to_string(dir.join("record.toml")).unwrap()
+            .parse()
+            .unwrap();
+        let action = &rec["Actions"][0];
+        let mut cmd = action["cmd"].as_array().unwrap().iter()
+            .map(|a| a.as_str().unwrap().to_string())
+            .collect::<Vec<String>>();
+        if *template != "finance" {
+            cmd.push("5".into());
+        }
+        dlog(&home).args(&cmd).assert().success();
+        let csv = std::fs::read_to_string(dir.join(format!("{}.csv", template))).unwrap();
+        assert!(csv.contains(&format!(",{},", cmd[0])), "{}: {}", template, csv);
+    }
+    let health = home.path().join("data").join("dlog").join("health").join("health.csv");
+    assert!(std::fs::read_to_string(health).unwrap().contains("weight,5,"));
+}
+
+#[test]
+fn template_save_writes_to_config_dir() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home)
+        .args(&["record", "new", "lifting", "--template", "workouts"])
+        .assert()
+        .success();
+    dlog(&home)
+        .args(&["record", "template", "save", "lifting", "gym"])
+        .assert()
+        .success();
+    let template = home.path().join("config").join("dlog")
+        .join("templates").join("gym.toml");
+    let template = std::fs::read_to_string(template).unwrap();
+    assert!(template.contains("Template = 'gym'"));
+    assert!(template.contains("Fact = 'pushups'"));
+    dlog(&home)
+        .args(&["record", "template", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("gym"));
+}
+
+#[test]
+fn record_get_nests_items_and_recent_entries() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    for km in &["3", "4", "5"] {
+        dlog(&home).args(&["item", "add", "health/exercise/running", "distance", km])
+            .assert()
+            .success();
+    }
+    let out = dlog(&home).args(&["record", "get", "health", "-n", "2"]).output().unwrap();
+    let out = String::from_utf8_lossy(&out.stdout).to_string();
+    let rows = out.lines()
+        .map(|l| l.trim_start_matches('│').trim_end().to_string())
+        .collect::<Vec<String>>();
+    assert!(rows.iter().any(|r| r.starts_with("   exercise")));
+    assert!(rows.iter().any(|r| r.starts_with("     running")));
+    assert_eq!(rows.iter().filter(|r| r.contains("distance")).count(), 2);
+    assert!(rows.iter().any(|r| r.contains("5")));
+    let out = dlog(&home).args(&["record", "get", "health", "--depth", "1"]).output().unwrap();
+    let out = String::from_utf8_lossy(&out.stdout).to_string();
+    assert!(out.contains("exercise"));
+    assert!(!out.contains("running"));
+}
+
+#[test]
+fn record_link_and_list() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["record", "new", "finance"]).assert().success();
+    dlog(&home).args(&["record", "link", "health", "-a", "daily", "--to", "record:finance"])
+        .assert()
+        .success();
+    dlog(&home).args(&["record", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("record:finance"));
+    dlog(&home).args(&["record", "search", "-a", "daily"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("health"))
+        .stdout(predicate::str::contains("│ finance").not());
+}
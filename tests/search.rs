@@ -0,0 +1,82 @@
+use predicates::prelude::*;
+
+mod common;
+use common::dlog;
+
+fn search(home: &tempfile::TempDir, args: &[&str]) -> Vec<serde_json::Value> {
+    let out = dlog(home).args(&["--output", "json", "search"]).args(args)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    serde_json::from_slice(&out).unwrap()
+}
+
+#[test]
+fn finds_facts_and_notes_best_first() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "mood", "feeling", "headache after poor sleep", "-r", "health"])
+        .assert()
+        .success();
+    dlog(&home).args(&["item", "add", "sleep", "quality", "poor", "-r", "health"])
+        .assert()
+        .success();
+    dlog(&home).args(&["note", "health", "-m", "Headache again, the third headache this week"])
+        .assert()
+        .success();
+    let hits = search(&home, &["headache"]);
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0]["kind"], "note");
+    assert_eq!(hits[1]["kind"], "fact");
+    assert_eq!(hits[1]["record"], "health/mood");
+    dlog(&home).args(&["search", "poor"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("quality = poor"))
+        .stdout(predicate::str::contains("headache after poor sleep"));
+}
+
+#[test]
+fn index_follows_changes_and_undo() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["search", "distance"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Nothing matched"));
+    dlog(&home).args(&["item", "add", "run", "distance", "5", "-r", "health"])
+        .assert()
+        .success();
+    assert_eq!(search(&home, &["distance"]).len(), 1);
+    dlog(&home).args(&["undo"]).assert().success();
+    assert!(search(&home, &["distance"]).is_empty());
+    dlog(&home).args(&["redo"]).assert().success();
+    assert_eq!(search(&home, &["distance"]).len(), 1);
+    dlog(&home).args(&["record", "delete", "health", "-y"]).assert().success();
+    assert!(search(&home, &["distance"]).is_empty());
+}
+
+#[test]
+fn kind_and_record_narrow_the_search() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["record", "new", "garden"]).assert().success();
+    dlog(&home).args(&["item", "add", "sleep", "hours", "7", "-r", "health"])
+        .assert()
+        .success();
+    dlog(&home).args(&["item", "add", "tomatoes", "sleep", "dormant", "-r", "garden"])
+        .assert()
+        .success();
+    assert_eq!(search(&home, &["sleep"]).len(), 2);
+    let hits = search(&home, &["sleep", "-k", "item"]);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0]["name"], "sleep");
+    let hits = search(&home, &["sleep", "-r", "garden"]);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0]["record"], "garden/tomatoes");
+    dlog(&home).args(&["search"])
+        .assert()
+        .failure();
+}
@@ -0,0 +1,207 @@
+use serde_json::{json, Value};
+use std::process::Child;
+
+mod common;
+use common::dlog;
+
+/// `dlog serve` on a free port, killed when dropped
+struct Serve(Child, String);
+
+impl Serve {
+    fn start(home: &tempfile::TempDir, token: &str) -> Self {
+        let (child, url) = common::listen(std::process::Command::new(assert_cmd::cargo::cargo_bin("dlog"))
+            .env("XDG_DATA_HOME", home.path().join("data"))
+            .env("XDG_CONFIG_HOME", home.path().join("config"))
+            .args(&["serve", "--bind", "127.0.0.1:0", "--token", token]));
+        Serve(child, url)
+    }
+
+    fn get(&self, path: &str) -> (u16, Value) {
+        let res = reqwest::blocking::Client::new()
+            .get(&format!("{}{}", self.1, path))
+            .bearer_auth("s3cret")
+            .send()
+            .unwrap();
+        (res.status().as_u16(), res.json().unwrap())
+    }
+
+    fn post(&self, path: &str, body: Value) -> (u16, Value) {
+        let res = reqwest::blocking::Client::new()
+            .post(&format!("{}{}", self.1, path))
+            .bearer_auth("s3cret")
+            .json(&body)
+            .send()
+            .unwrap();
+        (res.status().as_u16(), res.json().unwrap())
+    }
+}
+
+impl Drop for Serve {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+    }
+}
+
+#[test]
+fn facts_logged_over_http_are_listed() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    let serve = Serve::start(&home, "s3cret");
+    let (status, fact) = serve.post("/v1/facts",
+        json!({ "record": "health", "item": "night", "fact": "sleep", "value": "7" }));
+    assert_eq!(status, 201);
+    assert_eq!(fact["Fact"], "sleep");
+    serve.post("/v1/facts", json!({ "record": "health", "item": "night", "fact": "sleep", "value": "6" }));
+    let (status, facts) = serve.get("/v1/facts?record=health&item=night&limit=1");
+    assert_eq!(status, 200);
+    assert_eq!(facts.as_array().unwrap().len(), 1);
+    assert_eq!(facts[0]["Id"], serve.get("/v1/facts?fact=sleep").1[1]["Id"]);
+    let (_, items) = serve.get("/v1/items?record=health");
+    assert_eq!(items[0]["Record"], "health");
+    assert_eq!(items[0]["Item"], "night");
+    let (_, stats) = serve.get("/v1/stats?record=health");
+    assert_eq!(stats[0]["Fact"], "sleep");
+    assert_eq!(stats[0]["Entries"], 2);
+    drop(serve);
+    dlog(&home).args(&["item", "get", "health/night"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("6"));
+    dlog(&home).arg("history")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("dlog serve: POST /v1/facts"));
+    dlog(&home).arg("undo").assert().success();
+    let out = dlog(&home).args(&["item", "get", "health/night", "-o", "json"]).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&out.stdout).matches("\"sleep\"").count(), 1);
+}
+
+#[test]
+fn logged_facts_are_returned_as_saved() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["user", "new", "sam"]).assert().success();
+    let serve = Serve::start(&home, "s3cret");
+    let (status, fact) = serve.post("/v1/facts", json!({
+        "record": "health", "item": "night", "fact": "sleep", "value": "7", "notes": "slept well #rested"
+    }));
+    assert_eq!(status, 201);
+    assert_eq!(fact["Author"], "sam");
+    assert_eq!(fact["Attribute"][0]["Name"], "rested");
+    let (_, facts) = serve.get("/v1/facts?record=health&item=night");
+    assert_eq!(facts[0], fact);
+}
+
+#[test]
+fn records_links_and_search() {
+    let home = tempfile::tempdir().unwrap();
+    let serve = Serve::start(&home, "s3cret");
+    let (status, record) = serve.post("/v1/records",
+        json!({ "name": "garden", "description": "tomatoes and such" }));
+    assert_eq!(status, 201);
+    assert_eq!(record["Record"], "garden");
+    assert_eq!(serve.post("/v1/records", json!({ "name": "garden" })).0, 409);
+    serve.post("/v1/records", json!({ "name": "kitchen" }));
+    let (status, link) = serve.post("/v1/links",
+        json!({ "from": "record:garden", "to": "record:kitchen", "name": "feeds" }));
+    assert_eq!(status, 201);
+    assert_eq!(link["name"], "feeds");
+    let (_, links) = serve.get("/v1/links?entity=record:kitchen");
+    assert_eq!(links.as_array().unwrap().len(), 1);
+    serve.post("/v1/facts", json!({ "record": "kitchen", "fact": "ate", "value": "tomatoes" }));
+    let (_, found) = serve.get("/v1/search?q=tomato");
+    assert_eq!(found["records"][0]["Record"], "garden");
+    assert_eq!(found["facts"][0]["Fact"], "ate");
+    assert_eq!(serve.get("/v1/records").1.as_array().unwrap().len(), 2);
+    assert_eq!(serve.get("/v1/records/cellar").0, 404);
+}
+
+#[test]
+fn facts_logged_over_http_are_searched() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["search", "--reindex"]).assert().success();
+    let serve = Serve::start(&home, "s3cret");
+    let (status, _) = serve.post("/v1/facts",
+        json!({ "record": "health", "item": "mind", "fact": "meditation", "value": "20" }));
+    assert_eq!(status, 201);
+    drop(serve);
+    dlog(&home).args(&["search", "meditation"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("meditation"));
+}
+
+#[test]
+fn requests_need_the_token() {
+    let home = tempfile::tempdir().unwrap();
+    let serve = Serve::start(&home, "other");
+    let (status, body) = serve.get("/v1/records");
+    assert_eq!(status, 401);
+    assert!(body["error"].as_str().unwrap().contains("token"));
+}
+
+#[test]
+fn oversized_requests_are_refused_and_serving_goes_on() {
+    use std::io::{Read, Write};
+    let home = tempfile::tempdir().unwrap();
+    let serve = Serve::start(&home, "s3cret");
+    let send = |req: &str| {
+        let mut stream = std::net::TcpStream::connect(serve.1.trim_start_matches("http://")).unwrap();
+        stream.write_all(req.as_bytes()).unwrap();
+        let mut res = String::new();
+        stream.read_to_string(&mut res).map(|_| res)
+    };
+    let body = format!("POST /v1/facts HTTP/1.1\r\nContent-Length: {}\r\n\r\n", 17 * 1024 * 1024);
+    assert!(send(&body).unwrap().starts_with("HTTP/1.1 400"));
+    let long = format!("GET /v1/{} HTTP/1.1\r\n\r\n", "a".repeat(10_000));
+    if let Ok(res) = send(&long) {
+        assert!(res.starts_with("HTTP/1.1 400"));
+    }
+    let (status, _) = serve.get("/v1/records");
+    assert_eq!(status, 200);
+}
+
+#[test]
+fn names_which_would_leave_the_data_dir_are_refused() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    let serve = Serve::start(&home, "s3cret");
+    let (status, _) = serve.post("/v1/records", json!({"name": "../../../escaped"}));
+    assert_eq!(status, 400);
+    let (status, _) = serve.post("/v1/records", json!({"name": ".."}));
+    assert_eq!(status, 400);
+    let (status, _) = serve.post("/v1/items", json!({"record": "health", "item": "../../../../viaapi"}));
+    assert_eq!(status, 400);
+    let (status, _) = serve.post("/v1/items", json!({"record": "health", "item": "/tmp/viaapi"}));
+    assert_eq!(status, 400);
+    let (status, _) = serve.post("/v1/facts",
+        json!({"fact": "sleep", "value": "7", "record": "health", "item": "../out"}));
+    assert_eq!(status, 400);
+    assert!(!home.path().join("escaped").exists());
+    assert!(!home.path().join("viaapi").exists());
+    let (status, _) = serve.post("/v1/items", json!({"record": "health", "item": "exercise/running"}));
+    assert_eq!(status, 201);
+}
+
+#[test]
+fn locked_store_is_sealed_between_requests() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).env("DLOG_PASSPHRASE", "hunter2").args(&["auth", "lock"]).assert().success();
+    let (child, url) = common::listen(std::process::Command::new(assert_cmd::cargo::cargo_bin("dlog"))
+        .env("XDG_DATA_HOME", home.path().join("data"))
+        .env("XDG_CONFIG_HOME", home.path().join("config"))
+        .env("DLOG_PASSPHRASE", "hunter2")
+        .args(&["serve", "--bind", "127.0.0.1:0", "--token", "s3cret"]));
+    let serve = Serve(child, url);
+    let data = home.path().join("data").join("dlog");
+    assert!(!data.join("records.toml").exists());
+    let (status, _) = serve.post("/v1/facts",
+        json!({ "record": "health", "item": "night", "fact": "sleep", "value": "7" }));
+    assert_eq!(status, 201);
+    assert!(!data.join("records.toml").exists());
+    assert!(!data.join("health").join("record.toml").exists());
+    assert!(!data.join("health").join("night").join("night.csv").exists());
+    assert_eq!(serve.get("/v1/facts?record=health").1[0]["Value"], "7");
+}
@@ -0,0 +1,196 @@
+use predicates::prelude::*;
+use std::process::Child;
+
+mod common;
+use common::dlog;
+
+/// The reference server on a free port, killed when dropped
+struct Server(Child, String);
+
+impl Server {
+    fn start(dir: &tempfile::TempDir, token: Option<&str>) -> Self {
+        let mut cmd = std::process::Command::new(assert_cmd::cargo::cargo_bin("dlog-sync-server"));
+        cmd.args(&["--bind", "127.0.0.1:0", "--dir"])
+            .arg(dir.path());
+        if let Some(token) = token {
+            cmd.args(&["--token", token]);
+        }
+        let (child, url) = common::listen(&mut cmd);
+        Server(child, url)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+    }
+}
+
+#[test]
+fn pushed_facts_are_pulled_into_another_store() {
+    let (a, b, dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    let server = Server::start(&dir, Some("s3cret"));
+    dlog(&a).args(&["record", "new", "health"]).assert().success();
+    dlog(&a).args(&["item", "add", "health/night", "sleep", "7"]).assert().success();
+    dlog(&a).args(&["sync", "push", "--token", "s3cret", "--url", &server.1])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Pushed 2 entities"));
+    dlog(&a).args(&["sync", "push", "--token", "s3cret", "--url", &server.1])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Pushed 0 entities"));
+    dlog(&b).args(&["sync", "pull", "--token", "s3cret", "--url", &server.1])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Pulled 2 new"));
+    dlog(&b).args(&["item", "get", "health/night"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("sleep"))
+        .stdout(predicate::str::contains("7"));
+    dlog(&b).args(&["sync", "pull", "--token", "s3cret", "--url", &server.1])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Pulled 0 new and 0 updated"));
+}
+
+#[test]
+fn edits_after_a_push_are_pushed() {
+    let (a, b, dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    let server = Server::start(&dir, None);
+    dlog(&a).args(&["record", "new", "health"]).assert().success();
+    dlog(&a).args(&["item", "add", "health/night", "sleep", "7"]).assert().success();
+    dlog(&a).args(&["sync", "push", "--url", &server.1]).assert().success();
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    dlog(&a).args(&["fact", "edit", "last", "-v", "9.25"]).assert().success();
+    dlog(&a).args(&["sync", "push", "--url", &server.1])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Pushed 1 entities"));
+    dlog(&b).args(&["sync", "pull", "--url", &server.1]).assert().success();
+    dlog(&b).args(&["item", "get", "health/night"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("9.25"));
+}
+
+#[test]
+fn rewriting_a_record_file_unchanged_is_not_pushed() {
+    let (a, dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    let server = Server::start(&dir, None);
+    dlog(&a).args(&["record", "new", "health"]).assert().success();
+    dlog(&a).args(&["sync", "push", "--url", &server.1]).assert().success();
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    let toml = a.path().join("data").join("dlog").join("health").join("record.toml");
+    std::fs::write(&toml, std::fs::read(&toml).unwrap()).unwrap();
+    dlog(&a).args(&["sync", "push", "--url", &server.1])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Pushed 0 entities"));
+}
+
+#[test]
+fn the_server_wants_its_token() {
+    let (a, dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    let server = Server::start(&dir, Some("s3cret"));
+    dlog(&a).args(&["record", "new", "health"]).assert().success();
+    dlog(&a).args(&["sync", "push", "--token", "wrong", "--url", &server.1])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("401"));
+    dlog(&a).args(&["sync", "push"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("sync.url"));
+    dlog(&a).args(&["sync", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Last push: never"));
+}
+
+#[test]
+fn the_server_refuses_oversized_requests_and_keeps_serving() {
+    use std::io::{Read, Write};
+    let dir = tempfile::tempdir().unwrap();
+    let server = Server::start(&dir, Some("s3cret"));
+    let addr = server.1.trim_start_matches("http://");
+    let send = |req: &str| {
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream.write_all(req.as_bytes()).unwrap();
+        let mut res = String::new();
+        stream.read_to_string(&mut res).map(|_| res)
+    };
+    let long = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(10_000));
+    if let Ok(res) = send(&long) {
+        assert!(res.starts_with("HTTP/1.1 400"));
+    }
+    let many = format!("GET / HTTP/1.1\r\n{}\r\n", "X-A: b\r\n".repeat(101));
+    if let Ok(res) = send(&many) {
+        assert!(res.starts_with("HTTP/1.1 400"));
+    }
+    let res = send("GET /v1/changes HTTP/1.1\r\n\r\n").unwrap();
+    assert!(res.starts_with("HTTP/1.1 401"));
+}
+
+#[test]
+fn conflicting_changes_keep_the_newer_version() {
+    let (a, b, dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    let server = Server::start(&dir, None);
+    dlog(&a).args(&["record", "new", "health", "-d", "old notes"]).assert().success();
+    dlog(&a).args(&["item", "add", "health/night", "sleep", "7"]).assert().success();
+    dlog(&a).args(&["sync", "push", "--url", &server.1]).assert().success();
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    dlog(&b).args(&["record", "new", "health", "-d", "new notes"]).assert().success();
+    dlog(&b).args(&["item", "add", "health/day", "steps", "9000"]).assert().success();
+    dlog(&b).args(&["sync", "push", "--url", &server.1]).assert().success();
+    dlog(&a).args(&["sync", "pull", "--url", &server.1])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 conflicts"))
+        .stdout(predicate::str::is_match(r"record\s.*health\s.*Server").unwrap());
+    let toml = std::fs::read_to_string(a.path().join("data").join("dlog")
+        .join("health").join("record.toml")).unwrap();
+    assert!(toml.contains("new notes"));
+    dlog(&a).args(&["item", "get", "health/day"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("9000"));
+    dlog(&a).args(&["item", "get", "health/night"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("sleep"));
+}
+
+#[test]
+fn pulled_names_leading_out_of_the_store_are_skipped() {
+    let (a, dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    let server = Server::start(&dir, None);
+    let now = "2026-01-01T00:00:00+00:00";
+    let entities = serde_json::json!({"entities": [
+        {"id": "8f0c6f3e-2b1a-4c1e-9d57-0a0d9c3b1f01", "updated": now, "kind": "record",
+            "data": {"name": "../../escaped", "items": [], "created": now}},
+        {"id": "8f0c6f3e-2b1a-4c1e-9d57-0a0d9c3b1f02", "updated": now, "kind": "record",
+            "data": {"name": "garden", "items": ["../../../viasync"], "created": now}},
+        {"id": "8f0c6f3e-2b1a-4c1e-9d57-0a0d9c3b1f03", "updated": now, "kind": "fact",
+            "data": {"record": "health", "item": "/tmp/viasync", "fact": "sleep", "value": "7",
+                "created": now}},
+        {"id": "8f0c6f3e-2b1a-4c1e-9d57-0a0d9c3b1f04", "updated": now, "kind": "record",
+            "data": {"name": "health", "items": ["night"], "created": now}},
+    ]});
+    let res = reqwest::blocking::Client::new()
+        .post(&format!("{}/v1/entities", server.1))
+        .json(&entities)
+        .send()
+        .unwrap();
+    assert!(res.status().is_success());
+    dlog(&a).args(&["sync", "pull", "--url", &server.1])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Pulled 1 new"))
+        .stdout(predicate::str::contains("Skipped").count(3));
+    assert!(!a.path().join("escaped").exists());
+    assert!(!a.path().join("data").join("escaped").exists());
+    assert!(!a.path().join("viasync").exists());
+    dlog(&a).args(&["item", "get", "health/night"]).assert().success();
+}
@@ -0,0 +1,70 @@
+use predicates::prelude::*;
+
+mod common;
+use common::dlog;
+
+#[test]
+fn topic_get_shows_facts_across_records() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["record", "new", "work"]).assert().success();
+    dlog(&home).args(&["topic", "new", "sleep", "-d", "rest and naps"]).assert().success();
+    dlog(&home).args(&["item", "add", "health/night", "sleep", "7"]).assert().success();
+    dlog(&home).args(&["item", "add", "work/travel", "sleep", "4"]).assert().success();
+    dlog(&home).args(&["item", "add", "work/desk", "hours", "9"]).assert().success();
+    let out = dlog(&home).args(&["topic", "get", "sleep"]).output().unwrap();
+    let out = String::from_utf8_lossy(&out.stdout).to_string();
+    assert!(out.contains("rest and naps"));
+    assert!(out.contains("health"));
+    assert!(out.contains("work"));
+    assert!(out.contains("sleep (night)"));
+    assert!(out.contains("sleep (travel)"));
+    assert!(!out.contains("hours"));
+}
+
+#[test]
+fn tagged_items_and_subtopics_are_gathered() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["topic", "new", "fitness"]).assert().success();
+    dlog(&home).args(&["topic", "new", "running", "--parent", "fitness"]).assert().success();
+    dlog(&home).args(&["item", "add", "health/exercise/track", "laps", "12"]).assert().success();
+    dlog(&home).args(&["topic", "tag", "running", "item:exercise/track"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Tagged 1"));
+    dlog(&home).args(&["topic", "tag", "running", "item:exercise/track"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Tagged 0"));
+    dlog(&home).args(&["topic", "get", "fitness"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("exercise/track"))
+        .stdout(predicate::str::contains("1 entries"));
+    let out = dlog(&home).args(&["topic", "list"]).output().unwrap();
+    let out = String::from_utf8_lossy(&out.stdout).to_string();
+    let rows = out.lines()
+        .map(|l| l.trim_start_matches('│').trim_end().to_string())
+        .collect::<Vec<String>>();
+    assert!(rows.iter().any(|r| r.starts_with(" fitness")));
+    assert!(rows.iter().any(|r| r.starts_with("   running")));
+}
+
+#[test]
+fn unknown_topics_and_parents_are_refused() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["topic", "new", "naps", "-p", "sleep"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No parent topic named sleep"));
+    dlog(&home).args(&["topic", "tag", "sleep", "record:inbox"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("dlog topic new sleep"));
+    dlog(&home).args(&["topic", "new", "sleep"]).assert().success();
+    dlog(&home).args(&["topic", "new", "Sleep"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already exists"));
+}
@@ -0,0 +1,110 @@
+use predicates::prelude::*;
+
+mod common;
+use common::dlog;
+
+fn data(home: &tempfile::TempDir) -> std::path::PathBuf {
+    home.path().join("data").join("dlog")
+}
+
+#[test]
+fn deleted_entries_and_items_are_restored() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "night/nap", "sleep", "1", "-r", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "day", "mood", "5", "-r", "health"]).assert().success();
+    let day = data(&home).join("health").join("day").join("day.csv");
+    dlog(&home).args(&["fact", "delete", "last:mood"]).assert().success();
+    assert!(!std::fs::read_to_string(&day).unwrap().contains(",mood,5,"));
+    dlog(&home).args(&["item", "delete", "night", "-r", "health", "--cascade", "-y"]).assert().success();
+    assert!(!data(&home).join("health").join("night").exists());
+    dlog(&home).args(&["trash", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("entry").and(predicate::str::contains("night")));
+    dlog(&home).args(&["trash", "restore", "mood"]).assert().success();
+    assert!(std::fs::read_to_string(&day).unwrap().contains(",mood,5,"));
+    dlog(&home).args(&["trash", "restore", "night"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Restored item night to health"));
+    let nap = data(&home).join("health").join("night").join("nap").join("nap.csv");
+    assert!(std::fs::read_to_string(nap).unwrap().contains(",sleep,1,"));
+    dlog(&home).args(&["item", "list", "health"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("night/nap"));
+    dlog(&home).arg("trash").assert().success().stdout(predicate::str::contains("The trash is empty"));
+}
+
+#[test]
+fn deleting_a_record_trashes_everything_in_it() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "night", "sleep", "7", "-r", "health"]).assert().success();
+    dlog(&home).args(&["record", "delete", "inbox", "-y"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("The inbox can't be deleted"));
+    dlog(&home).args(&["record", "delete", "health", "-y"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Moved health (1 entries) to the trash"));
+    assert!(!data(&home).join("health").exists());
+    dlog(&home).args(&["record", "get", "health"]).assert().failure();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["trash", "restore", "health"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("A record named health"));
+    let out = dlog(&home).args(&["--output", "json", "trash", "list"]).output().unwrap();
+    let trashed: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let id = trashed[0]["Id"].as_str().unwrap().to_string();
+    dlog(&home).args(&["record", "delete", "health", "-y"]).assert().success();
+    dlog(&home).args(&["trash", "restore", &id]).assert().success();
+    let night = data(&home).join("health").join("night").join("night.csv");
+    assert!(std::fs::read_to_string(night).unwrap().contains(",sleep,7,"));
+}
+
+#[test]
+fn old_trash_is_purged_and_emptied() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["item", "add", "night", "sleep", "7"]).assert().success();
+    dlog(&home).args(&["item", "add", "day", "mood", "5"]).assert().success();
+    dlog(&home).args(&["fact", "delete", "--fact", "sleep"]).assert().success();
+    let toml = data(&home).join("trash").join("trash.toml");
+    let text = std::fs::read_to_string(&toml).unwrap();
+    let old = text.lines()
+        .map(|l| match l.starts_with("\"Deleted at\"") {
+            true => "\"Deleted at\" = \"2020-01-01T00:00:00+00:00\"".to_string(),
+            false => l.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    std::fs::write(&toml, old).unwrap();
+    dlog(&home).args(&["fact", "delete", "last:mood"]).assert().success();
+    dlog(&home).args(&["--output", "json", "trash", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mood").and(predicate::str::contains("sleep").not()));
+    dlog(&home).args(&["trash", "empty", "-y"]).assert().success();
+    dlog(&home).arg("trash").assert().success().stdout(predicate::str::contains("The trash is empty"));
+    assert_eq!(std::fs::read_dir(data(&home).join("trash")).unwrap().count(), 1);
+}
+
+#[test]
+fn emptied_trash_is_not_kept_in_the_history() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "night", "sleep", "7", "-r", "health"]).assert().success();
+    dlog(&home).args(&["item", "add", "day", "mood", "5", "-r", "health"]).assert().success();
+    dlog(&home).args(&["item", "delete", "night", "-r", "health", "--cascade", "-y"]).assert().success();
+    dlog(&home).args(&["fact", "delete", "last:mood"]).assert().success();
+    dlog(&home).args(&["trash", "empty", "-y"]).assert().success();
+    for blob in std::fs::read_dir(data(&home).join("history").join("blobs")).unwrap() {
+        let blob = std::fs::read_to_string(blob.unwrap().path()).unwrap_or_default();
+        assert!(!blob.contains(",sleep,7,") && !blob.contains(",mood,5,"));
+    }
+    dlog(&home).arg("undo").assert().success();
+    dlog(&home).arg("trash").assert().success().stdout(predicate::str::contains("The trash is empty"));
+}
@@ -0,0 +1,85 @@
+use predicates::prelude::*;
+
+mod common;
+use common::dlog;
+
+#[test]
+fn facts_are_authored_by_the_current_user() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "health"]).assert().success();
+    dlog(&home).args(&["user", "new", "sam"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Logging facts as sam"));
+    dlog(&home).args(&["user", "new", "alex"]).assert().success();
+    dlog(&home).args(&["item", "add", "health/night", "sleep", "7"]).assert().success();
+    dlog(&home).args(&["user", "switch", "alex"]).assert().success();
+    dlog(&home).args(&["item", "add", "health/night", "sleep", "5"]).assert().success();
+    dlog(&home).args(&["item", "add", "health/night", "sleep", "6"]).assert().success();
+    let csv = home.path().join("data").join("dlog").join("health")
+        .join("night").join("night.csv");
+    let csv = std::fs::read_to_string(csv).unwrap();
+    assert!(csv.lines().next().unwrap().ends_with("Author,Updated"));
+    assert_eq!(csv.lines().filter(|l| l.ends_with(",sam,")).count(), 1);
+    assert_eq!(csv.lines().filter(|l| l.ends_with(",alex,")).count(), 2);
+    let out = dlog(&home).args(&["user", "list"]).output().unwrap();
+    let out = String::from_utf8_lossy(&out.stdout).to_string();
+    assert!(out.lines().any(|l| l.contains("* alex") && l.contains(" 2 ")));
+    assert!(out.lines().any(|l| l.contains("  sam") && l.contains(" 1 ")));
+    dlog(&home).args(&["stats", "--user", "sam"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"sleep\s+│?\s*1\s+│?\s*7").unwrap());
+    dlog(&home).args(&["stats", "-u", "alex", "-r", "health"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"sleep\s+│?\s*2\s+│?\s*11").unwrap());
+}
+
+#[test]
+fn only_group_members_log_to_shared_records() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["record", "new", "groceries"]).assert().success();
+    dlog(&home).args(&["user", "new", "sam"]).assert().success();
+    dlog(&home).args(&["user", "new", "alex"]).assert().success();
+    dlog(&home).args(&["user", "new", "guest"]).assert().success();
+    dlog(&home).args(&["user", "group", "new", "home", "-m", "sam"]).assert().success();
+    dlog(&home).args(&["user", "group", "add", "home", "alex"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2 members"));
+    dlog(&home).args(&["user", "group", "share", "home", "groceries"]).assert().success();
+    dlog(&home).args(&["item", "add", "groceries/milk", "bought", "2"]).assert().success();
+    dlog(&home).args(&["user", "switch", "guest"]).assert().success();
+    dlog(&home).args(&["item", "add", "groceries/milk", "bought", "1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("shared by the group home"));
+    dlog(&home).args(&["stats", "--group", "home"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("bought"));
+    dlog(&home).args(&["user", "group", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("sam, alex"))
+        .stdout(predicate::str::contains("groceries"));
+}
+
+#[test]
+fn unknown_users_are_refused() {
+    let home = tempfile::tempdir().unwrap();
+    dlog(&home).args(&["user", "switch", "nobody"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("dlog user new nobody"));
+    dlog(&home).args(&["user", "group", "new", "team", "-m", "nobody"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No user named nobody"));
+    dlog(&home).args(&["user", "new", "sam"]).assert().success();
+    dlog(&home).args(&["user", "new", "Sam"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already exists"));
+}